@@ -2,11 +2,18 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec4 fragColor;
 
 layout(location = 0) out vec4 outColor;
 
 layout(binding = 0, set = 1) uniform sampler2D texSampler;
 
+layout(binding = 1, set = 1) uniform MaterialData {
+    vec4 color;
+    float reflectivity;
+    float smoothness;
+} material;
+
 void main() {
-    outColor = texture(texSampler, fragTexCoord);
+    outColor = texture(texSampler, fragTexCoord) * fragColor * material.color;
 }
@@ -8,15 +8,23 @@
 /* } ubo; */
 
 layout(push_constant) uniform Transform {
-        mat4 mvp;
+        mat4 viewproj;
 } transform;
 
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec2 inTexCoord;
+layout(location = 6) in vec4 inColor;
+// Per instance
+layout(location = 2) in mat4 inModel;
 
 layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec4 fragColor;
+
+// Must match the depth prepass exactly for depth testing with EQUAL
+invariant gl_Position;
 
 void main() {
-    gl_Position = transform.mvp * vec4(inPosition, 1.0);
+    gl_Position = transform.viewproj * inModel * vec4(inPosition, 1.0);
     fragTexCoord = inTexCoord;
+    fragColor = inColor;
 }
@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform Transform {
+        mat4 viewproj;
+} transform;
+
+layout(location = 0) in vec3 inPosition;
+// Per instance
+layout(location = 2) in mat4 inModel;
+
+// Must match the main pass exactly for depth testing with EQUAL
+invariant gl_Position;
+
+void main() {
+    gl_Position = transform.viewproj * inModel * vec4(inPosition, 1.0);
+}
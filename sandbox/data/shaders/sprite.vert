@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform Transform {
+        mat4 viewproj;
+} transform;
+
+// Sprite corners already transformed to world space
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+void main() {
+    gl_Position = transform.viewproj * vec4(inPosition, 1.0);
+    fragTexCoord = inTexCoord;
+}
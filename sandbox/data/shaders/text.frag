@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// The font atlas
+layout(binding = 0, set = 1) uniform sampler2D texSampler;
+
+void main() {
+    outColor = texture(texSampler, fragTexCoord);
+}
@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform Text {
+        vec2 screenSize;
+} text;
+
+// Glyph corners in pixels from the top left of the screen
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+void main() {
+    gl_Position = vec4(inPosition.xy / text.screenSize * 2.0 - 1.0, 0.0, 1.0);
+    fragTexCoord = inTexCoord;
+}
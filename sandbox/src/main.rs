@@ -7,11 +7,16 @@ fn main() {
     let mut application = Application::new("Sandbox");
     info!("Created application {}", application.name());
 
-    logger::init(log::LevelFilter::Trace);
+    logger::init(log::LevelFilter::Trace, Vec::new());
 
     application.add_window("Sandbox", 800, 600, WindowMode::Windowed);
 
     application.init_graphics();
+
+    if let Err(e) = application.load_scene("./data/scenes/default.json") {
+        error!("Failed to load scene '{}'", e);
+    }
+
     application.run();
 
     info!("Terminating application");
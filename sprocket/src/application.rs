@@ -1,55 +1,87 @@
 use crate::ecs::*;
 use crate::math::*;
 use crate::physics::Transform;
-use crate::{event::Event, graphics};
 use crate::{
-    graphics::window::{Window, WindowMode},
-    Time, Timer,
+    diagnostics::DiagnosticsSnapshot,
+    event::{Event, EventQueue, InputState},
+    graphics::{self, Camera},
+};
+use crate::{
+    graphics::window::{CursorMode, Window, WindowMode},
+    FrameLimiter, Scene, Time, Timer,
 };
 
-use graphics::vulkan::{renderer::Renderer, ResourceManager};
+use graphics::vulkan::{renderer::Renderer, PresentMode, ResourceManager};
 use log::{error, info};
 
-use std::{
-    sync::{mpsc, Arc},
-    time,
-};
+use std::{sync::Arc, time};
 
 pub struct Application {
     name: String,
     windows: Vec<Window>,
-    event_receiver: mpsc::Receiver<Event>,
-    event_sender: mpsc::Sender<Event>,
+    events: EventQueue,
     renderer: Option<Renderer>,
     graphics_context: Option<graphics::GraphicsContext>,
     resource_manager: Option<Arc<ResourceManager>>,
     component_manager: ComponentManager,
     entity_manager: EntityManager,
     time: Time,
+    input: InputState,
+    present_mode: PresentMode,
+    /// Caps the framerate unless vsync is enabled
+    frame_limiter: Option<FrameLimiter>,
+    headless: bool,
+    /// Enables the graphics validation layers if installed
+    validation: bool,
 }
 
 impl Application {
     /// Creates a new blank application with the given name
     pub fn new(name: &str) -> Application {
-        let (event_sender, event_receiver) = mpsc::channel::<Event>();
-
         Window::init_glfw();
+        Self::create(name, false)
+    }
+
+    /// Creates a new application without windows
+    /// Graphics are initialized without a surface or swapchain, which allows rendering to render
+    /// targets on machines without a display
+    pub fn new_headless(name: &str) -> Application {
+        Self::create(name, true)
+    }
+
+    fn create(name: &str, headless: bool) -> Application {
+        let mut component_manager = ComponentManager::new();
+        component_manager.register_component::<Transform>();
+        component_manager.register_component::<Camera>();
+
         Application {
             name: String::from(name),
             windows: Vec::new(),
-            event_receiver,
-            event_sender,
+            events: EventQueue::new(),
             renderer: None,
             graphics_context: None,
             resource_manager: None,
-            component_manager: ComponentManager::new(),
+            component_manager,
             entity_manager: EntityManager::new(),
             time: Time::new(),
+            input: InputState::new(),
+            present_mode: PresentMode::Mailbox,
+            frame_limiter: None,
+            headless,
+            validation: cfg!(debug_assertions),
         }
     }
 
+    /// Initializes graphics for all windows
+    /// The context is created with the surface of the first window
+    /// A headless application gets a context and resource manager but no renderer
     pub fn init_graphics(&mut self) {
-        self.graphics_context = match graphics::init(graphics::Api::Vulkan, &self.windows[0]) {
+        let context = match self.headless {
+            true => graphics::init_headless(graphics::Api::Vulkan, self.validation),
+            false => graphics::init(graphics::Api::Vulkan, &self.windows[0], self.validation),
+        };
+
+        self.graphics_context = match context {
             Ok(context) => Some(context),
             Err(msg) => {
                 error!("Failed to initialize graphics '{}'", msg);
@@ -61,10 +93,20 @@ impl Application {
         if let graphics::GraphicsContext::Vulkan(context) = self.graphics_context.as_ref().unwrap()
         {
             self.resource_manager = Some(Arc::new(ResourceManager::new(Arc::clone(context))));
+
+            // Reload changed resources in debug builds
+            if cfg!(debug_assertions) {
+                self.resource_manager.as_ref().unwrap().watch();
+            }
+
+            if self.headless {
+                return;
+            }
             self.renderer = match Renderer::new(
                 Arc::clone(context),
                 &self.windows[0],
                 Arc::clone(&self.resource_manager.as_ref().unwrap()),
+                self.present_mode,
             ) {
                 Ok(renderer) => Some(renderer),
                 Err(e) => {
@@ -72,18 +114,147 @@ impl Application {
                     None
                 }
             };
+
+            if let Some(renderer) = self.renderer.as_mut() {
+                for window in &self.windows[1..] {
+                    if let Err(e) = renderer.add_window(window) {
+                        error!(
+                            "Failed to add window '{}' to renderer '{}'",
+                            window.title(),
+                            e
+                        );
+                    }
+                }
+            }
         } else {
         }
     }
 
+    /// Enables or disables the graphics validation layers, enabled by default in debug builds
+    /// Validation is skipped with a warning if the layers are not installed
+    /// Needs to be called before graphics are initialized
+    pub fn set_validation(&mut self, validation: bool) {
+        self.validation = validation;
+    }
+
+    /// Enables or disables vsync
+    /// Vsync caps the framerate to the display refresh rate and reduces GPU usage
+    /// Can be called before or after graphics are initialized
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.present_mode = match vsync {
+            true => PresentMode::Fifo,
+            false => PresentMode::Mailbox,
+        };
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_present_mode(self.present_mode);
+        }
+    }
+
+    /// Caps the framerate to at most max_fps frames per second, or removes the cap if None
+    /// The cap is not enforced while vsync is enabled since presenting already waits for the
+    /// display
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.frame_limiter = max_fps.filter(|&fps| fps > 0).map(FrameLimiter::new);
+    }
+
+    /// Sets the cursor mode of all windows
+    /// Use CursorMode::Disabled for camera control with MouseMotion events
+    pub fn set_cursor_mode(&mut self, mode: CursorMode) {
+        self.windows
+            .iter_mut()
+            .for_each(|window| window.set_cursor_mode(mode));
+    }
+
+    /// Returns the graphics context if graphics have been initialized
+    pub fn graphics_context(&self) -> Option<&graphics::GraphicsContext> {
+        self.graphics_context.as_ref()
+    }
+
+    /// Returns the resource manager if graphics have been initialized
+    pub fn resource_manager(&self) -> Option<&Arc<ResourceManager>> {
+        self.resource_manager.as_ref()
+    }
+
+    /// Loads a scene from a json file and creates its entities
+    /// The models and materials of the entities are loaded through the resource manager
+    /// Should be called after init_graphics so that the entities are registered with the renderer
+    /// Returns the created entities in the order they appear in the scene
+    pub fn load_scene(&mut self, path: &str) -> graphics::Result<Vec<Entity>> {
+        let scene = Scene::load(path)?;
+
+        // Fail before creating any entities if a resource is missing
+        if let Some(resource_manager) = &self.resource_manager {
+            for spec in &scene.entities {
+                if let Some(model) = &spec.model {
+                    resource_manager.load_model(model)?;
+                }
+                if let Some(material) = &spec.material {
+                    resource_manager.load_material(material)?;
+                }
+            }
+        }
+
+        let entities = scene
+            .entities
+            .iter()
+            .map(|spec| {
+                let entity = self.entity_manager.create_entity();
+                self.component_manager
+                    .insert_component(entity, spec.transform);
+
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.insert_entity(entity, spec.transform);
+                    renderer.set_entity_model(
+                        entity,
+                        spec.model.as_deref(),
+                        spec.material.as_deref(),
+                    )?;
+                }
+
+                Ok(entity)
+            })
+            .collect::<graphics::Result<_>>()?;
+
+        info!("Loaded scene '{}'", path);
+        Ok(entities)
+    }
+
+    /// Destroys entity and removes all of its components
+    /// The entity is no longer rendered
+    pub fn despawn(&mut self, entity: Entity) {
+        self.component_manager.remove_all(entity);
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.remove_entity(entity);
+        }
+
+        self.entity_manager.destroy_entity(entity);
+    }
+
+    /// Opens a new window
+    /// Windows added after graphics are initialized get their own swapchain immediately
     pub fn add_window(&mut self, title: &str, width: i32, height: i32, mode: WindowMode) {
-        let window = Window::new(title, width, height, mode, self.event_sender.clone());
+        let window = Window::new(title, width, height, mode, self.events.sender());
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            if let Err(e) = renderer.add_window(&window) {
+                error!("Failed to add window '{}' to renderer '{}'", title, e);
+            }
+        }
+
         self.windows.push(window);
     }
 
     pub fn run(&mut self) {
+        if self.renderer.is_none() {
+            error!("Cannot run an application without a renderer");
+            return;
+        }
+
         let mut garbage_timer = Timer::with_target(time::Duration::from_secs(2));
         let mut timer = Timer::with_target(time::Duration::from_secs(5));
+        let mut reload_timer = Timer::with_target(time::Duration::from_secs(1));
 
         // Create some entities
         let entity = self.entity_manager.create_entity();
@@ -91,7 +262,12 @@ impl Application {
         self.component_manager
             .insert_component(entity, Transform::new(Vec3::zero()));
 
+        let camera = self.entity_manager.create_entity();
+        self.component_manager
+            .insert_component(camera, Camera::default());
+
         let renderer = self.renderer.as_mut().unwrap();
+        renderer.set_active_camera(camera);
 
         while !self.windows.is_empty() {
             renderer.insert_entity(
@@ -107,6 +283,15 @@ impl Application {
                 self.resource_manager.as_ref().unwrap().collect_garbage(5); // Change to swapchain.image_count() in renderer system
                 garbage_timer.restart();
             }
+            if reload_timer.signaled() {
+                if self.resource_manager.as_ref().unwrap().reload_changed() > 0 {
+                    renderer.request_recreate();
+                }
+                reload_timer.restart();
+            }
+
+            // Finish textures read in the background
+            self.resource_manager.as_ref().unwrap().poll_loads();
             if timer.signaled() {
                 info!(
                     "Frame: {}, elapsed: {}, delta: {}, fr: {}, us: {}",
@@ -122,33 +307,409 @@ impl Application {
                 );
                 timer.restart();
             }
-            // Process each window for events
-            self.windows
-                .iter()
-                .for_each(|window| window.process_events());
+            // Process the events of all windows
+            // Windows are redrawn from within the poll when refreshed to keep their contents
+            // updated while being resized, and are not drawn again this frame
+            let mut refreshed = Vec::new();
+            let mut result = Ok(());
+            let (windows, time, component_manager) =
+                (&self.windows, &self.time, &self.component_manager);
+
+            Window::poll_events_with(|id| {
+                if let Some(window) = windows.iter().find(|window| window.id() == id) {
+                    if result.is_ok() {
+                        result = renderer.draw_frame(window, time, component_manager);
+                        refreshed.push(id);
+                    }
+                }
+            });
+
+            for window in &self.windows {
+                if refreshed.contains(&window.id()) {
+                    continue;
+                }
+
+                if let Err(e) = renderer.draw_frame(window, &self.time, &self.component_manager) {
+                    error!("Stopping application: {}", e);
+                    return;
+                }
+            }
 
-            renderer.draw_frame(&self.windows[0], &self.time);
+            if let Err(e) = result {
+                error!("Stopping application: {}", e);
+                return;
+            }
 
             // Receive and handle events
-            while let Ok(event) = self.event_receiver.try_recv() {
-                if let Event::MousePosition(_, _) = event {
-                } else {
-                    info!("Event: {:?}", event);
+            // The per frame input is cleared first so that it can be queried until the next frame
+            self.input.end_frame();
+            for event in self.events.drain() {
+                self.input.handle_event(&event);
+                match event {
+                    Event::MousePosition(_, _)
+                    | Event::MouseMotion(_, _)
+                    | Event::WindowRefresh => {}
+                    _ => info!("Event: {:?}", event),
                 }
             }
+
+            renderer.end_frame();
+
+            // The swapchain and surface need to be destroyed before the window
+            for window in self.windows.iter().filter(|window| window.should_close()) {
+                renderer.remove_window(window.id());
+            }
             self.windows.retain(|window| !window.should_close());
+
+            if let Some(limiter) = &self.frame_limiter {
+                if self.present_mode != PresentMode::Fifo
+                    && self.present_mode != PresentMode::FifoRelaxed
+                {
+                    limiter.wait(self.time.frame_start());
+                }
+            }
+
             self.time.update();
         }
     }
 
+    /// Collects the diagnostics of the last finished frame
+    /// Frame times are averaged over the last frames and can be displayed directly
+    /// Walks all device allocations and loaded resources, and should not be called more often
+    /// than needed, E.g; every few frames for an overlay
+    pub fn diagnostics(&self) -> DiagnosticsSnapshot {
+        collect_diagnostics(
+            &self.time,
+            self.graphics_context.as_ref(),
+            self.resource_manager.as_ref(),
+        )
+    }
+
+    /// Returns the input state as of the current frame
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Waits for the device to idle and destroys the renderer, resource manager, graphics context
+    /// and windows in that order
+    /// Window surfaces are destroyed before their windows, and resources before the device
+    /// Called when the application is dropped, does nothing if already shut down
+    pub fn shutdown(&mut self) {
+        if let Some(graphics::GraphicsContext::Vulkan(context)) = &self.graphics_context {
+            if let Err(e) = context.wait_idle() {
+                error!("Failed to wait on device before shutting down '{}'", e);
+            }
+        }
+
+        self.renderer = None;
+        self.resource_manager = None;
+        self.graphics_context = None;
+        self.windows.clear();
+    }
 }
 
 impl Drop for Application {
     fn drop(&mut self) {
-        self.resource_manager = None;
+        self.shutdown();
         Window::terminate_glfw();
     }
 }
+
+/// Collects the frame times, device memory usage and loaded resources
+fn collect_diagnostics(
+    time: &Time,
+    graphics_context: Option<&graphics::GraphicsContext>,
+    resource_manager: Option<&Arc<ResourceManager>>,
+) -> DiagnosticsSnapshot {
+    let memory = match graphics_context {
+        Some(graphics::GraphicsContext::Vulkan(context)) => match context.memory_stats() {
+            Ok(memory) => Some(memory),
+            Err(e) => {
+                error!("Failed to calculate memory stats '{}'", e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let resources = resource_manager
+        .map(|resource_manager| resource_manager.info())
+        .unwrap_or_default();
+
+    DiagnosticsSnapshot::new(time, memory, &resources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_scene() {
+        let path = std::env::temp_dir().join("sprocket_load_scene.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "entities": [
+                    {
+                        "transform": { "position": { "x": 1.0, "y": 2.0, "z": 3.0 } },
+                        "model": "./data/models/cube.dae"
+                    },
+                    {
+                        "transform": { "position": { "x": -4.0, "y": 0.0, "z": 0.0 } },
+                        "model": "./data/models/suzanne.dae"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let scene = Scene::load(path.to_str().unwrap()).unwrap();
+        assert_ne!(scene.entities[0].model, scene.entities[1].model);
+
+        let mut app = Application::new_headless("load_scene");
+        let entities = app.load_scene(path.to_str().unwrap()).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(app.entity_manager.alive_count(), 2);
+
+        let position = |entity| {
+            app.component_manager
+                .get_component::<Transform>(entity)
+                .unwrap()
+                .position
+        };
+
+        assert_eq!(position(entities[0]), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(position(entities[1]), Vec3::new(-4.0, 0.0, 0.0));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn shutdown() {
+        let mut app = Application::new_headless("shutdown");
+        app.validation = false;
+        app.init_graphics();
+
+        app.resource_manager
+            .as_ref()
+            .unwrap()
+            .load_texture("../sandbox/data/textures/grid.png")
+            .unwrap();
+
+        app.shutdown();
+        assert!(app.resource_manager.is_none());
+        assert!(app.graphics_context.is_none());
+
+        // Shutting down again and dropping does nothing
+        app.shutdown();
+        drop(app);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut app = Application::new_headless("diagnostics");
+        for _ in 0..3 {
+            app.time.update();
+        }
+
+        let diagnostics = app.diagnostics();
+        assert_eq!(diagnostics.framecount, 3);
+        assert_eq!(diagnostics.resource_count, 0);
+        assert!(diagnostics.memory.is_none());
+    }
+
+    #[test]
+    fn despawn() {
+        let mut app = Application::new_headless("despawn");
+        let entity = app.entity_manager.create_entity();
+        let other = app.entity_manager.create_entity();
+
+        for &e in &[entity, other] {
+            app.component_manager
+                .insert_component(e, Transform::default());
+            app.component_manager.insert_component(e, Camera::default());
+        }
+
+        app.despawn(entity);
+        assert!(!app.entity_manager.is_alive(entity));
+        assert!(app
+            .component_manager
+            .get_component::<Transform>(entity)
+            .is_none());
+        assert!(app
+            .component_manager
+            .get_component::<Camera>(entity)
+            .is_none());
+        assert_eq!(app.component_manager.remove_all(entity), 0);
+
+        // Other entities keep their components
+        assert!(app.entity_manager.is_alive(other));
+        assert_eq!(app.component_manager.remove_all(other), 2);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn capture_frame() {
+        let mut app = Application::new("capture_frame");
+        app.validation = false;
+        app.add_window("capture_frame", 64, 64, WindowMode::Windowed);
+        app.init_graphics();
+
+        let window = &app.windows[0];
+        let renderer = app.renderer.as_mut().unwrap();
+
+        // Saturated channels are the same in sRGB and linear
+        renderer.set_clear_color(graphics::Color::new(1.0, 0.0, 1.0, 1.0));
+        renderer.request_capture(window);
+        renderer
+            .draw_frame(window, &app.time, &app.component_manager)
+            .unwrap();
+
+        let (extent, pixels) = renderer.capture_frame(window).unwrap();
+        let extent = ash::vk::Extent2D::from(extent);
+        assert_eq!(
+            pixels.len(),
+            extent.width as usize * extent.height as usize * 4
+        );
+        assert_eq!(&pixels[..4], &[255, 0, 255, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn skip_minimized() {
+        let mut app = Application::new("skip_minimized");
+        app.validation = false;
+        app.add_window("skip_minimized", 64, 64, WindowMode::Windowed);
+        app.init_graphics();
+
+        // Wait for the window manager to minimize the window
+        app.windows[0].minimize();
+        let start = time::Instant::now();
+        while app.windows[0].drawable() && start.elapsed() < time::Duration::from_secs(1) {
+            app.windows[0].process_events();
+        }
+        assert!(!app.windows[0].drawable());
+
+        // Nothing is drawn so no frame is captured
+        let window = &app.windows[0];
+        let renderer = app.renderer.as_mut().unwrap();
+        renderer.request_capture(window);
+        renderer
+            .draw_frame(window, &app.time, &app.component_manager)
+            .unwrap();
+        assert!(matches!(
+            renderer.capture_frame(window),
+            Err(graphics::Error::NoCapturedFrame)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn multiple_windows() {
+        let mut app = Application::new("multiple_windows");
+        app.validation = false;
+        app.add_window("first", 64, 64, WindowMode::Windowed);
+        app.add_window("second", 96, 64, WindowMode::Windowed);
+        app.init_graphics();
+        assert_eq!(app.renderer.as_ref().unwrap().swapchain_count(), 2);
+
+        // Windows added after the renderer get a swapchain immediately
+        app.add_window("third", 64, 96, WindowMode::Windowed);
+        let renderer = app.renderer.as_mut().unwrap();
+        assert_eq!(renderer.swapchain_count(), 3);
+
+        for window in &app.windows {
+            renderer
+                .draw_frame(window, &app.time, &app.component_manager)
+                .unwrap();
+        }
+
+        renderer.remove_window(app.windows[1].id());
+        assert_eq!(renderer.swapchain_count(), 2);
+        renderer
+            .draw_frame(&app.windows[0], &app.time, &app.component_manager)
+            .unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn resize_keeps_pipelines() {
+        let mut app = Application::new("resize_keeps_pipelines");
+        app.validation = false;
+        app.add_window("resize_keeps_pipelines", 64, 64, WindowMode::Windowed);
+        app.init_graphics();
+
+        let spec_path = std::env::temp_dir().join("sprocket_resize_keeps_pipelines.json");
+        std::fs::write(
+            &spec_path,
+            format!(
+                r#"{{
+                    "vertex_shader": "{}",
+                    "fragment_shader": "",
+                    "renderpass": "../sandbox/data/renderpasses/default.json",
+                    "layouts": []
+                }}"#,
+                graphics::vulkan::tests::write_empty_vertex_shader("sprocket_resize.vert.spv")
+            ),
+        )
+        .unwrap();
+
+        let resourcemanager = Arc::clone(app.resource_manager.as_ref().unwrap());
+        let handle = resourcemanager
+            .load_pipeline_handle(spec_path.to_str().unwrap())
+            .unwrap();
+        let pipeline = resourcemanager.resolve_pipeline(handle).unwrap();
+
+        let extent = |resourcemanager: &ResourceManager| {
+            ash::vk::Extent2D::from(resourcemanager.get_swapchain().unwrap().extent())
+        };
+        let old_extent = extent(&resourcemanager);
+
+        // Wait for the window manager to apply the new size
+        app.windows[0].set_size(128, 96);
+        let start = time::Instant::now();
+        while ash::vk::Extent2D::from(app.windows[0].framebuffer_extent()).width == 64
+            && start.elapsed() < time::Duration::from_secs(1)
+        {
+            app.windows[0].process_events();
+        }
+
+        let renderer = app.renderer.as_mut().unwrap();
+        renderer.request_recreate();
+        renderer
+            .draw_frame(&app.windows[0], &app.time, &app.component_manager)
+            .unwrap();
+
+        // The swapchain has a new extent but the pipeline is not rebuilt
+        let new_extent = extent(&resourcemanager);
+        assert_ne!(
+            (old_extent.width, old_extent.height),
+            (new_extent.width, new_extent.height)
+        );
+        let resolved = resourcemanager.resolve_pipeline(handle).unwrap();
+        assert!(Arc::ptr_eq(&pipeline, &resolved));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn diagnostics_resources() {
+        let mut app = Application::new_headless("diagnostics_resources");
+        app.validation = false;
+        app.init_graphics();
+        app.resource_manager
+            .as_ref()
+            .unwrap()
+            .load_texture("../sandbox/data/textures/grid.png")
+            .unwrap();
+        app.time.update();
+
+        let diagnostics = app.diagnostics();
+        assert_eq!(diagnostics.framecount, 1);
+        assert_eq!(diagnostics.resource_count, 1);
+        assert_eq!(diagnostics.resources, vec![("Texture", 1)]);
+        assert!(diagnostics.memory.is_some());
+    }
+}
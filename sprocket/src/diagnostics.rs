@@ -0,0 +1,46 @@
+use crate::graphics::vulkan::{MemoryStats, ResourceInfo};
+use crate::Time;
+
+/// Frame time, memory and resource statistics of a frame, E.g; for an on screen diagnostics
+/// overlay
+/// Frame times are in seconds over the frames averaged by Time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagnosticsSnapshot {
+    pub framecount: usize,
+    pub frame_time_min: f32,
+    pub frame_time_avg: f32,
+    pub frame_time_max: f32,
+    /// The framerate averaged over the same frames as the frame times
+    pub framerate: f32,
+    /// The device memory in use, None if graphics are not initialized
+    pub memory: Option<MemoryStats>,
+    /// The number of loaded resources of all types
+    pub resource_count: usize,
+    /// The number of loaded resources of each type in the order they were first seen, E.g;
+    /// ("Texture", 4)
+    pub resources: Vec<(&'static str, usize)>,
+}
+
+impl DiagnosticsSnapshot {
+    /// Aggregates the frame times of time, the memory stats, and the loaded resources
+    pub fn new(time: &Time, memory: Option<MemoryStats>, resources: &[ResourceInfo]) -> Self {
+        let mut counts: Vec<(&'static str, usize)> = Vec::new();
+        for resource in resources {
+            match counts.iter_mut().find(|(ty, _)| *ty == resource.ty()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((resource.ty(), 1)),
+            }
+        }
+
+        DiagnosticsSnapshot {
+            framecount: time.framecount(),
+            frame_time_min: time.min_delta(),
+            frame_time_avg: time.average_delta(),
+            frame_time_max: time.max_delta(),
+            framerate: time.average_framerate(),
+            memory,
+            resource_count: resources.len(),
+            resources: counts,
+        }
+    }
+}
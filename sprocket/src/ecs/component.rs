@@ -1,5 +1,5 @@
 use super::Entity;
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 
 /// The type of a component type id
 /// Is determined by the pointer to std::any::type_id
@@ -20,22 +20,22 @@ impl std::fmt::Debug for ComponentType {
 }
 
 /// Represents an update of component values
-/// Stores the components internally as a Vec<u8> and can therefore be stored along with different
-/// types
+/// Stores the components internally as a type erased Vec<(Entity, T)> and can therefore be stored
+/// along with different types
 /// Ability to be converted into a list of concrete types
 /// When converting with update.into::<T>(), T needs to be the same type as it was created with, or
 /// else it will panic!
 /// Use try_into to not panic!
 pub struct ComponentUpdate {
     ty: ComponentType,
-    data: Vec<u8>,
+    data: Box<dyn Any + Send>,
 }
 
 impl ComponentUpdate {
-    pub fn new<T: 'static>(components: Vec<(Entity, T)>) -> Self {
+    pub fn new<T: Send + 'static>(components: Vec<(Entity, T)>) -> Self {
         Self {
             ty: ComponentType::get::<T>(),
-            data: unsafe { std::mem::transmute::<Vec<(Entity, T)>, Vec<u8>>(components) },
+            data: Box::new(components),
         }
     }
 
@@ -47,21 +47,46 @@ impl ComponentUpdate {
     /// Returns None if the type converted into is not the same as the type it was created with
     /// Consumes self
     pub fn try_into<T: 'static>(self) -> Option<Vec<(Entity, T)>> {
-        if ComponentType::get::<T>() != self.ty {
-            return None;
-        }
-
-        Some(unsafe { std::mem::transmute::<Vec<u8>, Vec<(Entity, T)>>(self.data) })
+        self.data
+            .downcast::<Vec<(Entity, T)>>()
+            .ok()
+            .map(|components| *components)
     }
 }
 
 /// Converts a ComponentUpdate into a Vec of a concrete type
 impl<T: 'static> From<ComponentUpdate> for Vec<(Entity, T)> {
     fn from(components: ComponentUpdate) -> Self {
-        if ComponentType::get::<T>() != components.ty {
-            panic!("Attempt to convert ComponentUpdate into mismatched concrete type. Expected type {:?}. Actual type {:?}", components.ty, ComponentType::get::<T>());
+        let ty = components.ty;
+        match components.try_into() {
+            Some(components) => components,
+            None => panic!("Attempt to convert ComponentUpdate into mismatched concrete type. Expected type {:?}. Actual type {:?}", ty, ComponentType::get::<T>()),
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::EntityManager;
+
+    #[test]
+    fn round_trip() {
+        let mut entity_manager = EntityManager::new();
+        let components: Vec<_> = (0..16)
+            .map(|i| (entity_manager.create_entity(), [i, i * 2, u64::MAX - i, 7]))
+            .collect();
+
+        let update = ComponentUpdate::new(components.clone());
+        assert_eq!(update.ty(), ComponentType::get::<[u64; 4]>());
+        assert_eq!(Vec::<(Entity, [u64; 4])>::from(update), components);
+    }
+
+    #[test]
+    fn mismatched_type() {
+        let mut entity_manager = EntityManager::new();
+        let update = ComponentUpdate::new(vec![(entity_manager.create_entity(), 1u32)]);
 
-        unsafe { std::mem::transmute::<Vec<u8>, Vec<(Entity, T)>>(components.data) }
+        assert!(update.try_into::<u64>().is_none());
     }
 }
@@ -5,6 +5,10 @@ use std::{collections::HashMap, ops::Deref, ops::DerefMut};
 /// Interface for the generic concrete ComponentArray
 pub trait IComponentArray {
     fn component_type(&self) -> ComponentType;
+
+    /// Removes and drops the component of entity if any
+    /// Returns true if a component was removed
+    fn remove_entity(&mut self, entity: Entity) -> bool;
 }
 
 /// Represents an array that holds a components of type T associated to entities
@@ -12,9 +16,12 @@ pub trait IComponentArray {
 /// together used components together
 /// Can deref and deref_mut to a slice
 pub struct ComponentArray<T: 'static> {
-    /// Maps an entity id to and index in the array
+    /// Maps an entity index to and index in the array
     /// This is necessary since the array is not sparse
-    entity_map: HashMap<Entity, usize>,
+    entity_map: HashMap<usize, usize>,
+    /// The full entity handle owning the component at the same index
+    /// Used to reject stale handles with an old generation
+    entities: Vec<Entity>,
     /// A non-sparse list of components, index does not map to entity id
     components: Vec<T>,
 }
@@ -24,6 +31,7 @@ impl<T: 'static> ComponentArray<T> {
     pub fn new() -> Self {
         Self {
             entity_map: HashMap::new(),
+            entities: Vec::new(),
             components: Vec::new(),
         }
     }
@@ -32,6 +40,7 @@ impl<T: 'static> ComponentArray<T> {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             entity_map: HashMap::with_capacity(capacity),
+            entities: Vec::with_capacity(capacity),
             components: Vec::with_capacity(capacity),
         }
     }
@@ -39,29 +48,48 @@ impl<T: 'static> ComponentArray<T> {
     /// Returns a mutable component for an entity
     /// Returns None if component doesn't exist for entity
     pub fn get_component_mut(&mut self, entity: Entity) -> Option<&mut T> {
-        let index = self.entity_map.get(&entity)?;
-        Some(&mut self.components[*index])
+        let index = self.index_of(entity)?;
+        Some(&mut self.components[index])
     }
 
     /// Returns a component for an entity
     /// Returns None if component doesn't exist for entity
     pub fn get_component(&self, entity: Entity) -> Option<&T> {
-        let index = self.entity_map.get(&entity)?;
-        Some(&self.components[*index])
+        let index = self.index_of(entity)?;
+        Some(&self.components[index])
     }
 
     /// Inserts a component for entity
     /// If a component already exists for the entity, it is replaced and returned
+    /// A component left by a destroyed entity with the same index is dropped
+    /// Nothing is inserted if entity is a stale handle whose index has been reused by a newer
+    /// entity
     pub fn insert_component(&mut self, entity: Entity, component: T) -> Option<T> {
-        // Component already exists; replace
-        if let Some(index) = self.entity_map.get(&entity) {
-            Some(std::mem::replace(&mut self.components[*index], component))
+        // Index already used; replace
+        if let Some(&index) = self.entity_map.get(&entity.index()) {
+            let old_entity = self.entities[index];
+
+            // Don't overwrite the component of the live entity
+            if old_entity.generation() > entity.generation() {
+                return None;
+            }
+
+            let old = std::mem::replace(&mut self.components[index], component);
+            self.entities[index] = entity;
+
+            // Only return the component if it belonged to the same entity
+            if old_entity == entity {
+                Some(old)
+            } else {
+                None
+            }
         }
         // New component
         else {
             let component_index = self.components.len();
             self.components.push(component);
-            self.entity_map.insert(entity, component_index);
+            self.entities.push(entity);
+            self.entity_map.insert(entity.index(), component_index);
             None
         }
     }
@@ -77,8 +105,31 @@ impl<T: 'static> ComponentArray<T> {
     /// Removes and returns (if any) a component associated to entity
     /// Returns None if component doesn't exist for entity
     pub fn remove_component(&mut self, entity: Entity) -> Option<T> {
-        if let Some(index) = self.entity_map.remove(&entity) {
-            Some(self.components.remove(index))
+        let index = self.index_of(entity)?;
+        self.entity_map.remove(&entity.index());
+
+        // Move the last component into the hole and update its index
+        let component = self.components.swap_remove(index);
+        self.entities.swap_remove(index);
+        if let Some(moved) = self.entities.get(index) {
+            self.entity_map.insert(moved.index(), index);
+        }
+
+        Some(component)
+    }
+
+    /// Returns the entities in the same order as the components
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Returns the index in the array of the component for entity
+    /// Returns None if the stored component belongs to another generation of the entity
+    fn index_of(&self, entity: Entity) -> Option<usize> {
+        let index = *self.entity_map.get(&entity.index())?;
+
+        if self.entities[index] == entity {
+            Some(index)
         } else {
             None
         }
@@ -113,4 +164,40 @@ impl<T> IComponentArray for ComponentArray<T> {
     fn component_type(&self) -> ComponentType {
         ComponentType::get::<T>()
     }
+
+    fn remove_entity(&mut self, entity: Entity) -> bool {
+        self.remove_component(entity).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::EntityManager;
+
+    #[test]
+    fn stale_handle() {
+        let mut entity_manager = EntityManager::new();
+        let mut array = ComponentArray::new();
+
+        let old = entity_manager.create_entity();
+        array.insert_component(old, 1);
+        array.remove_component(old);
+        entity_manager.destroy_entity(old);
+
+        // Reuses the slot of the destroyed entity
+        let new = entity_manager.create_entity();
+        assert_eq!(old.index(), new.index());
+        assert_ne!(old, new);
+
+        array.insert_component(new, 2);
+        assert_eq!(array.get_component(old), None);
+        assert_eq!(array.get_component(new), Some(&2));
+
+        // The stale handle can not overwrite the component of the new entity
+        assert_eq!(array.insert_component(old, 3), None);
+        assert_eq!(array.get_component(new), Some(&2));
+        assert_eq!(array.remove_component(old), None);
+        assert_eq!(array.len(), 1);
+    }
 }
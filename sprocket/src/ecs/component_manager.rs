@@ -61,6 +61,16 @@ impl ComponentManager {
         component_array.remove_component(entity)
     }
 
+    /// Removes the components of entity from all registered component arrays
+    /// Returns the number of removed components
+    pub fn remove_all(&mut self, entity: Entity) -> usize {
+        self.component_arrays
+            .values_mut()
+            .map(|component_array| component_array.remove_entity(entity))
+            .filter(|removed| *removed)
+            .count()
+    }
+
     //     /// Processes the events that have happened since last time, like mutation, insertion, and
     //     /// removal
     //     /// Generates a list contaning a list of changed components for each component type
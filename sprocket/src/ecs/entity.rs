@@ -1,17 +1,35 @@
+/// A handle to an entity
+/// Consists of an index and a generation which is incremented each time the index is freed
+/// A handle to a destroyed entity will therefore not be equal to a new entity reusing the index
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    /// Returns the index of the entity
+    /// Indices are reused after an entity is destroyed
+    pub fn index(&self) -> usize {
+        self.index
+    }
 
-pub struct Entity(usize);
+    /// Returns how many times the index has been reused
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
 
 impl std::fmt::Display for Entity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Entity {}", self.0)
+        write!(f, "Entity {}:{}", self.index, self.generation)
     }
 }
 
 /// Converts an entity id to the underlying index type
 impl From<Entity> for usize {
     fn from(e: Entity) -> Self {
-        e.0
+        e.index
     }
 }
 
@@ -21,10 +39,12 @@ impl From<Entity> for usize {
 pub struct EntityManager {
     alive_count: usize,
 
-    /// A list of recently freed entity IDs that are available for use
-    /// If this list is empty, all freed spots are taken and new IDs can be taken numerically
-    /// Empty also means there are no holes in the IDs, and new IDs will be alive_count+1
-    free_ids: Vec<Entity>,
+    /// The current generation for each index ever handed out
+    generations: Vec<u32>,
+
+    /// A list of recently freed entity indices that are available for use
+    /// If this list is empty, all freed spots are taken and new indices are taken numerically
+    free_ids: Vec<usize>,
 }
 
 impl EntityManager {
@@ -37,6 +57,7 @@ impl EntityManager {
     pub fn new() -> Self {
         Self {
             alive_count: 0,
+            generations: Vec::new(),
             free_ids: Vec::new(),
         }
     }
@@ -46,26 +67,47 @@ impl EntityManager {
     /// Entities currently do not have names
     /// May be implemented with an Info component (to keep things consistent)
     pub fn create_entity(&mut self) -> Entity {
-        if let Some(id) = self.free_ids.pop() {
-            log::debug!("Reusing entity id {}", id);
-            id
+        self.alive_count += 1;
+
+        if let Some(index) = self.free_ids.pop() {
+            let entity = Entity {
+                index,
+                generation: self.generations[index],
+            };
+            log::debug!("Reusing entity id {}", entity);
+            entity
         } else {
-            let id = self.alive_count;
-            log::debug!("Creating new entity id {}", id);
-            self.alive_count += 1;
-            Entity(id)
+            let entity = Entity {
+                index: self.generations.len(),
+                generation: 0,
+            };
+            log::debug!("Creating new entity id {}", entity);
+            self.generations.push(0);
+            entity
         }
     }
 
     /// Destroys an entity
-    /// Entity should not be used afterwards
+    /// Entity should not be used afterwards and will not compare equal to any new entity
     pub fn destroy_entity(&mut self, entity: Entity) {
-        if entity.0 >= self.alive_count {
+        if !self.is_alive(entity) {
             log::error!("Invalid entity handle {}", entity);
             return;
         }
 
         log::debug!("Destroying entity with id {}", entity);
-        self.free_ids.push(entity)
+        self.generations[entity.index] = self.generations[entity.index].wrapping_add(1);
+        self.free_ids.push(entity.index);
+        self.alive_count -= 1;
+    }
+
+    /// Returns true if the entity has been created and not yet destroyed
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index) == Some(&entity.generation)
+    }
+
+    /// Returns the number of currently alive entities
+    pub fn alive_count(&self) -> usize {
+        self.alive_count
     }
 }
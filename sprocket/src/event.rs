@@ -1,26 +1,209 @@
+use crate::math::Vec2;
 use num_derive::FromPrimitive;
+use std::collections::HashSet;
+use std::sync::mpsc;
 
 #[derive(Debug, PartialEq)]
 pub enum Event {
     WindowClose,
+    /// The logical size of the window changed, in screen coordinates
     WindowResize(i32, i32),
+    /// The size of the window framebuffer changed, in pixels
+    /// Differs from the window size on HiDPI displays
+    FramebufferResize(i32, i32),
     WindowFocus(bool),
+    /// The window was minimized or restored from being minimized
+    WindowIconify(bool),
+    /// The contents of the window need to be redrawn, E.g; while it is being resized
+    /// Some platforms do not send resize events until a continuous resize is released
+    WindowRefresh,
 
     MousePosition(i32, i32),
+    /// Relative cursor movement since the last event
+    /// Only sent when the cursor is disabled, E.g; for camera control
+    MouseMotion(i32, i32),
     Scroll(i32, i32),
-    /// Key or mouse button was pressed down
-    KeyPress(KeyCode),
+    /// Key or mouse button was pressed down with the modifier keys held at the time
+    KeyPress(KeyCode, Modifiers),
     /// Key or mouse button was released
-    KeyRelease(KeyCode),
+    KeyRelease(KeyCode, Modifiers),
     /// Key was held down long enough to be repeated
-    KeyRepeat(KeyCode),
+    KeyRepeat(KeyCode, Modifiers),
     /// A full character taking into account modifier keys and dead keys
     /// Use this for user input
     CharacterType(char),
     Dummy(String),
 }
 
-#[derive(FromPrimitive, Debug, PartialEq)]
+/// A set of modifier keys held down together with a key
+/// The bits match the GLFW modifier bits
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(0x1);
+    pub const CONTROL: Modifiers = Modifiers(0x2);
+    pub const ALT: Modifiers = Modifiers(0x4);
+    pub const SUPER: Modifiers = Modifiers(0x8);
+
+    /// Decodes the mods passed to GLFW key and mouse button callbacks
+    /// Caps lock and num lock state are ignored
+    pub fn from_glfw(mods: i32) -> Self {
+        Modifiers(mods as u8 & 0xf)
+    }
+
+    /// Returns true if all modifiers in other are in self
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn shift(self) -> bool {
+        self.contains(Self::SHIFT)
+    }
+
+    pub fn control(self) -> bool {
+        self.contains(Self::CONTROL)
+    }
+
+    pub fn alt(self) -> bool {
+        self.contains(Self::ALT)
+    }
+
+    pub fn super_key(self) -> bool {
+        self.contains(Self::SUPER)
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, rhs: Modifiers) -> Modifiers {
+        Modifiers(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Modifiers) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Collects the events sent by windows through a channel
+/// The events can be taken all at once or filtered by kind, in the order they were sent
+/// Consecutive high frequency mouse events are coalesced until drained, only the latest mouse
+/// position is kept, and mouse motion and scroll are accumulated into a single event
+/// Consecutive window refreshes are collapsed into one
+pub struct EventQueue {
+    sender: mpsc::Sender<Event>,
+    receiver: mpsc::Receiver<Event>,
+    /// Received events not yet drained
+    pending: Vec<Event>,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        EventQueue {
+            sender,
+            receiver,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns a sender for windows to send events through
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
+    /// Moves the events sent since the last call from the channel into pending
+    fn receive(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            self.push(event);
+        }
+    }
+
+    /// Adds an event to pending, replacing or accumulating the last pending event if it is of the
+    /// same kind
+    /// Events are never coalesced across other events, E.g; a click is handled with the cursor
+    /// position from before it
+    fn push(&mut self, event: Event) {
+        let previous = match self.pending.last() {
+            Some(last) if coalesces(last, &event) => self.pending.pop(),
+            _ => None,
+        };
+
+        let event = match (previous, event) {
+            (Some(Event::MouseMotion(px, py)), Event::MouseMotion(x, y)) => {
+                Event::MouseMotion(px + x, py + y)
+            }
+            (Some(Event::Scroll(px, py)), Event::Scroll(x, y)) => Event::Scroll(px + x, py + y),
+            (_, event) => event,
+        };
+
+        self.pending.push(event);
+    }
+
+    /// Removes and returns all events
+    pub fn drain(&mut self) -> Vec<Event> {
+        self.receive();
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Removes and returns the events matching filter
+    /// Other events are kept for later calls
+    pub fn drain_filter<F: FnMut(&Event) -> bool>(&mut self, mut filter: F) -> Vec<Event> {
+        self.receive();
+        let (matching, rest) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|event| filter(event));
+        self.pending = rest;
+        matching
+    }
+
+    /// Removes and returns the key press, release and repeat events
+    pub fn key_events(&mut self) -> Vec<Event> {
+        self.drain_filter(|event| {
+            matches!(
+                event,
+                Event::KeyPress(..) | Event::KeyRelease(..) | Event::KeyRepeat(..)
+            )
+        })
+    }
+
+    /// Removes and returns the window and framebuffer resize events
+    pub fn resize_events(&mut self) -> Vec<Event> {
+        self.drain_filter(|event| {
+            matches!(
+                event,
+                Event::WindowResize(_, _) | Event::FramebufferResize(_, _)
+            )
+        })
+    }
+}
+
+/// Returns true if event replaces or accumulates into a pending event
+fn coalesces(pending: &Event, event: &Event) -> bool {
+    matches!(
+        (pending, event),
+        (Event::MousePosition(..), Event::MousePosition(..))
+            | (Event::MouseMotion(..), Event::MouseMotion(..))
+            | (Event::Scroll(..), Event::Scroll(..))
+            | (Event::WindowRefresh, Event::WindowRefresh)
+    )
+}
+
+#[derive(FromPrimitive, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum KeyCode {
     Invalid = 0,
     Space = 32,
@@ -154,3 +337,205 @@ pub enum KeyCode {
     Mouse5 = 354,
     Mouse6 = 355,
 }
+
+/// Keeps track of the current state of keys, mouse buttons, cursor and scroll
+/// Updated from the event stream and queried by systems
+/// Pressed, released and scroll are per frame and cleared by end_frame
+pub struct InputState {
+    down: HashSet<KeyCode>,
+    pressed: HashSet<KeyCode>,
+    released: HashSet<KeyCode>,
+    mouse_position: Vec2,
+    scroll_delta: Vec2,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            mouse_position: Vec2::zero(),
+            scroll_delta: Vec2::zero(),
+        }
+    }
+
+    /// Updates the state from an event
+    /// Events not related to input are ignored
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyPress(key, _) => {
+                self.down.insert(key);
+                self.pressed.insert(key);
+            }
+            Event::KeyRelease(key, _) => {
+                self.down.remove(&key);
+                self.released.insert(key);
+            }
+            Event::MousePosition(x, y) => self.mouse_position = Vec2::new(x as f32, y as f32),
+            Event::Scroll(x, y) => self.scroll_delta += Vec2::new(x as f32, y as f32),
+            // Keys are released when focus is lost without a release event
+            Event::WindowFocus(false) => self.down.clear(),
+            _ => {}
+        }
+    }
+
+    /// Clears the per frame state
+    /// Should be called once each frame before the events of the frame are handled, so that the
+    /// state can be queried for the rest of the frame
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.scroll_delta = Vec2::zero();
+    }
+
+    /// Returns true if the key or mouse button is currently held down
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.down.contains(&key)
+    }
+
+    /// Returns the modifier keys currently held down
+    pub fn modifiers(&self) -> Modifiers {
+        let mut modifiers = Modifiers::NONE;
+        let keys = [
+            (KeyCode::LeftShift, KeyCode::RightShift, Modifiers::SHIFT),
+            (
+                KeyCode::LeftControl,
+                KeyCode::RightControl,
+                Modifiers::CONTROL,
+            ),
+            (KeyCode::LeftAlt, KeyCode::RightAlt, Modifiers::ALT),
+            (KeyCode::LeftSuper, KeyCode::RightSuper, Modifiers::SUPER),
+        ];
+
+        for &(left, right, modifier) in keys.iter() {
+            if self.is_key_down(left) || self.is_key_down(right) {
+                modifiers |= modifier;
+            }
+        }
+        modifiers
+    }
+
+    /// Returns true if the key or mouse button was pressed this frame
+    pub fn was_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// Returns true if the key or mouse button was released this frame
+    pub fn was_released(&self, key: KeyCode) -> bool {
+        self.released.contains(&key)
+    }
+
+    /// Returns the last known cursor position in window coordinates
+    pub fn mouse_position(&self) -> Vec2 {
+        self.mouse_position
+    }
+
+    /// Returns the accumulated scroll this frame
+    pub fn scroll_delta(&self) -> Vec2 {
+        self.scroll_delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let mut events = EventQueue::new();
+        let sender = events.sender();
+
+        let press = || Event::KeyPress(KeyCode::A, Modifiers::NONE);
+        let release = || Event::KeyRelease(KeyCode::A, Modifiers::NONE);
+
+        sender.send(press()).unwrap();
+        sender.send(Event::WindowResize(800, 600)).unwrap();
+        sender.send(Event::CharacterType('a')).unwrap();
+        sender.send(release()).unwrap();
+        sender.send(Event::FramebufferResize(1600, 1200)).unwrap();
+        sender.send(Event::WindowClose).unwrap();
+
+        assert_eq!(events.key_events(), vec![press(), release()]);
+        assert_eq!(
+            events.resize_events(),
+            vec![
+                Event::WindowResize(800, 600),
+                Event::FramebufferResize(1600, 1200)
+            ]
+        );
+        assert_eq!(
+            events.drain_filter(|event| *event == Event::WindowClose),
+            vec![Event::WindowClose]
+        );
+
+        // Unmatched events are kept
+        assert_eq!(events.drain(), vec![Event::CharacterType('a')]);
+    }
+
+    #[test]
+    fn coalesce_keeps_order() {
+        let mut events = EventQueue::new();
+        let sender = events.sender();
+
+        sender.send(Event::MousePosition(1, 1)).unwrap();
+        sender.send(Event::MousePosition(2, 2)).unwrap();
+        sender
+            .send(Event::KeyPress(KeyCode::Mouse0, Modifiers::NONE))
+            .unwrap();
+        sender.send(Event::MousePosition(3, 3)).unwrap();
+        sender.send(Event::Scroll(0, 1)).unwrap();
+        sender.send(Event::Scroll(0, 2)).unwrap();
+
+        assert_eq!(
+            events.drain(),
+            vec![
+                Event::MousePosition(2, 2),
+                Event::KeyPress(KeyCode::Mouse0, Modifiers::NONE),
+                Event::MousePosition(3, 3),
+                Event::Scroll(0, 3),
+            ]
+        );
+        assert_eq!(events.drain(), Vec::new());
+    }
+
+    #[test]
+    fn input_state() {
+        let mut input = InputState::new();
+
+        input.end_frame();
+        input.handle_event(&Event::KeyPress(KeyCode::W, Modifiers::NONE));
+        input.handle_event(&Event::KeyPress(KeyCode::LeftShift, Modifiers::NONE));
+        input.handle_event(&Event::MousePosition(10, 20));
+        input.handle_event(&Event::Scroll(0, 1));
+        input.handle_event(&Event::Scroll(0, 2));
+
+        assert!(input.is_key_down(KeyCode::W));
+        assert!(input.was_pressed(KeyCode::W));
+        assert!(!input.is_key_down(KeyCode::A));
+        assert_eq!(input.modifiers(), Modifiers::SHIFT);
+        assert_eq!(input.mouse_position(), Vec2::new(10.0, 20.0));
+        assert_eq!(input.scroll_delta(), Vec2::new(0.0, 3.0));
+
+        // The next frame keeps held keys and the cursor but not the per frame state
+        input.end_frame();
+        input.handle_event(&Event::KeyRelease(KeyCode::W, Modifiers::NONE));
+
+        assert!(!input.is_key_down(KeyCode::W));
+        assert!(!input.was_pressed(KeyCode::W));
+        assert!(input.was_released(KeyCode::W));
+        assert!(input.is_key_down(KeyCode::LeftShift));
+        assert_eq!(input.mouse_position(), Vec2::new(10.0, 20.0));
+        assert_eq!(input.scroll_delta(), Vec2::zero());
+
+        // Keys are released when the window loses focus
+        input.handle_event(&Event::WindowFocus(false));
+        assert!(!input.is_key_down(KeyCode::LeftShift));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::math::*;
+use crate::physics::Transform;
+
+/// A component describing a perspective camera
+/// The camera looks along the forward direction of its transform, Transform::forward
+pub struct Camera {
+    /// The vertical field of view in radians
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub transform: Transform,
+}
+
+impl Camera {
+    pub fn new(fov: f32, near: f32, far: f32, transform: Transform) -> Self {
+        Camera {
+            fov,
+            near,
+            far,
+            transform,
+        }
+    }
+
+    /// Returns the matrix transforming from world space to view space
+    pub fn view_matrix(&self) -> Mat4 {
+        let position = self.transform.position;
+        Mat4::look_at(
+            position,
+            position + self.transform.forward(),
+            self.transform.up(),
+        )
+    }
+
+    /// Returns the perspective projection for a viewport with the given aspect ratio
+    pub fn projection_matrix(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective(aspect, self.fov, self.near, self.far)
+    }
+}
+
+impl Default for Camera {
+    /// A camera at z = 5 turned around to look back at the origin
+    fn default() -> Self {
+        let mut transform = Transform::new(Vec3::new(0.0, 0.0, 5.0));
+        transform.rotation = Quat::axis_angle(Vec3::up(), std::f32::consts::PI);
+        Camera::new(1.0, 0.1, 10.0, transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transforms a world space point into view space
+    fn to_view(camera: &Camera, point: Vec3) -> Vec3 {
+        camera.view_matrix().transform_point(point)
+    }
+
+    #[test]
+    fn looks_along_forward() {
+        let mut transform = Transform::new(Vec3::new(1.0, 2.0, 3.0));
+        transform.rotation = Quat::axis_angle(Vec3::up(), 0.7);
+        let camera = Camera::new(1.0, 0.1, 10.0, transform);
+
+        // A point in front of the camera is straight down the negative view z axis
+        let ahead = to_view(&camera, transform.position + transform.forward() * 4.0);
+        assert!(ahead.approx_eq(&Vec3::new(0.0, 0.0, -4.0), 1e-4));
+
+        let above = to_view(&camera, transform.position + transform.up());
+        assert!(above.approx_eq(&Vec3::new(0.0, 1.0, 0.0), 1e-4));
+    }
+
+    #[test]
+    fn moving_changes_view() {
+        let mut camera = Camera::default();
+        let point = Vec3::new(1.0, 2.0, 0.0);
+        let before = to_view(&camera, point);
+
+        // The default camera faces -z with +x to the right of the view, so moving it along +x
+        // moves the point the opposite way in view space
+        camera.transform.position += Vec3::right();
+        assert!(camera.view_matrix() != Camera::default().view_matrix());
+        assert!(to_view(&camera, point).approx_eq(&(before - Vec3::right()), 1e-4));
+    }
+
+    #[test]
+    fn default_looks_at_origin() {
+        let camera = Camera::default();
+        let origin = to_view(&camera, Vec3::zero());
+        assert!(origin.approx_eq(&Vec3::new(0.0, 0.0, -5.0), 1e-4));
+    }
+}
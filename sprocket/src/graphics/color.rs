@@ -0,0 +1,158 @@
+use crate::math::Vec4;
+use serde::{Deserialize, Serialize};
+
+/// Encoded sRGB values at or below this are on the linear segment of the transfer function
+const SRGB_BREAKPOINT: f32 = 0.04045;
+/// Linear values at or below this are on the linear segment of the transfer function
+const LINEAR_BREAKPOINT: f32 = 0.0031308;
+
+/// A color with red, green, blue and alpha components from 0 to 1
+/// Colors passed to the renderer, E.g; clear colors, are in linear space and are converted to
+/// sRGB when written to an sRGB swapchain
+/// Colors from image editors and color pickers are sRGB encoded and need to be converted with
+/// from_srgb8 or to_linear
+#[repr(C)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    /// Creates an opaque color
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub fn white() -> Self {
+        Self::rgb(1.0, 1.0, 1.0)
+    }
+
+    pub fn black() -> Self {
+        Self::rgb(0.0, 0.0, 0.0)
+    }
+
+    /// Creates a linear color from 8 bit sRGB encoded components, E.g; #bc8f8f
+    /// Alpha is always linear
+    pub fn from_srgb8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+        .to_linear()
+    }
+
+    /// Converts a linear color to 8 bit sRGB encoded components, rounded to the nearest value
+    pub fn to_srgb8(&self) -> [u8; 4] {
+        let srgb = self.to_srgb();
+        let quantize = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [
+            quantize(srgb.r),
+            quantize(srgb.g),
+            quantize(srgb.b),
+            quantize(srgb.a),
+        ]
+    }
+
+    /// Decodes an sRGB encoded color to linear using the sRGB transfer function
+    /// Alpha is left unchanged
+    pub fn to_linear(&self) -> Self {
+        let decode = |c: f32| {
+            if c <= SRGB_BREAKPOINT {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        Color::new(decode(self.r), decode(self.g), decode(self.b), self.a)
+    }
+
+    /// Encodes a linear color to sRGB using the inverse sRGB transfer function
+    /// Alpha is left unchanged
+    pub fn to_srgb(&self) -> Self {
+        let encode = |c: f32| {
+            if c <= LINEAR_BREAKPOINT {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+
+        Color::new(encode(self.r), encode(self.g), encode(self.b), self.a)
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::white()
+    }
+}
+
+impl From<Vec4> for Color {
+    fn from(v: Vec4) -> Self {
+        Color::new(v.x, v.y, v.z, v.w)
+    }
+}
+
+impl From<Color> for Vec4 {
+    fn from(color: Color) -> Self {
+        Vec4::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn srgb8_round_trip() {
+        let color = Color::from_srgb8(188, 143, 143, 128);
+        assert!(approx_eq(color.r, 0.502886));
+        assert!(approx_eq(color.a, 128.0 / 255.0));
+        assert_eq!(color.to_srgb8(), [188, 143, 143, 128]);
+
+        for c in 0..=255 {
+            assert_eq!(Color::from_srgb8(c, c, c, c).to_srgb8(), [c, c, c, c]);
+        }
+    }
+
+    #[test]
+    fn transfer_function() {
+        // Linear segment up to and including the breakpoint
+        let color = Color::rgb(SRGB_BREAKPOINT, 0.0, 1.0).to_linear();
+        assert!(approx_eq(color.r, SRGB_BREAKPOINT / 12.92));
+        assert!(approx_eq(color.r, LINEAR_BREAKPOINT));
+        assert_eq!(color.g, 0.0);
+        assert!(approx_eq(color.b, 1.0));
+
+        // Power segment above it
+        let color = Color::rgb(0.5, 0.0, 0.0).to_linear();
+        assert!(approx_eq(color.r, 0.214041));
+
+        let color = Color::rgb(LINEAR_BREAKPOINT, 0.214041, 1.0).to_srgb();
+        assert!(approx_eq(color.r, SRGB_BREAKPOINT));
+        assert!(approx_eq(color.g, 0.5));
+        assert!(approx_eq(color.b, 1.0));
+    }
+
+    #[test]
+    fn vec4() {
+        let color = Color::new(0.1, 0.2, 0.3, 0.4);
+        let v = Vec4::from(color);
+        assert!(v.approx_eq(&Vec4::new(0.1, 0.2, 0.3, 0.4), 0.0));
+        assert_eq!(Color::from(v), color);
+    }
+}
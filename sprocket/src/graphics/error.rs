@@ -26,11 +26,35 @@ pub enum Error {
     ParseError,
     UnimplementedFeature(&'static str),
     MissingDescriptorSet(u32),
+    MissingShader(String),
+    EmptyDescriptorSetLayout(usize),
+    GLTFError(String),
+    UnsupportedFormat(vk::Format),
+    PixelDataMismatch(usize, usize),
+    Headless,
+    UnsupportedSurface,
+    MismatchedCubemapFace(String),
+    MissingFeature(&'static str),
+    BufferOverflow(usize, usize),
+    ClearValueCount(usize, usize),
+    PushConstantsTooLarge(usize, usize),
+    NotHostVisible,
+    NoCapturedFrame,
+    MissingPass(String),
+    CyclicPasses,
+    InvalidFramesInFlight(usize, usize),
+    DeviceLost,
+    NoSupportedFormat(vk::FormatFeatureFlags),
+    InvalidSubpass(u32),
+    InvalidPoolSize(u64, u64),
 }
 
 impl From<vk::Result> for Error {
     fn from(error: vk::Result) -> Self {
-        Error::VulkanError(error)
+        match error {
+            vk::Result::ERROR_DEVICE_LOST => Error::DeviceLost,
+            error => Error::VulkanError(error),
+        }
     }
 }
 
@@ -95,6 +119,27 @@ impl std::fmt::Display for Error {
             Error::ParseError => write!(f, "Failed to parse string into a type"),
             Error::UnimplementedFeature(e) => write!(f, "Feature {} is not yet implemented", e),
             Error::MissingDescriptorSet(set_index) => write!(f, "Missing required descriptor set {}", set_index),
+            Error::MissingShader(path) => write!(f, "Cannot find shader '{}'", path),
+            Error::EmptyDescriptorSetLayout(set_index) => write!(f, "Descriptor set layout {} has no bindings", set_index),
+            Error::GLTFError(e) => write!(f, "Failed to read glTF file {}", e),
+            Error::UnsupportedFormat(format) => write!(f, "The format {:?} is not supported", format),
+            Error::PixelDataMismatch(supplied, expected) => write!(f, "Pixel data size does not match the image. Expected {} bytes, supplied {}", expected, supplied),
+            Error::Headless => write!(f, "The operation requires a window surface but the context is headless"),
+            Error::UnsupportedSurface => write!(f, "The device can not present to the window surface"),
+            Error::BufferOverflow(required, capacity) => write!(f, "Buffer of capacity {} can not hold {} elements", capacity, required),
+            Error::MissingFeature(feature) => write!(f, "The device feature '{}' is required but not enabled", feature),
+            Error::ClearValueCount(supplied, expected) => write!(f, "Clear value count does not match the renderpass attachments. Expected {}, supplied {}", expected, supplied),
+            Error::PushConstantsTooLarge(size, limit) => write!(f, "Push constants of {} bytes exceed the device limit of {} bytes", size, limit),
+            Error::NotHostVisible => write!(f, "The buffer is device local and can not be written to from the host"),
+            Error::NoCapturedFrame => write!(f, "No frame has been captured for the window"),
+            Error::MissingPass(name) => write!(f, "A render graph pass depends on the missing pass '{}'", name),
+            Error::CyclicPasses => write!(f, "The render graph passes have cyclic dependencies"),
+            Error::InvalidFramesInFlight(count, image_count) => write!(f, "Frames in flight must be between 1 and the swapchain image count {}, got {}", image_count, count),
+            Error::DeviceLost => write!(f, "The graphics device was lost"),
+            Error::NoSupportedFormat(features) => write!(f, "None of the candidate formats support {:?}", features),
+            Error::InvalidSubpass(index) => write!(f, "The renderpass has no subpass {}", index),
+            Error::InvalidPoolSize(slice_size, capacity) => write!(f, "A pool of {} slices of {} bytes is empty or too large", capacity, slice_size),
+            Error::MismatchedCubemapFace(path) => write!(f, "Cubemap face '{}' is not square or differs in size from the other faces", path),
         }
     }
 }
@@ -20,13 +20,30 @@ extern "C" {
     ) -> *mut ffi::c_void;
     pub fn glfwDestroyWindow(window: *mut GLFWwindow);
     pub fn glfwWindowShouldClose(window: *mut GLFWwindow) -> i32;
+    pub fn glfwSetWindowTitle(window: *mut GLFWwindow, title: *const i8);
+    pub fn glfwSetWindowSize(window: *mut GLFWwindow, width: i32, height: i32);
+    pub fn glfwSetWindowPos(window: *mut GLFWwindow, xpos: i32, ypos: i32);
+    pub fn glfwGetFramebufferSize(window: *mut GLFWwindow, width: *mut i32, height: *mut i32);
+    pub fn glfwGetWindowContentScale(window: *mut GLFWwindow, xscale: *mut f32, yscale: *mut f32);
+    pub fn glfwIconifyWindow(window: *mut GLFWwindow);
+    pub fn glfwMaximizeWindow(window: *mut GLFWwindow);
+    pub fn glfwRestoreWindow(window: *mut GLFWwindow);
     pub fn glfwPollEvents();
     pub fn glfwWindowHint(hint: i32, value: i32);
+    pub fn glfwDefaultWindowHints();
     pub fn glfwGetPrimaryMonitor() -> *const GLFWmonitor;
     pub fn glfwGetVideoMode(monitor: *const GLFWmonitor) -> *const GLFWvidmode;
+    pub fn glfwGetMonitors(count: *mut i32) -> *const *const GLFWmonitor;
+    pub fn glfwGetVideoModes(monitor: *const GLFWmonitor, count: *mut i32) -> *const GLFWvidmode;
+    pub fn glfwGetMonitorName(monitor: *const GLFWmonitor) -> *const i8;
+    pub fn glfwGetMonitorPos(monitor: *const GLFWmonitor, xpos: *mut i32, ypos: *mut i32);
 
     pub fn glfwSetWindowUserPointer(window: *mut GLFWwindow, pointer: *mut ffi::c_void);
     pub fn glfwGetWindowUserPointer(window: *mut GLFWwindow) -> *mut ffi::c_void;
+
+    pub fn glfwSetInputMode(window: *mut GLFWwindow, mode: i32, value: i32);
+    pub fn glfwGetInputMode(window: *mut GLFWwindow, mode: i32) -> i32;
+    pub fn glfwRawMouseMotionSupported() -> i32;
     // Callbacks
 
     pub fn glfwSetWindowCloseCallback(
@@ -47,7 +64,7 @@ extern "C" {
 
     pub fn glfwSetMouseButtonCallback(
         window: *mut GLFWwindow,
-        callback: extern "C" fn(window: *mut GLFWwindow, button: i32, action: i32),
+        callback: extern "C" fn(window: *mut GLFWwindow, button: i32, action: i32, mods: i32),
     );
 
     pub fn glfwSetScrollCallback(
@@ -62,10 +79,22 @@ extern "C" {
         window: *mut GLFWwindow,
         callback: extern "C" fn(window: *mut GLFWwindow, width: i32, height: i32),
     );
+    pub fn glfwSetFramebufferSizeCallback(
+        window: *mut GLFWwindow,
+        callback: extern "C" fn(window: *mut GLFWwindow, width: i32, height: i32),
+    );
     pub fn glfwSetWindowFocusCallback(
         window: *mut GLFWwindow,
         callback: extern "C" fn(window: *mut GLFWwindow, focused: i32),
     );
+    pub fn glfwSetWindowIconifyCallback(
+        window: *mut GLFWwindow,
+        callback: extern "C" fn(window: *mut GLFWwindow, iconified: i32),
+    );
+    pub fn glfwSetWindowRefreshCallback(
+        window: *mut GLFWwindow,
+        callback: extern "C" fn(window: *mut GLFWwindow),
+    );
     pub fn glfwSetCharCallback(
         window: *mut GLFWwindow,
         callback: extern "C" fn(window: *mut GLFWwindow, codepoint: u32),
@@ -148,6 +177,15 @@ pub const GLFW_NO_ROBUSTNESS: i32 = 0;
 pub const GLFW_NO_RESET_NOTIFICATION: i32 = 0x00031001;
 pub const GLFW_LOSE_CONTEXT_ON_RESET: i32 = 0x00031002;
 
+pub const GLFW_CURSOR: i32 = 0x00033001;
+pub const GLFW_STICKY_KEYS: i32 = 0x00033002;
+pub const GLFW_STICKY_MOUSE_BUTTONS: i32 = 0x00033003;
+pub const GLFW_LOCK_KEY_MODS: i32 = 0x00033004;
+pub const GLFW_RAW_MOUSE_MOTION: i32 = 0x00033005;
+pub const GLFW_CURSOR_NORMAL: i32 = 0x00034001;
+pub const GLFW_CURSOR_HIDDEN: i32 = 0x00034002;
+pub const GLFW_CURSOR_DISABLED: i32 = 0x00034003;
+
 pub const GLFW_RELEASE: i32 = 0;
 pub const GLFW_PRESS: i32 = 1;
 pub const GLFW_REPEAT: i32 = 2;
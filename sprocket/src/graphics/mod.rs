@@ -3,11 +3,19 @@ pub use log::{debug, error, info, trace, warn};
 use std::sync::Arc;
 use window::Window;
 
+pub mod camera;
+pub mod color;
 pub mod error;
+pub mod sprite;
+pub mod text;
 pub mod vulkan;
 pub mod window;
 
+pub use camera::Camera;
+pub use color::Color;
 pub use error::{Error, Result};
+pub use sprite::Sprite;
+pub use text::Font;
 
 pub enum GraphicsContext {
     Vulkan(Arc<vulkan::VulkanContext>),
@@ -22,9 +30,10 @@ pub enum Api {
 }
 
 /// Initializes the graphics api and returns a context
-pub fn init(api: Api, window: &Window) -> Result<GraphicsContext> {
+/// Validation enables the debug layers of the api if they are installed
+pub fn init(api: Api, window: &Window, validation: bool) -> Result<GraphicsContext> {
     match api {
-        Api::Vulkan => match vulkan::init(window) {
+        Api::Vulkan => match vulkan::init(window, validation) {
             Ok(context) => Ok(GraphicsContext::Vulkan(Arc::new(context))),
             Err(f) => Err(f),
         },
@@ -32,6 +41,16 @@ pub fn init(api: Api, window: &Window) -> Result<GraphicsContext> {
     }
 }
 
+/// Initializes the graphics api without a window and returns a context
+/// Rendering is only possible to offscreen render targets
+pub fn init_headless(api: Api, validation: bool) -> Result<GraphicsContext> {
+    match api {
+        Api::Vulkan => vulkan::init_headless(validation)
+            .map(|context| GraphicsContext::Vulkan(Arc::new(context))),
+        Api::OpenGL => Err(Error::UnsupportedAPI(api)),
+    }
+}
+
 pub struct Extent2D {
     width: u32,
     height: u32,
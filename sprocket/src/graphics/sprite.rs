@@ -0,0 +1,146 @@
+use super::vulkan::Vertex;
+use crate::math::{Vec2, Vec3};
+use crate::physics::Transform;
+use serde::{Deserialize, Serialize};
+
+/// A component drawing a textured quad for 2D rendering
+/// The quad lies in the xy plane of the entity's transform
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Sprite {
+    /// Path to the texture loaded through the resource manager
+    pub texture: String,
+    /// The width and height of the quad before scaling
+    pub size: Vec2,
+    /// The point of the quad placed at the position of the transform
+    /// (0, 0) is the top left corner and (1, 1) the bottom right
+    pub pivot: Vec2,
+    /// The texture coordinates of the top left corner
+    pub uv_min: Vec2,
+    /// The texture coordinates of the bottom right corner
+    pub uv_max: Vec2,
+}
+
+impl Sprite {
+    /// Creates a sprite showing the whole texture pivoted around its center
+    pub fn new(texture: &str, size: Vec2) -> Sprite {
+        Sprite {
+            texture: texture.to_owned(),
+            size,
+            pivot: Vec2::new(0.5, 0.5),
+            uv_min: Vec2::zero(),
+            uv_max: Vec2::one(),
+        }
+    }
+
+    /// Sets the point of the quad placed at the position of the transform
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    /// Shows only a sub rectangle of the texture
+    /// Used for sprites in a texture atlas
+    pub fn with_uv(mut self, uv_min: Vec2, uv_max: Vec2) -> Self {
+        self.uv_min = uv_min;
+        self.uv_max = uv_max;
+        self
+    }
+}
+
+/// Builds one textured quad of four vertices per sprite, transformed to world space
+/// The corners are in the order top left, top right, bottom right, bottom left
+/// The quads are indexed with text::quad_indices
+pub fn build_batch<'a, I>(sprites: I) -> Vec<Vertex>
+where
+    I: IntoIterator<Item = (&'a Sprite, &'a Transform)>,
+{
+    let mut vertices = Vec::new();
+
+    for (sprite, transform) in sprites {
+        let matrix = transform.matrix();
+        let (min, max) = (sprite.uv_min, sprite.uv_max);
+
+        let corners = [
+            (Vec2::new(0.0, 0.0), min),
+            (Vec2::new(1.0, 0.0), Vec2::new(max.x, min.y)),
+            (Vec2::new(1.0, 1.0), max),
+            (Vec2::new(0.0, 1.0), Vec2::new(min.x, max.y)),
+        ];
+
+        for (corner, uv) in corners.iter() {
+            let local = (*corner - sprite.pivot) * sprite.size;
+            let position = matrix.transform_point(Vec3::new(local.x, local.y, 0.0));
+            vertices.push(Vertex::new(position, *uv));
+        }
+    }
+
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch() {
+        let half = Vec2::new(0.5, 0.5);
+        let size = Vec2::new(2.0, 2.0);
+
+        // One sprite for each quarter of a 2x2 atlas
+        let mut scaled = Transform::new(Vec3::new(0.0, 10.0, 0.0));
+        scaled.scale = Vec3::new(2.0, 2.0, 1.0);
+        let sprites = [
+            (
+                Sprite::new("atlas.png", size).with_uv(Vec2::zero(), half),
+                Transform::new(Vec3::zero()),
+            ),
+            (
+                Sprite::new("atlas.png", size)
+                    .with_pivot(Vec2::zero())
+                    .with_uv(Vec2::new(0.5, 0.0), Vec2::new(1.0, 0.5)),
+                Transform::new(Vec3::new(10.0, 0.0, 0.0)),
+            ),
+            (
+                Sprite::new("atlas.png", size).with_uv(Vec2::new(0.0, 0.5), Vec2::new(0.5, 1.0)),
+                scaled,
+            ),
+            (
+                Sprite::new("atlas.png", size)
+                    .with_pivot(Vec2::one())
+                    .with_uv(half, Vec2::one()),
+                Transform::new(Vec3::new(5.0, 5.0, 0.0)),
+            ),
+        ];
+
+        let vertices = build_batch(
+            sprites
+                .iter()
+                .map(|(sprite, transform)| (sprite, transform)),
+        );
+        assert_eq!(vertices.len(), 16);
+
+        // The top left and bottom right corners of each quad
+        let expected = [
+            (Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0)),
+            (Vec3::new(10.0, 0.0, 0.0), Vec3::new(12.0, 2.0, 0.0)),
+            (Vec3::new(-2.0, 8.0, 0.0), Vec3::new(2.0, 12.0, 0.0)),
+            (Vec3::new(3.0, 3.0, 0.0), Vec3::new(5.0, 5.0, 0.0)),
+        ];
+
+        for (i, ((sprite, _), (top_left, bottom_right))) in
+            sprites.iter().zip(expected.iter()).enumerate()
+        {
+            let quad = &vertices[i * 4..i * 4 + 4];
+            assert!(quad[0].position.approx_eq(top_left, 1e-5));
+            assert!(quad[2].position.approx_eq(bottom_right, 1e-5));
+            assert!(quad[1]
+                .position
+                .approx_eq(&Vec3::new(bottom_right.x, top_left.y, 0.0), 1e-5));
+
+            assert_eq!(quad[0].uv, sprite.uv_min);
+            assert_eq!(quad[1].uv, Vec2::new(sprite.uv_max.x, sprite.uv_min.y));
+            assert_eq!(quad[2].uv, sprite.uv_max);
+            assert_eq!(quad[3].uv, Vec2::new(sprite.uv_min.x, sprite.uv_max.y));
+        }
+    }
+}
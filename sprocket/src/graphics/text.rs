@@ -0,0 +1,163 @@
+use super::vulkan::Vertex;
+use super::Result;
+use crate::math::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// A bitmap font stored as a grid of equally sized glyphs in an atlas texture
+/// The glyphs are laid out row by row starting at first_char
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Font {
+    /// The material drawing the glyphs
+    /// Its first texture is the atlas and its pipeline should expect text vertices
+    pub material: String,
+    /// The number of glyphs in each row of the atlas
+    pub columns: u32,
+    /// The number of glyph rows in the atlas
+    pub rows: u32,
+    /// The character of the top left glyph
+    pub first_char: u32,
+    /// The width of a glyph relative to its height
+    pub glyph_aspect: f32,
+}
+
+impl Font {
+    /// Reads a font description from a json file
+    pub fn load(path: &str) -> Result<Font> {
+        Ok(serde_json::from_str(&ex::fs::read_to_string(path)?)?)
+    }
+
+    /// Returns the top left and bottom right texture coordinates of the glyph for c
+    /// Returns None if the atlas does not contain c
+    pub fn glyph_uv(&self, c: char) -> Option<(Vec2, Vec2)> {
+        let index = (c as u32).checked_sub(self.first_char)?;
+        if index >= self.columns * self.rows {
+            return None;
+        }
+
+        let size = Vec2::new(1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        let min = Vec2::new(
+            (index % self.columns) as f32 * size.x,
+            (index / self.columns) as f32 * size.y,
+        );
+
+        Some((min, min + size))
+    }
+
+    /// Lays out text as one textured quad of four vertices per glyph
+    /// position is the top left corner of the text in pixels and scale the glyph height in pixels
+    /// Newlines start a new line below, characters not in the atlas only advance the cursor
+    /// The quads are indexed with quad_indices
+    pub fn layout(&self, text: &str, position: Vec2, scale: f32) -> Vec<Vertex> {
+        let advance = scale * self.glyph_aspect;
+        let mut vertices = Vec::with_capacity(text.len() * 4);
+        let mut cursor = position;
+
+        for c in text.chars() {
+            if c == '\n' {
+                cursor = Vec2::new(position.x, cursor.y + scale);
+                continue;
+            }
+
+            if let Some((min, max)) = self.glyph_uv(c) {
+                let (left, top) = (cursor.x, cursor.y);
+                let (right, bottom) = (cursor.x + advance, cursor.y + scale);
+
+                vertices.push(Vertex::new(Vec3::new(left, top, 0.0), min));
+                vertices.push(Vertex::new(
+                    Vec3::new(right, top, 0.0),
+                    Vec2::new(max.x, min.y),
+                ));
+                vertices.push(Vertex::new(Vec3::new(right, bottom, 0.0), max));
+                vertices.push(Vertex::new(
+                    Vec3::new(left, bottom, 0.0),
+                    Vec2::new(min.x, max.y),
+                ));
+            }
+
+            cursor.x += advance;
+        }
+
+        vertices
+    }
+}
+
+/// Returns the indices of glyph_count quads laid out by Font::layout
+/// Each quad is drawn as two triangles
+pub fn quad_indices(glyph_count: usize) -> Vec<u32> {
+    (0..glyph_count as u32)
+        .flat_map(|i| {
+            let base = i * 4;
+            vec![base, base + 1, base + 2, base + 2, base + 3, base]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16x6 atlas of the printable ascii characters
+    fn ascii_font() -> Font {
+        Font {
+            material: String::new(),
+            columns: 16,
+            rows: 6,
+            first_char: ' ' as u32,
+            glyph_aspect: 0.5,
+        }
+    }
+
+    #[test]
+    fn glyph_uv() {
+        let font = ascii_font();
+
+        let (min, max) = font.glyph_uv(' ').unwrap();
+        assert_eq!(min, Vec2::zero());
+        assert_eq!(max, Vec2::new(1.0 / 16.0, 1.0 / 6.0));
+
+        // 'a' is the second glyph of the fifth row
+        let (min, max) = font.glyph_uv('a').unwrap();
+        assert!(min.approx_eq(&Vec2::new(1.0 / 16.0, 4.0 / 6.0), 1e-6));
+        assert!(max.approx_eq(&Vec2::new(2.0 / 16.0, 5.0 / 6.0), 1e-6));
+
+        // The last glyph is the one before 0x80
+        assert!(font.glyph_uv('\u{7f}').is_some());
+        assert!(font.glyph_uv('\u{80}').is_none());
+        assert!(font.glyph_uv('\t').is_none());
+    }
+
+    #[test]
+    fn layout() {
+        let font = ascii_font();
+        let vertices = font.layout("abc", Vec2::new(10.0, 20.0), 16.0);
+        let indices = quad_indices(3);
+
+        // Two triangles of four vertices per glyph
+        assert_eq!(vertices.len(), 12);
+        assert_eq!(indices.len(), 6 * 3);
+        assert!(indices.iter().all(|&index| index < 12));
+
+        for (i, c) in "abc".chars().enumerate() {
+            let (min, max) = font.glyph_uv(c).unwrap();
+            let quad = &vertices[i * 4..i * 4 + 4];
+            assert_eq!(quad[0].uv, min);
+            assert_eq!(quad[2].uv, max);
+
+            // Glyphs advance by their width
+            let left = 10.0 + i as f32 * 8.0;
+            assert_eq!(quad[0].position, Vec3::new(left, 20.0, 0.0));
+            assert_eq!(quad[2].position, Vec3::new(left + 8.0, 36.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn layout_lines() {
+        let font = ascii_font();
+
+        // Missing glyphs only advance and newlines restart below
+        let vertices = font.layout("a\tb\nc", Vec2::zero(), 10.0);
+        assert_eq!(vertices.len(), 12);
+        assert_eq!(vertices[4].position, Vec3::new(10.0, 0.0, 0.0));
+        assert_eq!(vertices[8].position, Vec3::new(0.0, 10.0, 0.0));
+    }
+}
@@ -3,8 +3,11 @@ use super::CommandPool;
 use crate::graphics::Extent2D;
 use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk;
+use log::error;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
 
-use super::{Error, Result, VkAllocator};
+use super::{Error, Result, VkAllocator, VulkanContext};
 
 // Creates a staging buffer with specified size
 // Buffer is already mapped on creation
@@ -13,7 +16,8 @@ pub fn create_staging(
     size: u64,
 ) -> Result<(vk::Buffer, vk_mem::Allocation, vk_mem::AllocationInfo)> {
     allocator
-        .borrow()
+        .lock()
+        .unwrap()
         .create_buffer(
             &vk::BufferCreateInfo::builder()
                 .size(size)
@@ -29,94 +33,487 @@ pub fn create_staging(
         .map_err(|e| e.into())
 }
 
-/// Copies the contents of one buffer to another
-pub fn copy(
+/// The queues used to upload data into device local resources
+/// Copies are submitted to the transfer queue after which ownership of the destination is
+/// transferred to the graphics queue family
+/// Both are the same queue if the device has no dedicated transfer queue
+#[derive(Clone, Copy)]
+pub struct Transfer<'a> {
+    pub queue: vk::Queue,
+    pub commandpool: &'a CommandPool,
+    pub graphics_queue: vk::Queue,
+    pub graphics_pool: &'a CommandPool,
+    /// Locked and waited on by uploads that block until they have finished
+    pub fence: &'a Mutex<vk::Fence>,
+    /// Records the copies into an AsyncTransfer instead of submitting them if set
+    pub recording: Option<&'a RefCell<TransferRecording>>,
+}
+
+impl<'a> Transfer<'a> {
+    /// Returns true if uploaded resources need to change queue family before use
+    pub fn transfers_ownership(&self) -> bool {
+        self.commandpool.queue_family() != self.graphics_pool.queue_family()
+    }
+}
+
+/// Records commands into a one time command buffer, submits them to queue, and blocks on fence
+/// until they have finished executing
+/// Used for uploads and readbacks outside of the frame
+/// The fence is locked for the whole submit, which also serializes the use of commandpool
+pub fn immediate_submit<F>(
     device: &ash::Device,
     queue: vk::Queue,
     commandpool: &CommandPool,
+    fence: &Mutex<vk::Fence>,
+    record: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut CommandBuffer) -> Result<()>,
+{
+    let fence = *fence.lock().unwrap_or_else(|e| e.into_inner());
+    let mut commandbuffer = CommandBuffer::new_primary(device, commandpool, 1)?.remove(0);
+
+    let result = (|| -> Result<()> {
+        commandbuffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+        record(&mut commandbuffer)?;
+        commandbuffer.end()?;
+
+        unsafe { device.reset_fences(&[fence])? };
+        CommandBuffer::submit(device, &[&commandbuffer], queue, &[], &[], &[], fence)?;
+        unsafe { device.wait_for_fences(&[fence], true, u64::MAX)? };
+        Ok(())
+    })();
+
+    // Freed even if recording failed to not leak command buffers
+    commandbuffer.free(commandpool);
+    result
+}
+
+/// Copies the contents of one buffer to another on the transfer queue
+/// Ownership of dst_buffer is transferred to the graphics queue family
+pub fn copy(
+    device: &ash::Device,
+    transfer: &Transfer,
     src_buffer: vk::Buffer,
     dst_buffer: vk::Buffer,
     size: vk::DeviceSize,
 ) -> Result<()> {
-    let commandbuffer = &mut CommandBuffer::new_primary(device, commandpool, 1)?[0];
-
-    commandbuffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
-
-    let region = vk::BufferCopy::builder()
-        .src_offset(0)
-        .dst_offset(0)
-        .size(size)
-        .build();
-    unsafe { device.cmd_copy_buffer(commandbuffer.vk(), src_buffer, dst_buffer, &[region]) }
+    let ownership_barrier = |src_access_mask, dst_access_mask| {
+        vk::BufferMemoryBarrier::builder()
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .src_queue_family_index(transfer.commandpool.queue_family())
+            .dst_queue_family_index(transfer.graphics_pool.queue_family())
+            .buffer(dst_buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .build()
+    };
 
-    commandbuffer.end()?;
+    let ownership = transfer.transfers_ownership();
 
-    CommandBuffer::submit(
+    submit_transfer(
         device,
-        &[commandbuffer],
-        queue,
-        &[],
-        &[],
-        &[],
-        vk::Fence::null(),
-    )?;
+        transfer,
+        |commandbuffer| {
+            commandbuffer.copy_buffer(src_buffer, dst_buffer, size);
 
-    unsafe { device.queue_wait_idle(queue).map_err(|e| e.into()) }
+            // Release
+            if ownership {
+                commandbuffer.pipeline_barrier(
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    &[],
+                    &[ownership_barrier(
+                        vk::AccessFlags::TRANSFER_WRITE,
+                        vk::AccessFlags::default(),
+                    )],
+                    &[],
+                );
+            }
+        },
+        // Acquire
+        |commandbuffer| {
+            commandbuffer.pipeline_barrier(
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                &[],
+                &[ownership_barrier(
+                    vk::AccessFlags::default(),
+                    vk::AccessFlags::MEMORY_READ,
+                )],
+                &[],
+            );
+        },
+    )
 }
 
+/// Copies a buffer into all layers of an image on the transfer queue
+/// The image is transitioned from an undefined layout and left in SHADER_READ_ONLY_OPTIMAL, owned
+/// by the graphics queue family
 pub fn copy_to_image(
     device: &ash::Device,
-    queue: vk::Queue,
-    commandpool: &CommandPool,
+    transfer: &Transfer,
     src_buffer: vk::Buffer,
     dst_image: vk::Image,
     extent: Extent2D,
     aspect: vk::ImageAspectFlags,
+    layer_count: u32,
 ) -> Result<()> {
-    let region = vk::BufferImageCopy {
-        buffer_offset: 0,
-        buffer_row_length: 0,
-        buffer_image_height: 0,
-        image_subresource: vk::ImageSubresourceLayers {
-            aspect_mask: aspect,
-            mip_level: 0,
-            base_array_layer: 0,
-            layer_count: 1,
-        },
-        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
-        image_extent: vk::Extent3D {
-            width: extent.width,
-            height: extent.height,
-            depth: 1,
-        },
+    let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask, ownership: bool| {
+        let (src_family, dst_family) = match ownership {
+            true => (
+                transfer.commandpool.queue_family(),
+                transfer.graphics_pool.queue_family(),
+            ),
+            false => (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED),
+        };
+
+        vk::ImageMemoryBarrier::builder()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .src_queue_family_index(src_family)
+            .dst_queue_family_index(dst_family)
+            .image(dst_image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: aspect,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count,
+            })
+            .build()
     };
-    let commandbuffer = &mut CommandBuffer::new_primary(device, commandpool, 1)?[0];
 
-    commandbuffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+    let ownership = transfer.transfers_ownership();
 
-    unsafe {
-        device.cmd_copy_buffer_to_image(
-            commandbuffer.vk(),
-            src_buffer,
-            dst_image,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            &[region],
-        )
-    }
+    submit_transfer(
+        device,
+        transfer,
+        |commandbuffer| {
+            commandbuffer.pipeline_barrier(
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                &[],
+                &[],
+                &[barrier(
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::default(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    false,
+                )],
+            );
+
+            commandbuffer.copy_buffer_to_image(
+                src_buffer,
+                dst_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                extent,
+                aspect,
+                layer_count,
+            );
+
+            // Release to the graphics family, or transition directly if the same
+            let (dst_stage, dst_access_mask) = match ownership {
+                true => (
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::AccessFlags::default(),
+                ),
+                false => (
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::AccessFlags::SHADER_READ,
+                ),
+            };
+
+            commandbuffer.pipeline_barrier(
+                vk::PipelineStageFlags::TRANSFER,
+                dst_stage,
+                &[],
+                &[],
+                &[barrier(
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    dst_access_mask,
+                    ownership,
+                )],
+            );
+        },
+        // Acquire
+        |commandbuffer| {
+            commandbuffer.pipeline_barrier(
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                &[],
+                &[],
+                &[barrier(
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::default(),
+                    vk::AccessFlags::SHADER_READ,
+                    true,
+                )],
+            );
+        },
+    )
+}
 
-    commandbuffer.end()?;
+/// Submits the copies recorded by record_copy to the transfer queue and, if ownership is
+/// transferred, the acquire recorded by record_acquire to the graphics queue
+/// Blocks until both have finished executing, unless transfer records into an AsyncTransfer
+fn submit_transfer<C, A>(
+    device: &ash::Device,
+    transfer: &Transfer,
+    record_copy: C,
+    record_acquire: A,
+) -> Result<()>
+where
+    C: FnOnce(&mut CommandBuffer),
+    A: FnOnce(&mut CommandBuffer),
+{
+    if let Some(recording) = transfer.recording {
+        let mut recording = recording.borrow_mut();
+        record_copy(&mut recording.transfer);
+        if let Some(acquire) = &mut recording.acquire {
+            record_acquire(acquire);
+        }
+        return Ok(());
+    }
 
-    CommandBuffer::submit(
+    immediate_submit(
         device,
-        &[commandbuffer],
-        queue,
-        &[],
-        &[],
-        &[],
-        vk::Fence::null(),
+        transfer.queue,
+        transfer.commandpool,
+        transfer.fence,
+        |commandbuffer| {
+            record_copy(commandbuffer);
+            Ok(())
+        },
     )?;
 
-    unsafe { device.queue_wait_idle(queue).map_err(|e| e.into()) }
+    if !transfer.transfers_ownership() {
+        return Ok(());
+    }
+
+    immediate_submit(
+        device,
+        transfer.graphics_queue,
+        transfer.graphics_pool,
+        transfer.fence,
+        |commandbuffer| {
+            record_acquire(commandbuffer);
+            Ok(())
+        },
+    )
+}
+
+/// Destroys a staging buffer once the copies from it have finished
+/// The buffer is kept alive by the AsyncTransfer if transfer records into one
+pub fn destroy_staging(
+    allocator: &VkAllocator,
+    transfer: &Transfer,
+    buffer: vk::Buffer,
+    memory: vk_mem::Allocation,
+) -> Result<()> {
+    match transfer.recording {
+        Some(recording) => {
+            recording.borrow_mut().staging.push((buffer, memory));
+            Ok(())
+        }
+        None => allocator
+            .lock()
+            .unwrap()
+            .destroy_buffer(buffer, &memory)
+            .map_err(|e| e.into()),
+    }
+}
+
+/// The command buffers of an AsyncTransfer and the staging buffers they copy from
+pub struct TransferRecording {
+    transfer: CommandBuffer,
+    /// Acquires ownership on the graphics queue, None if the transfer queue is of the same family
+    acquire: Option<CommandBuffer>,
+    staging: Vec<(vk::Buffer, vk_mem::Allocation)>,
+}
+
+/// An upload submitted to the transfer queue without blocking, E.g; for resources loaded in the
+/// background
+/// Resources created with the Transfer of an AsyncTransfer record their copies into it instead of
+/// submitting them, and may not be used before the submitted copies have finished
+/// Ownership is acquired by the graphics queue, which waits on the copies with a semaphore
+/// Allocates from its own pools to not share the context's pools with other threads
+pub struct AsyncTransfer {
+    /// Pool on the transfer queue family, dropped before the context
+    commandpool: CommandPool,
+    /// Pool on the graphics queue family for the ownership acquire
+    graphics_pool: CommandPool,
+    context: Arc<VulkanContext>,
+    /// Only None while the upload is dropped
+    recording: Option<RefCell<TransferRecording>>,
+    /// Signaled by the copies and waited on by the acquire
+    semaphore: vk::Semaphore,
+    /// Signaled when the upload has finished
+    fence: vk::Fence,
+    submitted: bool,
+}
+
+impl AsyncTransfer {
+    /// Begins recording an upload
+    pub fn new(context: &Arc<VulkanContext>) -> Result<Self> {
+        let device = &context.device;
+        let pools = context.transfer();
+        let max_push_constants_size = context.info().max_push_constants_size;
+
+        let commandpool = CommandPool::new(
+            device,
+            pools.commandpool.queue_family(),
+            true,
+            false,
+            max_push_constants_size,
+        )?;
+        let graphics_pool = CommandPool::new(
+            device,
+            pools.graphics_pool.queue_family(),
+            true,
+            false,
+            max_push_constants_size,
+        )?;
+
+        let begin = |commandpool| -> Result<CommandBuffer> {
+            let mut commandbuffer = CommandBuffer::new_primary(device, commandpool, 1)?.remove(0);
+            commandbuffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+            Ok(commandbuffer)
+        };
+
+        let transfer = begin(&commandpool)?;
+        let acquire = match pools.transfers_ownership() {
+            true => Some(begin(&graphics_pool)?),
+            false => None,
+        };
+
+        let (semaphore, fence) = unsafe {
+            (
+                device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?,
+                device.create_fence(&vk::FenceCreateInfo::default(), None)?,
+            )
+        };
+
+        Ok(AsyncTransfer {
+            commandpool,
+            graphics_pool,
+            context: Arc::clone(context),
+            recording: Some(RefCell::new(TransferRecording {
+                transfer,
+                acquire,
+                staging: Vec::new(),
+            })),
+            semaphore,
+            fence,
+            submitted: false,
+        })
+    }
+
+    /// Returns the queues to create resources with whose copies are recorded into this upload
+    pub fn transfer(&self) -> Transfer<'_> {
+        Transfer {
+            commandpool: &self.commandpool,
+            graphics_pool: &self.graphics_pool,
+            recording: self.recording.as_ref(),
+            ..self.context.transfer()
+        }
+    }
+
+    /// Ends recording and submits the upload without waiting for it to finish
+    pub fn submit(&mut self) -> Result<()> {
+        let device = &self.context.device;
+        let transfer = self.context.transfer();
+        let recording = self.recording.as_mut().unwrap().get_mut();
+
+        recording.transfer.end()?;
+
+        match &mut recording.acquire {
+            Some(acquire) => {
+                acquire.end()?;
+
+                CommandBuffer::submit(
+                    device,
+                    &[&recording.transfer],
+                    transfer.queue,
+                    &[],
+                    &[],
+                    &[self.semaphore],
+                    vk::Fence::null(),
+                )?;
+
+                CommandBuffer::submit(
+                    device,
+                    &[acquire],
+                    transfer.graphics_queue,
+                    &[self.semaphore],
+                    &[vk::PipelineStageFlags::ALL_COMMANDS],
+                    &[],
+                    self.fence,
+                )?;
+            }
+            None => CommandBuffer::submit(
+                device,
+                &[&recording.transfer],
+                transfer.queue,
+                &[],
+                &[],
+                &[],
+                self.fence,
+            )?,
+        }
+
+        self.submitted = true;
+        Ok(())
+    }
+
+    /// Returns true if the submitted upload has finished executing
+    pub fn is_finished(&self) -> Result<bool> {
+        if !self.submitted {
+            return Ok(false);
+        }
+
+        unsafe { Ok(self.context.device.get_fence_status(self.fence)?) }
+    }
+}
+
+impl Drop for AsyncTransfer {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+
+        // The command and staging buffers can not be freed while in use
+        if self.submitted {
+            iferr!("Failed to wait for upload", unsafe {
+                device.wait_for_fences(&[self.fence], true, u64::MAX)
+            });
+        }
+
+        let recording = self.recording.take().unwrap().into_inner();
+
+        recording.transfer.free(&self.commandpool);
+        if let Some(acquire) = recording.acquire {
+            acquire.free(&self.graphics_pool);
+        }
+
+        let allocator = self.context.allocator.lock().unwrap();
+        for (buffer, memory) in recording.staging {
+            iferr!(
+                "Failed to destroy staging buffer",
+                allocator.destroy_buffer(buffer, &memory)
+            );
+        }
+
+        unsafe {
+            device.destroy_semaphore(self.semaphore, None);
+            device.destroy_fence(self.fence, None);
+        }
+    }
 }
 
 pub fn destroy(device: &ash::Device, buffer: vk::Buffer, memory: vk::DeviceMemory) {
@@ -1,29 +1,40 @@
 use super::{
-    DescriptorSet, Framebuffer, IndexBuffer, Material, Mesh, Pipeline, RenderPass, VertexBuffer,
+    ComputePipeline, DescriptorSet, Framebuffer, ImageFormat, IndexBuffer, InstanceBuffer,
+    Material, Mesh, Pipeline, RenderPass, VertexBuffer,
 };
 
+use crate::graphics::{Color, Extent2D};
 use ash::version::DeviceV1_0;
 
 use ash::vk;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use super::{Error, Result};
 
 pub struct CommandPool {
     device: ash::Device,
     pool: vk::CommandPool,
+    /// The queue family command buffers from the pool can be submitted to
+    queue_family: u32,
     transient: bool,
     partial_reset: bool,
+    /// The device limit of push constants in bytes given to allocated command buffers
+    max_push_constants_size: u32,
+    /// The number of command buffers allocated and not yet freed
+    allocated: AtomicUsize,
 }
 
 /// Represents a commandbuffer that stores graphics command to later be executed
 impl CommandPool {
     /// Creates a command pool for recording command buffers
     /// The transient options specifies that the command buffer
+    /// max_push_constants_size is the device limit checked when pushing constants
     pub fn new(
         device: &ash::Device,
         queue_family: u32,
         transient: bool,
         partial_reset: bool,
+        max_push_constants_size: u32,
     ) -> Result<CommandPool> {
         let mut flags = vk::CommandPoolCreateFlags::default();
         if transient {
@@ -43,10 +54,24 @@ impl CommandPool {
         Ok(CommandPool {
             device: device.clone(),
             pool,
+            queue_family,
             transient,
             partial_reset,
+            max_push_constants_size,
+            allocated: AtomicUsize::new(0),
         })
     }
+
+    /// Returns the queue family command buffers from the pool can be submitted to
+    pub fn queue_family(&self) -> u32 {
+        self.queue_family
+    }
+
+    /// Returns the number of command buffers allocated from the pool that have not been freed
+    /// Command buffers are freed with CommandBuffer::free or when the pool is destroyed
+    pub fn allocated_count(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
+    }
 }
 
 impl Drop for CommandPool {
@@ -55,10 +80,43 @@ impl Drop for CommandPool {
     }
 }
 
+/// The value an attachment is cleared to when a renderpass begins
+#[derive(Clone, Copy, Debug)]
+pub enum ClearValue {
+    /// A linear color
+    Color(Color),
+    /// Depth and stencil
+    DepthStencil(f32, u32),
+}
+
+impl From<ClearValue> for vk::ClearValue {
+    fn from(value: ClearValue) -> Self {
+        match value {
+            ClearValue::Color(color) => vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [color.r, color.g, color.b, color.a],
+                },
+            },
+            ClearValue::DepthStencil(depth, stencil) => vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil },
+            },
+        }
+    }
+}
+
+/// An image taking part in a blit, with the layout it is in and the extent blitted
+#[derive(Clone, Copy)]
+pub struct BlitImage {
+    pub image: vk::Image,
+    pub layout: vk::ImageLayout,
+    pub extent: Extent2D,
+}
+
 pub struct CommandBuffer {
     device: ash::Device,
     commandbuffer: vk::CommandBuffer,
     recording: bool,
+    max_push_constants_size: u32,
 }
 
 impl CommandBuffer {
@@ -73,6 +131,7 @@ impl CommandBuffer {
             .command_buffer_count(count as u32)
             .build();
         let commandbuffers = unsafe { device.allocate_command_buffers(&alloc_info)? };
+        commandpool.allocated.fetch_add(count, Ordering::Relaxed);
 
         Ok(commandbuffers
             .into_iter()
@@ -80,10 +139,21 @@ impl CommandBuffer {
                 device: device.clone(),
                 commandbuffer,
                 recording: false,
+                max_push_constants_size: commandpool.max_push_constants_size,
             })
             .collect())
     }
 
+    /// Frees the command buffer back to the pool it was allocated from
+    /// The command buffer must not be pending execution
+    pub fn free(self, commandpool: &CommandPool) {
+        unsafe {
+            self.device
+                .free_command_buffers(commandpool.pool, &[self.commandbuffer])
+        }
+        commandpool.allocated.fetch_sub(1, Ordering::Relaxed);
+    }
+
     pub fn begin(&mut self, begin_info: vk::CommandBufferUsageFlags) -> Result<()> {
         let begin_info = vk::CommandBufferBeginInfo::builder()
             .flags(begin_info)
@@ -109,6 +179,11 @@ impl CommandBuffer {
         Ok(())
     }
 
+    /// Returns true between begin and end
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
     pub fn submit(
         device: &ash::Device,
         commandbuffers: &[&CommandBuffer],
@@ -136,26 +211,55 @@ impl CommandBuffer {
         }
     }
 
+    /// Begins the renderpass clearing all color attachments to the linear clear_color
+    /// Depth attachments are cleared to a depth of 1.0 and stencil of 0
     pub fn begin_renderpass(
         &mut self,
         renderpass: &RenderPass,
         framebuffer: &Framebuffer,
-        clear_color: crate::math::Vec4,
+        clear_color: Color,
     ) {
-        let clear_values = [
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [clear_color.x, clear_color.y, clear_color.z, clear_color.w],
-                },
-            },
-            vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                },
-            },
-        ];
+        // Clear each attachment depending on if it's a color or depth attachment
+        let clear_values: Vec<vk::ClearValue> = renderpass
+            .spec()
+            .attachments
+            .iter()
+            .map(|attachment| match attachment.format {
+                ImageFormat::Depth => ClearValue::DepthStencil(1.0, 0),
+                _ => ClearValue::Color(clear_color),
+            })
+            .map(|value| value.into())
+            .collect();
+
+        self.begin_renderpass_raw(renderpass, framebuffer, &clear_values)
+    }
+
+    /// Begins the renderpass with one clear value per attachment in the renderpass spec
+    /// Returns an error if the number of clear values differs from the number of attachments
+    pub fn begin_renderpass_with(
+        &mut self,
+        renderpass: &RenderPass,
+        framebuffer: &Framebuffer,
+        clear_values: &[ClearValue],
+    ) -> Result<()> {
+        let attachment_count = renderpass.spec().attachments.len();
+        if clear_values.len() != attachment_count {
+            return Err(Error::ClearValueCount(clear_values.len(), attachment_count));
+        }
 
+        let clear_values: Vec<vk::ClearValue> =
+            clear_values.iter().map(|value| (*value).into()).collect();
+
+        self.begin_renderpass_raw(renderpass, framebuffer, &clear_values);
+        Ok(())
+    }
+
+    fn begin_renderpass_raw(
+        &mut self,
+        renderpass: &RenderPass,
+        framebuffer: &Framebuffer,
+        clear_values: &[vk::ClearValue],
+    ) {
         let renderpass_info = vk::RenderPassBeginInfo::builder()
             .render_pass(renderpass.vk())
             .framebuffer(framebuffer.vk())
@@ -163,7 +267,7 @@ impl CommandBuffer {
                 offset: vk::Offset2D { x: 0, y: 0 },
                 extent: framebuffer.extent().into(),
             })
-            .clear_values(&clear_values)
+            .clear_values(clear_values)
             .build();
 
         unsafe {
@@ -175,6 +279,14 @@ impl CommandBuffer {
         };
     }
 
+    /// Advances to the next subpass of the current renderpass
+    pub fn next_subpass(&self) {
+        unsafe {
+            self.device
+                .cmd_next_subpass(self.commandbuffer, vk::SubpassContents::INLINE)
+        }
+    }
+
     pub fn end_renderpass(&self) {
         unsafe { self.device.cmd_end_render_pass(self.commandbuffer) };
     }
@@ -189,6 +301,35 @@ impl CommandBuffer {
         };
     }
 
+    pub fn bind_compute_pipeline(&self, pipeline: &ComputePipeline) {
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                self.commandbuffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.vk(),
+            )
+        };
+    }
+
+    /// Binds one or more descriptor sets for a compute pipeline
+    pub fn bind_compute_descriptorsets(
+        &self,
+        pipeline: &ComputePipeline,
+        descriptor_sets: &[&DescriptorSet],
+    ) {
+        unsafe {
+            let sets: Vec<vk::DescriptorSet> = descriptor_sets.iter().map(|set| set.vk()).collect();
+            self.device.cmd_bind_descriptor_sets(
+                self.commandbuffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.layout(),
+                0,
+                &sets,
+                &[],
+            )
+        }
+    }
+
     /// Binds a vertex buffer separately
     pub fn bind_vertexbuffer(&self, vertexbuffer: &VertexBuffer) {
         unsafe {
@@ -201,6 +342,18 @@ impl CommandBuffer {
         }
     }
 
+    /// Binds the per instance data for instanced drawing to binding 1
+    pub fn bind_instancebuffer(&self, instancebuffer: &InstanceBuffer) {
+        unsafe {
+            self.device.cmd_bind_vertex_buffers(
+                self.commandbuffer,
+                1,
+                &[instancebuffer.buffer()],
+                &[0],
+            )
+        }
+    }
+
     /// Binds an index buffer separately
     pub fn bind_indexbuffer(&self, indexbuffer: &IndexBuffer) {
         unsafe {
@@ -251,14 +404,45 @@ impl CommandBuffer {
         }
     }
 
+    /// Binds one or more descriptor sets with the offsets of their dynamic uniform buffers
+    /// dynamic_offsets has one offset for each dynamic binding of the sets in order, E.g; from
+    /// UniformBufferPool::write
+    pub fn bind_descriptorsets_dynamic(
+        &self,
+        pipeline: &Pipeline,
+        descriptor_sets: &[&DescriptorSet],
+        dynamic_offsets: &[u32],
+    ) {
+        unsafe {
+            let sets: Vec<vk::DescriptorSet> = descriptor_sets.iter().map(|set| set.vk()).collect();
+            self.device.cmd_bind_descriptor_sets(
+                self.commandbuffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.layout(),
+                0,
+                &sets,
+                dynamic_offsets,
+            )
+        }
+    }
+
     /// Sets oush constants to the shaders
+    /// Returns an error if the constants do not fit within the device limit
     pub fn push_contants<T>(
         &self,
         pipeline_layout: vk::PipelineLayout,
         stages: vk::ShaderStageFlags,
         offset: u32,
         constants: &T,
-    ) {
+    ) -> Result<()> {
+        let end = offset as usize + std::mem::size_of::<T>();
+        if end > self.max_push_constants_size as usize {
+            return Err(Error::PushConstantsTooLarge(
+                end,
+                self.max_push_constants_size as usize,
+            ));
+        }
+
         let data: *const T = constants;
         unsafe {
             self.device.cmd_push_constants(
@@ -269,6 +453,38 @@ impl CommandBuffer {
                 std::slice::from_raw_parts(data as *const u8, std::mem::size_of::<T>()),
             )
         }
+
+        Ok(())
+    }
+
+    /// Sets the dynamic viewport to cover the whole extent
+    pub fn set_viewport(&self, extent: Extent2D) {
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+
+        unsafe {
+            self.device
+                .cmd_set_viewport(self.commandbuffer, 0, &[viewport])
+        }
+    }
+
+    /// Sets the dynamic scissor to cover the whole extent
+    pub fn set_scissor(&self, extent: Extent2D) {
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: extent.into(),
+        };
+
+        unsafe {
+            self.device
+                .cmd_set_scissor(self.commandbuffer, 0, &[scissor])
+        }
     }
 
     pub fn draw(&self) {
@@ -284,6 +500,166 @@ impl CommandBuffer {
         }
     }
 
+    /// Draws index_count indices starting at first_index of the bound index buffer
+    /// Used to draw parts of a batch with different materials
+    pub fn draw_indexed_range(&self, first_index: u32, index_count: u32) {
+        unsafe {
+            self.device
+                .cmd_draw_indexed(self.commandbuffer, index_count, 1, first_index, 0, 0)
+        }
+    }
+
+    /// Draws instance_count instances of the bound mesh in a single draw call
+    pub fn draw_indexed_instanced(&self, index_count: u32, instance_count: u32) {
+        unsafe {
+            self.device
+                .cmd_draw_indexed(self.commandbuffer, index_count, instance_count, 0, 0, 0)
+        }
+    }
+
+    /// Draws instance_count instances of the bound mesh starting at first_instance of the bound
+    /// instance buffer
+    /// Used to draw several batches from one instance buffer
+    pub fn draw_indexed_instances(
+        &self,
+        index_count: u32,
+        first_instance: u32,
+        instance_count: u32,
+    ) {
+        unsafe {
+            self.device.cmd_draw_indexed(
+                self.commandbuffer,
+                index_count,
+                instance_count,
+                0,
+                0,
+                first_instance,
+            )
+        }
+    }
+
+    /// Dispatches compute work groups with the bound compute pipeline
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        unsafe { self.device.cmd_dispatch(self.commandbuffer, x, y, z) }
+    }
+
+    /// Records a pipeline barrier between src_stage and dst_stage
+    pub fn pipeline_barrier(
+        &self,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+        memory_barriers: &[vk::MemoryBarrier],
+        buffer_barriers: &[vk::BufferMemoryBarrier],
+        image_barriers: &[vk::ImageMemoryBarrier],
+    ) {
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.commandbuffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::default(),
+                memory_barriers,
+                buffer_barriers,
+                image_barriers,
+            )
+        }
+    }
+
+    /// Copies size bytes from the start of src to the start of dst
+    pub fn copy_buffer(&self, src: vk::Buffer, dst: vk::Buffer, size: vk::DeviceSize) {
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size,
+        };
+
+        unsafe {
+            self.device
+                .cmd_copy_buffer(self.commandbuffer, src, dst, &[region])
+        }
+    }
+
+    /// Copies tightly packed texels from the start of src into the first mip level of layer_count
+    /// layers of dst
+    /// dst needs to be in layout, either TRANSFER_DST_OPTIMAL or GENERAL
+    pub fn copy_buffer_to_image(
+        &self,
+        src: vk::Buffer,
+        dst: vk::Image,
+        layout: vk::ImageLayout,
+        extent: Extent2D,
+        aspect: vk::ImageAspectFlags,
+        layer_count: u32,
+    ) {
+        let region = image_copy_region(extent, aspect, layer_count);
+
+        unsafe {
+            self.device
+                .cmd_copy_buffer_to_image(self.commandbuffer, src, dst, layout, &[region])
+        }
+    }
+
+    /// Copies the first mip level and layer of src into dst as tightly packed texels
+    /// src needs to be in layout, either TRANSFER_SRC_OPTIMAL or GENERAL
+    pub fn copy_image_to_buffer(
+        &self,
+        src: vk::Image,
+        layout: vk::ImageLayout,
+        dst: vk::Buffer,
+        extent: Extent2D,
+        aspect: vk::ImageAspectFlags,
+    ) {
+        let region = image_copy_region(extent, aspect, 1);
+
+        unsafe {
+            self.device
+                .cmd_copy_image_to_buffer(self.commandbuffer, src, layout, dst, &[region])
+        }
+    }
+
+    /// Blits the whole color of the first mip level and layer of src onto the whole of dst,
+    /// scaling and converting formats as necessary
+    /// src and dst need to be in TRANSFER_SRC_OPTIMAL and TRANSFER_DST_OPTIMAL or GENERAL
+    /// respectively
+    pub fn blit_image(&self, src: BlitImage, dst: BlitImage, filter: vk::Filter) {
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        let corners = |extent: Extent2D| {
+            [
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: extent.width as i32,
+                    y: extent.height as i32,
+                    z: 1,
+                },
+            ]
+        };
+
+        let region = vk::ImageBlit {
+            src_subresource: subresource,
+            src_offsets: corners(src.extent),
+            dst_subresource: subresource,
+            dst_offsets: corners(dst.extent),
+        };
+
+        unsafe {
+            self.device.cmd_blit_image(
+                self.commandbuffer,
+                src.image,
+                src.layout,
+                dst.image,
+                dst.layout,
+                &[region],
+                filter,
+            )
+        }
+    }
+
     /// Resets/Clears the commandbuffer allowing you to once again record commands
     // Normal comment
     pub fn reset(&self) -> Result<()> {
@@ -294,3 +670,210 @@ impl CommandBuffer {
         self.commandbuffer
     }
 }
+
+/// Returns a region covering the first mip level of layer_count layers of an image of extent
+fn image_copy_region(
+    extent: Extent2D,
+    aspect: vk::ImageAspectFlags,
+    layer_count: u32,
+) -> vk::BufferImageCopy {
+    vk::BufferImageCopy {
+        buffer_offset: 0,
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: aspect,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count,
+        },
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        image_extent: vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, RenderPassSpec, RenderTarget};
+    use std::sync::Arc;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn clear_value_count() {
+        let context = vulkan::test_context();
+
+        // A color and a depth attachment
+        let contents =
+            std::fs::read_to_string("../sandbox/data/renderpasses/offscreen.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        let renderpass = RenderPass::new(
+            &context.device,
+            spec,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::D32_SFLOAT,
+        )
+        .unwrap();
+        let target =
+            RenderTarget::new(&context, Arc::new(renderpass), Extent2D::new(4, 4)).unwrap();
+
+        context
+            .immediate_submit(|commandbuffer| {
+                let color = ClearValue::Color(Color::new(0.0, 0.0, 0.0, 1.0));
+                let depth = ClearValue::DepthStencil(1.0, 0);
+
+                let result = commandbuffer.begin_renderpass_with(
+                    target.renderpass(),
+                    target.framebuffer(),
+                    &[color],
+                );
+                assert!(matches!(result, Err(Error::ClearValueCount(1, 2))));
+
+                commandbuffer.begin_renderpass_with(
+                    target.renderpass(),
+                    target.framebuffer(),
+                    &[color, depth],
+                )?;
+                commandbuffer.end_renderpass();
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn copy_region() {
+        let region = image_copy_region(Extent2D::new(64, 32), vk::ImageAspectFlags::COLOR, 6);
+        assert_eq!(region.buffer_offset, 0);
+        assert_eq!(
+            region.image_subresource.aspect_mask,
+            vk::ImageAspectFlags::COLOR
+        );
+        assert_eq!(region.image_subresource.layer_count, 6);
+        assert_eq!(
+            (
+                region.image_extent.width,
+                region.image_extent.height,
+                region.image_extent.depth
+            ),
+            (64, 32, 1)
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn record_copy() {
+        let context = vulkan::test_context();
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        // Host visible so the copy can be read back
+        let create = || {
+            context
+                .allocator
+                .lock()
+                .unwrap()
+                .create_buffer(
+                    &vk::BufferCreateInfo::builder()
+                        .size(bytes.len() as u64)
+                        .usage(
+                            vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+                        )
+                        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                        .build(),
+                    &vk_mem::AllocationCreateInfo {
+                        usage: vk_mem::MemoryUsage::GpuToCpu,
+                        flags: vk_mem::AllocationCreateFlags::MAPPED,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let (src, src_memory, src_info) = create();
+        let (dst, dst_memory, dst_info) = create();
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), src_info.get_mapped_data(), bytes.len())
+        };
+
+        // A command buffer is only recording between begin and end
+        let pool = context.generic_pool();
+        let mut commandbuffer = CommandBuffer::new_primary(&context.device, pool, 1)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert!(!commandbuffer.is_recording());
+        commandbuffer
+            .begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+            .unwrap();
+        assert!(commandbuffer.is_recording());
+        commandbuffer.copy_buffer(src, dst, bytes.len() as u64);
+        assert!(commandbuffer.is_recording());
+        commandbuffer.end().unwrap();
+        assert!(!commandbuffer.is_recording());
+        assert!(matches!(commandbuffer.end(), Err(Error::NotRecording)));
+        commandbuffer.free(pool);
+
+        // Copies record into the command buffer of immediate_submit
+        context
+            .immediate_submit(|commandbuffer| {
+                assert!(commandbuffer.is_recording());
+                commandbuffer.copy_buffer(src, dst, bytes.len() as u64);
+                Ok(())
+            })
+            .unwrap();
+
+        let copied = unsafe { std::slice::from_raw_parts(dst_info.get_mapped_data(), bytes.len()) };
+        assert_eq!(copied, &bytes[..]);
+
+        let allocator = context.allocator.lock().unwrap();
+        allocator.destroy_buffer(src, &src_memory).unwrap();
+        allocator.destroy_buffer(dst, &dst_memory).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn push_constants_too_large() {
+        let context = vulkan::test_context();
+
+        // Mock a device with the minimum guaranteed limit
+        let pool = CommandPool::new(
+            &context.device,
+            context.queue_families.graphics.unwrap(),
+            true,
+            true,
+            128,
+        )
+        .unwrap();
+        let commandbuffer = CommandBuffer::new_primary(&context.device, &pool, 1)
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        let result = commandbuffer.push_contants(
+            vk::PipelineLayout::null(),
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &[0u8; 300],
+        );
+        assert!(matches!(
+            result,
+            Err(Error::PushConstantsTooLarge(300, 128))
+        ));
+
+        // The offset counts towards the limit
+        let result = commandbuffer.push_contants(
+            vk::PipelineLayout::null(),
+            vk::ShaderStageFlags::VERTEX,
+            96,
+            &[0f32; 16],
+        );
+        assert!(matches!(
+            result,
+            Err(Error::PushConstantsTooLarge(160, 128))
+        ));
+
+        commandbuffer.free(&pool);
+    }
+}
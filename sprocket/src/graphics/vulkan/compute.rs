@@ -0,0 +1,195 @@
+use super::pipeline::{create_shader_module, PushConstantRange};
+use super::{
+    resources::Resource, DescriptorSetLayout, DescriptorSetLayoutSpec, Error, Result, VulkanContext,
+};
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+use serde::{Deserialize, Serialize};
+use std::{ffi::CStr, path::Path};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ComputePipelineSpec {
+    pub shader: String,
+    /// The descriptor set layouts
+    pub layouts: Vec<DescriptorSetLayoutSpec>,
+    #[serde(default)]
+    pub push_constants: Vec<PushConstantRange>,
+}
+
+impl ComputePipelineSpec {
+    /// Validates the spec before creating a compute pipeline from it
+    /// Checks that the shader exists and that no descriptor set layout is empty
+    pub fn validate(&self) -> Result<()> {
+        if !Path::new(&self.shader).is_file() {
+            return Err(Error::MissingShader(self.shader.to_owned()));
+        }
+
+        for (i, layout) in self.layouts.iter().enumerate() {
+            if layout.bindings.is_empty() {
+                return Err(Error::EmptyDescriptorSetLayout(i));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A pipeline with a single compute shader
+/// Used for GPU work outside of renderpasses like particle updates and image processing
+pub struct ComputePipeline {
+    device: ash::Device,
+    layout: vk::PipelineLayout,
+    set_layouts: Vec<DescriptorSetLayout>,
+    pipeline: vk::Pipeline,
+    spec: ComputePipelineSpec,
+}
+
+impl Resource for ComputePipeline {
+    fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
+        let spec: ComputePipelineSpec = serde_json::from_str(&ex::fs::read_to_string(path)?)?;
+        spec.validate()?;
+
+        Self::new(resourcemanager.context(), spec)
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec![self.spec.shader.to_owned()]
+    }
+}
+
+impl ComputePipeline {
+    pub fn new(context: &VulkanContext, spec: ComputePipelineSpec) -> Result<ComputePipeline> {
+        let device = &context.device;
+        let shader_module = create_shader_module(device, &spec.shader)?;
+
+        let result = Self::with_module(context, spec, shader_module);
+
+        // The shader module is no longer needed once the pipeline is created, or if it failed
+        unsafe { device.destroy_shader_module(shader_module, None) };
+        result
+    }
+
+    fn with_module(
+        context: &VulkanContext,
+        spec: ComputePipelineSpec,
+        shader_module: vk::ShaderModule,
+    ) -> Result<ComputePipeline> {
+        let device = &context.device;
+        let shader_entry_point = unsafe { CStr::from_ptr("main\0".as_ptr() as _) };
+
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader_module)
+            .name(shader_entry_point)
+            .build();
+
+        // Pipeline layout
+        let set_layouts = spec
+            .layouts
+            .iter()
+            .map(|layout_spec| DescriptorSetLayout::new(device, layout_spec.clone()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let vk_set_layouts: Vec<vk::DescriptorSetLayout> =
+            set_layouts.iter().map(|layout| layout.vk()).collect();
+
+        let push_constants: Vec<vk::PushConstantRange> = spec
+            .push_constants
+            .iter()
+            .map(|range| range.to_vk())
+            .collect();
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&vk_set_layouts)
+            .push_constant_ranges(&push_constants);
+
+        let layout = unsafe { device.create_pipeline_layout(&pipeline_layout_info, None)? };
+
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(layout)
+            .build();
+
+        let pipeline = unsafe {
+            device.create_compute_pipelines(context.pipeline_cache(), &[pipeline_info], None)
+        };
+
+        let pipeline = match pipeline {
+            Ok(pipelines) => pipelines[0],
+            Err((_, e)) => {
+                unsafe { device.destroy_pipeline_layout(layout, None) };
+                return Err(Error::VulkanError(e));
+            }
+        };
+
+        Ok(ComputePipeline {
+            device: device.clone(),
+            layout,
+            set_layouts,
+            pipeline,
+            spec,
+        })
+    }
+
+    pub fn vk(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    pub fn set_layouts(&self) -> &[DescriptorSetLayout] {
+        &self.set_layouts[..]
+    }
+
+    pub fn spec(&self) -> &ComputePipelineSpec {
+        &self.spec
+    }
+}
+
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline_layout(self.layout, None);
+            self.device.destroy_pipeline(self.pipeline, None);
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, tests::write_empty_compute_shader};
+
+    #[test]
+    fn validate() {
+        let spec = ComputePipelineSpec {
+            shader: "missing.comp.spv".to_owned(),
+            layouts: Vec::new(),
+            push_constants: Vec::new(),
+        };
+        assert!(matches!(spec.validate(), Err(Error::MissingShader(_))));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn dispatch() {
+        let context = vulkan::test_context();
+
+        let spec = ComputePipelineSpec {
+            shader: write_empty_compute_shader("sprocket_dispatch.comp.spv"),
+            layouts: Vec::new(),
+            push_constants: Vec::new(),
+        };
+        spec.validate().unwrap();
+        let pipeline = ComputePipeline::new(&context, spec).unwrap();
+
+        let result = context.compute(|commandbuffer| {
+            commandbuffer.bind_compute_pipeline(&pipeline);
+            commandbuffer.dispatch(1, 1, 1);
+        });
+        assert!(result.is_ok());
+    }
+}
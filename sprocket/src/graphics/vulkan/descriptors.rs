@@ -1,10 +1,12 @@
 use super::{Error, Result};
-use super::{Sampler, Texture, UniformBuffer};
+use super::{Sampler, StorageBuffer, Texture, UniformBuffer, UniformBufferPool};
 use ash::version::DeviceV1_0;
 use ash::vk;
+use log::error;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::ptr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct DescriptorSetLayoutSpec {
@@ -44,10 +46,12 @@ pub enum DescriptorType {
     // UniformTexelBuffer= 4,
     // StorageTexelBuffer= 5,
     UniformBuffer = 6,
-    // StorageBuffer= 7,
-    // UniformBufferDynamic= 8,
+    StorageBuffer = 7,
+    /// A uniform buffer bound with an offset when binding the set, E.g; a UniformBufferPool
+    UniformBufferDynamic = 8,
     // StorageBufferDynamic= 9,
-    // InputAttachment= 10,
+    /// An attachment written by a previous subpass of the same renderpass
+    InputAttachment = 10,
 }
 
 impl From<DescriptorType> for vk::DescriptorType {
@@ -121,8 +125,19 @@ impl DescriptorPool {
         device: &ash::Device,
         sizes: &[vk::DescriptorPoolSize],
         max_sets: u32,
+    ) -> Result<DescriptorPool> {
+        Self::with_flags(device, sizes, max_sets, Default::default())
+    }
+
+    /// Creates a pool with flags, E.g; FREE_DESCRIPTOR_SET to allow freeing sets individually
+    pub fn with_flags(
+        device: &ash::Device,
+        sizes: &[vk::DescriptorPoolSize],
+        max_sets: u32,
+        flags: vk::DescriptorPoolCreateFlags,
     ) -> Result<DescriptorPool> {
         let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(flags)
             .pool_sizes(sizes)
             .max_sets(max_sets);
 
@@ -133,6 +148,24 @@ impl DescriptorPool {
             sizes: sizes.into(),
         })
     }
+
+    /// Returns all descriptor sets allocated from the pool back to it
+    /// None of the sets may be in use
+    pub fn reset(&self) -> Result<()> {
+        unsafe {
+            self.device
+                .reset_descriptor_pool(self.pool, Default::default())?
+        };
+        Ok(())
+    }
+
+    /// Returns sets allocated from the pool back to it
+    /// The pool needs to have been created with FREE_DESCRIPTOR_SET and none of the sets may be in
+    /// use
+    pub fn free(&self, sets: &[vk::DescriptorSet]) -> Result<()> {
+        unsafe { self.device.free_descriptor_sets(self.pool, sets) };
+        Ok(())
+    }
 }
 
 impl Drop for DescriptorPool {
@@ -143,6 +176,184 @@ impl Drop for DescriptorPool {
     }
 }
 
+/// The number of descriptor sets each pool block of a DescriptorAllocator can hold
+const SETS_PER_BLOCK: u32 = 64;
+
+/// The number of descriptors of each type reserved per set in a pool block
+const DESCRIPTORS_PER_SET: u32 = 4;
+
+/// A pool of a DescriptorAllocator
+struct DescriptorBlock {
+    pool: DescriptorPool,
+    /// The number of sets that can still be allocated from the pool
+    available: u32,
+}
+
+/// Hands out descriptor sets from shared pools
+/// A new pool block is created when the existing ones are exhausted
+/// Freed sets are returned to the block they were allocated from and reused
+pub struct DescriptorAllocator {
+    device: ash::Device,
+    blocks: Vec<DescriptorBlock>,
+    /// The index of the block each allocated set belongs to
+    owners: HashMap<vk::DescriptorSet, usize>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(device: &ash::Device) -> DescriptorAllocator {
+        DescriptorAllocator {
+            device: device.clone(),
+            blocks: Vec::new(),
+            owners: HashMap::new(),
+        }
+    }
+
+    /// Allocates count descriptor sets with layout from the first block with room
+    /// Creates a new pool block if all are exhausted
+    /// The sets need to be returned with free, prefer DescriptorAllocation which does so when
+    /// dropped
+    pub fn allocate(
+        &mut self,
+        layout: &DescriptorSetLayout,
+        count: u32,
+    ) -> Result<Vec<DescriptorSet>> {
+        let mut allocated = None;
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.available < count {
+                continue;
+            }
+
+            match DescriptorSet::new(&self.device, &block.pool, layout, count) {
+                Ok(sets) => {
+                    allocated = Some((index, sets));
+                    break;
+                }
+                Err(Error::VulkanError(vk::Result::ERROR_OUT_OF_POOL_MEMORY))
+                | Err(Error::VulkanError(vk::Result::ERROR_FRAGMENTED_POOL)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let (index, sets) = match allocated {
+            Some(allocated) => allocated,
+            None => {
+                self.blocks.push(DescriptorBlock {
+                    pool: Self::create_block(&self.device)?,
+                    available: SETS_PER_BLOCK,
+                });
+
+                let index = self.blocks.len() - 1;
+                let sets =
+                    DescriptorSet::new(&self.device, &self.blocks[index].pool, layout, count)?;
+                (index, sets)
+            }
+        };
+
+        self.blocks[index].available -= count;
+        for set in &sets {
+            self.owners.insert(set.set, index);
+        }
+
+        Ok(sets)
+    }
+
+    /// Returns sets allocated by allocate to their blocks
+    /// None of the sets may be in use or used after
+    pub fn free(&mut self, sets: &[DescriptorSet]) -> Result<()> {
+        let mut freed: HashMap<usize, Vec<vk::DescriptorSet>> = HashMap::new();
+        for set in sets {
+            if let Some(index) = self.owners.remove(&set.set) {
+                freed.entry(index).or_default().push(set.set);
+            }
+        }
+
+        for (index, sets) in freed {
+            let block = &mut self.blocks[index];
+            block.pool.free(&sets)?;
+            block.available += sets.len() as u32;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of pool blocks created
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of sets currently allocated
+    pub fn allocated_count(&self) -> usize {
+        self.owners.len()
+    }
+
+    fn create_block(device: &ash::Device) -> Result<DescriptorPool> {
+        let sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: SETS_PER_BLOCK * DESCRIPTORS_PER_SET,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: SETS_PER_BLOCK * DESCRIPTORS_PER_SET,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                descriptor_count: SETS_PER_BLOCK * DESCRIPTORS_PER_SET,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: SETS_PER_BLOCK * DESCRIPTORS_PER_SET,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::INPUT_ATTACHMENT,
+                descriptor_count: SETS_PER_BLOCK * DESCRIPTORS_PER_SET,
+            },
+        ];
+
+        DescriptorPool::with_flags(
+            device,
+            &sizes,
+            SETS_PER_BLOCK,
+            vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+        )
+    }
+}
+
+/// Descriptor sets allocated from a shared DescriptorAllocator
+/// The sets are freed when dropped, so the allocation may not be dropped while the sets are in use
+pub struct DescriptorAllocation {
+    allocator: Arc<Mutex<DescriptorAllocator>>,
+    sets: Vec<DescriptorSet>,
+}
+
+impl DescriptorAllocation {
+    /// Allocates count descriptor sets with layout from allocator
+    pub fn new(
+        allocator: &Arc<Mutex<DescriptorAllocator>>,
+        layout: &DescriptorSetLayout,
+        count: u32,
+    ) -> Result<DescriptorAllocation> {
+        let sets = allocator.lock().unwrap().allocate(layout, count)?;
+        Ok(DescriptorAllocation {
+            allocator: Arc::clone(allocator),
+            sets,
+        })
+    }
+
+    pub fn sets(&self) -> &[DescriptorSet] {
+        &self.sets
+    }
+}
+
+impl Drop for DescriptorAllocation {
+    fn drop(&mut self) {
+        iferr!(
+            "Failed to free descriptor sets",
+            self.allocator.lock().unwrap().free(&self.sets)
+        );
+    }
+}
+
 pub struct DescriptorSet {
     set: vk::DescriptorSet,
 }
@@ -175,18 +386,20 @@ impl DescriptorSet {
     /// The number of samplers should be the same as the number of textures
     /// Sampler and textures are combined so that texture [2] uses sampler [2]
     /// The spec is applied to all passes descriptor sets but one
-    pub fn write<'a, U, T, S>(
+    pub fn write<'a, U, B, T, S>(
         device: &ash::Device,
         sets: &[DescriptorSet],
         spec: &DescriptorSetLayoutSpec,
         mut uniformbuffers: U,
+        mut storagebuffers: B,
         mut textures: T,
         mut samplers: S,
     ) -> Result<()>
     where
         U: Iterator<Item = &'a UniformBuffer>,
-        T: Iterator<Item = &'a Arc<Texture>>,
-        S: Iterator<Item = &'a Arc<Sampler>>,
+        B: Iterator<Item = &'a StorageBuffer>,
+        T: Iterator<Item = &'a Texture>,
+        S: Iterator<Item = &'a Sampler>,
     {
         let bindings = &spec.bindings;
         // The number of uniform buffers specified in the bindings
@@ -196,6 +409,12 @@ impl DescriptorSet {
             .count()
             * sets.len();
 
+        let sb_count = bindings
+            .iter()
+            .filter(|binding| binding.ty == DescriptorType::StorageBuffer)
+            .count()
+            * sets.len();
+
         let image_count = bindings
             .iter()
             .filter(|binding| binding.ty == DescriptorType::CombinedImageSampler)
@@ -203,7 +422,9 @@ impl DescriptorSet {
             * sets.len();
 
         let mut descriptor_writes = Vec::with_capacity(bindings.len() * sets.len());
-        let mut buffer_infos = Vec::with_capacity(ub_count);
+        let mut buffer_infos = Vec::with_capacity(ub_count + sb_count);
+        let mut ub_written = 0;
+        let mut sb_written = 0;
         let mut image_infos = Vec::with_capacity(image_count);
 
         for set in sets.iter() {
@@ -217,10 +438,12 @@ impl DescriptorSet {
                                 return Err(Error::MismatchedBinding(
                                     vk::DescriptorType::UNIFORM_BUFFER,
                                     ub_count as u32,
-                                    buffer_infos.len() as u32,
+                                    ub_written,
                                 ))
                             }
                         };
+                        ub_written += 1;
+
                         buffer_infos.push(vk::DescriptorBufferInfo {
                             buffer: buffer.buffer(),
                             range: buffer.size(),
@@ -240,6 +463,38 @@ impl DescriptorSet {
                             p_next: ptr::null(),
                         })
                     }
+                    DescriptorType::StorageBuffer => {
+                        let buffer = match storagebuffers.next() {
+                            Some(buffer) => buffer,
+                            None => {
+                                return Err(Error::MismatchedBinding(
+                                    vk::DescriptorType::STORAGE_BUFFER,
+                                    sb_count as u32,
+                                    sb_written,
+                                ))
+                            }
+                        };
+                        sb_written += 1;
+
+                        buffer_infos.push(vk::DescriptorBufferInfo {
+                            buffer: buffer.buffer(),
+                            range: buffer.size(),
+                            offset: 0,
+                        });
+
+                        descriptor_writes.push(vk::WriteDescriptorSet {
+                            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                            dst_set: set.set,
+                            dst_binding: binding.slot,
+                            dst_array_element: 0,
+                            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                            descriptor_count: 1,
+                            p_buffer_info: &buffer_infos[buffer_infos.len() - 1],
+                            p_image_info: ptr::null(),
+                            p_texel_buffer_view: ptr::null(),
+                            p_next: ptr::null(),
+                        })
+                    }
                     DescriptorType::CombinedImageSampler => {
                         let texture = match textures.next() {
                             Some(texture) => texture,
@@ -282,6 +537,11 @@ impl DescriptorSet {
                             p_next: ptr::null(),
                         })
                     }
+                    // Written separately with write_input_attachments since they change with the
+                    // framebuffer
+                    DescriptorType::InputAttachment => {}
+                    // Written separately with write_uniform_pool since their range is one slice
+                    DescriptorType::UniformBufferDynamic => {}
                 }
             }
         }
@@ -289,6 +549,100 @@ impl DescriptorSet {
         Ok(())
     }
 
+    /// Writes the input attachment bindings of the sets
+    /// Works like write but for input attachments which are read without a sampler
+    /// attachments[set][index] is the nth input attachment binding of the set
+    pub fn write_input_attachments<'a, A>(
+        device: &ash::Device,
+        sets: &[DescriptorSet],
+        spec: &DescriptorSetLayoutSpec,
+        mut attachments: A,
+    ) -> Result<()>
+    where
+        A: Iterator<Item = &'a Texture>,
+    {
+        let bindings: Vec<_> = spec
+            .bindings
+            .iter()
+            .filter(|binding| binding.ty == DescriptorType::InputAttachment)
+            .collect();
+
+        let attachment_count = bindings.len() * sets.len();
+
+        let mut descriptor_writes = Vec::with_capacity(attachment_count);
+        let mut image_infos = Vec::with_capacity(attachment_count);
+
+        for set in sets.iter() {
+            for binding in &bindings {
+                let attachment = match attachments.next() {
+                    Some(attachment) => attachment,
+                    None => {
+                        return Err(Error::MismatchedBinding(
+                            vk::DescriptorType::INPUT_ATTACHMENT,
+                            attachment_count as u32,
+                            image_infos.len() as u32,
+                        ))
+                    }
+                };
+
+                image_infos.push(vk::DescriptorImageInfo {
+                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    image_view: attachment.image_view(),
+                    sampler: vk::Sampler::null(),
+                });
+
+                descriptor_writes.push(vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: set.set,
+                    dst_binding: binding.slot,
+                    dst_array_element: 0,
+                    descriptor_type: vk::DescriptorType::INPUT_ATTACHMENT,
+                    descriptor_count: 1,
+                    p_buffer_info: ptr::null(),
+                    p_image_info: &image_infos[image_infos.len() - 1],
+                    p_texel_buffer_view: ptr::null(),
+                    p_next: ptr::null(),
+                })
+            }
+        }
+        unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) };
+        Ok(())
+    }
+
+    /// Writes the dynamic uniform buffer bindings of the sets to the buffer of pool
+    /// Each binding covers one slice, selected by the dynamic offset when binding the set
+    pub fn write_uniform_pool(
+        device: &ash::Device,
+        sets: &[DescriptorSet],
+        spec: &DescriptorSetLayoutSpec,
+        pool: &UniformBufferPool,
+    ) {
+        let buffer_info = [vk::DescriptorBufferInfo {
+            buffer: pool.buffer().buffer(),
+            offset: 0,
+            range: pool.slice_size(),
+        }];
+
+        let descriptor_writes: Vec<_> = sets
+            .iter()
+            .flat_map(|set| {
+                spec.bindings
+                    .iter()
+                    .filter(|binding| binding.ty == DescriptorType::UniformBufferDynamic)
+                    .map(move |binding| {
+                        vk::WriteDescriptorSet::builder()
+                            .dst_set(set.set)
+                            .dst_binding(binding.slot)
+                            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                            .buffer_info(&buffer_info)
+                            .build()
+                    })
+            })
+            .collect();
+
+        unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) };
+    }
+
     pub fn vk(&self) -> vk::DescriptorSet {
         self.set
     }
@@ -296,3 +650,97 @@ impl DescriptorSet {
 
 // DescriptorSets are freed when the pool is freed
 // impl Drop for DescriptorSet
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn allocator_grows_and_reuses_blocks() {
+        let context = vulkan::test_context();
+
+        let spec = DescriptorSetLayoutSpec {
+            bindings: vec![DescriptorSetLayoutBinding {
+                slot: 0,
+                ty: DescriptorType::UniformBuffer,
+                count: 1,
+                stages: vec![ShaderStage::Vertex],
+            }],
+        };
+        let layout = DescriptorSetLayout::new(&context.device, spec).unwrap();
+        let allocator = Arc::new(Mutex::new(DescriptorAllocator::new(&context.device)));
+
+        let allocate = || {
+            (0..SETS_PER_BLOCK + 1)
+                .map(|_| DescriptorAllocation::new(&allocator, &layout, 1))
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+        };
+
+        // More sets than a single block can hold
+        let allocations = allocate();
+        assert_eq!(allocator.lock().unwrap().block_count(), 2);
+        assert_eq!(
+            allocator.lock().unwrap().allocated_count(),
+            SETS_PER_BLOCK as usize + 1
+        );
+
+        // Freed sets are reused instead of creating more blocks
+        drop(allocations);
+        assert_eq!(allocator.lock().unwrap().allocated_count(), 0);
+
+        let _allocations = allocate();
+        assert_eq!(allocator.lock().unwrap().block_count(), 2);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn write_storage_buffer() {
+        let context = vulkan::test_context();
+
+        let spec = DescriptorSetLayoutSpec {
+            bindings: vec![DescriptorSetLayoutBinding {
+                slot: 0,
+                ty: DescriptorType::StorageBuffer,
+                count: 1,
+                stages: vec![ShaderStage::Compute],
+            }],
+        };
+        let layout = DescriptorSetLayout::new(&context.device, spec.clone()).unwrap();
+        let allocator = Arc::new(Mutex::new(DescriptorAllocator::new(&context.device)));
+        let allocation = DescriptorAllocation::new(&allocator, &layout, 1).unwrap();
+
+        let storagebuffer = StorageBuffer::new(&context.allocator, 256).unwrap();
+
+        DescriptorSet::write(
+            &context.device,
+            allocation.sets(),
+            &spec,
+            std::iter::empty(),
+            std::iter::once(&storagebuffer),
+            std::iter::empty(),
+            std::iter::empty(),
+        )
+        .unwrap();
+
+        // A missing storage buffer is an error
+        assert!(matches!(
+            DescriptorSet::write(
+                &context.device,
+                allocation.sets(),
+                &spec,
+                std::iter::empty(),
+                std::iter::empty(),
+                std::iter::empty(),
+                std::iter::empty(),
+            ),
+            Err(Error::MismatchedBinding(
+                vk::DescriptorType::STORAGE_BUFFER,
+                1,
+                0
+            ))
+        ));
+    }
+}
@@ -127,3 +127,207 @@ impl From<ImageLayout> for vk::ImageLayout {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+impl From<Filter> for vk::Filter {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => Self::NEAREST,
+            Filter::Linear => Self::LINEAR,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum MipmapMode {
+    Nearest,
+    Linear,
+}
+
+impl From<MipmapMode> for vk::SamplerMipmapMode {
+    fn from(mode: MipmapMode) -> Self {
+        match mode {
+            MipmapMode::Nearest => Self::NEAREST,
+            MipmapMode::Linear => Self::LINEAR,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum AddressMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl From<AddressMode> for vk::SamplerAddressMode {
+    fn from(mode: AddressMode) -> Self {
+        match mode {
+            AddressMode::Repeat => Self::REPEAT,
+            AddressMode::MirroredRepeat => Self::MIRRORED_REPEAT,
+            AddressMode::ClampToEdge => Self::CLAMP_TO_EDGE,
+            AddressMode::ClampToBorder => Self::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum CompareOp {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+impl From<CompareOp> for vk::CompareOp {
+    fn from(op: CompareOp) -> Self {
+        match op {
+            CompareOp::Never => Self::NEVER,
+            CompareOp::Less => Self::LESS,
+            CompareOp::Equal => Self::EQUAL,
+            CompareOp::LessOrEqual => Self::LESS_OR_EQUAL,
+            CompareOp::Greater => Self::GREATER,
+            CompareOp::NotEqual => Self::NOT_EQUAL,
+            CompareOp::GreaterOrEqual => Self::GREATER_OR_EQUAL,
+            CompareOp::Always => Self::ALWAYS,
+        }
+    }
+}
+
+/// Which faces of a triangle are discarded when rasterizing
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+impl From<CullMode> for vk::CullModeFlags {
+    fn from(mode: CullMode) -> Self {
+        match mode {
+            CullMode::None => Self::NONE,
+            CullMode::Front => Self::FRONT,
+            CullMode::Back => Self::BACK,
+            CullMode::FrontAndBack => Self::FRONT_AND_BACK,
+        }
+    }
+}
+
+/// How triangles are rasterized
+/// Line and Point require the fill_mode_non_solid device feature
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+pub enum PolygonMode {
+    /// The triangles are filled
+    #[default]
+    Fill,
+    /// Only the edges are drawn, used for wireframes
+    Line,
+    /// Only the vertices are drawn
+    Point,
+}
+
+impl From<PolygonMode> for vk::PolygonMode {
+    fn from(mode: PolygonMode) -> Self {
+        match mode {
+            PolygonMode::Fill => Self::FILL,
+            PolygonMode::Line => Self::LINE,
+            PolygonMode::Point => Self::POINT,
+        }
+    }
+}
+
+/// The winding order of front facing triangles
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+pub enum FrontFace {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+impl From<FrontFace> for vk::FrontFace {
+    fn from(face: FrontFace) -> Self {
+        match face {
+            FrontFace::Clockwise => Self::CLOCKWISE,
+            FrontFace::CounterClockwise => Self::COUNTER_CLOCKWISE,
+        }
+    }
+}
+
+/// How the output color of a fragment is combined with the color already in the attachment
+/// Blending happens in the space of the attachment format, the swapchain is sRGB so blending is
+/// not done in linear space
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+pub enum BlendMode {
+    /// The output replaces the existing color
+    #[default]
+    Opaque,
+    /// Blends based on the output alpha, used for transparency
+    AlphaBlend,
+    /// The output weighted by its alpha is added to the existing color
+    Additive,
+    /// The output is multiplied with the existing color
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn to_vk(&self) -> vk::PipelineColorBlendAttachmentState {
+        let (blend_enable, src_color, dst_color, src_alpha, dst_alpha) = match self {
+            BlendMode::Opaque => (
+                false,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ZERO,
+            ),
+            BlendMode::AlphaBlend => (
+                true,
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (
+                true,
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::ONE,
+            ),
+            BlendMode::Multiply => (
+                true,
+                vk::BlendFactor::DST_COLOR,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::ONE,
+            ),
+        };
+
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(blend_enable)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(src_alpha)
+            .dst_alpha_blend_factor(dst_alpha)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+}
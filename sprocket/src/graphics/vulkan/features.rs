@@ -0,0 +1,133 @@
+use ash::vk;
+
+/// The device features used by the engine
+/// Used both to request features and to describe what a device supports or has enabled
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct DeviceFeatures {
+    /// Required by samplers with anisotropic filtering
+    pub sampler_anisotropy: bool,
+    pub shader_clip_distance: bool,
+    /// Required by the Line and Point polygon modes
+    pub fill_mode_non_solid: bool,
+    /// Required by depth bias with a non zero clamp
+    pub depth_bias_clamp: bool,
+}
+
+/// Features a device needs to have to be picked
+pub const REQUIRED_FEATURES: DeviceFeatures = DeviceFeatures {
+    sampler_anisotropy: true,
+    shader_clip_distance: true,
+    fill_mode_non_solid: false,
+    depth_bias_clamp: false,
+};
+
+/// Features enabled only if the device supports them
+pub const OPTIONAL_FEATURES: DeviceFeatures = DeviceFeatures {
+    sampler_anisotropy: false,
+    shader_clip_distance: false,
+    fill_mode_non_solid: true,
+    depth_bias_clamp: true,
+};
+
+impl DeviceFeatures {
+    pub fn from_vk(features: &vk::PhysicalDeviceFeatures) -> DeviceFeatures {
+        DeviceFeatures {
+            sampler_anisotropy: features.sampler_anisotropy == vk::TRUE,
+            shader_clip_distance: features.shader_clip_distance == vk::TRUE,
+            fill_mode_non_solid: features.fill_mode_non_solid == vk::TRUE,
+            depth_bias_clamp: features.depth_bias_clamp == vk::TRUE,
+        }
+    }
+
+    pub fn to_vk(&self) -> vk::PhysicalDeviceFeatures {
+        vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: self.sampler_anisotropy as vk::Bool32,
+            shader_clip_distance: self.shader_clip_distance as vk::Bool32,
+            fill_mode_non_solid: self.fill_mode_non_solid as vk::Bool32,
+            depth_bias_clamp: self.depth_bias_clamp as vk::Bool32,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if every feature requested in self is in supported
+    pub fn supported_by(&self, supported: &DeviceFeatures) -> bool {
+        self.missing(supported).is_empty()
+    }
+
+    /// Returns the names of the features requested in self that are not in supported
+    pub fn missing(&self, supported: &DeviceFeatures) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.sampler_anisotropy && !supported.sampler_anisotropy {
+            missing.push("sampler_anisotropy");
+        }
+        if self.shader_clip_distance && !supported.shader_clip_distance {
+            missing.push("shader_clip_distance");
+        }
+        if self.fill_mode_non_solid && !supported.fill_mode_non_solid {
+            missing.push("fill_mode_non_solid");
+        }
+        if self.depth_bias_clamp && !supported.depth_bias_clamp {
+            missing.push("depth_bias_clamp");
+        }
+        missing
+    }
+
+    /// Returns the features in both self and other
+    pub fn intersection(&self, other: &DeviceFeatures) -> DeviceFeatures {
+        DeviceFeatures {
+            sampler_anisotropy: self.sampler_anisotropy && other.sampler_anisotropy,
+            shader_clip_distance: self.shader_clip_distance && other.shader_clip_distance,
+            fill_mode_non_solid: self.fill_mode_non_solid && other.fill_mode_non_solid,
+            depth_bias_clamp: self.depth_bias_clamp && other.depth_bias_clamp,
+        }
+    }
+
+    /// Returns the features in either self or other
+    pub fn union(&self, other: &DeviceFeatures) -> DeviceFeatures {
+        DeviceFeatures {
+            sampler_anisotropy: self.sampler_anisotropy || other.sampler_anisotropy,
+            shader_clip_distance: self.shader_clip_distance || other.shader_clip_distance,
+            fill_mode_non_solid: self.fill_mode_non_solid || other.fill_mode_non_solid,
+            depth_bias_clamp: self.depth_bias_clamp || other.depth_bias_clamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_features() {
+        let supported = DeviceFeatures {
+            sampler_anisotropy: true,
+            shader_clip_distance: true,
+            ..Default::default()
+        };
+
+        assert!(REQUIRED_FEATURES.supported_by(&supported));
+        assert!(DeviceFeatures::default().supported_by(&supported));
+
+        assert_eq!(
+            OPTIONAL_FEATURES.missing(&supported),
+            vec!["fill_mode_non_solid", "depth_bias_clamp"]
+        );
+        assert!(!OPTIONAL_FEATURES.supported_by(&supported));
+
+        // Only the supported optional features are enabled
+        let enabled = REQUIRED_FEATURES.union(&OPTIONAL_FEATURES.intersection(&supported));
+        assert_eq!(enabled, supported);
+    }
+
+    #[test]
+    fn vk_features() {
+        let features = REQUIRED_FEATURES.union(&OPTIONAL_FEATURES);
+        let vk_features = features.to_vk();
+        assert_eq!(vk_features.sampler_anisotropy, vk::TRUE);
+        assert_eq!(vk_features.geometry_shader, vk::FALSE);
+        assert_eq!(DeviceFeatures::from_vk(&vk_features), features);
+
+        let none = DeviceFeatures::default();
+        assert_eq!(DeviceFeatures::from_vk(&none.to_vk()), none);
+    }
+}
@@ -4,6 +4,8 @@ use super::Result;
 use crate::graphics::Extent2D;
 use ash::version::DeviceV1_0;
 use ash::vk;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 pub struct Framebuffer {
     device: ash::Device,
@@ -56,3 +58,135 @@ impl Drop for Framebuffer {
         }
     }
 }
+
+/// Identifies a framebuffer by what it was created with
+#[derive(Hash, PartialEq, Eq)]
+struct FramebufferKey {
+    renderpass: vk::RenderPass,
+    attachments: Vec<vk::ImageView>,
+    width: u32,
+    height: u32,
+}
+
+/// Reuses framebuffers created with the same renderpass, attachments and extent, E.g; passes
+/// rendering to the same swapchain image
+/// The attachments of the cached framebuffers need to outlive the cache, so it is cleared or
+/// dropped together with them, E.g; when the swapchain is recreated on resize
+pub struct FramebufferCache {
+    device: ash::Device,
+    framebuffers: HashMap<FramebufferKey, Arc<Framebuffer>>,
+}
+
+impl FramebufferCache {
+    pub fn new(device: &ash::Device) -> Self {
+        FramebufferCache {
+            device: device.clone(),
+            framebuffers: HashMap::new(),
+        }
+    }
+
+    /// Returns the framebuffer of renderpass with attachments and extent
+    /// Creates and caches the framebuffer if it does not exist
+    pub fn get(
+        &mut self,
+        attachments: &[&Texture],
+        renderpass: &RenderPass,
+        extent: Extent2D,
+    ) -> Result<Arc<Framebuffer>> {
+        let key = FramebufferKey {
+            renderpass: renderpass.vk(),
+            attachments: attachments
+                .iter()
+                .map(|attachment| attachment.image_view())
+                .collect(),
+            width: extent.width,
+            height: extent.height,
+        };
+
+        if let Some(framebuffer) = self.framebuffers.get(&key) {
+            return Ok(Arc::clone(framebuffer));
+        }
+
+        let framebuffer = Arc::new(Framebuffer::new(
+            &self.device,
+            attachments,
+            renderpass,
+            extent,
+        )?);
+        self.framebuffers.insert(key, Arc::clone(&framebuffer));
+        Ok(framebuffer)
+    }
+
+    /// Removes all framebuffers, E.g; before their attachments are destroyed
+    /// Framebuffers still referenced elsewhere are destroyed when the last reference is dropped
+    pub fn clear(&mut self) {
+        self.framebuffers.clear();
+    }
+
+    /// Returns the number of cached framebuffers
+    pub fn len(&self) -> usize {
+        self.framebuffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.framebuffers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, renderpass::RenderPassSpec};
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn framebuffer_cache() {
+        let context = vulkan::test_context();
+
+        let contents =
+            std::fs::read_to_string("../sandbox/data/renderpasses/default.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        let color_format = vk::Format::B8G8R8A8_SRGB;
+        let depth_format = vk::Format::D32_SFLOAT;
+        let renderpass =
+            RenderPass::new(&context.device, spec, color_format, depth_format).unwrap();
+
+        let attachments = |extent| {
+            let color = Texture::new(
+                &context.allocator,
+                &context.device,
+                color_format,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                vk::ImageAspectFlags::COLOR,
+                vk::ImageTiling::OPTIMAL,
+                extent,
+            )
+            .unwrap();
+            let depth =
+                Texture::new_depth(&context.allocator, &context.device, depth_format, extent)
+                    .unwrap();
+            (color, depth)
+        };
+
+        let mut cache = FramebufferCache::new(&context.device);
+        let extent = Extent2D::new(64, 64);
+        let (color, depth) = attachments(extent);
+
+        let first = cache.get(&[&color, &depth], &renderpass, extent).unwrap();
+        let second = cache.get(&[&color, &depth], &renderpass, extent).unwrap();
+        assert_eq!(first.vk(), second.vk());
+        assert_eq!(cache.len(), 1);
+
+        // A resize recreates the attachments and clears the cache
+        cache.clear();
+        assert!(cache.is_empty());
+        drop((first, second, color, depth));
+
+        let extent = Extent2D::new(128, 96);
+        let (color, depth) = attachments(extent);
+        let resized = cache.get(&[&color, &depth], &renderpass, extent).unwrap();
+        assert_eq!(resized.extent().width, 128);
+        assert_eq!(resized.extent().height, 96);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use super::enums::{AddressMode, BlendMode, CompareOp, CullMode, FrontFace, PolygonMode};
+use super::{
+    CommandBuffer, DepthState, DescriptorPool, DescriptorSet, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutSpec, DescriptorType, Pipeline, PipelineSpec, ResourceManager, Result,
+    Sampler, SamplerSpec, ShaderStage, Texture, VertexLayout,
+};
+
+use ash::vk;
+use std::collections::HashMap;
+
+/// The vertex shader generating a fullscreen triangle from gl_VertexIndex
+const FULLSCREEN_VERTEX_SHADER: &str = "./data/shaders/fullscreen.vert.spv";
+
+/// The maximum number of different input textures before the pass needs to be reset
+const MAX_INPUTS: u32 = 16;
+
+/// Draws a single triangle covering the whole framebuffer with a user supplied fragment shader
+/// Used as the base for post processing effects
+/// The input texture is bound at set 0 binding 0 as a combined image sampler and the fragment
+/// shader receives the uv at location 0
+pub struct FullscreenPass {
+    device: ash::Device,
+    pipeline: Pipeline,
+    sampler: Sampler,
+    descriptor_pool: DescriptorPool,
+    /// Descriptor sets are written once for each input texture and reused
+    descriptor_sets: HashMap<vk::ImageView, DescriptorSet>,
+}
+
+impl FullscreenPass {
+    /// Creates a fullscreen pass running the fragment shader in the first subpass of renderpass
+    pub fn new(
+        resourcemanager: &ResourceManager,
+        fragment_shader: &str,
+        renderpass: &str,
+    ) -> Result<Self> {
+        let context = resourcemanager.context();
+
+        let spec = PipelineSpec {
+            vertex_shader: FULLSCREEN_VERTEX_SHADER.to_owned(),
+            fragment_shader: fragment_shader.to_owned(),
+            geometry_shader: String::new(),
+            renderpass: renderpass.to_owned(),
+            subpass: 0,
+            layouts: vec![DescriptorSetLayoutSpec {
+                bindings: vec![DescriptorSetLayoutBinding {
+                    slot: 0,
+                    ty: DescriptorType::CombinedImageSampler,
+                    count: 1,
+                    stages: vec![ShaderStage::Fragment],
+                }],
+            }],
+            push_constants: Vec::new(),
+            vertex_layout: VertexLayout::Empty,
+            depth: DepthState {
+                test: false,
+                write: false,
+                compare_op: CompareOp::Always,
+                bias: None,
+            },
+            cull_mode: CullMode::None,
+            front_face: FrontFace::Clockwise,
+            polygon_mode: PolygonMode::Fill,
+            blend_mode: BlendMode::Opaque,
+        };
+
+        spec.validate()?;
+
+        let pipeline = Pipeline::new(&context.device, spec, resourcemanager)?;
+
+        let sampler = Sampler::with_spec(
+            context,
+            &SamplerSpec {
+                address_mode_u: AddressMode::ClampToEdge,
+                address_mode_v: AddressMode::ClampToEdge,
+                address_mode_w: AddressMode::ClampToEdge,
+                anisotropy: None,
+                ..Default::default()
+            },
+        )?;
+
+        let descriptor_pool = DescriptorPool::new(
+            &context.device,
+            &[vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: MAX_INPUTS,
+            }],
+            MAX_INPUTS,
+        )?;
+
+        Ok(FullscreenPass {
+            device: context.device.clone(),
+            pipeline,
+            sampler,
+            descriptor_pool,
+            descriptor_sets: HashMap::new(),
+        })
+    }
+
+    /// Records the fullscreen draw sampling from input_texture
+    /// The renderpass must have been begun and the viewport and scissor set
+    /// The input texture must be in the layout it reports
+    pub fn run(&mut self, commandbuffer: &CommandBuffer, input_texture: &Texture) -> Result<()> {
+        let set = match self.descriptor_sets.get(&input_texture.image_view()) {
+            Some(set) => set,
+            None => {
+                let layout = &self.pipeline.set_layouts()[0];
+                let set = DescriptorSet::new(&self.device, &self.descriptor_pool, layout, 1)?
+                    .pop()
+                    .unwrap();
+
+                DescriptorSet::write(
+                    &self.device,
+                    std::slice::from_ref(&set),
+                    layout.spec(),
+                    [].iter(),
+                    [].iter(),
+                    std::iter::once(input_texture),
+                    std::iter::once(&self.sampler),
+                )?;
+
+                self.descriptor_sets
+                    .entry(input_texture.image_view())
+                    .or_insert(set)
+            }
+        };
+
+        commandbuffer.bind_pipeline(&self.pipeline);
+        commandbuffer.bind_descriptorsets(&self.pipeline, &[set]);
+        commandbuffer.draw();
+        Ok(())
+    }
+
+    /// Frees the descriptor sets of all previously used input textures
+    /// Should be called when the input textures are recreated, E.g; on resize
+    /// The pass must not be in use by any executing command buffer
+    pub fn reset(&mut self) -> Result<()> {
+        self.descriptor_sets.clear();
+        self.descriptor_pool.reset()
+    }
+
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+}
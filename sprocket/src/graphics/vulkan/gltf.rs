@@ -0,0 +1,548 @@
+//! Loads meshes from glTF 2.0 files
+//! Supports both .gltf with external or base64 embedded buffers and binary .glb files
+use super::{model::MeshData, tangents, Error, Mesh, Result, Transfer, Vertex, VkAllocator};
+use crate::math::*;
+use ex::fs;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const COMPONENT_UNSIGNED_BYTE: u32 = 5121;
+const COMPONENT_UNSIGNED_SHORT: u32 = 5123;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    /// Integer components are mapped to 0..1
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+/// Loads all mesh primitives in a .gltf or .glb file
+/// Meshes with several primitives are split into one mesh per primitive named name.index
+/// POSITION, NORMAL, TEXCOORD_0 and COLOR_0 are read
+/// Missing normals and all tangents are generated
+pub fn load_meshes(
+    allocator: &VkAllocator,
+    device: &ash::Device,
+    transfer: &Transfer,
+    path: &str,
+) -> Result<Vec<(String, Mesh)>> {
+    read_meshes(path)?
+        .into_iter()
+        .map(|mesh| {
+            Mesh::new(allocator, device, transfer, &mesh.vertices, &mesh.indices)
+                .map(|created| (mesh.name, created))
+        })
+        .collect()
+}
+
+/// Reads the mesh primitives of a .gltf or .glb file like load_meshes without uploading them
+pub fn read_meshes(path: &str) -> Result<Vec<MeshData>> {
+    let bytes = fs::read(path)?;
+
+    let (document, bin): (Document, Option<&[u8]>) = if is_glb(&bytes) {
+        let (json, bin) = parse_glb(&bytes)?;
+        (serde_json::from_slice(json)?, bin)
+    } else {
+        (serde_json::from_slice(&bytes)?, None)
+    };
+
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    let buffers = document
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| load_buffer(buffer, i, bin, directory))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut meshes = Vec::new();
+
+    for (mesh_index, mesh) in document.meshes.iter().enumerate() {
+        let name = match &mesh.name {
+            Some(name) => name.to_owned(),
+            None => format!("mesh{}", mesh_index),
+        };
+
+        for (primitive_index, primitive) in mesh.primitives.iter().enumerate() {
+            let (vertices, indices) = parse_primitive(&document, &buffers, primitive)?;
+
+            let name = match mesh.primitives.len() {
+                1 => name.clone(),
+                _ => format!("{}.{}", name, primitive_index),
+            };
+
+            meshes.push(MeshData {
+                name,
+                vertices,
+                indices,
+            });
+        }
+    }
+
+    Ok(meshes)
+}
+
+fn is_glb(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && read_u32(bytes, 0) == GLB_MAGIC
+}
+
+/// Splits a glb file into the json chunk and the optional binary chunk
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let version = read_u32(bytes, 4);
+    if version != 2 {
+        return Err(Error::GLTFError(format!(
+            "Unsupported glb version {}",
+            version
+        )));
+    }
+
+    let length = (read_u32(bytes, 8) as usize).min(bytes.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+
+    while offset + 8 <= length {
+        let chunk_length = read_u32(bytes, offset) as usize;
+        let chunk_type = read_u32(bytes, offset + 4);
+        let start = offset + 8;
+        let end = start + chunk_length;
+
+        if end > length {
+            return Err(Error::GLTFError("Truncated glb chunk".to_owned()));
+        }
+
+        match chunk_type {
+            GLB_CHUNK_JSON => json = Some(&bytes[start..end]),
+            GLB_CHUNK_BIN => bin = Some(&bytes[start..end]),
+            // Unknown chunks must be ignored
+            _ => {}
+        }
+
+        offset = end;
+    }
+
+    match json {
+        Some(json) => Ok((json, bin)),
+        None => Err(Error::GLTFError("Missing json chunk in glb".to_owned())),
+    }
+}
+
+/// Loads the contents of a buffer from either a data uri, a file relative to directory, or the glb
+/// binary chunk
+fn load_buffer(
+    buffer: &Buffer,
+    index: usize,
+    bin: Option<&[u8]>,
+    directory: &Path,
+) -> Result<Vec<u8>> {
+    let data = match &buffer.uri {
+        Some(uri) if uri.starts_with("data:") => {
+            let base64_start = uri.find(";base64,").ok_or_else(|| {
+                Error::GLTFError("Only base64 data uris are supported".to_owned())
+            })? + ";base64,".len();
+
+            base64::decode(&uri[base64_start..]).map_err(|e| {
+                Error::GLTFError(format!("Invalid base64 in buffer {}: {}", index, e))
+            })?
+        }
+        Some(uri) => fs::read(directory.join(uri))?,
+        // Only the first buffer may refer to the glb binary chunk
+        None if index == 0 => bin
+            .ok_or_else(|| Error::GLTFError("Missing glb binary chunk".to_owned()))?
+            .to_vec(),
+        None => return Err(Error::GLTFError(format!("Buffer {} has no uri", index))),
+    };
+
+    if data.len() < buffer.byte_length {
+        return Err(Error::GLTFError(format!(
+            "Buffer {} is shorter than its byte length",
+            index
+        )));
+    }
+
+    Ok(data)
+}
+
+fn parse_primitive(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    primitive: &Primitive,
+) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let positions = match primitive.attributes.get("POSITION") {
+        Some(&accessor) => read_floats(document, buffers, accessor, 3)?,
+        None => return Err(Error::GLTFError("Primitive has no POSITION".to_owned())),
+    };
+
+    let vertex_count = positions.len() / 3;
+
+    let uvs = match primitive.attributes.get("TEXCOORD_0") {
+        Some(&accessor) => read_floats(document, buffers, accessor, 2)?,
+        None => vec![0.0; vertex_count * 2],
+    };
+
+    // Vertex colors are white if missing
+    let colors = match primitive.attributes.get("COLOR_0") {
+        Some(&accessor) => read_colors(document, buffers, accessor)?,
+        None => vec![1.0; vertex_count * 4],
+    };
+
+    // Zero normals are generated after indexing
+    let normals = match primitive.attributes.get("NORMAL") {
+        Some(&accessor) => read_floats(document, buffers, accessor, 3)?,
+        None => vec![0.0; vertex_count * 3],
+    };
+
+    if uvs.len() / 2 != vertex_count
+        || colors.len() / 4 != vertex_count
+        || normals.len() / 3 != vertex_count
+    {
+        return Err(Error::GLTFError(
+            "Attribute counts in primitive do not match".to_owned(),
+        ));
+    }
+
+    let mut vertices = (0..vertex_count)
+        .map(|i| {
+            Vertex::new(
+                Vec3::new(positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]),
+                Vec2::new(uvs[i * 2], uvs[i * 2 + 1]),
+            )
+            .with_color(Vec4::new(
+                colors[i * 4],
+                colors[i * 4 + 1],
+                colors[i * 4 + 2],
+                colors[i * 4 + 3],
+            ))
+            .with_normal(Vec3::new(
+                normals[i * 3],
+                normals[i * 3 + 1],
+                normals[i * 3 + 2],
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    // Non indexed primitives draw the vertices in order
+    let indices = match primitive.indices {
+        Some(accessor) => read_indices(document, buffers, accessor)?,
+        None => (0..vertex_count as u32).collect(),
+    };
+
+    if let Some(index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(Error::GLTFError(format!("Index {} out of range", index)));
+    }
+
+    tangents::generate_normals(&mut vertices, &indices);
+    tangents::generate_tangents(&mut vertices, &indices);
+
+    Ok((vertices, indices))
+}
+
+/// Returns the accessor, its data starting at the first element, and the stride between elements
+fn accessor_data<'a>(
+    document: &'a Document,
+    buffers: &'a [Vec<u8>],
+    index: usize,
+    component_size: usize,
+) -> Result<(&'a Accessor, &'a [u8], usize)> {
+    let accessor = document
+        .accessors
+        .get(index)
+        .ok_or_else(|| Error::GLTFError(format!("Missing accessor {}", index)))?;
+
+    let view_index = accessor
+        .buffer_view
+        .ok_or_else(|| Error::GLTFError(format!("Accessor {} has no buffer view", index)))?;
+
+    let view = document
+        .buffer_views
+        .get(view_index)
+        .ok_or_else(|| Error::GLTFError(format!("Missing buffer view {}", view_index)))?;
+
+    let buffer = buffers
+        .get(view.buffer)
+        .ok_or_else(|| Error::GLTFError(format!("Missing buffer {}", view.buffer)))?;
+
+    let element_size = component_size * component_count(&accessor.ty)?;
+    let stride = view.byte_stride.unwrap_or(element_size);
+
+    let start = view.byte_offset + accessor.byte_offset;
+    let end = view.byte_offset + view.byte_length;
+
+    // The last element does not need a full stride
+    let required = match accessor.count {
+        0 => 0,
+        count => (count - 1) * stride + element_size,
+    };
+
+    if end > buffer.len() || start + required > end {
+        return Err(Error::GLTFError(format!(
+            "Accessor {} is out of bounds",
+            index
+        )));
+    }
+
+    Ok((accessor, &buffer[start..end], stride))
+}
+
+/// Reads a float or normalized u8 or u16 accessor with components per element into a flat list
+/// Normalized components are mapped to 0..1, E.g; for COLOR_0 and TEXCOORD_0
+fn read_floats(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    index: usize,
+    components: usize,
+) -> Result<Vec<f32>> {
+    let (component_type, normalized) = document
+        .accessors
+        .get(index)
+        .map(|accessor| (accessor.component_type, accessor.normalized))
+        .ok_or_else(|| Error::GLTFError(format!("Missing accessor {}", index)))?;
+
+    let component_size = match (component_type, normalized) {
+        (COMPONENT_FLOAT, _) => 4,
+        (COMPONENT_UNSIGNED_BYTE, true) => 1,
+        (COMPONENT_UNSIGNED_SHORT, true) => 2,
+        _ => {
+            return Err(Error::GLTFError(format!(
+                "Accessor {} is not floats or normalized integers",
+                index
+            )))
+        }
+    };
+
+    let (accessor, data, stride) = accessor_data(document, buffers, index, component_size)?;
+
+    if component_count(&accessor.ty)? != components {
+        return Err(Error::GLTFError(format!(
+            "Accessor {} does not have {} components",
+            index, components
+        )));
+    }
+
+    let mut result = Vec::with_capacity(accessor.count * components);
+
+    for i in 0..accessor.count {
+        for j in 0..components {
+            let offset = i * stride + j * component_size;
+            result.push(match component_size {
+                1 => data[offset] as f32 / u8::MAX as f32,
+                2 => u16::from_le_bytes([data[offset], data[offset + 1]]) as f32 / u16::MAX as f32,
+                _ => f32::from_bits(read_u32(data, offset)),
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads an rgb or rgba color accessor into a flat list of rgba colors
+/// Missing alpha is opaque
+fn read_colors(document: &Document, buffers: &[Vec<u8>], index: usize) -> Result<Vec<f32>> {
+    let components = match document.accessors.get(index) {
+        Some(accessor) => component_count(&accessor.ty)?,
+        None => return Err(Error::GLTFError(format!("Missing accessor {}", index))),
+    };
+
+    let colors = read_floats(document, buffers, index, components)?;
+    match components {
+        4 => Ok(colors),
+        3 => Ok(colors
+            .chunks(3)
+            .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 1.0])
+            .collect()),
+        _ => Err(Error::GLTFError(format!(
+            "Color accessor {} is not rgb or rgba",
+            index
+        ))),
+    }
+}
+
+/// Reads an index accessor of u8, u16, or u32
+fn read_indices(document: &Document, buffers: &[Vec<u8>], index: usize) -> Result<Vec<u32>> {
+    let component_type = document
+        .accessors
+        .get(index)
+        .map(|accessor| accessor.component_type)
+        .ok_or_else(|| Error::GLTFError(format!("Missing accessor {}", index)))?;
+
+    let component_size = match component_type {
+        COMPONENT_UNSIGNED_BYTE => 1,
+        COMPONENT_UNSIGNED_SHORT => 2,
+        COMPONENT_UNSIGNED_INT => 4,
+        ty => {
+            return Err(Error::GLTFError(format!(
+                "Unsupported index component type {}",
+                ty
+            )))
+        }
+    };
+
+    let (accessor, data, stride) = accessor_data(document, buffers, index, component_size)?;
+
+    Ok((0..accessor.count)
+        .map(|i| {
+            let offset = i * stride;
+            match component_size {
+                1 => data[offset] as u32,
+                2 => u16::from_le_bytes([data[offset], data[offset + 1]]) as u32,
+                _ => read_u32(data, offset),
+            }
+        })
+        .collect())
+}
+
+fn component_count(ty: &str) -> Result<usize> {
+    match ty {
+        "SCALAR" => Ok(1),
+        "VEC2" => Ok(2),
+        "VEC3" => Ok(3),
+        "VEC4" => Ok(4),
+        _ => Err(Error::GLTFError(format!(
+            "Unsupported accessor type {}",
+            ty
+        ))),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A document with a single buffer view over the whole buffer and the given accessors
+    fn document(accessors: &str, byte_length: usize) -> Document {
+        serde_json::from_str(&format!(
+            r#"{{
+                "accessors": [{}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": {} }}],
+                "buffers": [{{ "byteLength": {} }}]
+            }}"#,
+            accessors, byte_length, byte_length
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn normalized_accessors() {
+        let buffer = vec![0, 255, 51, 0, 0, 0, 255, 255];
+        let document = document(
+            r#"
+            {
+                "bufferView": 0, "componentType": 5121, "normalized": true,
+                "count": 1, "type": "VEC3"
+            },
+            {
+                "bufferView": 0, "byteOffset": 4, "componentType": 5123, "normalized": true,
+                "count": 1, "type": "VEC2"
+            },
+            { "bufferView": 0, "componentType": 5121, "count": 1, "type": "VEC3" }
+            "#,
+            buffer.len(),
+        );
+        let buffers = [buffer];
+
+        // Missing alpha of an rgb color is opaque
+        let colors = read_colors(&document, &buffers, 0).unwrap();
+        assert_eq!(colors, vec![0.0, 1.0, 0.2, 1.0]);
+
+        let texcoords = read_floats(&document, &buffers, 1, 2).unwrap();
+        assert_eq!(texcoords, vec![0.0, 1.0]);
+
+        // Integers that are not normalized are not floats
+        assert!(matches!(
+            read_floats(&document, &buffers, 2, 3),
+            Err(Error::GLTFError(_))
+        ));
+    }
+
+    #[test]
+    fn float_accessors() {
+        let buffer: Vec<u8> = [1.0f32, -2.0, 0.5]
+            .iter()
+            .flat_map(|f| f.to_le_bytes().to_vec())
+            .collect();
+        let document = document(
+            r#"{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" }"#,
+            buffer.len(),
+        );
+        let buffers = [buffer];
+
+        assert_eq!(
+            read_floats(&document, &buffers, 0, 3).unwrap(),
+            vec![1.0, -2.0, 0.5]
+        );
+        assert!(matches!(
+            read_floats(&document, &buffers, 0, 2),
+            Err(Error::GLTFError(_))
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use super::buffer;
-use super::CommandPool;
+use super::Transfer;
 use super::{Result, VkAllocator};
 use ash::vk;
 use std::sync::Arc;
@@ -11,19 +11,42 @@ pub struct IndexBuffer {
     size: vk::DeviceSize,
     /// The number of elements in the buffer
     count: u32,
+    index_type: vk::IndexType,
 }
 
 impl IndexBuffer {
+    /// Creates an index buffer with 32 bit indices
     pub fn new(
         allocator: &VkAllocator,
         device: &ash::Device,
-        queue: vk::Queue,
-        commandpool: &CommandPool,
+        transfer: &Transfer,
         indices: &[u32],
+    ) -> Result<IndexBuffer> {
+        Self::create(allocator, device, transfer, indices, vk::IndexType::UINT32)
+    }
+
+    /// Creates an index buffer with 16 bit indices
+    /// Uses half the memory of 32 bit indices and can be used for meshes with at most 65536
+    /// vertices
+    pub fn new_u16(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        indices: &[u16],
+    ) -> Result<IndexBuffer> {
+        Self::create(allocator, device, transfer, indices, vk::IndexType::UINT16)
+    }
+
+    fn create<T: Copy>(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        indices: &[T],
+        index_type: vk::IndexType,
     ) -> Result<IndexBuffer> {
         let buffer_size = match indices.len() {
             0 => 1024,
-            n => (n * std::mem::size_of_val(&indices[0])) as u64,
+            n => (n * std::mem::size_of::<T>()) as u64,
         };
 
         let (staging_buffer, staging_memory, staging_info) =
@@ -35,7 +58,7 @@ impl IndexBuffer {
             std::ptr::copy_nonoverlapping(indices.as_ptr() as _, data, buffer_size as usize);
         }
 
-        let (buffer, memory, _) = allocator.borrow().create_buffer(
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
             &vk::BufferCreateInfo::builder()
                 .size(buffer_size)
                 .usage(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER)
@@ -47,18 +70,9 @@ impl IndexBuffer {
             },
         )?;
 
-        buffer::copy(
-            device,
-            queue,
-            commandpool,
-            staging_buffer,
-            buffer,
-            buffer_size,
-        )?;
+        buffer::copy(device, transfer, staging_buffer, buffer, buffer_size)?;
 
-        allocator
-            .borrow()
-            .destroy_buffer(staging_buffer, &staging_memory)?;
+        buffer::destroy_staging(allocator, transfer, staging_buffer, staging_memory)?;
 
         Ok(IndexBuffer {
             allocator: Arc::clone(allocator),
@@ -66,14 +80,16 @@ impl IndexBuffer {
             memory: memory,
             size: buffer_size,
             count: indices.len() as u32,
+            index_type,
         })
     }
     pub fn buffer(&self) -> vk::Buffer {
         self.buffer
     }
 
+    /// Returns the type of the indices, UINT16 or UINT32
     pub fn index_type(&self) -> vk::IndexType {
-        vk::IndexType::UINT32
+        self.index_type
     }
 
     /// Returns the number of indices in the buffer
@@ -85,7 +101,8 @@ impl IndexBuffer {
 impl Drop for IndexBuffer {
     fn drop(&mut self) {
         self.allocator
-            .borrow()
+            .lock()
+            .unwrap()
             .destroy_buffer(self.buffer, &self.memory)
             .expect("Failed to free vulkan memory");
     }
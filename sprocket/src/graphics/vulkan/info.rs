@@ -0,0 +1,139 @@
+use ash::vk;
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum DeviceType {
+    Other,
+    IntegratedGpu,
+    DiscreteGpu,
+    VirtualGpu,
+    Cpu,
+}
+
+impl From<vk::PhysicalDeviceType> for DeviceType {
+    fn from(ty: vk::PhysicalDeviceType) -> Self {
+        match ty {
+            vk::PhysicalDeviceType::INTEGRATED_GPU => DeviceType::IntegratedGpu,
+            vk::PhysicalDeviceType::DISCRETE_GPU => DeviceType::DiscreteGpu,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => DeviceType::VirtualGpu,
+            vk::PhysicalDeviceType::CPU => DeviceType::Cpu,
+            _ => DeviceType::Other,
+        }
+    }
+}
+
+/// Describes the physical device picked by the context
+/// Can be serialized and attached to bug reports
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_type: DeviceType,
+    /// The highest supported vulkan version as major.minor.patch
+    pub api_version: String,
+    /// The vendor specific driver version
+    pub driver_version: u32,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub max_image_dimension_2d: u32,
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_uniform_buffer_range: u32,
+    /// Dynamic uniform buffer offsets need to be a multiple of this
+    pub min_uniform_buffer_offset_alignment: u64,
+    pub max_color_attachments: u32,
+    pub max_sampler_anisotropy: f32,
+}
+
+impl DeviceInfo {
+    pub fn from_properties(properties: &vk::PhysicalDeviceProperties) -> DeviceInfo {
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+        let limits = &properties.limits;
+
+        DeviceInfo {
+            name: name.to_string_lossy().into_owned(),
+            device_type: properties.device_type.into(),
+            api_version: format!(
+                "{}.{}.{}",
+                vk::version_major(properties.api_version),
+                vk::version_minor(properties.api_version),
+                vk::version_patch(properties.api_version)
+            ),
+            driver_version: properties.driver_version,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_bound_descriptor_sets: limits.max_bound_descriptor_sets,
+            max_uniform_buffer_range: limits.max_uniform_buffer_range,
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            max_color_attachments: limits.max_color_attachments,
+            max_sampler_anisotropy: limits.max_sampler_anisotropy,
+        }
+    }
+}
+
+/// The device memory allocated by the allocator of a context
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryStats {
+    /// The number of device memory blocks allocated
+    pub block_count: u32,
+    /// The number of allocations of resources in the blocks
+    pub allocation_count: u32,
+    /// The bytes occupied by allocations
+    pub used_bytes: u64,
+    /// The bytes allocated in blocks but not occupied
+    pub unused_bytes: u64,
+}
+
+impl From<vk_mem::ffi::VmaStatInfo> for MemoryStats {
+    fn from(info: vk_mem::ffi::VmaStatInfo) -> Self {
+        MemoryStats {
+            block_count: info.blockCount,
+            allocation_count: info.allocationCount,
+            used_bytes: info.usedBytes,
+            unused_bytes: info.unusedBytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+
+    #[test]
+    fn from_properties() {
+        let mut properties = vk::PhysicalDeviceProperties::default();
+        for (dst, src) in properties.device_name.iter_mut().zip(b"Test GPU") {
+            *dst = *src as _;
+        }
+        properties.device_type = vk::PhysicalDeviceType::DISCRETE_GPU;
+        properties.api_version = vk::make_version(1, 2, 131);
+        properties.limits.max_image_dimension2_d = 16384;
+        properties.limits.max_push_constants_size = 256;
+
+        let info = DeviceInfo::from_properties(&properties);
+        assert_eq!(info.name, "Test GPU");
+        assert_eq!(info.device_type, DeviceType::DiscreteGpu);
+        assert_eq!(info.api_version, "1.2.131");
+        assert_eq!(info.max_image_dimension_2d, 16384);
+        assert_eq!(info.max_push_constants_size, 256);
+
+        // Serializes for bug reports
+        let json = serde_json::to_string(&info).unwrap();
+        let info: DeviceInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(info.name, "Test GPU");
+        assert_eq!(info.device_type, DeviceType::DiscreteGpu);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn context_info() {
+        let context = vulkan::test_context();
+        let info = context.info();
+        assert!(!info.name.is_empty());
+        assert!(info.max_image_dimension_2d > 0);
+        assert!(info.max_push_constants_size >= 128);
+    }
+}
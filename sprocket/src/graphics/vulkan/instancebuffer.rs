@@ -0,0 +1,112 @@
+use super::{Result, VkAllocator};
+use crate::math::Mat4;
+use ash::vk;
+use std::sync::Arc;
+
+/// The number of instances allocated for if an empty buffer is created
+const DEFAULT_CAPACITY: usize = 64;
+
+/// A host visible vertex buffer of per instance model matrices
+/// Bound to binding 1 for instanced drawing
+/// The buffer grows when more instances than its capacity are written
+pub struct InstanceBuffer {
+    allocator: VkAllocator,
+    buffer: vk::Buffer,
+    memory: vk_mem::Allocation,
+    /// The number of instances the buffer can hold
+    capacity: usize,
+    /// The number of instances written
+    count: u32,
+}
+
+impl InstanceBuffer {
+    /// Creates an instance buffer with space for capacity model matrices
+    pub fn new(allocator: &VkAllocator, capacity: usize) -> Result<InstanceBuffer> {
+        let capacity = capacity.max(1);
+        let (buffer, memory) = Self::allocate(allocator, capacity)?;
+
+        Ok(InstanceBuffer {
+            allocator: Arc::clone(allocator),
+            buffer,
+            memory,
+            capacity,
+            count: 0,
+        })
+    }
+
+    /// Creates an instance buffer with a default capacity
+    pub fn empty(allocator: &VkAllocator) -> Result<InstanceBuffer> {
+        Self::new(allocator, DEFAULT_CAPACITY)
+    }
+
+    fn allocate(
+        allocator: &VkAllocator,
+        capacity: usize,
+    ) -> Result<(vk::Buffer, vk_mem::Allocation)> {
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
+            &vk::BufferCreateInfo::builder()
+                .size((capacity * std::mem::size_of::<Mat4>()) as u64)
+                .usage(vk::BufferUsageFlags::VERTEX_BUFFER)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build(),
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::CpuToGpu,
+                ..Default::default()
+            },
+        )?;
+
+        Ok((buffer, memory))
+    }
+
+    /// Writes the model matrices to the buffer replacing the previous instances
+    /// Reallocates the buffer if instances do not fit
+    /// The buffer must not be in use by the device
+    pub fn write(&mut self, instances: &[Mat4]) -> Result<()> {
+        if instances.len() > self.capacity {
+            let capacity = instances.len().next_power_of_two();
+            let (buffer, memory) = Self::allocate(&self.allocator, capacity)?;
+
+            self.allocator
+                .lock()
+                .unwrap()
+                .destroy_buffer(self.buffer, &self.memory)?;
+
+            self.buffer = buffer;
+            self.memory = memory;
+            self.capacity = capacity;
+        }
+
+        let size = std::mem::size_of_val(instances);
+
+        let mapped: *mut u8 = self.allocator.lock().unwrap().map_memory(&self.memory)?;
+        unsafe { std::ptr::copy_nonoverlapping(instances.as_ptr() as *const u8, mapped, size) }
+        self.allocator.lock().unwrap().unmap_memory(&self.memory)?;
+
+        self.count = instances.len() as u32;
+        Ok(())
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Returns the number of instances last written
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns the number of instances the buffer can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Drop for InstanceBuffer {
+    fn drop(&mut self) {
+        self.allocator
+            .lock()
+            .unwrap()
+            .destroy_buffer(self.buffer, &self.memory)
+            .expect("Failed to free vulkan memory");
+    }
+}
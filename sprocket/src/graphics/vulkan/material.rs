@@ -1,35 +1,112 @@
 use super::{
-    resources::Resource, DescriptorPool, DescriptorSet, DescriptorType, Error, Pipeline,
-    ResourceManager, Result, Sampler, Texture,
+    resources::Resource, DescriptorAllocation, DescriptorSet, Error, Pipeline, ResourceManager,
+    Result, Sampler, SamplerSpec, Texture, TextureUsageHint, UniformBuffer,
 };
 
-use ash::vk;
+use crate::graphics::Color;
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MaterialSpec {
     pipeline: String,
     textures: Vec<String>,
-    // TODO coming features
-    // color: Color,
-    // reflectivity: f32,
-    // smoothness: f32,
+    /// The sampler used for each texture in the same order
+    /// Textures without a corresponding sampler use the default sampler
+    #[serde(default)]
+    samplers: Vec<SamplerSpec>,
+    /// How each texture in the same order is sampled
+    /// Textures without a corresponding hint are sRGB encoded colors
+    #[serde(default)]
+    texture_hints: Vec<TextureUsageHint>,
+    /// The linear tint multiplied with the textures
+    #[serde(default)]
+    color: Color,
+    #[serde(default)]
+    reflectivity: f32,
+    #[serde(default)]
+    smoothness: f32,
+}
+
+/// The material parameters as laid out in the material uniform buffer
+/// Matches the std140 layout of the block in the shaders
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MaterialData {
+    pub color: Color,
+    pub reflectivity: f32,
+    pub smoothness: f32,
+    _padding: [f32; 2],
 }
 
 pub struct Material {
     pipeline: Arc<Pipeline>,
     textures: Vec<Arc<Texture>>,
     samplers: Vec<Arc<Sampler>>,
-    descriptor_sets: Vec<DescriptorSet>,
-    /// May be removed and replaced with descriptor pool management
-    descriptor_pool: DescriptorPool,
+    /// Holds the MaterialData of the spec
+    /// Bound to the uniform buffer binding of the per material set if the pipeline has one
+    uniformbuffer: UniformBuffer,
+    /// Allocated from the shared pools of the resource manager and freed with the material
+    descriptor_sets: DescriptorAllocation,
     spec: MaterialSpec,
 }
 
+impl MaterialSpec {
+    /// Creates a spec for a material with the default samplers
+    pub fn new(pipeline: &str, textures: Vec<String>) -> MaterialSpec {
+        MaterialSpec {
+            pipeline: pipeline.to_owned(),
+            textures,
+            samplers: Vec::new(),
+            texture_hints: Vec::new(),
+            color: Color::white(),
+            reflectivity: 0.0,
+            smoothness: 0.0,
+        }
+    }
+
+    /// Sets the linear tint multiplied with the textures
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets how each texture is sampled, E.g; TextureUsageHint::Linear for normal maps
+    pub fn with_texture_hints(mut self, hints: Vec<TextureUsageHint>) -> Self {
+        self.texture_hints = hints;
+        self
+    }
+
+    pub fn with_pbr(mut self, reflectivity: f32, smoothness: f32) -> Self {
+        self.reflectivity = reflectivity;
+        self.smoothness = smoothness;
+        self
+    }
+
+    /// Returns the parameters uploaded to the material uniform buffer
+    pub fn data(&self) -> MaterialData {
+        MaterialData {
+            color: self.color,
+            reflectivity: self.reflectivity,
+            smoothness: self.smoothness,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
 impl Resource for Material {
     fn load(resourcemanager: &ResourceManager, path: &str) -> Result<Self> {
-        let spec = serde_json::from_str(&ex::fs::read_to_string(path)?)?;
+        let contents = ex::fs::read_to_string(path)?;
+        Self::load_from_reader(resourcemanager, &mut contents.as_bytes(), path)
+    }
+
+    fn load_from_reader(
+        resourcemanager: &ResourceManager,
+        reader: &mut dyn Read,
+        _: &str,
+    ) -> Result<Self> {
+        let spec = serde_json::from_reader(reader)?;
         Self::new(spec, resourcemanager)
     }
 }
@@ -41,7 +118,11 @@ impl Material {
         let textures: Vec<Arc<Texture>> = spec
             .textures
             .iter()
-            .map(|tex| resourcemanager.load_texture(tex))
+            .enumerate()
+            .map(|(i, tex)| {
+                let hint = spec.texture_hints.get(i).cloned().unwrap_or_default();
+                resourcemanager.load_texture_with_hint(tex, hint)
+            })
             .collect::<Result<_>>()?;
 
         let context = resourcemanager.context();
@@ -53,61 +134,37 @@ impl Material {
             None => return Err(Error::MissingDescriptorSet(1)),
         };
 
-        let descriptor_pool_sizes = [
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: (per_material_layout
-                    .spec()
-                    .bindings
-                    .iter()
-                    .filter(|binding| binding.ty == DescriptorType::CombinedImageSampler)
-                    .count()
-                    * swapchain.image_count()) as u32,
-            },
-            // vk::DescriptorPoolSize {
-            //     ty: vk::DescriptorType::UNIFORM_BUFFER,
-            //     descriptor_count: per_material_layout
-            //         .spec()
-            //         .bindings
-            //         .iter()
-            //         .filter(|binding| binding.ty == DescriptorType::UniformBuffer)
-            //         .count() as u32,
-            // },
-        ];
-
-        // Create pool for this material only
-        // Will be changed when implementing descriptor pool management
-        let descriptor_pool = DescriptorPool::new(
-            &context.device,
-            &descriptor_pool_sizes,
-            swapchain.image_count() as u32,
-        )?;
+        let descriptor_sets = resourcemanager
+            .allocate_descriptor_sets(&per_material_layout, swapchain.image_count() as u32)?;
 
-        let descriptor_sets = DescriptorSet::new(
-            &context.device,
-            &descriptor_pool,
-            &per_material_layout,
-            swapchain.image_count() as u32,
-        )?;
+        let default_sampler = SamplerSpec::default();
+        let samplers: Vec<Arc<Sampler>> = (0..textures.len())
+            .map(|i| spec.samplers.get(i).unwrap_or(&default_sampler))
+            .map(|sampler| Sampler::with_spec(context, sampler).map(Arc::new))
+            .collect::<Result<_>>()?;
 
-        let samplers = vec![Arc::new(Sampler::new(&context.device)?)];
+        let data = spec.data();
+        let uniformbuffer =
+            UniformBuffer::new(&context.allocator, std::mem::size_of_val(&data) as u64)?;
+        uniformbuffer.write(&data, None)?;
 
-        // Write the per material descriptor set with the textures
+        // Write the per material descriptor set with the parameters and textures
         DescriptorSet::write(
             &context.device,
-            &descriptor_sets,
+            descriptor_sets.sets(),
             &per_material_layout.spec(),
+            std::iter::repeat(&uniformbuffer),
             [].iter(),
-            textures.iter().cycle(),
-            samplers.iter().cycle(),
+            textures.iter().map(Arc::as_ref).cycle(),
+            samplers.iter().map(Arc::as_ref).cycle(),
         )?;
 
         Ok(Material {
             pipeline,
             textures,
             samplers,
+            uniformbuffer,
             descriptor_sets,
-            descriptor_pool,
             spec,
         })
     }
@@ -120,9 +177,19 @@ impl Material {
         &self.textures
     }
 
+    /// Returns the sampler used for each texture
+    pub fn samplers(&self) -> &[Arc<Sampler>] {
+        &self.samplers
+    }
+
+    /// Returns the buffer holding the MaterialData of the spec
+    pub fn uniformbuffer(&self) -> &UniformBuffer {
+        &self.uniformbuffer
+    }
+
     /// Returns the per material descriptor sets for each swapchain image
     pub fn descriptor_sets(&self) -> &[DescriptorSet] {
-        &self.descriptor_sets[..]
+        self.descriptor_sets.sets()
     }
 
     pub fn spec(&self) -> &MaterialSpec {
@@ -135,3 +202,64 @@ impl Material {
         Self::new(self.spec.clone(), resourcemanager)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::enums::AddressMode;
+    use ash::vk;
+
+    #[test]
+    fn material_data() {
+        let spec: MaterialSpec = serde_json::from_str(
+            r#"{
+                "pipeline": "./data/pipelines/default.json",
+                "textures": ["./data/textures/uv.png"],
+                "color": { "r": 1.0, "g": 0.5, "b": 0.25, "a": 1.0 },
+                "smoothness": 0.75
+            }"#,
+        )
+        .unwrap();
+
+        let data = spec.data();
+        assert_eq!(data.color, Color::new(1.0, 0.5, 0.25, 1.0));
+        assert_eq!(data.reflectivity, 0.0);
+        assert_eq!(data.smoothness, 0.75);
+
+        // The std140 block is a vec4 and two floats padded to 16 bytes
+        assert_eq!(std::mem::size_of::<MaterialData>(), 32);
+    }
+
+    #[test]
+    fn samplers() {
+        let spec: MaterialSpec = serde_json::from_str(
+            r#"{
+                "pipeline": "./data/pipelines/default.json",
+                "textures": ["./data/textures/uv.png", "./data/textures/grid.png"],
+                "samplers": [{
+                    "mag_filter": "Nearest",
+                    "min_filter": "Nearest",
+                    "address_mode_u": "ClampToEdge",
+                    "address_mode_v": "ClampToEdge",
+                    "anisotropy": null
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.samplers.len(), 1);
+        let sampler = &spec.samplers[0];
+        assert_eq!(vk::Filter::from(sampler.mag_filter), vk::Filter::NEAREST);
+        assert_eq!(vk::Filter::from(sampler.min_filter), vk::Filter::NEAREST);
+        assert_eq!(
+            vk::SamplerAddressMode::from(sampler.address_mode_u),
+            vk::SamplerAddressMode::CLAMP_TO_EDGE
+        );
+        assert_eq!(sampler.address_mode_v, AddressMode::ClampToEdge);
+        assert_eq!(sampler.anisotropy, None);
+
+        // Fields left out use the default sampler
+        assert_eq!(sampler.mipmap_mode, SamplerSpec::default().mipmap_mode);
+        assert_eq!(sampler.address_mode_w, AddressMode::Repeat);
+    }
+}
@@ -1,4 +1,5 @@
-use super::{CommandPool, IndexBuffer, Result, Vertex, VertexBuffer, VkAllocator};
+use super::{IndexBuffer, Result, Transfer, Vertex, VertexBuffer, VkAllocator};
+use crate::math::Aabb;
 use ash::vk;
 use log::info;
 
@@ -6,26 +7,36 @@ use log::info;
 pub struct Mesh {
     vertexbuffer: VertexBuffer,
     indexbuffer: IndexBuffer,
+    bounds: Aabb,
 }
 
 impl Mesh {
     /// Creates a new mesh with given vertices and indices
+    /// The indices are stored as u16 if all vertices can be indexed by it
     pub fn new(
         allocator: &VkAllocator,
         device: &ash::Device,
-        queue: vk::Queue,
-        commandpool: &CommandPool,
+        transfer: &Transfer,
         vertices: &[Vertex],
         indices: &[u32],
     ) -> Result<Mesh> {
-        let vertexbuffer = VertexBuffer::new(allocator, device, queue, commandpool, vertices)?;
-        let indexbuffer = IndexBuffer::new(allocator, device, queue, commandpool, indices)?;
+        let vertexbuffer = VertexBuffer::new(allocator, device, transfer, vertices)?;
+        let indexbuffer = match index_type(vertices.len()) {
+            vk::IndexType::UINT16 => {
+                let indices: Vec<u16> = indices.iter().map(|index| *index as u16).collect();
+                IndexBuffer::new_u16(allocator, device, transfer, &indices)?
+            }
+            _ => IndexBuffer::new(allocator, device, transfer, indices)?,
+        };
+
+        let bounds = Aabb::from_points(vertices.iter().map(|vertex| vertex.position));
 
         info!("Created new mesh");
 
         Ok(Mesh {
             vertexbuffer,
             indexbuffer,
+            bounds,
         })
     }
 
@@ -50,4 +61,66 @@ impl Mesh {
     pub fn index_count(&self) -> u32 {
         self.indexbuffer.count()
     }
+
+    /// Returns the bounding box of the vertex positions in model space
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+/// Returns the smallest index type that can index vertex_count vertices
+fn index_type(vertex_count: usize) -> vk::IndexType {
+    if vertex_count <= u16::MAX as usize + 1 {
+        vk::IndexType::UINT16
+    } else {
+        vk::IndexType::UINT32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+    use crate::math::{Vec2, Vec3};
+
+    #[test]
+    fn smallest_index_type() {
+        assert_eq!(index_type(3), vk::IndexType::UINT16);
+        assert_eq!(index_type(65536), vk::IndexType::UINT16);
+        assert_eq!(index_type(65537), vk::IndexType::UINT32);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn u16_indices() {
+        let context = vulkan::test_context();
+        let transfer = context.transfer();
+
+        let indexbuffer =
+            IndexBuffer::new_u16(&context.allocator, &context.device, &transfer, &[0, 1, 2])
+                .unwrap();
+        assert_eq!(indexbuffer.index_type(), vk::IndexType::UINT16);
+        assert_eq!(indexbuffer.count(), 3);
+
+        let indexbuffer =
+            IndexBuffer::new(&context.allocator, &context.device, &transfer, &[0, 1, 2]).unwrap();
+        assert_eq!(indexbuffer.index_type(), vk::IndexType::UINT32);
+
+        // Small meshes pick 16 bit indices
+        let vertices = [
+            Vertex::new(Vec3::zero(), Vec2::zero()),
+            Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec2::zero()),
+            Vertex::new(Vec3::new(0.0, 1.0, 0.0), Vec2::zero()),
+        ];
+        let mesh = Mesh::new(
+            &context.allocator,
+            &context.device,
+            &transfer,
+            &vertices,
+            &[0, 1, 2],
+        )
+        .unwrap();
+        assert_eq!(mesh.indexbuffer().index_type(), vk::IndexType::UINT16);
+        assert_eq!(mesh.index_count(), 3);
+    }
 }
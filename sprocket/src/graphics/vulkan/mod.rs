@@ -1,27 +1,29 @@
 #![allow(dead_code)]
 use crate::graphics::glfw;
 use crate::*;
-use std::cell::RefCell;
 use std::collections::HashSet;
 use std::ffi::{c_void, CStr, CString};
 use std::ptr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use ash::extensions::khr::Surface;
 use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
-use ash::{vk, vk::Handle, Entry};
+use ash::{vk, vk::Handle as _, Entry};
 
 mod texture;
-pub use texture::Texture;
+pub use texture::{Pixels, Texture, TextureUsageHint};
 
 pub mod sampler;
-pub use sampler::Sampler;
+pub use sampler::{Sampler, SamplerSpec};
 
 mod swapchain;
-pub use swapchain::Swapchain;
+pub use swapchain::{FrameCapture, PresentMode, Swapchain, SwapchainSpec};
 
 mod pipeline;
-pub use pipeline::{Pipeline, PipelineSpec};
+pub use pipeline::{DepthBias, DepthState, Pipeline, PipelineSpec};
+
+mod compute;
+pub use compute::{ComputePipeline, ComputePipelineSpec};
 
 mod renderpass;
 pub use renderpass::{
@@ -29,29 +31,47 @@ pub use renderpass::{
 };
 
 mod framebuffer;
-pub use framebuffer::Framebuffer;
+pub use framebuffer::{Framebuffer, FramebufferCache};
+
+mod rendertarget;
+pub use rendertarget::RenderTarget;
+
+mod rendergraph;
+pub use rendergraph::{PassTarget, RenderGraph};
 
 mod commandbuffer;
+pub use commandbuffer::BlitImage;
+pub use commandbuffer::ClearValue;
 pub use commandbuffer::CommandBuffer;
 pub use commandbuffer::CommandPool;
 
 pub mod renderer;
 
+pub mod fullscreen;
+pub use fullscreen::FullscreenPass;
+
 pub mod vertexbuffer;
 pub use vertexbuffer::Vertex;
-pub use vertexbuffer::VertexBuffer;
+pub use vertexbuffer::{VertexBuffer, VertexLayout};
 
 pub mod indexbuffer;
 pub use indexbuffer::IndexBuffer;
 
+pub mod instancebuffer;
+pub use instancebuffer::InstanceBuffer;
+
+pub mod storagebuffer;
+pub use storagebuffer::StorageBuffer;
+
 pub mod uniformbuffer;
 pub use uniformbuffer::UniformBuffer;
 pub use uniformbuffer::UniformBufferObject;
+pub use uniformbuffer::UniformBufferPool;
 
 pub mod descriptors;
 pub use descriptors::{
-    DescriptorPool, DescriptorSet, DescriptorSetLayout, DescriptorSetLayoutBinding,
-    DescriptorSetLayoutSpec, DescriptorType, ShaderStage,
+    DescriptorAllocation, DescriptorAllocator, DescriptorPool, DescriptorSet, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutSpec, DescriptorType, ShaderStage,
 };
 
 pub mod mesh;
@@ -60,18 +80,32 @@ pub use mesh::Mesh;
 pub mod model;
 pub use model::Model;
 
+pub mod gltf;
+
+pub mod tangents;
+
 pub mod material;
-pub use material::{Material, MaterialSpec};
+pub use material::{Material, MaterialData, MaterialSpec};
 
 mod buffer;
 pub use super::{Error, Result};
+pub use buffer::{AsyncTransfer, Transfer, TransferRecording};
 
 mod resources;
-pub use resources::ResourceManager;
+pub use resources::{AsyncResource, Handle, Resource, ResourceInfo, ResourceManager};
 
 pub mod enums;
 
-pub type VkAllocator = Arc<RefCell<vk_mem::Allocator>>;
+mod features;
+pub use features::DeviceFeatures;
+use features::{OPTIONAL_FEATURES, REQUIRED_FEATURES};
+
+mod info;
+pub use info::{DeviceInfo, DeviceType, MemoryStats};
+
+/// The allocator shared by all resources of a context
+/// Locked for the duration of each allocation or mapping
+pub type VkAllocator = Arc<Mutex<vk_mem::Allocator>>;
 
 pub struct VulkanContext {
     entry: ash::Entry,
@@ -79,36 +113,274 @@ pub struct VulkanContext {
     physical_device: vk::PhysicalDevice,
     device: ash::Device,
     debug_utils_loader: ash::extensions::ext::DebugUtils,
-    debug_messenger: vk::DebugUtilsMessengerEXT,
+    /// None if validation is disabled
+    debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
     surface_loader: Surface,
-    surface: vk::SurfaceKHR,
+    /// The window surface, None if the context is headless
+    surface: Option<vk::SurfaceKHR>,
     queue_families: QueueFamilies,
     graphics_queue: vk::Queue,
+    /// Same as the graphics queue if the context is headless
     present_queue: vk::Queue,
+    compute_queue: vk::Queue,
+    /// Same as the graphics queue if the device has no dedicated transfer queue
+    transfer_queue: vk::Queue,
     allocator: VkAllocator,
-    /// A pool for on-off operations like transfers
-    generic_pool: Option<CommandPool>,
+    /// A transient pool on the graphics queue family for one-off operations like transfers
+    /// Created on first use
+    generic_pool: OnceLock<CommandPool>,
+    /// A pool for command buffers submitted to the compute queue
+    compute_pool: Option<CommandPool>,
+    /// A pool for uploads submitted to the transfer queue
+    transfer_pool: Option<CommandPool>,
+    /// Waited on by one time submits instead of waiting for the whole queue to idle
+    /// Locked for the whole submit, which serializes the use of the fence and of the generic,
+    /// compute and transfer pools between threads
+    immediate_fence: Mutex<vk::Fence>,
+    /// Shared by all pipelines to speed up creating and recreating them
+    pipeline_cache: vk::PipelineCache,
+    /// The required and supported optional device features that were enabled
+    features: DeviceFeatures,
+    /// Properties and limits of the physical device
+    info: DeviceInfo,
 }
 
 impl VulkanContext {
+    /// Returns the transient command pool on the graphics queue family used by one time submits
+    /// The pool is created on first use and lives as long as the context
+    /// One time submits lock the immediate fence while using the pool, so other users need to
+    /// go through immediate_submit to not use it from several threads at once
     pub fn generic_pool(&self) -> &CommandPool {
-        self.generic_pool.as_ref().unwrap()
+        self.generic_pool.get_or_init(|| {
+            CommandPool::new(
+                &self.device,
+                self.queue_families.graphics.unwrap(),
+                true,
+                true,
+                self.info.max_push_constants_size,
+            )
+            .expect("Failed to create the generic command pool")
+        })
+    }
+
+    /// Returns the command pool for command buffers submitted to the compute queue
+    pub fn compute_pool(&self) -> &CommandPool {
+        self.compute_pool.as_ref().unwrap()
+    }
+
+    /// Returns the queues used to upload data into device local resources
+    pub fn transfer(&self) -> Transfer<'_> {
+        Transfer {
+            queue: self.transfer_queue,
+            commandpool: self.transfer_pool.as_ref().unwrap(),
+            graphics_queue: self.graphics_queue,
+            graphics_pool: self.generic_pool(),
+            fence: &self.immediate_fence,
+            recording: None,
+        }
+    }
+
+    /// Records commands using a one time command buffer from the generic pool and submits them to
+    /// the graphics queue
+    /// Blocks until the commands have finished executing
+    /// The command buffer is freed afterwards, even if record returns an error
+    pub fn immediate_submit<F>(&self, record: F) -> Result<()>
+    where
+        F: FnOnce(&mut CommandBuffer) -> Result<()>,
+    {
+        buffer::immediate_submit(
+            &self.device,
+            self.graphics_queue,
+            self.generic_pool(),
+            &self.immediate_fence,
+            record,
+        )
+    }
+
+    /// Submits command buffers allocated from the compute pool to the compute queue
+    /// The fence is signaled when the commands have finished executing and may be null
+    pub fn submit_compute(
+        &self,
+        commandbuffers: &[&CommandBuffer],
+        fence: vk::Fence,
+    ) -> Result<()> {
+        CommandBuffer::submit(
+            &self.device,
+            commandbuffers,
+            self.compute_queue,
+            &[],
+            &[],
+            &[],
+            fence,
+        )
+    }
+
+    /// Records compute commands using a one time command buffer and submits them to the compute
+    /// queue
+    /// Blocks on a fence until the commands have finished executing
+    pub fn compute<F>(&self, record: F) -> Result<()>
+    where
+        F: FnOnce(&mut CommandBuffer),
+    {
+        buffer::immediate_submit(
+            &self.device,
+            self.compute_queue,
+            self.compute_pool(),
+            &self.immediate_fence,
+            |commandbuffer| {
+                record(commandbuffer);
+                Ok(())
+            },
+        )
+    }
+
+    /// Returns the first of candidates supporting features with tiling on the device
+    pub fn find_supported_format(
+        &self,
+        candidates: &[vk::Format],
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Result<vk::Format> {
+        find_supported_format(
+            &self.instance,
+            self.physical_device,
+            candidates,
+            tiling,
+            features,
+        )
+    }
+
+    /// Returns the cache all pipelines are created with
+    pub fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.pipeline_cache
+    }
+
+    /// Returns the contents of the pipeline cache
+    /// The data is specific to the device and driver, and is ignored by others when loaded
+    pub fn pipeline_cache_data(&self) -> Result<Vec<u8>> {
+        unsafe {
+            self.device
+                .get_pipeline_cache_data(self.pipeline_cache)
+                .map_err(|e| e.into())
+        }
+    }
+
+    /// Merges data previously returned by pipeline_cache_data into the pipeline cache
+    /// Pipelines created afterwards can reuse the cached state
+    pub fn merge_pipeline_cache(&self, data: &[u8]) -> Result<()> {
+        unsafe {
+            let loaded = create_pipeline_cache(&self.device, data)?;
+
+            let result = self.device.fp_v1_0().merge_pipeline_caches(
+                self.device.handle(),
+                self.pipeline_cache,
+                1,
+                &loaded,
+            );
+
+            self.device.destroy_pipeline_cache(loaded, None);
+
+            match result {
+                vk::Result::SUCCESS => Ok(()),
+                e => Err(e.into()),
+            }
+        }
+    }
+
+    /// Writes the pipeline cache to a file to be loaded by the next run
+    pub fn save_pipeline_cache(&self, path: &str) -> Result<()> {
+        let data = self.pipeline_cache_data()?;
+        ex::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Loads a pipeline cache written by save_pipeline_cache
+    /// Should be called before resources are loaded to speed up the creation of their pipelines
+    pub fn load_pipeline_cache(&self, path: &str) -> Result<()> {
+        let data = ex::fs::read(path)?;
+        self.merge_pipeline_cache(&data)
+    }
+
+    /// Blocks until all queues of the device are idle
+    pub fn wait_idle(&self) -> Result<()> {
+        unsafe { self.device.device_wait_idle()? };
+        Ok(())
+    }
+
+    /// Returns the total device memory allocated for resources
+    /// Traverses all allocations, so should not be called more than once per frame
+    pub fn memory_stats(&self) -> Result<MemoryStats> {
+        let stats = self.allocator.lock().unwrap().calculate_stats()?;
+        Ok(stats.total.into())
+    }
+
+    /// Returns the name, type and limits of the physical device in use
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    /// Returns the device features that were enabled
+    /// Optional features are only enabled if supported
+    pub fn features(&self) -> &DeviceFeatures {
+        &self.features
+    }
+
+    /// Returns true if the context was created without a window surface
+    /// A headless context can only render to render targets
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Creates a surface for an additional window
+    /// Fails if the present queue of the device can not present to it
+    /// The surface needs to be destroyed before the window
+    pub fn create_window_surface(&self, window: &Window) -> Result<vk::SurfaceKHR> {
+        let present = self.queue_families.present.ok_or(Error::Headless)?;
+
+        unsafe {
+            let surface = create_surface(&self.instance, window)?;
+
+            let supported = self
+                .surface_loader
+                .get_physical_device_surface_support(self.physical_device, present, surface)
+                .unwrap_or(false);
+
+            if !supported {
+                self.surface_loader.destroy_surface(surface, None);
+                return Err(Error::UnsupportedSurface);
+            }
+
+            Ok(surface)
+        }
     }
 }
 
 impl Drop for VulkanContext {
     fn drop(&mut self) {
         info!("Dropping vulkan context");
-        self.generic_pool = None;
+        // Cheap when the application has already waited on shutdown, and keeps contexts dropped
+        // without an orderly shutdown from destroying objects that are still in use
+        if let Err(e) = self.wait_idle() {
+            error!("Failed to wait on device '{}'", e);
+        }
+        self.generic_pool.take();
+        self.compute_pool = None;
+        self.transfer_pool = None;
         unsafe {
-            self.allocator.borrow_mut().destroy();
-            // Drop data before device
-            // This will later migrate out to materials and alike
-            self.device.device_wait_idle().unwrap();
+            self.device
+                .destroy_fence(*self.immediate_fence.get_mut().unwrap(), None);
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None);
+            self.allocator.lock().unwrap().destroy();
+            // The device is idle since it was waited on and all resources have been dropped
             self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
-            self.debug_utils_loader
-                .destroy_debug_utils_messenger(self.debug_messenger, None);
+            if let Some(surface) = self.surface {
+                self.surface_loader.destroy_surface(surface, None);
+            }
+            if let Some(debug_messenger) = self.debug_messenger {
+                self.debug_utils_loader
+                    .destroy_debug_utils_messenger(debug_messenger, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -118,15 +390,19 @@ pub struct QueueFamilies {
     pub graphics: Option<u32>,
     pub present: Option<u32>,
     pub compute: Option<u32>,
+    /// A family dedicated to transfers if the device has one, otherwise the graphics family
+    pub transfer: Option<u32>,
     pub present_support: bool,
 }
 
 impl QueueFamilies {
+    /// Finds the queue families of a physical device
+    /// If surface is None no present family is searched for
     unsafe fn find(
         instance: &ash::Instance,
         physical_device: &vk::PhysicalDevice,
         surface_loader: &Surface,
-        surface: &vk::SurfaceKHR,
+        surface: Option<&vk::SurfaceKHR>,
     ) -> QueueFamilies {
         let families = instance.get_physical_device_queue_family_properties(*physical_device);
         let mut graphics_family = None;
@@ -134,17 +410,29 @@ impl QueueFamilies {
         let mut compute_family = None;
         let mut present_support = false;
         for (i, family) in families.iter().enumerate() {
+            // Without a surface any family supporting both graphics and compute will do
+            if surface.is_none()
+                && family
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            {
+                graphics_family = Some(i as u32);
+                compute_family = Some(i as u32);
+                break;
+            }
             if family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
                 graphics_family = Some(i as u32);
             }
-            if surface_loader
-                .get_physical_device_surface_support(*physical_device, i as u32, *surface)
-                .unwrap_or(false)
-            {
-                presentation_family = Some(i as u32);
-                present_support = surface_loader
+            if let Some(surface) = surface {
+                if surface_loader
                     .get_physical_device_surface_support(*physical_device, i as u32, *surface)
-                    .unwrap_or(false);
+                    .unwrap_or(false)
+                {
+                    presentation_family = Some(i as u32);
+                    present_support = surface_loader
+                        .get_physical_device_surface_support(*physical_device, i as u32, *surface)
+                        .unwrap_or(false);
+                }
             }
             if family.queue_flags.contains(vk::QueueFlags::COMPUTE) {
                 compute_family = Some(i as u32);
@@ -155,44 +443,111 @@ impl QueueFamilies {
             graphics: graphics_family,
             present: presentation_family,
             compute: compute_family,
+            transfer: Self::find_transfer(&families).or(graphics_family),
             present_support,
         }
     }
+
+    /// Returns the first family that supports transfers but not graphics or compute
+    /// Such families usually map to dedicated DMA engines that run alongside rendering
+    fn find_transfer(families: &[vk::QueueFamilyProperties]) -> Option<u32> {
+        families
+            .iter()
+            .position(|family| {
+                family.queue_count > 0
+                    && family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                    && !family
+                        .queue_flags
+                        .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            })
+            .map(|i| i as u32)
+    }
+}
+
+/// The layers enabled when validation is requested
+const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
+
+/// Initializes vulkan with the surface of window
+/// Validation layers and debug messages are enabled if validation is true and the layers are
+/// installed
+pub fn init(window: &Window, validation: bool) -> Result<VulkanContext> {
+    init_context(Some(window), validation)
+}
+
+/// Initializes vulkan without a window, surface or swapchain
+/// Does not require GLFW to be initialized
+pub fn init_headless(validation: bool) -> Result<VulkanContext> {
+    init_context(None, validation)
 }
 
-pub fn init(window: &Window) -> Result<VulkanContext> {
+/// Creates a headless context for tests needing a Vulkan device
+/// Those tests are ignored by default and run with `cargo test -- --ignored`
+#[cfg(test)]
+pub(crate) fn test_context() -> VulkanContext {
+    init_headless(false).expect("Failed to create a headless Vulkan context")
+}
+
+fn init_context(window: Option<&Window>, validation: bool) -> Result<VulkanContext> {
     unsafe {
         let entry = match Entry::new() {
             Ok(entry) => entry,
             Err(_) => return Err(Error::UnsupportedAPI(super::Api::Vulkan)),
         };
 
-        let validation_layers = ["VK_LAYER_KHRONOS_validation"];
-        let device_extensions = ["VK_KHR_swapchain"];
+        let device_extensions: &[&str] = match window {
+            Some(_) => &["VK_KHR_swapchain"],
+            None => &[],
+        };
 
-        // Ensure all requested layers exist
-        check_validation_layer_support(&entry, &validation_layers)?;
-        let instance = create_instance(&entry, &validation_layers)?;
+        let layers = validation_layers(&entry, validation);
+        let validation = !layers.is_empty();
+        let instance = create_instance(&entry, layers, window.is_some(), validation)?;
 
         let debug_utils_loader = ash::extensions::ext::DebugUtils::new(&entry, &instance);
 
-        let debug_messenger = create_debug_messenger(&debug_utils_loader)?;
-        let surface = create_surface(&instance, &window)?;
+        let debug_messenger = match validation {
+            true => Some(create_debug_messenger(&debug_utils_loader)?),
+            false => None,
+        };
+        let surface = match window {
+            Some(window) => Some(create_surface(&instance, window)?),
+            None => None,
+        };
         // Choose physical devices
 
         let surface_loader = Surface::new(&entry, &instance);
-        let (physical_device, queue_families) =
-            find_physical_device(&instance, &surface_loader, &surface, &device_extensions)?;
+        let (physical_device, queue_families) = find_physical_device(
+            &instance,
+            &surface_loader,
+            surface.as_ref(),
+            device_extensions,
+            &REQUIRED_FEATURES,
+        )?;
+
+        let info =
+            DeviceInfo::from_properties(&instance.get_physical_device_properties(physical_device));
+
+        let supported_features =
+            DeviceFeatures::from_vk(&instance.get_physical_device_features(physical_device));
+
+        let features =
+            REQUIRED_FEATURES.union(&OPTIONAL_FEATURES.intersection(&supported_features));
 
         let device = create_device(
             &instance,
             physical_device,
             &queue_families,
-            &device_extensions,
+            device_extensions,
+            &features,
         )?;
 
         let graphics_queue = device.get_device_queue(queue_families.graphics.unwrap(), 0);
-        let present_queue = device.get_device_queue(queue_families.present.unwrap(), 0);
+        let present_queue = match queue_families.present {
+            Some(present) => device.get_device_queue(present, 0),
+            None => graphics_queue,
+        };
+        let compute_queue = device.get_device_queue(queue_families.compute.unwrap(), 0);
+        let transfer_queue = device.get_device_queue(queue_families.transfer.unwrap(), 0);
 
         let allocator_info = vk_mem::AllocatorCreateInfo {
             device: device.clone(),
@@ -204,9 +559,25 @@ pub fn init(window: &Window) -> Result<VulkanContext> {
             heap_size_limits: None,
         };
 
-        let allocator = Arc::new(RefCell::new(vk_mem::Allocator::new(&allocator_info)?));
+        let allocator = Arc::new(Mutex::new(vk_mem::Allocator::new(&allocator_info)?));
+
+        let compute_pool = CommandPool::new(
+            &device,
+            queue_families.compute.unwrap(),
+            true,
+            true,
+            info.max_push_constants_size,
+        )?;
+        let transfer_pool = CommandPool::new(
+            &device,
+            queue_families.transfer.unwrap(),
+            true,
+            true,
+            info.max_push_constants_size,
+        )?;
 
-        let generic_pool = CommandPool::new(&device, queue_families.graphics.unwrap(), true, true)?;
+        let immediate_fence = create_fence(&device)?;
+        let pipeline_cache = create_pipeline_cache(&device, &[])?;
 
         Ok(VulkanContext {
             entry,
@@ -220,8 +591,16 @@ pub fn init(window: &Window) -> Result<VulkanContext> {
             queue_families,
             graphics_queue,
             present_queue,
+            compute_queue,
+            transfer_queue,
             allocator,
-            generic_pool: Some(generic_pool),
+            generic_pool: OnceLock::new(),
+            compute_pool: Some(compute_pool),
+            transfer_pool: Some(transfer_pool),
+            immediate_fence: Mutex::new(immediate_fence),
+            pipeline_cache,
+            features,
+            info,
         })
     }
 
@@ -230,7 +609,15 @@ pub fn init(window: &Window) -> Result<VulkanContext> {
     //
 }
 
-unsafe fn create_instance(entry: &ash::Entry, layers: &[&str]) -> Result<ash::Instance> {
+/// Creates an instance with the given layers
+/// The extensions required by GLFW are only enabled if window_extensions is true
+/// The debug utils extension for debug messages is only enabled if debug_utils is true
+unsafe fn create_instance(
+    entry: &ash::Entry,
+    layers: &[&str],
+    window_extensions: bool,
+    debug_utils: bool,
+) -> Result<ash::Instance> {
     let app_name = CString::new("Sprocket").unwrap();
     let app_info = vk::ApplicationInfo::builder()
         .application_name(&app_name)
@@ -240,15 +627,19 @@ unsafe fn create_instance(entry: &ash::Entry, layers: &[&str]) -> Result<ash::In
         .api_version(vk::make_version(1, 0, 0));
 
     // Extension support
-    let mut glfw_extension_count = 0;
-    let glfw_extensions = glfw::glfwGetRequiredInstanceExtensions(&mut glfw_extension_count);
-
-    let mut extensions = Vec::with_capacity(glfw_extension_count as usize);
-    for i in 0..glfw_extension_count {
-        let extension = *glfw_extensions.offset(i as isize);
-        extensions.push(extension);
+    let mut extensions = Vec::new();
+    if window_extensions {
+        let mut glfw_extension_count = 0;
+        let glfw_extensions = glfw::glfwGetRequiredInstanceExtensions(&mut glfw_extension_count);
+
+        for i in 0..glfw_extension_count {
+            let extension = *glfw_extensions.offset(i as isize);
+            extensions.push(extension);
+        }
+    }
+    if debug_utils {
+        extensions.push(b"VK_EXT_debug_utils\0".as_ptr() as *const i8);
     }
-    extensions.push(b"VK_EXT_debug_utils\0".as_ptr() as *const i8);
 
     // Convert the slice to *const *const null terminated
     let layers = utils::vec_to_null_terminated(layers);
@@ -263,7 +654,23 @@ unsafe fn create_instance(entry: &ash::Entry, layers: &[&str]) -> Result<ash::In
         .map_err(|e| Error::InstanceError(e))
 }
 
-fn check_validation_layer_support(entry: &ash::Entry, layers: &[&str]) -> Result<()> {
+/// Returns the layers to enable, empty if validation is off or the layers are missing
+fn validation_layers<E: EntryV1_0>(entry: &E, validation: bool) -> &'static [&'static str] {
+    if !validation {
+        return &[];
+    }
+
+    // Continue without validation on systems without the layers, E.g; without the Vulkan SDK
+    match check_validation_layer_support(entry, &VALIDATION_LAYERS) {
+        Ok(()) => &VALIDATION_LAYERS,
+        Err(e) => {
+            warn!("{}, continuing without validation", e);
+            &[]
+        }
+    }
+}
+
+fn check_validation_layer_support<E: EntryV1_0>(entry: &E, layers: &[&str]) -> Result<()> {
     let available_layers = entry.enumerate_instance_layer_properties()?;
 
     let available_layers: Vec<&CStr> = available_layers
@@ -334,12 +741,17 @@ unsafe fn rate_device(
     instance: &ash::Instance,
     device: &vk::PhysicalDevice,
     surface_loader: &Surface,
-    surface: &vk::SurfaceKHR,
+    surface: Option<&vk::SurfaceKHR>,
     extensions: &[&str],
+    required_features: &DeviceFeatures,
 ) -> u32 {
     let mut score = 1;
     let properties = instance.get_physical_device_properties(*device);
-    // let features = instance.get_physical_device_features(*device);
+    let features = DeviceFeatures::from_vk(&instance.get_physical_device_features(*device));
+
+    if !required_features.supported_by(&features) {
+        return 0;
+    }
 
     let queue_families = QueueFamilies::find(instance, device, surface_loader, surface);
 
@@ -368,29 +780,33 @@ unsafe fn rate_device(
             return 0;
         }
     }
-    if queue_families.graphics.is_none() {
-        return 0;
-    }
-    if queue_families.present.is_none() {
+    if queue_families.graphics.is_none() || queue_families.compute.is_none() {
         return 0;
     }
 
-    if !queue_families.present_support {
-        return 0;
-    }
+    // Presentation is only required with a surface
+    if let Some(surface) = surface {
+        if queue_families.present.is_none() {
+            return 0;
+        }
 
-    // Check adequate swapchain support
-    let (_capabilities, formats, present_modes) =
-        match Swapchain::query_support(device, surface_loader, surface) {
-            Ok(v) => v,
-            Err(_) => return 0,
-        };
+        if !queue_families.present_support {
+            return 0;
+        }
 
-    if formats.is_empty() {
-        return 0;
-    }
-    if present_modes.is_empty() {
-        return 0;
+        // Check adequate swapchain support
+        let (_capabilities, formats, present_modes) =
+            match Swapchain::query_support(device, surface_loader, surface) {
+                Ok(v) => v,
+                Err(_) => return 0,
+            };
+
+        if formats.is_empty() {
+            return 0;
+        }
+        if present_modes.is_empty() {
+            return 0;
+        }
     }
 
     if properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
@@ -407,15 +823,23 @@ unsafe fn rate_device(
 unsafe fn find_physical_device(
     instance: &ash::Instance,
     surface_loader: &Surface,
-    surface: &vk::SurfaceKHR,
+    surface: Option<&vk::SurfaceKHR>,
     device_extensions: &[&str],
+    required_features: &DeviceFeatures,
 ) -> Result<(vk::PhysicalDevice, QueueFamilies)> {
     let devices = instance.enumerate_physical_devices().unwrap_or_default();
 
     let best_device = match devices
         .iter()
         .zip(devices.iter().map(|device| {
-            rate_device(instance, device, surface_loader, surface, device_extensions)
+            rate_device(
+                instance,
+                device,
+                surface_loader,
+                surface,
+                device_extensions,
+                required_features,
+            )
         }))
         .filter(|(_, score)| *score > 0)
         .max_by(|(_, prev_score), (_, score)| score.cmp(prev_score))
@@ -441,6 +865,7 @@ unsafe fn create_device(
     pdevice: vk::PhysicalDevice,
     queue_families: &QueueFamilies,
     device_extensions: &[&str],
+    features: &DeviceFeatures,
 ) -> Result<ash::Device> {
     let priorities = [1.0];
 
@@ -448,7 +873,11 @@ unsafe fn create_device(
 
     let mut unique_families = HashSet::new();
     unique_families.insert(queue_families.graphics.unwrap());
-    unique_families.insert(queue_families.present.unwrap());
+    if let Some(present) = queue_families.present {
+        unique_families.insert(present);
+    }
+    unique_families.insert(queue_families.compute.unwrap());
+    unique_families.insert(queue_families.transfer.unwrap());
     debug!("Unique queue families {}", unique_families.len());
 
     for queue_family in unique_families {
@@ -459,11 +888,7 @@ unsafe fn create_device(
         queue_infos.push(queue_info);
     }
 
-    let features = vk::PhysicalDeviceFeatures {
-        shader_clip_distance: 1,
-        sampler_anisotropy: vk::TRUE,
-        ..Default::default()
-    };
+    let features = features.to_vk();
 
     // Convert the slice to *const *const null terminated
     let device_extensions = utils::vec_to_null_terminated(device_extensions);
@@ -488,6 +913,52 @@ fn create_semaphore(device: &ash::Device) -> Result<vk::Semaphore> {
     }
 }
 
+/// Returns the first of candidates whose properties for tiling contain features
+fn find_supported_format(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> Result<vk::Format> {
+    pick_supported_format(candidates, tiling, features, |format| unsafe {
+        instance.get_physical_device_format_properties(physical_device, format)
+    })
+}
+
+/// Returns the first of candidates whose properties for tiling contain features
+/// The properties of each format are queried with properties
+fn pick_supported_format<F>(
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+    properties: F,
+) -> Result<vk::Format>
+where
+    F: Fn(vk::Format) -> vk::FormatProperties,
+{
+    candidates
+        .iter()
+        .cloned()
+        .find(|&format| {
+            let properties = properties(format);
+            match tiling {
+                vk::ImageTiling::LINEAR => properties.linear_tiling_features.contains(features),
+                _ => properties.optimal_tiling_features.contains(features),
+            }
+        })
+        .ok_or(Error::NoSupportedFormat(features))
+}
+
+/// Creates a pipeline cache with initial data, which may be empty
+/// Data from another device or driver is ignored
+unsafe fn create_pipeline_cache(device: &ash::Device, data: &[u8]) -> Result<vk::PipelineCache> {
+    let create_info = vk::PipelineCacheCreateInfo::builder().initial_data(data);
+    device
+        .create_pipeline_cache(&create_info, None)
+        .map_err(|e| e.into())
+}
+
 fn create_fence(device: &ash::Device) -> Result<vk::Fence> {
     let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
     unsafe { device.create_fence(&fence_info, None).map_err(|e| e.into()) }
@@ -529,3 +1000,349 @@ unsafe extern "system" fn debug_callback(
     }
     vk::FALSE
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ash::EntryCustom;
+    use std::os::raw::c_char;
+
+    /// Reports the validation layer as installed if VALIDATION is true, and no layers otherwise
+    extern "system" fn enumerate_layers<const VALIDATION: bool>(
+        count: *mut u32,
+        properties: *mut vk::LayerProperties,
+    ) -> vk::Result {
+        unsafe {
+            *count = VALIDATION as u32;
+            if VALIDATION && !properties.is_null() {
+                let mut layer = vk::LayerProperties::default();
+                for (dst, src) in layer
+                    .layer_name
+                    .iter_mut()
+                    .zip(VALIDATION_LAYERS[0].bytes())
+                {
+                    *dst = src as c_char;
+                }
+                *properties = layer;
+            }
+        }
+        vk::Result::SUCCESS
+    }
+
+    extern "system" fn get_instance_proc_addr<const VALIDATION: bool>(
+        _instance: vk::Instance,
+        name: *const c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        let name = unsafe { CStr::from_ptr(name) };
+        match name.to_bytes() {
+            b"vkEnumerateInstanceLayerProperties" => unsafe {
+                std::mem::transmute::<*const c_void, vk::PFN_vkVoidFunction>(
+                    enumerate_layers::<VALIDATION> as *const c_void,
+                )
+            },
+            _ => None,
+        }
+    }
+
+    /// Creates an entry which only implements enumerating the instance layers
+    fn mock_entry<const VALIDATION: bool>() -> EntryCustom<()> {
+        EntryCustom::new_custom((), |_, _| {
+            get_instance_proc_addr::<VALIDATION> as *const c_void
+        })
+    }
+
+    #[test]
+    fn find_transfer() {
+        let family = |queue_flags| vk::QueueFamilyProperties {
+            queue_flags,
+            queue_count: 1,
+            ..Default::default()
+        };
+
+        let all = vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER;
+        let families = [
+            family(all),
+            family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+            family(vk::QueueFlags::TRANSFER | vk::QueueFlags::SPARSE_BINDING),
+        ];
+        assert_eq!(QueueFamilies::find_transfer(&families), Some(2));
+
+        // No dedicated transfer family
+        assert_eq!(QueueFamilies::find_transfer(&families[..2]), None);
+    }
+
+    #[test]
+    fn validation_layers_are_optional() {
+        let without_layer = mock_entry::<false>();
+        assert!(validation_layers(&without_layer, false).is_empty());
+        assert!(validation_layers(&without_layer, true).is_empty());
+
+        let with_layer = mock_entry::<true>();
+        assert!(validation_layers(&with_layer, false).is_empty());
+        assert_eq!(validation_layers(&with_layer, true), VALIDATION_LAYERS);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn rate_missing_features() {
+        let context = test_context();
+        let rate = |features: &DeviceFeatures| unsafe {
+            rate_device(
+                &context.instance,
+                &context.physical_device,
+                &context.surface_loader,
+                None,
+                &[],
+                features,
+            )
+        };
+
+        assert!(rate(&REQUIRED_FEATURES) > 0);
+
+        // A device lacking any single requested feature scores 0
+        let supported = DeviceFeatures::from_vk(unsafe {
+            &context
+                .instance
+                .get_physical_device_features(context.physical_device)
+        });
+        let features = [
+            DeviceFeatures {
+                sampler_anisotropy: true,
+                ..Default::default()
+            },
+            DeviceFeatures {
+                shader_clip_distance: true,
+                ..Default::default()
+            },
+            DeviceFeatures {
+                fill_mode_non_solid: true,
+                ..Default::default()
+            },
+            DeviceFeatures {
+                depth_bias_clamp: true,
+                ..Default::default()
+            },
+        ];
+
+        for feature in &features {
+            assert_eq!(rate(feature) > 0, feature.supported_by(&supported));
+        }
+    }
+
+    #[test]
+    fn allocator_is_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<VkAllocator>();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn allocate_from_threads() {
+        let context = test_context();
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let allocator = Arc::clone(&context.allocator);
+                std::thread::spawn(move || {
+                    for _ in 0..64 {
+                        let (buffer, memory, _) = allocator
+                            .lock()
+                            .unwrap()
+                            .create_buffer(
+                                &vk::BufferCreateInfo::builder()
+                                    .size(1024)
+                                    .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+                                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                                    .build(),
+                                &vk_mem::AllocationCreateInfo {
+                                    usage: vk_mem::MemoryUsage::CpuToGpu,
+                                    ..Default::default()
+                                },
+                            )
+                            .unwrap();
+
+                        allocator
+                            .lock()
+                            .unwrap()
+                            .destroy_buffer(buffer, &memory)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn immediate_submit() {
+        let context = test_context();
+
+        let mut recorded = 0;
+        for _ in 0..2 {
+            context
+                .immediate_submit(|_| {
+                    recorded += 1;
+                    Ok(())
+                })
+                .unwrap();
+
+            // The submit blocks until the fence is signaled
+            let fence = *context.immediate_fence.lock().unwrap();
+            let signaled = unsafe { context.device.get_fence_status(fence) };
+            assert_eq!(signaled, Ok(true));
+        }
+        assert_eq!(recorded, 2);
+
+        // A failed recording frees its command buffer and is not submitted
+        let result = context.immediate_submit(|_| Err(Error::NotRecording));
+        assert!(matches!(result, Err(Error::NotRecording)));
+        assert!(context.immediate_submit(|_| Ok(())).is_ok());
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn generic_pool() {
+        let context = test_context();
+
+        // Created once on first use
+        assert!(std::ptr::eq(context.generic_pool(), context.generic_pool()));
+        assert_eq!(
+            context.generic_pool().queue_family(),
+            context.queue_families.graphics.unwrap()
+        );
+        assert!(context.immediate_submit(|_| Ok(())).is_ok());
+
+        // One time submits lock the fence and pool, so they may come from several threads
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..8 {
+                        context.immediate_submit(|_| Ok(())).unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn pick_supported_format() {
+        let depth = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT;
+        let candidates = [
+            vk::Format::D32_SFLOAT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ];
+
+        // Only the stencil formats support depth attachments with optimal tiling
+        let properties = |format| match format {
+            vk::Format::D32_SFLOAT => vk::FormatProperties {
+                linear_tiling_features: depth,
+                ..Default::default()
+            },
+            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+                vk::FormatProperties {
+                    optimal_tiling_features: depth | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+                    ..Default::default()
+                }
+            }
+            _ => Default::default(),
+        };
+
+        let pick = |candidates: &[vk::Format], tiling| {
+            super::pick_supported_format(candidates, tiling, depth, properties)
+        };
+
+        assert_eq!(
+            pick(&candidates, vk::ImageTiling::OPTIMAL).unwrap(),
+            vk::Format::D32_SFLOAT_S8_UINT
+        );
+        assert_eq!(
+            pick(&candidates, vk::ImageTiling::LINEAR).unwrap(),
+            vk::Format::D32_SFLOAT
+        );
+        assert!(matches!(
+            pick(&candidates[..1], vk::ImageTiling::OPTIMAL),
+            Err(Error::NoSupportedFormat(features)) if features == depth
+        ));
+    }
+
+    /// A compute shader with an empty main, assembled by hand
+    #[rustfmt::skip]
+    const EMPTY_COMPUTE_SHADER: [u32; 35] = [
+        0x0723_0203, 0x0001_0000, 0, 5, 0, // Header with 5 ids
+        0x0002_0011, 1, // OpCapability Shader
+        0x0003_000e, 0, 1, // OpMemoryModel Logical GLSL450
+        0x0005_000f, 5, 1, 0x6e69_616d, 0, // OpEntryPoint GLCompute %1 "main"
+        0x0006_0010, 1, 17, 1, 1, 1, // OpExecutionMode %1 LocalSize 1 1 1
+        0x0002_0013, 2, // %2 = OpTypeVoid
+        0x0003_0021, 3, 2, // %3 = OpTypeFunction %2
+        0x0005_0036, 2, 1, 0, 3, // %1 = OpFunction %2 None %3
+        0x0002_00f8, 4, // %4 = OpLabel
+        0x0001_00fd, // OpReturn
+        0x0001_0038, // OpFunctionEnd
+    ];
+
+    /// A vertex shader with an empty main, assembled by hand
+    #[rustfmt::skip]
+    const EMPTY_VERTEX_SHADER: [u32; 29] = [
+        0x0723_0203, 0x0001_0000, 0, 5, 0, // Header with 5 ids
+        0x0002_0011, 1, // OpCapability Shader
+        0x0003_000e, 0, 1, // OpMemoryModel Logical GLSL450
+        0x0005_000f, 0, 1, 0x6e69_616d, 0, // OpEntryPoint Vertex %1 "main"
+        0x0002_0013, 2, // %2 = OpTypeVoid
+        0x0003_0021, 3, 2, // %3 = OpTypeFunction %2
+        0x0005_0036, 2, 1, 0, 3, // %1 = OpFunction %2 None %3
+        0x0002_00f8, 4, // %4 = OpLabel
+        0x0001_00fd, // OpReturn
+        0x0001_0038, // OpFunctionEnd
+    ];
+
+    /// Writes EMPTY_COMPUTE_SHADER to a file in the temp directory and returns its path
+    pub(super) fn write_empty_compute_shader(name: &str) -> String {
+        write_shader(name, &EMPTY_COMPUTE_SHADER)
+    }
+
+    /// Writes EMPTY_VERTEX_SHADER to a file in the temp directory and returns its path
+    pub(crate) fn write_empty_vertex_shader(name: &str) -> String {
+        write_shader(name, &EMPTY_VERTEX_SHADER)
+    }
+
+    fn write_shader(name: &str, code: &[u32]) -> String {
+        let path = std::env::temp_dir().join(name);
+        let bytes: Vec<u8> = code
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn pipeline_cache() {
+        let context = test_context();
+
+        let spec = ComputePipelineSpec {
+            shader: write_empty_compute_shader("sprocket_pipeline_cache.comp.spv"),
+            layouts: Vec::new(),
+            push_constants: Vec::new(),
+        };
+
+        // The second pipeline is created from the cached state of the first
+        assert!(ComputePipeline::new(&context, spec.clone()).is_ok());
+        assert!(ComputePipeline::new(&context, spec).is_ok());
+
+        let data = context.pipeline_cache_data().unwrap();
+        assert!(context.merge_pipeline_cache(&data).is_ok());
+
+        let path = std::env::temp_dir().join("sprocket_pipeline_cache.bin");
+        let path = path.to_str().unwrap();
+        context.save_pipeline_cache(path).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), data);
+        assert!(context.load_pipeline_cache(path).is_ok());
+    }
+}
@@ -1,141 +1,449 @@
-use super::{resources::Resource, CommandPool, Error, Mesh, Result, Vertex, VkAllocator};
+use super::{
+    gltf,
+    resources::{AsyncResource, Resource},
+    tangents, Error, Mesh, Result, Transfer, Vertex,
+};
 use crate::math::*;
-use ash::{self, vk};
 use ex::fs;
 use std::collections::HashMap;
+use std::path::Path;
 pub struct Model {
     meshes: HashMap<String, Mesh>,
+    /// The mesh names in the order they appear in the file
+    names: Vec<String>,
+    bounds: Aabb,
+}
+
+/// The geometry of a mesh read from a model file before it is uploaded
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
 }
 
 impl Resource for Model {
-    // Loads a model from a collada file into meshes
+    // Loads a model from a collada or glTF file into meshes
     fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
+        let meshes = Self::read(path)?;
+        Self::upload(
+            resourcemanager,
+            path,
+            meshes,
+            &resourcemanager.context().transfer(),
+        )
+    }
+}
+
+impl AsyncResource for Model {
+    type Data = Vec<MeshData>;
+
+    fn read(path: &str) -> Result<Vec<MeshData>> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("gltf") | Some("glb") => gltf::read_meshes(path),
+            _ => read_collada(path),
+        }
+    }
+
+    fn upload(
+        resourcemanager: &super::ResourceManager,
+        path: &str,
+        data: Vec<MeshData>,
+        transfer: &Transfer,
+    ) -> Result<Self> {
         let context = resourcemanager.context();
-        let allocator = &context.allocator;
-        let device = &context.device;
-        let queue = context.graphics_queue;
-        let commandpool = context.generic_pool();
 
-        let root = simple_xml::from_string(&fs::read_to_string(path)?)?;
-        let lib_geometries = &root.try_get_nodes("library_geometries")?[0];
         let mut meshes = HashMap::new();
+        let mut names = Vec::new();
 
-        let asset = &root.try_get_nodes("asset")?[0];
-        let up_axis = &asset.try_get_nodes("up_axis")?[0];
-        let axis_transform = match &up_axis.content[..] {
-            "Z_UP" => |v: Vec3| Vec3::new(v.x, -v.z, -v.y),
-            "Y_UP" => |v: Vec3| v,
-            "X_UP" => |v: Vec3| Vec3::new(v.y, v.x, v.z),
-            _ => {
-                log::warn!("Unrecognized up axis '{}'", up_axis.content);
-                |v: Vec3| v
-            }
-        };
-
-        for geometry in lib_geometries.try_get_nodes("geometry")?.iter() {
-            let (name, mesh) = parse_collada_geometry(
-                geometry,
-                allocator,
-                device,
-                queue,
-                commandpool,
-                axis_transform,
+        for MeshData {
+            name,
+            vertices,
+            indices,
+        } in data
+        {
+            let mesh = Mesh::new(
+                &context.allocator,
+                &context.device,
+                transfer,
+                &vertices,
+                &indices,
             )?;
-            meshes.insert(name, mesh);
+
+            if meshes.insert(name.clone(), mesh).is_none() {
+                names.push(name);
+            } else {
+                log::warn!("Duplicate geometry name '{}' in '{}'", name, path);
+            }
         }
 
-        Ok(Model { meshes })
+        let bounds = names
+            .iter()
+            .map(|name| meshes[name].bounds())
+            .fold(None, |acc: Option<Aabb>, bounds| match acc {
+                Some(acc) => Some(acc.union(&bounds)),
+                None => Some(bounds),
+            })
+            .unwrap_or_else(|| Aabb::new(Vec3::zero(), Vec3::zero()));
+
+        Ok(Model {
+            meshes,
+            names,
+            bounds,
+        })
     }
 }
 
 impl Model {
+    /// Returns the nth mesh in the order they appear in the file
     pub fn get_mesh_index(&self, index: usize) -> Option<&Mesh> {
-        self.meshes.iter().skip(index).next().map(|(_, v)| v)
+        self.names.get(index).and_then(|name| self.meshes.get(name))
+    }
+
+    /// Returns the mesh with the given geometry name
+    pub fn get_mesh(&self, name: &str) -> Option<&Mesh> {
+        self.meshes.get(name)
+    }
+
+    /// Returns the names and meshes in the order they appear in the file
+    pub fn meshes(&self) -> impl Iterator<Item = (&str, &Mesh)> {
+        self.names
+            .iter()
+            .map(move |name| (name.as_str(), &self.meshes[name]))
+    }
+
+    /// Returns the names of all meshes in the order they appear in the file
+    pub fn mesh_names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(|name| name.as_str())
+    }
+
+    /// Returns the union of the bounds of all meshes
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
     }
 }
 
+// Loads all geometries in a collada file in the order they appear
+fn read_collada(path: &str) -> Result<Vec<MeshData>> {
+    let root = simple_xml::from_string(&fs::read_to_string(path)?)?;
+    let lib_geometries = &root.try_get_nodes("library_geometries")?[0];
+
+    let asset = &root.try_get_nodes("asset")?[0];
+    let up_axis = &asset.try_get_nodes("up_axis")?[0];
+    let axis_transform = match &up_axis.content[..] {
+        "Z_UP" => |v: Vec3| Vec3::new(v.x, -v.z, -v.y),
+        "Y_UP" => |v: Vec3| v,
+        "X_UP" => |v: Vec3| Vec3::new(v.y, v.x, v.z),
+        _ => {
+            log::warn!("Unrecognized up axis '{}'", up_axis.content);
+            |v: Vec3| v
+        }
+    };
+
+    lib_geometries
+        .try_get_nodes("geometry")?
+        .iter()
+        .map(|geometry| parse_collada_geometry(geometry, axis_transform))
+        .collect()
+}
+
 // Parses a single mesh/geometry from a collada xml structure
 fn parse_collada_geometry(
     node: &simple_xml::Node,
-
-    allocator: &VkAllocator,
-    device: &ash::Device,
-    queue: vk::Queue,
-    commandpool: &CommandPool,
     axis_transform: fn(Vec3) -> Vec3,
-) -> Result<(String, Mesh)> {
+) -> Result<MeshData> {
     let name = node.try_get_attribute("name")?;
-    let id = node.try_get_attribute("id")?;
     let mesh = &node.try_get_nodes("mesh")?[0];
-    let sources = mesh.try_get_nodes("source")?;
 
-    let source_positions = id.to_owned() + "-positions";
-    let source_normals = id.to_owned() + "-normals";
-    let source_map_0 = id.to_owned() + "-map-0";
+    // Parse all float sources by id, E.g; positions, normals, uvs and colors
+    let mut sources: HashMap<&str, ColladaSource> = HashMap::new();
+    for source in mesh.try_get_nodes("source")? {
+        let source_id = source.try_get_attribute("id")?;
+        sources.insert(source_id, ColladaSource::parse(source)?);
+    }
+
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
 
-    // Create new empty array
-    let mut positions: Vec<f32> = Vec::new();
-    let mut _normals: Vec<f32> = Vec::new();
-    let mut uvs: Vec<f32> = Vec::new();
+    let mut vertex_map: HashMap<(usize, usize, usize, usize), usize> = HashMap::new();
 
-    // Parse all positions, normals and uvs
-    for source in sources {
-        let source_id = source.try_get_attribute("id")?;
-        let array = &source.try_get_nodes("float_array")?[0];
+    for primitive in collada_primitives(mesh) {
+        let inputs = PrimitiveInputs::parse(primitive, mesh, &sources)?;
+        let (index_list, polygons) = parse_collada_polygons(primitive, inputs.stride)?;
+
+        // Triangulate each polygon as a fan around its first vertex
+        for (first, count) in polygons {
+            for i in 1..count.saturating_sub(1) {
+                for corner in &[first, first + i, first + i + 1] {
+                    let vertex = &index_list[corner * inputs.stride..];
+                    let pos = vertex[inputs.position];
+                    let normal = inputs.normal.map(|(offset, _)| vertex[offset]).unwrap_or(0);
+                    let uv = inputs.uv.map(|(offset, _)| vertex[offset]).unwrap_or(0);
+                    let color = inputs.color.map(|(offset, _)| vertex[offset]).unwrap_or(0);
+                    let key = (pos, normal, uv, color);
+
+                    indices.push(match vertex_map.get(&key) {
+                        Some(i) => *i as u32,
+                        // Create new vertex and add to map
+                        // Push the new node index to the indeices
+                        None => {
+                            vertices.push(Vertex {
+                                // Get the correct vertex from the positions array
+                                // Correctly transform
+                                position: axis_transform(array_to_vec3(
+                                    &inputs.positions.data,
+                                    pos,
+                                )),
+                                uv: match inputs.uv {
+                                    Some((_, uvs)) => array_to_vec2(&uvs.data, uv),
+                                    None => Vec2::zero(),
+                                },
+                                color: match inputs.color {
+                                    Some((_, colors)) => colors.color(color)?,
+                                    None => Vec4::one(),
+                                },
+                                // Missing normals are generated after all vertices are read
+                                normal: match inputs.normal {
+                                    Some((_, normals)) => {
+                                        axis_transform(array_to_vec3(&normals.data, normal))
+                                    }
+                                    None => Vec3::zero(),
+                                },
+                                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+                            });
+                            vertex_map.insert(key, vertices.len() - 1);
+                            (vertices.len() - 1) as u32
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    tangents::generate_normals(&mut vertices, &indices);
+    tangents::generate_tangents(&mut vertices, &indices);
+
+    Ok(MeshData {
+        name: name.to_owned(),
+        vertices,
+        indices,
+    })
+}
 
-        if source_id == &source_positions {
-            positions = parse_xml_array(&array, None)?;
-        } else if source_id == &source_normals {
-            _normals = parse_xml_array(&array, None)?;
-        } else if source_id == &source_map_0 {
-            uvs = parse_xml_array(&array, None)?;
+/// The primitive kinds which are loaded from a collada mesh
+const COLLADA_PRIMITIVES: [&str; 3] = ["triangles", "polylist", "trifans"];
+
+/// Returns all supported primitive nodes of a collada mesh
+fn collada_primitives(mesh: &simple_xml::Node) -> Vec<&simple_xml::Node> {
+    COLLADA_PRIMITIVES
+        .iter()
+        .filter_map(|tag| mesh.get_nodes(tag))
+        .flatten()
+        .collect()
+}
+
+/// The first vertex and vertex count of a polygon in a collada primitive
+type Polygon = (usize, usize);
+
+/// Parses the vertex indices of a collada primitive and splits them into polygons
+/// Returns the indices and the polygons
+/// Each vertex consists of stride indices, one for each input
+fn parse_collada_polygons(
+    primitive: &simple_xml::Node,
+    stride: usize,
+) -> Result<(Vec<usize>, Vec<Polygon>)> {
+    let mut index_list: Vec<usize> = Vec::new();
+    let mut polygons = Vec::new();
+
+    match &primitive.tag[..] {
+        // Each p is a single fan
+        "trifans" => {
+            for p in primitive.try_get_nodes("p")? {
+                let first = index_list.len() / stride;
+                index_list.extend(parse_xml_array::<usize>(p, Some(0))?);
+                polygons.push((first, index_list.len() / stride - first));
+            }
         }
+        // vcount holds the number of vertices of each polygon
+        "polylist" => {
+            index_list = parse_xml_array(&primitive.try_get_nodes("p")?[0], Some(0))?;
+            let vcount: Vec<usize> =
+                parse_xml_array(&primitive.try_get_nodes("vcount")?[0], Some(0))?;
+
+            let mut first = 0;
+            for count in vcount {
+                polygons.push((first, count));
+                first += count;
+            }
+        }
+        _ => {
+            index_list = parse_xml_array(&primitive.try_get_nodes("p")?[0], Some(0))?;
+            polygons = (0..index_list.len() / stride / 3)
+                .map(|i| (i * 3, 3))
+                .collect();
+        }
+    }
+
+    // Guard against indexing past the end for malformed files
+    let vertex_count = polygons.iter().map(|(_, count)| count).sum::<usize>();
+    if vertex_count * stride > index_list.len() {
+        return Err(Error::ParseError);
     }
 
-    let triangles = &mesh.try_get_nodes("triangles")?[0];
+    Ok((index_list, polygons))
+}
+
+/// The offsets of each input in the vertices of a collada primitive
+struct PrimitiveInputs<'a> {
+    /// The number of indices per vertex
+    stride: usize,
+    position: usize,
+    positions: &'a ColladaSource,
+    /// The offset and the source of the normals
+    normal: Option<(usize, &'a ColladaSource)>,
+    /// The offset and the source of the first texture coordinate set
+    uv: Option<(usize, &'a ColladaSource)>,
+    /// The offset and the source of the first vertex color set
+    color: Option<(usize, &'a ColladaSource)>,
+}
+
+impl<'a> PrimitiveInputs<'a> {
+    fn parse(
+        primitive: &simple_xml::Node,
+        mesh: &simple_xml::Node,
+        sources: &'a HashMap<&str, ColladaSource>,
+    ) -> Result<Self> {
+        let mut stride = 0;
+        let mut position = None;
+        let mut normal = None;
+        let mut uv = None;
+        let mut color = None;
 
-    // Parse the triangles/indices
-    let triangles_list: Vec<usize> = parse_xml_array(
-        &triangles.try_get_nodes("p")?[0],
-        Some(
-            triangles
-                .try_get_attribute("count")?
+        for input in primitive.try_get_nodes("input")? {
+            let offset = input
+                .try_get_attribute("offset")?
                 .parse::<usize>()
-                .map_err(|_| Error::ParseError)?,
-        ),
-    )?;
+                .map_err(|_| Error::ParseError)?;
+            let source = input.try_get_attribute("source")?.trim_start_matches('#');
 
-    let mut vertices: Vec<Vertex> = Vec::new();
-    let mut indices: Vec<u32> = Vec::new();
+            stride = stride.max(offset + 1);
+
+            match &input.try_get_attribute("semantic")?[..] {
+                "VERTEX" => {
+                    let positions = collada_vertices_positions(mesh, source)?;
+                    position = Some((offset, lookup_source(sources, positions)?));
+                }
+                "NORMAL" => normal = Some((offset, lookup_source(sources, source)?)),
+                "TEXCOORD" if uv.is_none() => {
+                    uv = Some((offset, lookup_source(sources, source)?));
+                }
+                "COLOR" if color.is_none() => {
+                    let colors = lookup_source(sources, source)?;
+                    if colors.stride < 3 {
+                        log::warn!(
+                            "Ignoring vertex colors '{}' with less than 3 channels",
+                            source
+                        );
+                    } else {
+                        color = Some((offset, colors));
+                    }
+                }
+                _ => {}
+            }
+        }
 
-    let mut vertex_map: HashMap<(usize, usize, usize), usize> = HashMap::new();
+        let (position, positions) = position.ok_or(Error::ParseError)?;
 
-    for i in (0..triangles_list.len()).step_by(3) {
-        let pos = triangles_list[i];
-        let normal = triangles_list[i + 1];
-        let uv = triangles_list[i + 2];
+        Ok(PrimitiveInputs {
+            stride,
+            position,
+            positions,
+            normal,
+            uv,
+            color,
+        })
+    }
+}
 
-        indices.push(match vertex_map.get(&(pos, normal, uv)) {
-            Some(i) => {
-                // log::info!("Reusing vertex {},{}", pos, uv);
-                *i as u32
+/// Returns the id of the position source of the vertices node with id
+fn collada_vertices_positions<'a>(mesh: &'a simple_xml::Node, id: &str) -> Result<&'a str> {
+    for vertices in mesh.try_get_nodes("vertices")? {
+        if vertices.try_get_attribute("id")? != id {
+            continue;
+        }
+
+        for input in vertices.try_get_nodes("input")? {
+            if input.try_get_attribute("semantic")? == "POSITION" {
+                return Ok(input.try_get_attribute("source")?.trim_start_matches('#'));
             }
-            // Create new vertex and add to map
-            // Push the new node index to the indeices
+        }
+    }
+
+    log::error!("No positions found for collada vertices '{}'", id);
+    Err(Error::ParseError)
+}
+
+/// A float array source of a collada mesh
+struct ColladaSource {
+    data: Vec<f32>,
+    /// The number of floats per element
+    stride: usize,
+}
+
+impl ColladaSource {
+    fn parse(source: &simple_xml::Node) -> Result<Self> {
+        let array = &source.try_get_nodes("float_array")?[0];
+
+        let stride = source
+            .get_nodes("technique_common")
+            .and_then(|technique| technique[0].get_nodes("accessor"))
+            .and_then(|accessor| accessor[0].get_attribute("stride"))
+            .and_then(|stride| stride.parse::<usize>().ok())
+            .unwrap_or(1);
+
+        Ok(ColladaSource {
+            data: parse_xml_array(&array, None)?,
+            stride,
+        })
+    }
+
+    /// Returns the rgb or rgba color at index
+    /// Missing alpha is opaque
+    /// Returns an error if the color is out of bounds of the source
+    fn color(&self, index: usize) -> Result<Vec4> {
+        let len = match self.stride {
+            4 => 4,
+            _ => 3,
+        };
+
+        let color = match index
+            .checked_mul(self.stride)
+            .and_then(|start| self.data.get(start..start + len))
+        {
+            Some(color) => color,
             None => {
-                vertices.push(Vertex {
-                    // Get the correct vertex from the positions array
-                    // Correctly transform
-                    position: axis_transform(array_to_vec3(&positions, pos)),
-                    uv: array_to_vec2(&uvs, uv),
-                });
-                vertex_map.insert((pos, normal, uv), vertices.len() - 1);
-                (vertices.len() - 1) as u32
+                log::error!("Collada color index {} is out of bounds", index);
+                return Err(Error::ParseError);
             }
-        });
+        };
+
+        Ok(match self.stride {
+            4 => Vec4::new(color[0], color[1], color[2], color[3]),
+            _ => Vec4::new(color[0], color[1], color[2], 1.0),
+        })
+    }
+}
+
+fn lookup_source<'a>(
+    sources: &'a HashMap<&str, ColladaSource>,
+    id: &str,
+) -> Result<&'a ColladaSource> {
+    match sources.get(id) {
+        Some(source) => Ok(source),
+        None => {
+            log::error!("Missing collada source '{}'", id);
+            Err(Error::ParseError)
+        }
     }
-    Mesh::new(allocator, device, queue, commandpool, &vertices, &indices)
-        .map(|mesh| (name.to_owned(), mesh))
 }
 
 /// Creates a vector from 3 elements in an array of floats
@@ -171,7 +479,7 @@ fn parse_xml_array<T: std::str::FromStr>(
 
     let mut result = Vec::with_capacity(count);
 
-    for val in node.content.split(" ") {
+    for val in node.content.split_whitespace() {
         result.push(val.parse::<T>().map_err(|_| {
             log::error!("'{}'", val);
             Error::ParseError
@@ -180,3 +488,195 @@ fn parse_xml_array<T: std::str::FromStr>(
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, ResourceManager};
+    use std::sync::Arc;
+
+    #[test]
+    fn collada_polylist() {
+        let geometry = simple_xml::from_string(
+            r##"<geometry id="quad-mesh" name="quad">
+                <mesh>
+                    <source id="quad-positions">
+                        <float_array id="quad-positions-array" count="12">0 0 0 1 0 0 1 1 0 0 1 0</float_array>
+                        <technique_common>
+                            <accessor source="#quad-positions-array" count="4" stride="3"/>
+                        </technique_common>
+                    </source>
+                    <source id="quad-normals">
+                        <float_array id="quad-normals-array" count="3">0 0 1</float_array>
+                        <technique_common>
+                            <accessor source="#quad-normals-array" count="1" stride="3"/>
+                        </technique_common>
+                    </source>
+                    <vertices id="quad-vertices">
+                        <input semantic="POSITION" source="#quad-positions"/>
+                    </vertices>
+                    <polylist count="1">
+                        <input semantic="NORMAL" source="#quad-normals" offset="1"/>
+                        <input semantic="VERTEX" source="#quad-vertices" offset="0"/>
+                        <vcount>4</vcount>
+                        <p>0 0 1 0 2 0 3 0</p>
+                    </polylist>
+                </mesh>
+            </geometry>"##,
+        )
+        .unwrap();
+
+        let mesh = parse_collada_geometry(&geometry, |v| v).unwrap();
+        assert_eq!(mesh.name, "quad");
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.vertices[2].position, Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.vertices[2].normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn collada_color() {
+        let rgb = ColladaSource {
+            data: vec![0.0, 0.5, 1.0],
+            stride: 3,
+        };
+        assert!(rgb.color(0).unwrap() == Vec4::new(0.0, 0.5, 1.0, 1.0));
+        assert!(matches!(rgb.color(1), Err(Error::ParseError)));
+        assert!(matches!(rgb.color(usize::MAX), Err(Error::ParseError)));
+
+        // Truncated alpha
+        let rgba = ColladaSource {
+            data: vec![0.0, 0.5, 1.0],
+            stride: 4,
+        };
+        assert!(rgba.color(0).is_err());
+    }
+
+    /// A collada geometry of a single polygon in the z plane
+    fn collada_polygon(name: &str, positions: &[f32]) -> String {
+        let count = positions.len() / 3;
+        let positions = positions
+            .iter()
+            .map(f32::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let p = (0..count)
+            .map(|i| format!("{} 0", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        format!(
+            r##"<geometry id="{name}-mesh" name="{name}">
+                <mesh>
+                    <source id="{name}-positions">
+                        <float_array id="{name}-positions-array" count="{len}">{positions}</float_array>
+                        <technique_common>
+                            <accessor source="#{name}-positions-array" count="{count}" stride="3"/>
+                        </technique_common>
+                    </source>
+                    <source id="{name}-normals">
+                        <float_array id="{name}-normals-array" count="3">0 0 1</float_array>
+                        <technique_common>
+                            <accessor source="#{name}-normals-array" count="1" stride="3"/>
+                        </technique_common>
+                    </source>
+                    <vertices id="{name}-vertices">
+                        <input semantic="POSITION" source="#{name}-positions"/>
+                    </vertices>
+                    <polylist count="1">
+                        <input semantic="NORMAL" source="#{name}-normals" offset="1"/>
+                        <input semantic="VERTEX" source="#{name}-vertices" offset="0"/>
+                        <vcount>{count}</vcount>
+                        <p>{p}</p>
+                    </polylist>
+                </mesh>
+            </geometry>"##,
+            name = name,
+            len = count * 3,
+            count = count,
+            positions = positions,
+            p = p,
+        )
+    }
+
+    /// Writes a collada file with a quad named zeta followed by a triangle named alpha
+    fn write_multiple_geometries(name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(
+            &path,
+            format!(
+                r#"<COLLADA>
+                    <asset><up_axis>Y_UP</up_axis></asset>
+                    <library_geometries>{}{}</library_geometries>
+                </COLLADA>"#,
+                collada_polygon(
+                    "zeta",
+                    &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0]
+                ),
+                collada_polygon("alpha", &[0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0]),
+            ),
+        )
+        .unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn collada_geometry_order() {
+        let path = write_multiple_geometries("sprocket_geometry_order.dae");
+
+        let meshes = read_collada(&path).unwrap();
+        let names: Vec<_> = meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+        assert_eq!(names, vec!["zeta", "alpha"]);
+        assert_eq!(meshes[0].indices.len(), 6);
+        assert_eq!(meshes[1].indices.len(), 3);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn get_mesh_by_name() {
+        let context = vulkan::test_context();
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+
+        let path = write_multiple_geometries("sprocket_get_mesh_by_name.dae");
+        let model = resourcemanager.load_model(&path).unwrap();
+
+        assert_eq!(
+            model.mesh_names().collect::<Vec<_>>(),
+            vec!["zeta", "alpha"]
+        );
+
+        let quad = model.get_mesh("zeta").unwrap();
+        let triangle = model.get_mesh("alpha").unwrap();
+        assert_eq!(quad.index_count(), 6);
+        assert_eq!(triangle.index_count(), 3);
+        assert!(triangle
+            .bounds()
+            .max
+            .approx_eq(&Vec3::new(2.0, 2.0, 0.0), 1e-5));
+        assert!(model.get_mesh("missing").is_none());
+
+        // Indices follow the order in the file
+        assert!(std::ptr::eq(model.get_mesh_index(0).unwrap(), quad));
+        assert!(std::ptr::eq(model.get_mesh_index(1).unwrap(), triangle));
+        assert!(model.get_mesh_index(2).is_none());
+    }
+
+    const CUBE: &str = "../sandbox/data/models/cube.dae";
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn bounds() {
+        let context = vulkan::test_context();
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+
+        let model = resourcemanager.load_model(CUBE).unwrap();
+        let expected = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::one());
+
+        for (_, mesh) in model.meshes() {
+            assert!(mesh.bounds().min.approx_eq(&expected.min, 1e-5));
+            assert!(mesh.bounds().max.approx_eq(&expected.max, 1e-5));
+        }
+        assert!(model.bounds().min.approx_eq(&expected.min, 1e-5));
+        assert!(model.bounds().max.approx_eq(&expected.max, 1e-5));
+    }
+}
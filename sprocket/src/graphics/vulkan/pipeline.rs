@@ -1,26 +1,181 @@
 use super::descriptors::ShaderStage;
-use super::{resources::Resource, DescriptorSetLayout, DescriptorSetLayoutSpec, Error, Result};
-use super::{vertexbuffer::Vertex, RenderPass};
+use super::enums::{BlendMode, CompareOp, CullMode, FrontFace, PolygonMode};
+use super::{
+    resources::Resource, DescriptorSetLayout, DescriptorSetLayoutSpec, DeviceFeatures, DeviceInfo,
+    Error, Result,
+};
+use super::{vertexbuffer::VertexLayout, RenderPass};
 
 use ash::version::DeviceV1_0;
 use ash::vk;
 use ex::fs;
 use serde::{Deserialize, Serialize};
-use std::{ffi::CStr, sync::Arc};
+use std::io::Read;
+use std::{ffi::CStr, path::Path, sync::Arc};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PipelineSpec {
     pub vertex_shader: String,
+    /// May be empty for depth only pipelines
     pub fragment_shader: String,
+    #[serde(default)]
     pub geometry_shader: String,
     pub renderpass: String,
+    /// The index of the subpass in the renderpass the pipeline is used in
+    #[serde(default)]
+    pub subpass: u32,
     /// The descriptor set layouts
     /// If used in a normal rendering setup the setup is like this
     /// 0: Global data
     /// 1: Per material data
     /// 2: Per entity/draw data
     pub layouts: Vec<DescriptorSetLayoutSpec>,
+    #[serde(default)]
     pub push_constants: Vec<PushConstantRange>,
+    #[serde(default)]
+    pub vertex_layout: VertexLayout,
+    #[serde(default)]
+    pub depth: DepthState,
+    #[serde(default)]
+    pub cull_mode: CullMode,
+    #[serde(default)]
+    pub front_face: FrontFace,
+    #[serde(default)]
+    pub polygon_mode: PolygonMode,
+    /// The blend mode used for all color attachments
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+}
+
+impl PipelineSpec {
+    /// Validates the spec before creating a pipeline from it
+    /// Checks that all referenced shaders exist and that no descriptor set layout is empty
+    pub fn validate(&self) -> Result<()> {
+        if !Path::new(&self.vertex_shader).is_file() {
+            return Err(Error::MissingShader(self.vertex_shader.to_owned()));
+        }
+
+        // Fragment and geometry shaders are optional
+        for shader in &[&self.fragment_shader, &self.geometry_shader] {
+            if !shader.is_empty() && !Path::new(shader).is_file() {
+                return Err(Error::MissingShader(shader.to_string()));
+            }
+        }
+
+        for (i, layout) in self.layouts.iter().enumerate() {
+            if layout.bindings.is_empty() {
+                return Err(Error::EmptyDescriptorSetLayout(i));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the device features required by the spec are enabled
+    pub fn validate_features(&self, features: &DeviceFeatures) -> Result<()> {
+        if self.polygon_mode != PolygonMode::Fill && !features.fill_mode_non_solid {
+            return Err(Error::MissingFeature("fill_mode_non_solid"));
+        }
+
+        if let Some(bias) = &self.depth.bias {
+            if bias.clamp != 0.0 && !features.depth_bias_clamp {
+                return Err(Error::MissingFeature("depth_bias_clamp"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the spec does not exceed the limits of the device
+    pub fn validate_limits(&self, info: &DeviceInfo) -> Result<()> {
+        for range in &self.push_constants {
+            let end = (range.offset + range.size) as usize;
+            if end > info.max_push_constants_size as usize {
+                return Err(Error::PushConstantsTooLarge(
+                    end,
+                    info.max_push_constants_size as usize,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the rasterization state with the polygon mode, culling and depth bias of the spec
+    fn rasterization_state(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(self.polygon_mode.into())
+            .cull_mode(self.cull_mode.into())
+            .front_face(self.front_face.into())
+            .line_width(1.0);
+
+        match &self.depth.bias {
+            Some(bias) => rasterizer
+                .depth_bias_enable(true)
+                .depth_bias_constant_factor(bias.constant_factor)
+                .depth_bias_clamp(bias.clamp)
+                .depth_bias_slope_factor(bias.slope_factor),
+            None => rasterizer.depth_bias_enable(false),
+        }
+        .build()
+    }
+}
+
+/// Missing fields are read as their default
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DepthState {
+    pub test: bool,
+    pub write: bool,
+    /// Use Greater or GreaterOrEqual for a reversed depth range
+    pub compare_op: CompareOp,
+    /// Offsets the depth of rasterized fragments
+    /// Used to avoid shadow acne when rendering shadow maps
+    pub bias: Option<DepthBias>,
+}
+
+impl DepthState {
+    pub fn to_vk(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            depth_test_enable: self.test as vk::Bool32,
+            depth_write_enable: self.write as vk::Bool32,
+            depth_compare_op: self.compare_op.into(),
+            depth_bounds_test_enable: vk::FALSE,
+            min_depth_bounds: 0.0,
+            max_depth_bounds: 1.0,
+            stencil_test_enable: vk::FALSE,
+            front: Default::default(),
+            back: Default::default(),
+            flags: Default::default(),
+            p_next: std::ptr::null(),
+        }
+    }
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        DepthState {
+            test: true,
+            write: true,
+            compare_op: CompareOp::Less,
+            bias: None,
+        }
+    }
+}
+
+/// The depth bias is calculated as constant_factor * r + slope_factor * max_slope
+/// where r is the smallest resolvable depth difference
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    /// The maximum or minimum bias
+    /// Anything but 0 requires the depth_bias_clamp feature
+    #[serde(default)]
+    pub clamp: f32,
+    pub slope_factor: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -53,11 +208,35 @@ pub struct Pipeline {
 
 impl Resource for Pipeline {
     fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
-        let spec: PipelineSpec = serde_json::from_str(&ex::fs::read_to_string(path)?)?;
+        let contents = ex::fs::read_to_string(path)?;
+        Self::load_from_reader(resourcemanager, &mut contents.as_bytes(), path)
+    }
+
+    fn load_from_reader(
+        resourcemanager: &super::ResourceManager,
+        reader: &mut dyn Read,
+        _: &str,
+    ) -> Result<Self> {
+        let spec: PipelineSpec = serde_json::from_reader(reader)?;
+        spec.validate()?;
         let context = resourcemanager.context();
+        spec.validate_features(context.features())?;
+        spec.validate_limits(context.info())?;
 
         Self::new(&context.device, spec, resourcemanager)
     }
+
+    fn dependencies(&self) -> Vec<String> {
+        [
+            &self.spec.vertex_shader,
+            &self.spec.fragment_shader,
+            &self.spec.geometry_shader,
+        ]
+        .iter()
+        .filter(|shader| !shader.is_empty())
+        .map(|shader| shader.to_string())
+        .collect()
+    }
 }
 
 impl Pipeline {
@@ -71,7 +250,10 @@ impl Pipeline {
         // Shader stages
         let vertex_shader_module = create_shader_module(device, &spec.vertex_shader)?;
 
-        let fragment_shader_module = create_shader_module(device, &spec.fragment_shader)?;
+        let fragment_shader_module = match spec.fragment_shader.as_str() {
+            "" => None,
+            path => Some(create_shader_module(device, path)?),
+        };
 
         let vertex_shader_info = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::VERTEX)
@@ -79,17 +261,21 @@ impl Pipeline {
             .name(&shader_entry_point)
             .build();
 
-        let fragment_shader_info = vk::PipelineShaderStageCreateInfo::builder()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(fragment_shader_module)
-            .name(&shader_entry_point)
-            .build();
+        let mut shader_stages = vec![vertex_shader_info];
 
-        let shader_stages = [vertex_shader_info, fragment_shader_info];
+        if let Some(fragment_shader_module) = fragment_shader_module {
+            shader_stages.push(
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(fragment_shader_module)
+                    .name(&shader_entry_point)
+                    .build(),
+            );
+        }
 
         // Vertex input
-        let binding_descriptions = [Vertex::binding_description()];
-        let attribute_descriptions = Vertex::attribute_descriptions();
+        let binding_descriptions = spec.vertex_layout.binding_descriptions();
+        let attribute_descriptions = spec.vertex_layout.attribute_descriptions();
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&binding_descriptions)
             .vertex_attribute_descriptions(&attribute_descriptions);
@@ -99,43 +285,14 @@ impl Pipeline {
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
             .primitive_restart_enable(false);
 
-        let extent = resourcemanager.get_swapchain().unwrap().extent();
-
         // Viewports and scissors
-        let viewports = [vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: extent.width as f32,
-            height: extent.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        }];
-
-        let scissors = [vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: vk::Extent2D {
-                width: extent.width,
-                height: extent.height,
-            },
-        }];
-
+        // Set dynamically when recording to not depend on the swapchain extent
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&viewports)
-            .scissors(&scissors);
+            .viewport_count(1)
+            .scissor_count(1);
 
         // Rasterizer
-        let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
-            .depth_clamp_enable(false)
-            .rasterizer_discard_enable(false)
-            .polygon_mode(vk::PolygonMode::FILL)
-            .cull_mode(vk::CullModeFlags::NONE)
-            .depth_bias_enable(false)
-            .front_face(vk::FrontFace::CLOCKWISE)
-            .line_width(1.0)
-            .depth_bias_enable(false)
-            .depth_bias_constant_factor(0.0)
-            .depth_bias_clamp(0.0)
-            .depth_bias_slope_factor(0.0);
+        let rasterizer = spec.rasterization_state();
 
         // Multisampling
         let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
@@ -146,53 +303,31 @@ impl Pipeline {
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false);
 
-        // Depth and stencil testing
-        // TODO
+        let renderpass = resourcemanager.load_renderpass(&spec.renderpass)?;
 
         // Color blending
-        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
-            .color_write_mask(
-                vk::ColorComponentFlags::R
-                    | vk::ColorComponentFlags::G
-                    | vk::ColorComponentFlags::B
-                    | vk::ColorComponentFlags::A,
-            )
-            .blend_enable(false)
-            .src_color_blend_factor(vk::BlendFactor::ONE)
-            .dst_color_blend_factor(vk::BlendFactor::ZERO)
-            .src_alpha_blend_factor(vk::BlendFactor::ONE)
-            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-            .alpha_blend_op(vk::BlendOp::ADD)
-            .build();
-
-        let color_blend_attachments = [color_blend_attachment];
+        let color_blend_attachment = spec.blend_mode.to_vk();
+
+        // One blend state for each color attachment in the subpass
+        let subpass = renderpass
+            .spec()
+            .subpasses
+            .get(spec.subpass as usize)
+            .ok_or(Error::InvalidSubpass(spec.subpass))?;
+        let color_blend_attachments = vec![color_blend_attachment; subpass.color_attachments.len()];
         let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
             .attachments(&color_blend_attachments)
             .blend_constants([0.0; 4]);
 
-        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo {
-            s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
-            depth_bounds_test_enable: vk::FALSE,
-            min_depth_bounds: 0.0,
-            max_depth_bounds: 1.0,
-            stencil_test_enable: vk::FALSE,
-            front: Default::default(),
-            back: Default::default(),
-            flags: Default::default(),
-            p_next: std::ptr::null(),
-        };
+        let depth_stencil_state = spec.depth.to_vk();
 
         // Dynamic state
-        // TODO
-        // let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::LINE_WIDTH];
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
 
-        // let dynamic_states =
-        //     vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+        let dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
         // Pipeline layout
         let mut set_layouts = Vec::with_capacity(spec.layouts.len());
@@ -217,8 +352,6 @@ impl Pipeline {
         let pipeline_layout =
             unsafe { device.create_pipeline_layout(&pipeline_layout_info, None)? };
 
-        let renderpass = resourcemanager.load_renderpass(&spec.renderpass)?;
-
         let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stages)
             .vertex_input_state(&vertex_input_info)
@@ -228,23 +361,30 @@ impl Pipeline {
             .multisample_state(&multisampling)
             .color_blend_state(&color_blending)
             .depth_stencil_state(&depth_stencil_state)
+            .dynamic_state(&dynamic_state)
             .layout(pipeline_layout)
             .render_pass(renderpass.vk())
-            .subpass(0)
+            .subpass(spec.subpass)
             .base_pipeline_handle(vk::Pipeline::null())
             .base_pipeline_index(-1)
             .build();
 
         let pipeline = unsafe {
             device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+                .create_graphics_pipelines(
+                    resourcemanager.context().pipeline_cache(),
+                    &[pipeline_info],
+                    None,
+                )
                 .map_err(|e| Error::VulkanError(e.1))?[0]
         };
 
         // Destroy shader modules
         unsafe {
             device.destroy_shader_module(vertex_shader_module, None);
-            device.destroy_shader_module(fragment_shader_module, None);
+            if let Some(fragment_shader_module) = fragment_shader_module {
+                device.destroy_shader_module(fragment_shader_module, None);
+            }
         }
 
         Ok(Pipeline {
@@ -269,8 +409,13 @@ impl Pipeline {
         &self.set_layouts[..]
     }
 
+    /// Returns the renderpass the pipeline was created for
+    pub fn renderpass(&self) -> &Arc<RenderPass> {
+        &self.renderpass
+    }
+
     /// Returns self created again from spec but with updated values
-    /// Called when swapchain is recreated
+    /// Called when the renderpasses are recreated
     pub fn recreate(&self, resourcemanager: &super::ResourceManager) -> Result<Pipeline> {
         Self::new(&self.device, self.spec.clone(), resourcemanager)
     }
@@ -285,7 +430,10 @@ impl Drop for Pipeline {
     }
 }
 
-fn create_shader_module(device: &ash::Device, filename: &str) -> Result<vk::ShaderModule> {
+pub(super) fn create_shader_module(
+    device: &ash::Device,
+    filename: &str,
+) -> Result<vk::ShaderModule> {
     let mut file = fs::File::open(filename)?;
 
     let code = match ash::util::read_spv(&mut file) {
@@ -301,3 +449,253 @@ fn create_shader_module(device: &ash::Device, filename: &str) -> Result<vk::Shad
             .map_err(|e| e.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX_SHADER: &str = "../sandbox/data/shaders/default.vert";
+
+    #[test]
+    fn minimal_spec() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{}",
+                "fragment_shader": "",
+                "renderpass": "./data/renderpasses/default.json",
+                "layouts": []
+            }}"#,
+            VERTEX_SHADER
+        ))
+        .unwrap();
+
+        spec.validate().unwrap();
+        assert_eq!(spec.subpass, 0);
+        assert!(spec.push_constants.is_empty());
+        assert!(spec.depth.test && spec.depth.write);
+        assert_eq!(spec.depth.compare_op, CompareOp::Less);
+        assert_eq!(spec.cull_mode, CullMode::default());
+        assert_eq!(spec.polygon_mode, PolygonMode::Fill);
+    }
+
+    #[test]
+    fn full_spec() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{vertex}",
+                "fragment_shader": "{vertex}",
+                "geometry_shader": "",
+                "renderpass": "./data/renderpasses/default.json",
+                "subpass": 1,
+                "layouts": [
+                    {{ "bindings": [{{ "slot": 0, "ty": "UniformBuffer", "count": 1, "stages": ["Vertex"] }}] }}
+                ],
+                "push_constants": [{{ "size": 64, "offset": 0, "stages": ["Vertex"] }}],
+                "vertex_layout": "PositionOnly",
+                "depth": {{
+                    "test": true,
+                    "write": false,
+                    "compare_op": "GreaterOrEqual",
+                    "bias": {{ "constant_factor": 1.25, "slope_factor": 1.75 }}
+                }},
+                "cull_mode": "Back",
+                "front_face": "CounterClockwise",
+                "polygon_mode": "Line",
+                "blend_mode": "AlphaBlend"
+            }}"#,
+            vertex = VERTEX_SHADER
+        ))
+        .unwrap();
+
+        spec.validate().unwrap();
+        assert_eq!(spec.subpass, 1);
+        assert_eq!(spec.push_constants[0].to_vk().size, 64);
+        assert_eq!(spec.vertex_layout, VertexLayout::PositionOnly);
+        assert!(!spec.depth.write);
+        assert_eq!(spec.depth.bias.as_ref().unwrap().clamp, 0.0);
+        assert_eq!(spec.cull_mode, CullMode::Back);
+        assert_eq!(spec.front_face, FrontFace::CounterClockwise);
+        assert_eq!(spec.blend_mode, BlendMode::AlphaBlend);
+    }
+
+    #[test]
+    fn wireframe_requires_feature() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{}",
+                "fragment_shader": "",
+                "renderpass": "./data/renderpasses/default.json",
+                "layouts": [],
+                "polygon_mode": "Line"
+            }}"#,
+            VERTEX_SHADER
+        ))
+        .unwrap();
+        assert_eq!(
+            vk::PolygonMode::from(spec.polygon_mode),
+            vk::PolygonMode::LINE
+        );
+
+        let error = spec
+            .validate_features(&DeviceFeatures::default())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::MissingFeature("fill_mode_non_solid")
+        ));
+        assert_eq!(
+            error.to_string(),
+            "The device feature 'fill_mode_non_solid' is required but not enabled"
+        );
+
+        let features = DeviceFeatures {
+            fill_mode_non_solid: true,
+            ..Default::default()
+        };
+        assert!(spec.validate_features(&features).is_ok());
+
+        // Filled triangles need no features
+        let spec = PipelineSpec {
+            polygon_mode: PolygonMode::Fill,
+            ..spec
+        };
+        assert!(spec.validate_features(&DeviceFeatures::default()).is_ok());
+    }
+
+    #[test]
+    fn blend_modes() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{}",
+                "fragment_shader": "",
+                "renderpass": "./data/renderpasses/default.json",
+                "layouts": [],
+                "blend_mode": "AlphaBlend"
+            }}"#,
+            VERTEX_SHADER
+        ))
+        .unwrap();
+
+        let state = spec.blend_mode.to_vk();
+        assert_eq!(state.blend_enable, vk::TRUE);
+        assert_eq!(state.src_color_blend_factor, vk::BlendFactor::SRC_ALPHA);
+        assert_eq!(
+            state.dst_color_blend_factor,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+        );
+        assert_eq!(state.color_blend_op, vk::BlendOp::ADD);
+
+        // Opaque by default, writing the output unchanged
+        let state = BlendMode::default().to_vk();
+        assert_eq!(state.blend_enable, vk::FALSE);
+        assert_eq!(state.src_color_blend_factor, vk::BlendFactor::ONE);
+        assert_eq!(state.dst_color_blend_factor, vk::BlendFactor::ZERO);
+        assert_eq!(state.color_write_mask, vk::ColorComponentFlags::all());
+
+        let state = BlendMode::Additive.to_vk();
+        assert_eq!(state.dst_color_blend_factor, vk::BlendFactor::ONE);
+        let state = BlendMode::Multiply.to_vk();
+        assert_eq!(state.src_color_blend_factor, vk::BlendFactor::DST_COLOR);
+    }
+
+    #[test]
+    fn depth_bias_state() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{}",
+                "fragment_shader": "",
+                "renderpass": "./data/renderpasses/shadow.json",
+                "layouts": [],
+                "depth": {{
+                    "compare_op": "Greater",
+                    "bias": {{ "constant_factor": 1.25, "slope_factor": 1.75 }}
+                }}
+            }}"#,
+            VERTEX_SHADER
+        ))
+        .unwrap();
+
+        let rasterizer = spec.rasterization_state();
+        assert_eq!(rasterizer.depth_bias_enable, vk::TRUE);
+        assert_eq!(rasterizer.depth_bias_constant_factor, 1.25);
+        assert_eq!(rasterizer.depth_bias_slope_factor, 1.75);
+        assert_eq!(rasterizer.depth_bias_clamp, 0.0);
+
+        let depth = spec.depth.to_vk();
+        assert_eq!(depth.depth_compare_op, vk::CompareOp::GREATER);
+        assert_eq!(depth.depth_test_enable, vk::TRUE);
+        assert_eq!(depth.depth_write_enable, vk::TRUE);
+
+        // No bias and a less compare op by default
+        let spec = PipelineSpec {
+            depth: DepthState::default(),
+            ..spec
+        };
+        assert_eq!(spec.rasterization_state().depth_bias_enable, vk::FALSE);
+        assert_eq!(spec.depth.to_vk().depth_compare_op, vk::CompareOp::LESS);
+    }
+
+    #[test]
+    fn partial_depth_state() {
+        let depth: DepthState = serde_json::from_str(r#"{ "compare_op": "Greater" }"#).unwrap();
+        assert!(depth.test && depth.write);
+        assert_eq!(depth.compare_op, CompareOp::Greater);
+        assert!(depth.bias.is_none());
+    }
+
+    #[test]
+    fn invalid_spec() {
+        let spec = |vertex_shader: &str, layouts: &str| -> PipelineSpec {
+            serde_json::from_str(&format!(
+                r#"{{
+                    "vertex_shader": "{}",
+                    "fragment_shader": "",
+                    "renderpass": "",
+                    "layouts": {}
+                }}"#,
+                vertex_shader, layouts
+            ))
+            .unwrap()
+        };
+
+        assert!(matches!(
+            spec("./missing.vert.spv", "[]").validate(),
+            Err(Error::MissingShader(_))
+        ));
+        assert!(matches!(
+            spec(VERTEX_SHADER, r#"[{ "bindings": [] }]"#).validate(),
+            Err(Error::EmptyDescriptorSetLayout(0))
+        ));
+    }
+
+    #[test]
+    fn push_constants_within_limits() {
+        let spec: PipelineSpec = serde_json::from_str(&format!(
+            r#"{{
+                "vertex_shader": "{}",
+                "fragment_shader": "",
+                "renderpass": "./data/renderpasses/default.json",
+                "layouts": [],
+                "push_constants": [{{ "size": 300, "offset": 0, "stages": ["Vertex"] }}]
+            }}"#,
+            VERTEX_SHADER
+        ))
+        .unwrap();
+
+        // The minimum guaranteed limit
+        let mut properties = vk::PhysicalDeviceProperties::default();
+        properties.limits.max_push_constants_size = 128;
+        let info = DeviceInfo::from_properties(&properties);
+
+        let error = spec.validate_limits(&info).unwrap_err();
+        assert!(matches!(error, Error::PushConstantsTooLarge(300, 128)));
+        assert_eq!(
+            error.to_string(),
+            "Push constants of 300 bytes exceed the device limit of 128 bytes"
+        );
+
+        properties.limits.max_push_constants_size = 512;
+        let info = DeviceInfo::from_properties(&properties);
+        assert!(spec.validate_limits(&info).is_ok());
+    }
+}
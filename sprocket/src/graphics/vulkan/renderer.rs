@@ -1,41 +1,207 @@
 use super::VulkanContext;
 use super::*;
 use crate::graphics::vulkan;
-use ecs::{ComponentArray, Entity};
-use math::Mat4;
+use ecs::{ComponentArray, ComponentManager, Entity};
+use graphics::{sprite, text, Camera, Color, Extent2D, Font, Sprite};
+use math::{Mat4, Vec2};
 use physics::Transform;
+use std::collections::HashMap;
+use std::hash::Hash;
 use std::sync::Arc;
 
-const MAX_FRAMES_IN_FLIGHT: usize = 2;
+/// The number of frames recorded while the device is still rendering previous ones unless set with
+/// set_frames_in_flight
+const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+/// The number of swapchain images requested unless set with set_swapchain_image_count
+const SWAPCHAIN_IMAGE_COUNT: u32 = 3;
 
+const DEFAULT_MATERIAL: &str = "./data/materials/default.json";
+/// The default material variant which tests depth against the depth prepass
+const DEFAULT_PREPASS_MATERIAL: &str = "./data/materials/default_prepass.json";
+const DEPTH_PREPASS_PIPELINE: &str = "./data/pipelines/depth_prepass.json";
+/// The pipeline of the materials created for sprite textures
+const SPRITE_PIPELINE: &str = "./data/pipelines/sprite.json";
+/// The model drawn for each entity
+const DEFAULT_MODEL: &str = "./data/models/suzanne.dae";
+/// A depth only renderpass leaving the depth sampleable
+const SHADOW_RENDERPASS: &str = "./data/renderpasses/shadow.json";
+/// Renders depth only with a depth bias against shadow acne
+const SHADOW_PIPELINE: &str = "./data/pipelines/shadow.json";
+
+/// The names of the passes in the render graph of each window
+const DEPTH_PREPASS: &str = "depth_prepass";
+const MAIN_PASS: &str = "main";
+
+/// The linear color windows are cleared to before drawing unless set with set_clear_color
+const DEFAULT_CLEAR_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.01,
+    a: 1.0,
+};
+
+/// The number of quads the text and sprite buffers of each swapchain image initially hold
+const DEFAULT_QUAD_CAPACITY: usize = 256;
+
+/// Push constants shared by all instances in a draw
+/// The model matrix of each instance is read from the instance buffer
 struct EntityData {
-    mvp: Mat4,
+    viewproj: Mat4,
+}
+
+/// The model and material an entity is drawn with instead of the default model and material
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct EntityModel {
+    model: Option<Handle<Model>>,
+    /// Replaces the default and mesh materials for all meshes of the model if set
+    material: Option<Handle<Material>>,
+}
+
+/// Entities drawn with the same model and material, one instanced draw per mesh
+struct ModelBatch {
+    model: Arc<Model>,
+    material: Option<Handle<Material>>,
+    first_instance: u32,
+    instance_count: u32,
+}
+
+/// Push constants of the text pipeline
+/// Glyph vertices are in pixels and converted to clip space using the screen size
+struct TextData {
+    screen_size: Vec2,
 }
 
+/// Text queued with draw_text
+struct TextDraw {
+    text: String,
+    position: Vec2,
+    scale: f32,
+}
+
+/// Draws the entities of a component manager to windows
+/// The device must be idle when the renderer is dropped, E.g; by Application::shutdown
 pub struct Renderer {
     context: Arc<VulkanContext>,
     resourcemanager: Arc<ResourceManager>,
+    /// The swapchain and frame data of each window keyed by window id
+    targets: HashMap<u32, WindowTarget>,
+    frame_count: usize,
+    entities: ComponentArray<Transform>,
+    /// The models and materials of entities not drawn with the defaults
+    entity_models: ComponentArray<EntityModel>,
+    /// The paths of the models and materials used by entities
+    /// Loaded into the frame data of each window when first drawn
+    model_paths: HashMap<Handle<Model>, String>,
+    material_paths: HashMap<Handle<Material>, String>,
+    sprites: ComponentArray<(Sprite, Transform, Handle<Texture>)>,
+    /// A material for each sprite texture
+    sprite_materials: HashMap<Handle<Texture>, Arc<Material>>,
+    /// Whether the sprite materials are recreated on the next frame
+    sprite_materials_stale: bool,
+    depth_prepass: bool,
+    present_mode: PresentMode,
+    active_camera: Option<Entity>,
+    font: Option<Font>,
+    /// Text drawn to all windows until end_frame
+    text: Vec<TextDraw>,
+    /// The number of frames recorded while the device is still rendering previous ones
+    frames_in_flight: usize,
+    /// The number of images requested for new swapchains
+    swapchain_image_count: u32,
+    /// Material paths replacing the default material for the meshes with the given names
+    mesh_materials: HashMap<String, String>,
+    /// The model matrices of all entities when rendering shadow maps
+    shadow_instances: InstanceBuffer,
+    /// The linear color windows are cleared to
+    clear_color: Color,
+}
+
+/// The surface, swapchain and synchronization of a single window
+/// Windows are presented and resized independently of each other
+/// Fields are dropped in order so that the swapchain is destroyed before the surface
+/// The device must be idle when a target is dropped, which remove_window and
+/// Application::shutdown wait for
+struct WindowTarget {
+    device: ash::Device,
     image_available_semaphores: Vec<vk::Semaphore>,
     render_finished_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
     images_in_flight: Vec<vk::Fence>,
     current_frame: usize,
+    recreate_requested: bool,
+    /// Whether the next frame is copied to capture
+    capture_requested: bool,
+    /// The last captured frame, readable once the device has finished the frame
+    capture: Option<FrameCapture>,
     data: Data,
-    frame_count: usize,
-    entities: ComponentArray<Transform>,
+    surface: WindowSurface,
+    /// A lost surface the swapchain was created from, destroyed once the swapchain is recreated
+    lost_surface: Option<WindowSurface>,
+}
+
+/// A window surface destroyed on drop if it was created by the renderer
+/// The surface of the first window is owned by the context
+struct WindowSurface {
+    loader: Surface,
+    surface: vk::SurfaceKHR,
+    owned: bool,
 }
 
 struct Data {
     swapchain: Arc<Swapchain>,
     commandpool: CommandPool,
     commandbuffers: Vec<CommandBuffer>,
-    framebuffers: Vec<Framebuffer>,
-    material: Arc<Material>,
-    model: Arc<Model>,
     uniformbuffers: Vec<UniformBuffer>,
     descriptor_pool: DescriptorPool,
+    present_mode: PresentMode,
+    /// The depth prepass if enabled followed by the main pass
+    graph: RenderGraph<FrameState>,
+    /// The framebuffers of the swapchain images shared by the passes
+    /// Recreated with the swapchain
+    framebuffers: FramebufferCache,
+    frame: FrameState,
+}
+
+/// The state the render graph passes of a window record with
+/// The per frame fields are updated before the graph is recorded
+struct FrameState {
+    material: Arc<Material>,
+    model: Arc<Model>,
+    /// The models and materials of entities, kept loaded until the window is recreated
+    models: HashMap<Handle<Model>, Arc<Model>>,
+    materials: HashMap<Handle<Material>, Arc<Material>>,
+    /// The entities grouped by model and material
+    batches: Vec<ModelBatch>,
+    /// Materials replacing material for the meshes of model with the given names
+    mesh_materials: HashMap<String, Arc<Material>>,
+    /// Renders all entities to depth only before the main pass
+    /// The main pass then only shades the closest fragments
+    prepass_pipeline: Option<Arc<Pipeline>>,
+    /// The material of the font if one is set
+    text_material: Option<Arc<Material>>,
     global_descriptors: Vec<DescriptorSet>,
-    renderpass: Arc<RenderPass>,
+    /// The model matrices of all entities, one buffer per swapchain image
+    instancebuffers: Vec<InstanceBuffer>,
+    /// The glyph quads of queued text, one per swapchain image
+    text_buffers: Vec<QuadBuffer>,
+    /// The batched sprite quads, one per swapchain image
+    sprite_buffers: Vec<QuadBuffer>,
+    /// The swapchain image being recorded
+    image_index: u32,
+    entity_data: EntityData,
+    sprite_data: EntityData,
+    /// The material, first quad and quad count of each sprite batch
+    sprite_batches: Vec<(Arc<Material>, u32, u32)>,
+    text_data: TextData,
+    text_quads: u32,
+}
+
+/// Holds textured quads drawn in a frame
+struct QuadBuffer {
+    vertexbuffer: VertexBuffer,
+    indexbuffer: IndexBuffer,
+    /// The number of quads indexbuffer has indices for
+    capacity: usize,
 }
 
 impl Renderer {
@@ -43,198 +209,649 @@ impl Renderer {
         self.entities.insert_component(entity, transform);
     }
 
+    /// Draws entity as a sprite in screen space on top of the scene
+    /// One pixel is one unit with the origin in the center of the window
+    /// Sprites sharing a texture are drawn in a single batch
+    /// The texture is loaded immediately, which fails if it doesn't exist
+    pub fn insert_sprite(
+        &mut self,
+        entity: Entity,
+        sprite: Sprite,
+        transform: Transform,
+    ) -> Result<()> {
+        let texture = self.resourcemanager.load_texture_handle(&sprite.texture)?;
+        self.sprites
+            .insert_component(entity, (sprite, transform, texture));
+        Ok(())
+    }
+
+    /// Draws entity with the model and material at the given paths instead of the defaults
+    /// The meshes of the model use the default and mesh materials if material is None
+    /// The resources are loaded immediately, which fails if they don't exist
+    pub fn set_entity_model(
+        &mut self,
+        entity: Entity,
+        model: Option<&str>,
+        material: Option<&str>,
+    ) -> Result<()> {
+        let model = match model {
+            Some(path) => {
+                let handle = self.resourcemanager.load_model_handle(path)?;
+                self.model_paths.insert(handle, path.to_owned());
+                Some(handle)
+            }
+            None => None,
+        };
+
+        let material = match material {
+            Some(path) => {
+                let handle = self.resourcemanager.load_material_handle(path)?;
+                self.material_paths.insert(handle, path.to_owned());
+                Some(handle)
+            }
+            None => None,
+        };
+
+        self.entity_models
+            .insert_component(entity, EntityModel { model, material });
+        Ok(())
+    }
+
+    /// Stops rendering entity
+    pub fn remove_entity(&mut self, entity: Entity) {
+        self.entities.remove_component(entity);
+        self.entity_models.remove_component(entity);
+        self.sprites.remove_component(entity);
+    }
+
+    /// Sets the entity whose Camera component is used for rendering
+    /// A default camera is used if the entity has no Camera component
+    pub fn set_active_camera(&mut self, entity: Entity) {
+        self.active_camera = Some(entity);
+    }
+
+    /// Sets the linear color windows are cleared to before drawing
+    /// The color is encoded to sRGB when written to an sRGB swapchain
+    /// Takes effect on the next frame
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+        for target in self.targets.values_mut() {
+            // Only the main pass has a color attachment to clear
+            iferr!(
+                "Failed to set clear color",
+                target.data.graph.set_clear_color(MAIN_PASS, color)
+            );
+        }
+    }
+
+    /// Returns the linear color windows are cleared to
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Enables or disables the depth prepass
+    /// Takes effect on the next frame
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
+
+    /// Sets the preferred present mode
+    /// Falls back to the first supported of swapchain::FALLBACK_PRESENT_MODES if not supported,
+    /// E.g; Mailbox, FifoRelaxed, Fifo and then Immediate
+    /// Takes effect on the next frame
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+    }
+
+    /// Sets the number of images requested for the swapchains
+    /// The count is clamped to what each window surface supports
+    /// Takes effect when the swapchains are recreated on the next frame
+    /// Returns an error if count is less than the frames in flight
+    pub fn set_swapchain_image_count(&mut self, count: u32) -> Result<()> {
+        validate_frames_in_flight(self.frames_in_flight, count as usize)?;
+
+        self.swapchain_image_count = count;
+        self.targets
+            .values_mut()
+            .for_each(|target| target.recreate_requested = true);
+        Ok(())
+    }
+
+    /// Sets the number of frames recorded while the device is still rendering previous ones
+    /// Fewer frames lower the input latency, more frames keep the device busy when frame times
+    /// vary
+    /// Needs to be at least one and at most the swapchain image count of every window
+    /// Waits for the device to be idle
+    pub fn set_frames_in_flight(&mut self, count: usize) -> Result<()> {
+        for target in self.targets.values() {
+            validate_frames_in_flight(count, target.data.swapchain.image_count())?;
+        }
+
+        for target in self.targets.values_mut() {
+            target.set_frames_in_flight(count)?;
+        }
+
+        self.frames_in_flight = count;
+        Ok(())
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
+    }
+
+    /// Recreates the swapchains of all windows on the next frame
+    /// Used to pick up resources that have been reloaded
+    pub fn request_recreate(&mut self) {
+        self.targets
+            .values_mut()
+            .for_each(|target| target.recreate_requested = true);
+        self.sprite_materials_stale = true;
+    }
+
+    /// Sets the bitmap font used by draw_text
+    /// The font material is loaded when the windows are recreated on the next frame
+    pub fn set_font(&mut self, path: &str) -> Result<()> {
+        let font = Font::load(path)?;
+        self.resourcemanager.load_material(&font.material)?;
+        self.font = Some(font);
+        self.request_recreate();
+        Ok(())
+    }
+
+    /// Draws the meshes named mesh with the material at path instead of the default material
+    /// The material is used when the windows are recreated on the next frame
+    pub fn set_mesh_material(&mut self, mesh: &str, path: &str) -> Result<()> {
+        self.resourcemanager.load_material(path)?;
+        self.mesh_materials.insert(mesh.to_owned(), path.to_owned());
+        self.request_recreate();
+        Ok(())
+    }
+
+    /// Draws text on top of the scene in all windows
+    /// position is the top left corner in pixels and scale the glyph height in pixels
+    /// The text is drawn each frame until end_frame, and not at all if no font is set
+    pub fn draw_text(&mut self, text: &str, position: Vec2, scale: f32) {
+        self.text.push(TextDraw {
+            text: text.to_owned(),
+            position,
+            scale,
+        });
+    }
+
+    /// Blocks until the device has finished all submitted frames
+    pub fn wait_idle(&self) -> Result<()> {
+        self.context.wait_idle()
+    }
+
+    /// Called after all windows have been drawn
+    /// Clears the text queued with draw_text
+    pub fn end_frame(&mut self) {
+        self.text.clear();
+        self.frame_count += 1;
+    }
+
+    /// Records draw commands into target using a one time command buffer
+    /// The renderpass of the target is begun and the viewport and scissor set before calling record
+    /// Blocks until the commands have finished executing
+    pub fn render_to<F>(&self, target: &RenderTarget, clear_color: Color, record: F) -> Result<()>
+    where
+        F: FnOnce(&mut CommandBuffer),
+    {
+        target.render(&self.context, clear_color, record)
+    }
+
+    /// Creates a depth only render target for render_shadow_map
+    pub fn create_shadow_map(&self, extent: Extent2D) -> Result<RenderTarget> {
+        let renderpass = self.resourcemanager.load_renderpass(SHADOW_RENDERPASS)?;
+        RenderTarget::new(&self.context, renderpass, extent)
+    }
+
+    /// Renders the depth of all entities as seen with light_viewproj into a render target created
+    /// by create_shadow_map
+    /// Blocks until rendered, after which the depth texture of shadow_map can be sampled
+    pub fn render_shadow_map(
+        &mut self,
+        shadow_map: &RenderTarget,
+        light_viewproj: Mat4,
+    ) -> Result<()> {
+        let pipeline = self.resourcemanager.load_pipeline(SHADOW_PIPELINE)?;
+        let default_model = self.resourcemanager.load_model(DEFAULT_MODEL)?;
+        let models = self
+            .model_paths
+            .iter()
+            .map(|(handle, path)| Ok((*handle, self.resourcemanager.load_model(path)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let (instances, batches) = self.batch_entities(&default_model, &models);
+
+        // The previous shadow map has finished rendering since render_to blocks
+        self.shadow_instances.write(&instances)?;
+
+        let instancebuffer = &self.shadow_instances;
+        let data = EntityData {
+            viewproj: light_viewproj,
+        };
+
+        self.render_to(shadow_map, Color::black(), |commandbuffer| {
+            commandbuffer.bind_pipeline(&pipeline);
+            iferr!(
+                "Failed to push constants",
+                commandbuffer.push_contants(
+                    pipeline.layout(),
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &data,
+                )
+            );
+
+            commandbuffer.bind_instancebuffer(instancebuffer);
+            draw_batches(commandbuffer, &batches);
+        })
+    }
+
+    /// Creates a renderer presenting to window
+    /// The window needs to be the one the context was created with as its surface is reused
     pub fn new(
         context: Arc<VulkanContext>,
         window: &Window,
         resourcemanager: Arc<ResourceManager>,
+        present_mode: PresentMode,
     ) -> Result<Renderer> {
-        let mut image_available_semaphores = Vec::new();
-        let mut render_finished_semaphores = Vec::new();
-        let mut in_flight_fences = Vec::new();
-        let mut images_in_flight = Vec::new();
-
-        // Create the semaphores
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
-            image_available_semaphores.push(vulkan::create_semaphore(&context.device)?);
-            render_finished_semaphores.push(vulkan::create_semaphore(&context.device)?);
-            in_flight_fences.push(vulkan::create_fence(&context.device)?);
-        }
-
-        let data = Self::create_data(&context, window, &resourcemanager)?;
+        let surface = WindowSurface {
+            loader: context.surface_loader.clone(),
+            surface: context.surface.ok_or(Error::Headless)?,
+            owned: false,
+        };
 
-        for _ in 0..data.swapchain.image_count() {
-            images_in_flight.push(vk::Fence::null());
-        }
+        let shadow_instances = InstanceBuffer::empty(&context.allocator)?;
 
-        Ok(Renderer {
+        let mut renderer = Renderer {
             context,
-            image_available_semaphores,
-            render_finished_semaphores,
-            in_flight_fences,
-            images_in_flight,
-            current_frame: 0,
-            data,
+            targets: HashMap::new(),
             frame_count: 0,
             resourcemanager,
             entities: ComponentArray::new(),
-        })
+            entity_models: ComponentArray::new(),
+            model_paths: HashMap::new(),
+            material_paths: HashMap::new(),
+            sprites: ComponentArray::new(),
+            sprite_materials: HashMap::new(),
+            sprite_materials_stale: false,
+            depth_prepass: false,
+            present_mode,
+            active_camera: None,
+            font: None,
+            text: Vec::new(),
+            frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+            swapchain_image_count: SWAPCHAIN_IMAGE_COUNT,
+            mesh_materials: HashMap::new(),
+            shadow_instances,
+            clear_color: DEFAULT_CLEAR_COLOR,
+        };
+
+        let target = WindowTarget::new(&renderer, window, surface)?;
+        renderer.targets.insert(window.id(), target);
+        Ok(renderer)
     }
 
-    pub fn draw_frame(&mut self, window: &Window, _time: &Time) {
+    /// Creates a surface and swapchain for an additional window
+    /// Does nothing if the window is already rendered to
+    pub fn add_window(&mut self, window: &Window) -> Result<()> {
+        if self.targets.contains_key(&window.id()) {
+            return Ok(());
+        }
+
+        let surface = WindowSurface {
+            loader: self.context.surface_loader.clone(),
+            surface: self.context.create_window_surface(window)?,
+            owned: true,
+        };
+
+        let target = WindowTarget::new(self, window, surface)?;
+
+        self.targets.insert(window.id(), target);
+        Ok(())
+    }
+
+    /// Destroys the swapchain and surface of a window
+    /// Needs to be called before the window is destroyed
+    /// Waits for the device to be idle since the swapchain may still be in use
+    pub fn remove_window(&mut self, window_id: u32) {
+        if !self.targets.contains_key(&window_id) {
+            return;
+        }
+
+        iferr!("Failed to wait on device", self.context.wait_idle());
+        self.targets.remove(&window_id);
+    }
+
+    /// Copies the next frame drawn to window so that it can be read with capture_frame
+    /// The copy is recorded into the command buffer of the frame before it is presented
+    pub fn request_capture(&mut self, window: &Window) {
+        if let Some(target) = self.targets.get_mut(&window.id()) {
+            target.capture_requested = true;
+        }
+    }
+
+    /// Reads back the frame last captured for window after request_capture as tightly packed
+    /// rgba8 pixels, row by row from the top
+    /// The pixels are sRGB encoded if the swapchain is, and the size is that of
+    /// window.framebuffer_extent() when the frame was drawn
+    /// Waits for the device to be idle, so should not be called every frame
+    pub fn capture_frame(&self, window: &Window) -> Result<(Extent2D, Vec<u8>)> {
+        let capture = self
+            .targets
+            .get(&window.id())
+            .and_then(|target| target.capture.as_ref())
+            .ok_or(Error::NoCapturedFrame)?;
+
+        self.context.wait_idle()?;
+        Ok((capture.extent(), capture.pixels()?))
+    }
+
+    /// Returns the number of windows with a swapchain
+    pub fn swapchain_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Draws the entities to window
+    /// The window needs to have been added to the renderer
+    /// Errors are logged and the frame skipped, except for Error::DeviceLost which is returned
+    /// since the context can not be used anymore and needs to be recreated, or the application
+    /// exited
+    /// A lost window surface is recreated
+    pub fn draw_frame(
+        &mut self,
+        window: &Window,
+        _time: &Time,
+        component_manager: &ComponentManager,
+    ) -> Result<()> {
+        // Nothing can be presented to a minimized window
+        // Recreating the swapchain with a zero sized extent would fail
+        if !window.drawable() {
+            return Ok(());
+        }
+
+        self.load_sprite_materials();
+
+        // Take the target out of the map to not borrow self while drawing
+        let mut target = match self.targets.remove(&window.id()) {
+            Some(target) => target,
+            None => {
+                error!(
+                    "Window '{}' has not been added to the renderer",
+                    window.title()
+                );
+                return Ok(());
+            }
+        };
+
+        let result = self.draw_target(&mut target, window, component_manager);
+        self.targets.insert(window.id(), target);
+
+        match result {
+            Err(Error::DeviceLost) => Err(Error::DeviceLost),
+            Err(e) => {
+                error!("Failed to draw window '{}': {}", window.title(), e);
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Creates the materials of sprite textures which have not been drawn before
+    fn load_sprite_materials(&mut self) {
+        // Pick up reloaded pipelines and textures
+        if self.sprite_materials_stale {
+            unsafe {
+                iferr!(
+                    "Failed to wait for device",
+                    self.context.device.device_wait_idle()
+                );
+            }
+            self.sprite_materials.clear();
+            self.sprite_materials_stale = false;
+        }
+
+        for (sprite, _, texture) in &self.sprites {
+            if self.sprite_materials.contains_key(texture) {
+                continue;
+            }
+
+            let spec = MaterialSpec::new(SPRITE_PIPELINE, vec![sprite.texture.to_owned()]);
+            match Material::new(spec, &self.resourcemanager) {
+                Ok(material) => {
+                    self.sprite_materials.insert(*texture, Arc::new(material));
+                }
+                Err(e) => error!(
+                    "Failed to create sprite material for '{}': {}",
+                    sprite.texture, e
+                ),
+            }
+        }
+    }
+
+    fn draw_target(
+        &self,
+        target: &mut WindowTarget,
+        window: &Window,
+        component_manager: &ComponentManager,
+    ) -> Result<()> {
+        // Recreate if settings changed since the data was created
+        if self.depth_prepass != target.data.frame.prepass_pipeline.is_some()
+            || self.present_mode != target.data.present_mode
+            || target.recreate_requested
+        {
+            target.recreate_requested = false;
+            if !self.recreate(target, window) {
+                return Ok(());
+            }
+        }
+
         let device = &self.context.device;
+        let current_frame = target.current_frame;
 
-        vulkan::wait_for_fences(device, &[self.in_flight_fences[self.current_frame]], true);
+        // Waited on directly to notice a lost device
+        unsafe {
+            device.wait_for_fences(&[target.in_flight_fences[current_frame]], true, u64::MAX)?
+        };
 
         // Update uniform buffer for this frame
-        let (image_index, suboptimal) = match self
-            .data
-            .swapchain
-            .acquire_next_image(&self.image_available_semaphores[self.current_frame])
-        {
-            Ok(v) => v,
-            Err(Error::VulkanError(vk::Result::ERROR_OUT_OF_DATE_KHR)) => {
-                self.recreate(window);
-                return;
+        let image_index = match swapchain_status(
+            target
+                .data
+                .swapchain
+                .acquire_next_image(&target.image_available_semaphores[current_frame]),
+        )? {
+            SwapchainStatus::Ready(image_index) => image_index,
+            SwapchainStatus::Recreate => {
+                self.recreate(target, window);
+                return Ok(());
             }
-            Err(e) => {
-                error!("Failed to present to swapchain '{}'", e);
-                return;
+            SwapchainStatus::RecreateSurface => {
+                self.recreate_surface(target, window);
+                return Ok(());
             }
         };
 
-        if suboptimal {
-            self.recreate(window);
-            return;
+        // Check if a previous frame is using this image (i.e. there is its fence to wait on)
+        // The command buffer and instance buffer of the image can't be touched until it is done
+        if target.images_in_flight[image_index as usize] != vk::Fence::null() {
+            unsafe {
+                device.wait_for_fences(
+                    &[target.images_in_flight[image_index as usize]],
+                    true,
+                    u64::MAX,
+                )?
+            };
         }
 
-        // Reset and record command buffers
-        let commandbuffer = &mut self.data.commandbuffers[image_index as usize];
+        let data = &mut target.data;
+        let frame = &mut data.frame;
+        frame.image_index = image_index;
 
-        iferr!("Failed to reset commandbuffers", commandbuffer.reset());
-        iferr!(
-            "Failed to begin recording command buffer",
-            commandbuffer.begin(Default::default())
-        );
+        // Entities sharing a model and material are drawn as one instanced batch
+        self.load_entity_models(frame)?;
+        let (instances, batches) = self.batch_entities(&frame.model, &frame.models);
+        frame.batches = batches;
 
-        commandbuffer.begin_renderpass(
-            &self.data.renderpass,
-            &self.data.framebuffers[image_index as usize],
-            math::Vec4::new(0.0, 0.0, 0.01, 1.0),
-        );
-        // TODO MaterialComponent and MeshComponent
-        let material = &self.data.material;
-        let mesh = self.data.model.get_mesh_index(0).unwrap();
-
-        // Iterate all entities
-        for transform in &mut self.entities.into_iter() {
-            commandbuffer.bind_material(
-                &material,
-                &self.data.global_descriptors[image_index as usize],
-                image_index,
-            );
-            let model = Mat4::translate(transform.position);
-            let view = Mat4::translate(Vec3::new(0.0, 0.0, -5.0)); // Camera
-            let proj = Mat4::perspective(window.aspect(), 1.0, 0.1, 10.0); // Camera
+        frame.instancebuffers[image_index as usize].write(&instances)?;
 
-            // proj: Mat4::ortho(window.aspect() as f32 * 2.0, 2 as f32, 0.0, 10.0),
-            let entity_data = EntityData {
-                mvp: model * view * proj,
-            };
+        let default_camera = Camera::default();
+        let camera = self
+            .active_camera
+            .and_then(|entity| component_manager.get_component::<Camera>(entity))
+            .unwrap_or(&default_camera);
 
-            commandbuffer.push_contants(
-                material.pipeline().layout(),
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                &entity_data,
-            );
+        let view = camera.view_matrix();
+        let proj = camera.projection_matrix(window.aspect());
 
-            commandbuffer.bind_mesh(mesh);
-            commandbuffer.draw_indexed(mesh.index_count());
-        }
+        frame.entity_data = EntityData {
+            viewproj: view * proj,
+        };
 
-        commandbuffer.end_renderpass();
+        // Batch the sprites, one draw per texture
+        let mut sprites: Vec<&(Sprite, Transform, Handle<Texture>)> = self
+            .sprites
+            .into_iter()
+            .filter(|(_, _, texture)| self.sprite_materials.contains_key(texture))
+            .collect();
 
-        iferr!(
-            "Failed to begin recording command buffer",
-            commandbuffer.end()
-        );
+        frame.sprite_batches.clear();
+        if !sprites.is_empty() {
+            sprites.sort_by_key(|sprite| sprite.2);
 
-        // let ub_data = UniformBufferObject {
-        //     model:
-        //     // Mat4::rotate_z(self.frame_count as f32 / 30.0),
-        //     Mat4::rotate_y(time.elapsed_f32())
-        //         * Mat4::translate(Vec3::new(0.0, 0.5 * time.elapsed_f32().sin(), 0.0))
-        //     * Mat4::translate(Vec3::new(0.0, 0.0, -5.0)),
-        //     view: Mat4::identity(),
-        //     proj: Mat4::perspective(window.aspect(), 1.0, 0.1, 10.0),
-        //     // proj: Mat4::ortho(window.aspect() as f32 * 2.0, 2 as f32, 0.0, 10.0),
-        // };
-
-        // iferr!(
-        //     "Failed to write to uniformbuffer",
-        // self.data.uniformbuffers[image_index as usize].write(&ub_data, None)
-        // );
+            let vertices = sprite::build_batch(sprites.iter().map(|sprite| (&sprite.0, &sprite.1)));
 
-        // Check if a previous frame is using this image (i.e. there is its fence to wait on)
-        if self.images_in_flight[image_index as usize] != vk::Fence::null() {
-            vulkan::wait_for_fences(device, &[self.images_in_flight[image_index as usize]], true)
+            frame.sprite_buffers[image_index as usize].write(&self.context, &vertices)?;
+
+            let mut first = 0;
+            while first < sprites.len() {
+                let texture = sprites[first].2;
+                let count = sprites[first..]
+                    .iter()
+                    .take_while(|sprite| sprite.2 == texture)
+                    .count();
+
+                frame.sprite_batches.push((
+                    Arc::clone(&self.sprite_materials[&texture]),
+                    first as u32,
+                    count as u32,
+                ));
+                first += count;
+            }
         }
 
-        self.images_in_flight[image_index as usize] = self.in_flight_fences[self.current_frame];
+        // Lay out the queued text
+        frame.text_quads = 0;
+        if let (Some(font), Some(_)) = (&self.font, &frame.text_material) {
+            let vertices: Vec<Vertex> = self
+                .text
+                .iter()
+                .flat_map(|draw| font.layout(&draw.text, draw.position, draw.scale))
+                .collect();
+
+            if !vertices.is_empty() {
+                frame.text_buffers[image_index as usize].write(&self.context, &vertices)?;
+                frame.text_quads = vertices.len() as u32 / 4;
+            }
+        }
+
+        let extent = data.swapchain.extent();
+        frame.sprite_data = EntityData {
+            viewproj: Mat4::ortho(extent.width as f32, extent.height as f32, -1.0, 1.0),
+        };
+        frame.text_data = TextData {
+            screen_size: Vec2::new(extent.width as f32, extent.height as f32),
+        };
+
+        // Reset and record command buffers
+        let commandbuffer = &mut data.commandbuffers[image_index as usize];
+
+        commandbuffer.reset()?;
+        commandbuffer.begin(Default::default())?;
+
+        data.graph
+            .record(commandbuffer, image_index as usize, &data.frame);
+
+        // The main pass leaves the image ready to present
+        let capture = if target.capture_requested {
+            Some(data.swapchain.record_capture(
+                &self.context.allocator,
+                commandbuffer,
+                image_index as usize,
+            )?)
+        } else {
+            None
+        };
+
+        commandbuffer.end()?;
+
+        target.images_in_flight[image_index as usize] = target.in_flight_fences[current_frame];
 
         // Submit the primary command buffer
-        let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
-        let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
+        let wait_semaphores = [target.image_available_semaphores[current_frame]];
+        let signal_semaphores = [target.render_finished_semaphores[current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
 
-        vulkan::reset_fences(device, &[self.in_flight_fences[self.current_frame]]);
+        vulkan::reset_fences(device, &[target.in_flight_fences[current_frame]]);
 
-        iferr!(
-            "Failed to submit command buffers for rendering",
-            commandbuffer::CommandBuffer::submit(
-                device,
-                &[&self.data.commandbuffers[image_index as usize]],
-                self.context.graphics_queue,
-                &wait_semaphores,
-                &wait_stages,
-                &signal_semaphores,
-                self.in_flight_fences[self.current_frame],
-            )
-        );
+        commandbuffer::CommandBuffer::submit(
+            device,
+            &[&target.data.commandbuffers[image_index as usize]],
+            self.context.graphics_queue,
+            &wait_semaphores,
+            &wait_stages,
+            &signal_semaphores,
+            target.in_flight_fences[current_frame],
+        )?;
+
+        if capture.is_some() {
+            // The previous capture may still be written to by an earlier frame
+            if target.capture.is_some() {
+                self.context.wait_idle()?;
+            }
+            target.capture = capture;
+            target.capture_requested = false;
+        }
 
         // Present it to the swapchain
-        let suboptimal = match self.data.swapchain.present(
-            image_index,
-            self.context.present_queue,
-            &signal_semaphores,
-        ) {
-            Ok(v) => v,
-            Err(Error::VulkanError(vk::Result::ERROR_OUT_OF_DATE_KHR)) => {
-                self.recreate(window);
-                return;
+        let presented = target
+            .data
+            .swapchain
+            .present(image_index, self.context.present_queue, &signal_semaphores)
+            .map(|suboptimal| ((), suboptimal));
+
+        match swapchain_status(presented)? {
+            SwapchainStatus::Ready(()) => {}
+            SwapchainStatus::Recreate => {
+                self.recreate(target, window);
+                return Ok(());
             }
-            Err(e) => {
-                error!("Failed to present to swapchain '{}'", e);
-                return;
+            SwapchainStatus::RecreateSurface => {
+                self.recreate_surface(target, window);
+                return Ok(());
             }
-        };
-
-        if suboptimal {
-            self.recreate(window);
-            return;
         }
 
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
-        self.frame_count += 1;
+        target.current_frame = (current_frame + 1) % target.in_flight_fences.len();
+        Ok(())
     }
 
-    fn recreate(&mut self, window: &Window) {
-        info!("Recreating renderer");
-        unsafe {
-            iferr!(
-                "Failed to wait for device",
-                self.context.device.device_wait_idle()
-            );
+    /// Recreates the swapchain of a single window
+    /// Other windows keep their swapchains
+    /// Returns false and defers the recreation to the next frame if the window has no area, E.g;
+    /// when minimized
+    fn recreate(&self, target: &mut WindowTarget, window: &Window) -> bool {
+        if !window.drawable() {
+            target.recreate_requested = true;
+            return false;
+        }
+
+        info!("Recreating renderer for window '{}'", window.title());
+        if let Err(e) = unsafe { self.context.device.device_wait_idle() } {
+            error!("Failed to wait for device '{}'", e);
+            return false;
         }
 
         log::info!("Recreating resource manager");
@@ -243,26 +860,134 @@ impl Renderer {
             Err(e) => log::error!("Failed to recreate resource manager: {}", e),
         };
 
-        self.data = iferr!(
-            "Failed to recreate renderer",
-            Self::create_data(&self.context, window, &self.resourcemanager)
-        );
+        target.data = match self.create_data(&target.surface.surface, window) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to recreate renderer '{}'", e);
+                return false;
+            }
+        };
+
+        // The old swapchain is destroyed so the lost surface is no longer used
+        target.lost_surface = None;
+
+        // The device is idle so no image is in use
+        target.images_in_flight = vec![vk::Fence::null(); target.data.swapchain.image_count()];
+        true
     }
 
-    fn create_data(
-        context: &Arc<VulkanContext>,
-        window: &Window,
-        resourcemanager: &Arc<ResourceManager>,
-    ) -> Result<Data> {
+    /// Replaces the lost surface of a window and recreates the swapchain on the new surface
+    /// The new surface is kept if the swapchain could not be recreated, E.g; when minimized, and the
+    /// swapchain recreation is deferred to the next frame
+    /// Returns false if either could not be created
+    fn recreate_surface(&self, target: &mut WindowTarget, window: &Window) -> bool {
+        info!("Recreating lost surface for window '{}'", window.title());
+
+        let surface = match self.context.create_window_surface(window) {
+            Ok(surface) => WindowSurface {
+                loader: self.context.surface_loader.clone(),
+                surface,
+                owned: true,
+            },
+            Err(e) => {
+                error!("Failed to recreate surface '{}'", e);
+                return false;
+            }
+        };
+
+        // The lost surface is destroyed after the swapchain created from it
+        // If a previous replacement never got a swapchain it is not used and can be destroyed
+        let lost = std::mem::replace(&mut target.surface, surface);
+        if target.lost_surface.is_none() {
+            target.lost_surface = Some(lost);
+        }
+
+        if !self.recreate(target, window) {
+            target.recreate_requested = true;
+            return false;
+        }
+
+        true
+    }
+
+    /// Loads the entity models and materials which frame does not hold yet
+    fn load_entity_models(&self, frame: &mut FrameState) -> Result<()> {
+        for (handle, path) in &self.model_paths {
+            if !frame.models.contains_key(handle) {
+                // Resolve by handle and only fall back to the path if the model was collected
+                let model = match self.resourcemanager.resolve_model(*handle) {
+                    Some(model) => model,
+                    None => self.resourcemanager.load_model(path)?,
+                };
+                frame.models.insert(*handle, model);
+            }
+        }
+
+        for (handle, path) in &self.material_paths {
+            if !frame.materials.contains_key(handle) {
+                let material = match self.resourcemanager.resolve_material(*handle) {
+                    Some(material) => material,
+                    None => self.resourcemanager.load_material(path)?,
+                };
+                frame.materials.insert(*handle, material);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Groups the entities by model and material
+    /// Returns the model matrices of all entities ordered by batch, and the batches
+    /// Entities whose model is not in models are skipped
+    fn batch_entities(
+        &self,
+        default_model: &Arc<Model>,
+        models: &HashMap<Handle<Model>, Arc<Model>>,
+    ) -> (Vec<Mat4>, Vec<ModelBatch>) {
+        let (instances, groups) = group_instances(&self.entities, |entity| {
+            self.entity_models
+                .get_component(entity)
+                .copied()
+                .unwrap_or_default()
+        });
+
+        let batches = groups
+            .into_iter()
+            .filter_map(|(key, first_instance, instance_count)| {
+                let model = match key.model {
+                    Some(handle) => models.get(&handle)?,
+                    None => default_model,
+                };
+
+                Some(ModelBatch {
+                    model: Arc::clone(model),
+                    material: key.material,
+                    first_instance,
+                    instance_count,
+                })
+            })
+            .collect();
+
+        (instances, batches)
+    }
+
+    /// Creates the swapchain and frame data for a window surface with the current settings
+    fn create_data(&self, surface: &vk::SurfaceKHR, window: &Window) -> Result<Data> {
+        let context = &self.context;
+        let resourcemanager = &self.resourcemanager;
+        let font = self.font.as_ref();
+        let depth_prepass = self.depth_prepass;
+        let present_mode = self.present_mode;
+        let clear_color = self.clear_color;
+
         let swapchain = Arc::new(Swapchain::new(
-            &context.instance,
-            &context.physical_device,
-            &context.device,
-            &context.allocator,
-            &context.surface_loader,
-            &context.surface,
-            &context.queue_families,
-            window.extent(),
+            context,
+            surface,
+            SwapchainSpec {
+                extent: window.framebuffer_extent(),
+                present_mode,
+                image_count: self.swapchain_image_count,
+            },
         )?);
 
         resourcemanager.set_swapchain(Arc::clone(&swapchain));
@@ -310,6 +1035,7 @@ impl Renderer {
             uniformbuffers.iter(),
             [].iter(),
             [].iter(),
+            [].iter(),
         )?;
 
         let commandpool = CommandPool::new(
@@ -317,73 +1043,669 @@ impl Renderer {
             context.queue_families.graphics.unwrap(),
             false,
             true,
+            context.info().max_push_constants_size,
         )?;
 
-        let material = resourcemanager.load_material("./data/materials/default.json")?;
-        let renderpass = resourcemanager.load_renderpass("./data/renderpasses/default.json")?;
+        let material = resourcemanager.load_material(match depth_prepass {
+            true => DEFAULT_PREPASS_MATERIAL,
+            false => DEFAULT_MATERIAL,
+        })?;
 
-        let mut framebuffers = Vec::with_capacity(swapchain.image_count());
-        for i in 0..swapchain.image_count() {
-            framebuffers.push(Framebuffer::new(
-                &context.device,
-                &[swapchain.image(i), swapchain.depth_image()],
-                &renderpass,
-                swapchain.extent(),
-            )?)
-        }
+        let renderpass = Arc::clone(material.pipeline().renderpass());
+        let mut graph = RenderGraph::new();
+        let mut framebuffers = FramebufferCache::new(&context.device);
+
+        let prepass_pipeline = if depth_prepass {
+            let pipeline = resourcemanager.load_pipeline(DEPTH_PREPASS_PIPELINE)?;
+
+            let prepass_framebuffers = (0..swapchain.image_count())
+                .map(|_| {
+                    framebuffers.get(
+                        &[swapchain.depth_image()],
+                        pipeline.renderpass(),
+                        swapchain.extent(),
+                    )
+                })
+                .collect::<Result<_>>()?;
+
+            graph.add_pass(
+                DEPTH_PREPASS,
+                Arc::clone(pipeline.renderpass()),
+                PassTarget::PerImage(prepass_framebuffers),
+                clear_color,
+                &[],
+                record_depth_prepass,
+            );
+            Some(pipeline)
+        } else {
+            None
+        };
+
+        let main_framebuffers = (0..swapchain.image_count())
+            .map(|i| {
+                framebuffers.get(
+                    &[swapchain.image(i), swapchain.depth_image()],
+                    &renderpass,
+                    swapchain.extent(),
+                )
+            })
+            .collect::<Result<_>>()?;
 
-        let mut commandbuffers =
+        let dependencies: &[&str] = if depth_prepass { &[DEPTH_PREPASS] } else { &[] };
+        graph.add_pass(
+            MAIN_PASS,
+            renderpass,
+            PassTarget::PerImage(main_framebuffers),
+            clear_color,
+            dependencies,
+            record_main_pass,
+        );
+        graph.build()?;
+
+        // Command buffers are recorded every frame
+        let commandbuffers =
             CommandBuffer::new_primary(&context.device, &commandpool, swapchain.image_count())?;
 
-        let model = resourcemanager.load_model("./data/models/suzanne.dae")?;
+        let instancebuffers = (0..swapchain.image_count())
+            .map(|_| InstanceBuffer::empty(&context.allocator))
+            .collect::<Result<_>>()?;
 
-        let mesh = model.get_mesh_index(0).unwrap();
+        let model = resourcemanager.load_model(DEFAULT_MODEL)?;
+        let mesh_materials = self
+            .mesh_materials
+            .iter()
+            .map(|(mesh, path)| Ok((mesh.to_owned(), resourcemanager.load_material(path)?)))
+            .collect::<Result<_>>()?;
 
-        for (i, commandbuffer) in commandbuffers.iter_mut().enumerate() {
-            commandbuffer.begin(Default::default())?;
-            commandbuffer.begin_renderpass(
-                &renderpass,
-                &framebuffers[i],
-                math::Vec4::new(0.0, 0.0, 0.01, 1.0),
-            );
-            commandbuffer.bind_material(&material, &global_descriptors[i], i as u32);
-            commandbuffer.bind_mesh(mesh);
-            commandbuffer.draw_indexed(mesh.index_count());
-            commandbuffer.end_renderpass();
-            commandbuffer.end()?;
-        }
+        let text_material = match font {
+            Some(font) => Some(resourcemanager.load_material(&font.material)?),
+            None => None,
+        };
+
+        let text_buffers = (0..swapchain.image_count())
+            .map(|_| QuadBuffer::new(context, DEFAULT_QUAD_CAPACITY))
+            .collect::<Result<_>>()?;
+
+        let sprite_buffers = (0..swapchain.image_count())
+            .map(|_| QuadBuffer::new(context, DEFAULT_QUAD_CAPACITY))
+            .collect::<Result<_>>()?;
 
         Ok(Data {
             swapchain,
             commandpool,
             commandbuffers,
-            framebuffers,
-            material,
-            model,
             uniformbuffers,
             descriptor_pool,
-            global_descriptors,
-            renderpass,
+            present_mode,
+            graph,
+            framebuffers,
+            frame: FrameState {
+                material,
+                model,
+                models: HashMap::new(),
+                materials: HashMap::new(),
+                batches: Vec::new(),
+                mesh_materials,
+                prepass_pipeline,
+                text_material,
+                global_descriptors,
+                instancebuffers,
+                text_buffers,
+                sprite_buffers,
+                image_index: 0,
+                entity_data: EntityData {
+                    viewproj: Mat4::identity(),
+                },
+                sprite_data: EntityData {
+                    viewproj: Mat4::identity(),
+                },
+                sprite_batches: Vec::new(),
+                text_data: TextData {
+                    screen_size: Vec2::zero(),
+                },
+                text_quads: 0,
+            },
         })
     }
 }
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
-        unsafe {
+/// Groups the model matrices of entities by key, keeping the order keys are first seen in
+/// Returns the matrices ordered by group, and the key, first instance and instance count of each
+/// group
+fn group_instances<K, F>(
+    entities: &ComponentArray<Transform>,
+    key: F,
+) -> (Vec<Mat4>, Vec<(K, u32, u32)>)
+where
+    K: Hash + Eq + Copy,
+    F: Fn(Entity) -> K,
+{
+    let mut indices: HashMap<K, usize> = HashMap::new();
+    let mut groups: Vec<(K, Vec<Mat4>)> = Vec::new();
+
+    for (entity, transform) in entities.entities().iter().zip(entities.iter()) {
+        let key = key(*entity);
+        let index = *indices.entry(key).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+
+        groups[index].1.push(transform.matrix());
+    }
+
+    let mut instances = Vec::with_capacity(entities.len());
+    let mut ranges = Vec::with_capacity(groups.len());
+    for (key, matrices) in groups {
+        ranges.push((key, instances.len() as u32, matrices.len() as u32));
+        instances.extend(matrices);
+    }
+
+    (instances, ranges)
+}
+
+/// Draws each mesh of the batches with the bound pipeline
+fn draw_batches(commandbuffer: &CommandBuffer, batches: &[ModelBatch]) {
+    for batch in batches {
+        for (_, mesh) in batch.model.meshes() {
+            commandbuffer.bind_mesh(mesh);
+            commandbuffer.draw_indexed_instances(
+                mesh.index_count(),
+                batch.first_instance,
+                batch.instance_count,
+            );
+        }
+    }
+}
+
+/// Records all entities to depth only
+fn record_depth_prepass(commandbuffer: &mut CommandBuffer, frame: &FrameState) {
+    let pipeline = match &frame.prepass_pipeline {
+        Some(pipeline) => pipeline,
+        None => return,
+    };
+
+    let instancebuffer = &frame.instancebuffers[frame.image_index as usize];
+
+    commandbuffer.bind_pipeline(pipeline);
+    iferr!(
+        "Failed to push constants",
+        commandbuffer.push_contants(
+            pipeline.layout(),
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &frame.entity_data,
+        )
+    );
+
+    commandbuffer.bind_instancebuffer(instancebuffer);
+    draw_batches(commandbuffer, &frame.batches);
+}
+
+/// Pairs each draw with whether its material differs from the material of the previous draw
+fn mark_rebinds<'a, T, M: 'a>(
+    draws: impl IntoIterator<Item = (T, &'a Arc<M>)>,
+) -> impl Iterator<Item = (T, &'a Arc<M>, bool)> {
+    let mut bound: Option<&Arc<M>> = None;
+    draws.into_iter().map(move |(draw, material)| {
+        let rebind = bound.is_none_or(|bound| !Arc::ptr_eq(bound, material));
+        bound = Some(material);
+        (draw, material, rebind)
+    })
+}
+
+/// Records the entities, then the sprites and text on top
+fn record_main_pass(commandbuffer: &mut CommandBuffer, frame: &FrameState) {
+    let image_index = frame.image_index;
+    let global_descriptors = &frame.global_descriptors[image_index as usize];
+    let instancebuffer = &frame.instancebuffers[image_index as usize];
+
+    commandbuffer.bind_instancebuffer(instancebuffer);
+
+    // Draw each mesh of the batches, only rebinding the material when it changes
+    let draws = frame.batches.iter().flat_map(|batch| {
+        let batch_material = batch
+            .material
+            .and_then(|handle| frame.materials.get(&handle));
+
+        batch.model.meshes().map(move |(name, mesh)| {
+            let material = batch_material
+                .or_else(|| frame.mesh_materials.get(name))
+                .unwrap_or(&frame.material);
+            ((batch, mesh), material)
+        })
+    });
+
+    for ((batch, mesh), material, rebind) in mark_rebinds(draws) {
+        if rebind {
+            commandbuffer.bind_material(material, global_descriptors, image_index);
+            iferr!(
+                "Failed to push constants",
+                commandbuffer.push_contants(
+                    material.pipeline().layout(),
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &frame.entity_data,
+                )
+            );
+        }
+
+        commandbuffer.bind_mesh(mesh);
+        commandbuffer.draw_indexed_instances(
+            mesh.index_count(),
+            batch.first_instance,
+            batch.instance_count,
+        );
+    }
+
+    // Draw the sprites on top, one batch per texture
+    if !frame.sprite_batches.is_empty() {
+        let sprite_buffer = &frame.sprite_buffers[image_index as usize];
+        commandbuffer.bind_vertexbuffer(&sprite_buffer.vertexbuffer);
+        commandbuffer.bind_indexbuffer(&sprite_buffer.indexbuffer);
+
+        for (material, first, count) in &frame.sprite_batches {
+            commandbuffer.bind_material(material, global_descriptors, image_index);
             iferr!(
-                "Failed to wait on device",
-                self.context.device.device_wait_idle()
+                "Failed to push constants",
+                commandbuffer.push_contants(
+                    material.pipeline().layout(),
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &frame.sprite_data,
+                )
             );
+            commandbuffer.draw_indexed_range(first * 6, count * 6);
+        }
+    }
+
+    // Draw the text on top
+    if let Some(text_material) = &frame.text_material {
+        if frame.text_quads == 0 {
+            return;
+        }
+
+        let text_buffer = &frame.text_buffers[image_index as usize];
+        commandbuffer.bind_material(text_material, global_descriptors, image_index);
+
+        iferr!(
+            "Failed to push constants",
+            commandbuffer.push_contants(
+                text_material.pipeline().layout(),
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &frame.text_data,
+            )
+        );
+
+        commandbuffer.bind_vertexbuffer(&text_buffer.vertexbuffer);
+        commandbuffer.bind_indexbuffer(&text_buffer.indexbuffer);
+        commandbuffer.draw_indexed(frame.text_quads * 6);
+    }
+}
+
+impl QuadBuffer {
+    fn new(context: &VulkanContext, capacity: usize) -> Result<QuadBuffer> {
+        Ok(QuadBuffer {
+            vertexbuffer: VertexBuffer::new_dynamic(&context.allocator, capacity * 4)?,
+            indexbuffer: Self::create_indices(context, capacity)?,
+            capacity,
+        })
+    }
+
+    fn create_indices(context: &VulkanContext, capacity: usize) -> Result<IndexBuffer> {
+        IndexBuffer::new(
+            &context.allocator,
+            &context.device,
+            &context.transfer(),
+            &text::quad_indices(capacity),
+        )
+    }
+
+    /// Writes the quads and grows the indices if needed
+    /// The buffers must not be in use by the device
+    fn write(&mut self, context: &VulkanContext, vertices: &[Vertex]) -> Result<()> {
+        let quad_count = vertices.len() / 4;
+        if quad_count > self.capacity {
+            self.capacity = quad_count.next_power_of_two();
+            self.indexbuffer = Self::create_indices(context, self.capacity)?;
+        }
+
+        self.vertexbuffer.write(vertices)
+    }
+}
+
+impl WindowTarget {
+    /// Creates the swapchain and frame data of window with the settings of renderer
+    fn new(renderer: &Renderer, window: &Window, surface: WindowSurface) -> Result<WindowTarget> {
+        let context = &renderer.context;
+        let data = renderer.create_data(&surface.surface, window)?;
+
+        let images_in_flight = vec![vk::Fence::null(); data.swapchain.image_count()];
+
+        let mut target = WindowTarget {
+            device: context.device.clone(),
+            image_available_semaphores: Vec::new(),
+            render_finished_semaphores: Vec::new(),
+            in_flight_fences: Vec::new(),
+            images_in_flight,
+            current_frame: 0,
+            recreate_requested: false,
+            capture_requested: false,
+            capture: None,
+            data,
+            surface,
+            lost_surface: None,
+        };
+
+        target.set_frames_in_flight(renderer.frames_in_flight)?;
+        Ok(target)
+    }
+
+    /// Recreates the semaphores and fences for count frames in flight
+    /// Waits for the device to be idle
+    fn set_frames_in_flight(&mut self, count: usize) -> Result<()> {
+        validate_frames_in_flight(count, self.data.swapchain.image_count())?;
 
-            for semaphore in &self.image_available_semaphores {
-                self.context.device.destroy_semaphore(*semaphore, None);
+        unsafe { self.device.device_wait_idle()? };
+        self.destroy_sync();
+
+        let (image_available, render_finished, in_flight) = create_sync(&self.device, count)?;
+        self.image_available_semaphores = image_available;
+        self.render_finished_semaphores = render_finished;
+        self.in_flight_fences = in_flight;
+
+        // The device is idle so no image is in use
+        self.images_in_flight = vec![vk::Fence::null(); self.data.swapchain.image_count()];
+        self.current_frame = 0;
+        Ok(())
+    }
+
+    fn destroy_sync(&mut self) {
+        unsafe {
+            for semaphore in self.image_available_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for semaphore in self.render_finished_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for fence in self.in_flight_fences.drain(..) {
+                self.device.destroy_fence(fence, None);
             }
-            for semaphore in &self.render_finished_semaphores {
-                self.context.device.destroy_semaphore(*semaphore, None);
+        }
+    }
+}
+
+/// Creates the image available and render finished semaphores, and the in flight fences for count
+/// frames in flight
+fn create_sync(
+    device: &ash::Device,
+    count: usize,
+) -> Result<(Vec<vk::Semaphore>, Vec<vk::Semaphore>, Vec<vk::Fence>)> {
+    let mut image_available = Vec::with_capacity(count);
+    let mut render_finished = Vec::with_capacity(count);
+    let mut in_flight = Vec::with_capacity(count);
+    for _ in 0..count {
+        image_available.push(vulkan::create_semaphore(device)?);
+        render_finished.push(vulkan::create_semaphore(device)?);
+        in_flight.push(vulkan::create_fence(device)?);
+    }
+    Ok((image_available, render_finished, in_flight))
+}
+
+/// Returns an error unless count is at least one and at most the swapchain image count
+fn validate_frames_in_flight(count: usize, image_count: usize) -> Result<()> {
+    if count == 0 || count > image_count {
+        return Err(Error::InvalidFramesInFlight(count, image_count));
+    }
+    Ok(())
+}
+
+/// What to do with a window after acquiring or presenting a swapchain image
+#[derive(Debug, PartialEq)]
+enum SwapchainStatus<T> {
+    /// The swapchain is usable and the frame continues
+    Ready(T),
+    /// The swapchain is out of date or suboptimal and needs to be recreated
+    Recreate,
+    /// The surface was lost and both it and the swapchain need to be recreated
+    RecreateSurface,
+}
+
+/// Maps the result and suboptimal flag of acquiring or presenting a swapchain image to what to do
+/// with the window
+/// Other errors are returned, E.g; Error::DeviceLost
+fn swapchain_status<T>(result: Result<(T, bool)>) -> Result<SwapchainStatus<T>> {
+    match result {
+        Ok((_, true)) => Ok(SwapchainStatus::Recreate),
+        Ok((value, false)) => Ok(SwapchainStatus::Ready(value)),
+        Err(Error::VulkanError(vk::Result::ERROR_OUT_OF_DATE_KHR)) => Ok(SwapchainStatus::Recreate),
+        Err(Error::VulkanError(vk::Result::ERROR_SURFACE_LOST_KHR)) => {
+            Ok(SwapchainStatus::RecreateSurface)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl Drop for WindowTarget {
+    fn drop(&mut self) {
+        self.destroy_sync();
+    }
+}
+
+impl Drop for WindowSurface {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { self.loader.destroy_surface(self.surface, None) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::EntityManager;
+    use crate::event::Event;
+    use crate::math::Vec3;
+    use crate::WindowMode;
+    use std::sync::mpsc;
+    use vulkan::enums::{AttachmentLoadOp, CompareOp};
+
+    /// Reads a spec from a path relative to the sandbox, E.g; ./data/pipelines/default.json
+    fn read_spec<T: serde::de::DeserializeOwned>(path: &str) -> T {
+        let path = std::path::Path::new("../sandbox").join(path);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn swapchain_status_from_result() {
+        assert_eq!(
+            swapchain_status(Ok((2, false))).unwrap(),
+            SwapchainStatus::Ready(2)
+        );
+        assert_eq!(
+            swapchain_status(Ok((2, true))).unwrap(),
+            SwapchainStatus::Recreate
+        );
+
+        let status = swapchain_status::<u32>(Err(vk::Result::ERROR_OUT_OF_DATE_KHR.into()));
+        assert_eq!(status.unwrap(), SwapchainStatus::Recreate);
+
+        // A lost surface is recreated instead of failing the frame
+        let status = swapchain_status::<u32>(Err(vk::Result::ERROR_SURFACE_LOST_KHR.into()));
+        assert_eq!(status.unwrap(), SwapchainStatus::RecreateSurface);
+
+        // A lost device can not be recovered from by the renderer
+        let status = swapchain_status::<u32>(Err(vk::Result::ERROR_DEVICE_LOST.into()));
+        assert!(matches!(status, Err(Error::DeviceLost)));
+    }
+
+    /// Creates a window sending events to sender and a renderer drawing to it
+    /// The renderer needs to be dropped before the window
+    // The resource manager is shared the same way as by Application
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn window_renderer(title: &str, sender: mpsc::Sender<Event>) -> (Window, Renderer) {
+        Window::init_glfw();
+        let window = Window::new(title, 64, 64, WindowMode::Windowed, sender);
+        let context = Arc::new(vulkan::init(&window, false).unwrap());
+        let resourcemanager = Arc::new(ResourceManager::new(Arc::clone(&context)));
+        let renderer = Renderer::new(context, &window, resourcemanager, PresentMode::Fifo).unwrap();
+        (window, renderer)
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn defer_recreate_while_minimized() {
+        let (sender, _receiver) = mpsc::channel();
+        let (mut window, mut renderer) = window_renderer("minimized", sender);
+
+        // Wait for the window manager to minimize the window
+        window.minimize();
+        let start = std::time::Instant::now();
+        while window.drawable() && start.elapsed() < std::time::Duration::from_secs(1) {
+            window.process_events();
+        }
+        assert!(!window.drawable());
+
+        // The swapchain is kept and recreated on a later frame
+        let mut target = renderer.targets.remove(&window.id()).unwrap();
+        let swapchain = Arc::clone(&target.data.swapchain);
+        assert!(!renderer.recreate(&mut target, &window));
+        assert!(target.recreate_requested);
+        assert!(Arc::ptr_eq(&swapchain, &target.data.swapchain));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and a display"]
+    fn recreate_lost_surface() {
+        let (sender, _receiver) = mpsc::channel();
+        let (window, mut renderer) = window_renderer("surface_lost", sender);
+
+        let mut target = renderer.targets.remove(&window.id()).unwrap();
+        let surface = target.surface.surface;
+        let swapchain = target.data.swapchain.vk();
+
+        // The window gets a new surface and a swapchain created from it
+        // The lost surface is destroyed along with the old swapchain
+        assert!(renderer.recreate_surface(&mut target, &window));
+        assert_ne!(target.surface.surface, surface);
+        assert_ne!(target.data.swapchain.vk(), swapchain);
+        assert!(target.lost_surface.is_none());
+
+        renderer.targets.insert(window.id(), target);
+    }
+
+    #[test]
+    fn group_instances_by_model() {
+        let mut entity_manager = EntityManager::new();
+        let mut entities = ComponentArray::new();
+        let mut models = HashMap::new();
+
+        for (i, model) in ["cube", "suzanne", "cube"].iter().enumerate() {
+            let entity = entity_manager.create_entity();
+            entities.insert_component(entity, Transform::new(Vec3::new(i as f32, 0.0, 0.0)));
+            models.insert(entity, *model);
+        }
+
+        let (instances, groups) = group_instances(&entities, |entity| models[&entity]);
+
+        assert_eq!(groups, vec![("cube", 0, 2), ("suzanne", 2, 1)]);
+        assert_eq!(instances.len(), 3);
+        assert!(instances[1] == Transform::new(Vec3::new(2.0, 0.0, 0.0)).matrix());
+        assert!(instances[2] == Transform::new(Vec3::new(1.0, 0.0, 0.0)).matrix());
+    }
+
+    #[test]
+    fn one_batch_per_model() {
+        let mut entity_manager = EntityManager::new();
+        let mut entities = ComponentArray::new();
+
+        for i in 0..1000 {
+            let entity = entity_manager.create_entity();
+            entities.insert_component(entity, Transform::new(Vec3::new(i as f32, 0.0, 0.0)));
+        }
+
+        // All entities sharing a model are drawn with a single instanced draw
+        let (instances, groups) = group_instances(&entities, |_| "cube");
+        assert_eq!(groups, vec![("cube", 0, 1000)]);
+        assert_eq!(instances.len(), 1000);
+    }
+
+    #[test]
+    fn mark_rebinds() {
+        let default = Arc::new("default");
+        let glass = Arc::new("glass");
+
+        // Both meshes of a model are drawn with a single bind of the shared material
+        let draws = super::mark_rebinds(vec![("body", &default), ("wheels", &default)]);
+        let rebinds: Vec<_> = draws.map(|(mesh, _, rebind)| (mesh, rebind)).collect();
+        assert_eq!(rebinds, [("body", true), ("wheels", false)]);
+
+        let draws = super::mark_rebinds(vec![
+            ("body", &default),
+            ("windows", &glass),
+            ("wheels", &default),
+        ]);
+        let rebinds: Vec<_> = draws
+            .map(|(_, material, rebind)| (**material, rebind))
+            .collect();
+        assert_eq!(
+            rebinds,
+            [("default", true), ("glass", true), ("default", true)]
+        );
+    }
+
+    #[test]
+    fn validate_frames_in_flight() {
+        assert!(super::validate_frames_in_flight(3, 3).is_ok());
+        assert!(matches!(
+            super::validate_frames_in_flight(0, 3),
+            Err(Error::InvalidFramesInFlight(0, 3))
+        ));
+        assert!(matches!(
+            super::validate_frames_in_flight(3, 2),
+            Err(Error::InvalidFramesInFlight(3, 2))
+        ));
+    }
+
+    #[test]
+    fn depth_prepass_specs() {
+        // The prepass only writes depth from the positions
+        let prepass: PipelineSpec = read_spec(DEPTH_PREPASS_PIPELINE);
+        assert!(prepass.fragment_shader.is_empty());
+        assert_eq!(prepass.vertex_layout, VertexLayout::InstancedPositionOnly);
+        assert!(prepass.depth.test && prepass.depth.write);
+
+        // The main pass keeps the prepass depth and only shades the nearest fragments
+        let material: serde_json::Value = read_spec(DEFAULT_PREPASS_MATERIAL);
+        let main: PipelineSpec = read_spec(material["pipeline"].as_str().unwrap());
+        assert!(main.depth.test && !main.depth.write);
+        assert_eq!(main.depth.compare_op, CompareOp::Equal);
+
+        let renderpass: RenderPassSpec = read_spec(&main.renderpass);
+        let depth = renderpass.subpasses[0].depth_attachment.unwrap();
+        assert!(matches!(
+            renderpass.attachments[depth].load_op,
+            AttachmentLoadOp::Load
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn three_frames_in_flight() {
+        let context = vulkan::test_context();
+
+        let (image_available, render_finished, in_flight) =
+            create_sync(&context.device, 3).unwrap();
+        assert_eq!(image_available.len(), 3);
+        assert_eq!(render_finished.len(), 3);
+        assert_eq!(in_flight.len(), 3);
+
+        unsafe {
+            for semaphore in image_available.into_iter().chain(render_finished) {
+                context.device.destroy_semaphore(semaphore, None);
             }
-            for fence in &self.in_flight_fences {
-                self.context.device.destroy_fence(*fence, None);
+            for fence in in_flight {
+                context.device.destroy_fence(fence, None);
             }
         }
     }
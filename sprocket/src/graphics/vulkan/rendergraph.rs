@@ -0,0 +1,258 @@
+use super::{CommandBuffer, Error, Framebuffer, RenderPass, RenderTarget, Result};
+use crate::graphics::Color;
+use std::sync::Arc;
+
+/// The framebuffers a pass renders into
+pub enum PassTarget {
+    /// One framebuffer per swapchain image, indexed by the image being recorded
+    PerImage(Vec<Arc<Framebuffer>>),
+    /// An offscreen render target rendered to each frame
+    Offscreen(Arc<RenderTarget>),
+}
+
+impl PassTarget {
+    fn framebuffer(&self, image_index: usize) -> &Framebuffer {
+        match self {
+            PassTarget::PerImage(framebuffers) => &framebuffers[image_index],
+            PassTarget::Offscreen(target) => target.framebuffer(),
+        }
+    }
+}
+
+/// Records the draw commands of a pass
+/// Called inside the renderpass with the frame context of the graph
+type RecordFn<C> = Box<dyn Fn(&mut CommandBuffer, &C)>;
+
+struct Pass<C> {
+    name: String,
+    renderpass: Arc<RenderPass>,
+    target: PassTarget,
+    clear_color: Color,
+    dependencies: Vec<String>,
+    record: RecordFn<C>,
+}
+
+/// Named renderpasses recorded in dependency order into one command buffer, E.g; shadow, main and
+/// post processing passes
+/// Each pass begins its renderpass on its target, sets the viewport and scissor to the target and
+/// calls its record function with the frame context C
+pub struct RenderGraph<C> {
+    passes: Vec<Pass<C>>,
+    /// Indices into passes in the order they are recorded
+    /// Empty until build is called
+    order: Vec<usize>,
+}
+
+impl<C> Default for RenderGraph<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> RenderGraph<C> {
+    pub fn new() -> Self {
+        RenderGraph {
+            passes: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Adds a pass recorded after the passes named in dependencies
+    /// The pass is recorded once build has resolved the order
+    pub fn add_pass<F>(
+        &mut self,
+        name: &str,
+        renderpass: Arc<RenderPass>,
+        target: PassTarget,
+        clear_color: Color,
+        dependencies: &[&str],
+        record: F,
+    ) where
+        F: Fn(&mut CommandBuffer, &C) + 'static,
+    {
+        self.passes.push(Pass {
+            name: name.to_owned(),
+            renderpass,
+            target,
+            clear_color,
+            dependencies: dependencies.iter().map(|v| (*v).to_owned()).collect(),
+            record: Box::new(record),
+        });
+        self.order.clear();
+    }
+
+    /// Resolves the recording order from the dependencies
+    /// Passes that do not depend on each other keep the order they were added in
+    /// Returns an error if a dependency does not exist or the dependencies are cyclic
+    pub fn build(&mut self) -> Result<()> {
+        let passes: Vec<_> = self
+            .passes
+            .iter()
+            .map(|pass| (pass.name.as_str(), pass.dependencies.as_slice()))
+            .collect();
+
+        self.order = resolve_order(&passes)?;
+        Ok(())
+    }
+
+    /// Sets the color the target of the named pass is cleared to
+    /// Returns an error if the pass does not exist
+    pub fn set_clear_color(&mut self, name: &str, clear_color: Color) -> Result<()> {
+        match self.passes.iter_mut().find(|pass| pass.name == name) {
+            Some(pass) => {
+                pass.clear_color = clear_color;
+                Ok(())
+            }
+            None => Err(Error::MissingPass(name.to_owned())),
+        }
+    }
+
+    /// Returns the color the target of the named pass is cleared to
+    /// Returns None if the pass does not exist
+    pub fn clear_color(&self, name: &str) -> Option<Color> {
+        self.passes
+            .iter()
+            .find(|pass| pass.name == name)
+            .map(|pass| pass.clear_color)
+    }
+
+    /// Returns the names of the passes in the order they are recorded
+    pub fn order(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .map(|&i| self.passes[i].name.as_str())
+            .collect()
+    }
+
+    /// Returns true if the graph has been built since the last pass was added
+    pub fn is_built(&self) -> bool {
+        self.order.len() == self.passes.len()
+    }
+
+    /// Records all passes in order
+    /// image_index selects the framebuffer of passes rendering to swapchain images
+    /// Nothing is recorded unless the graph has been built
+    pub fn record(&self, commandbuffer: &mut CommandBuffer, image_index: usize, context: &C) {
+        for &i in &self.order {
+            let pass = &self.passes[i];
+            let framebuffer = pass.target.framebuffer(image_index);
+
+            commandbuffer.begin_renderpass(&pass.renderpass, framebuffer, pass.clear_color);
+            commandbuffer.set_viewport(framebuffer.extent());
+            commandbuffer.set_scissor(framebuffer.extent());
+            (pass.record)(commandbuffer, context);
+            commandbuffer.end_renderpass();
+        }
+    }
+}
+
+/// Returns the indices of passes in the order they are recorded from the name and dependencies of
+/// each pass
+fn resolve_order(passes: &[(&str, &[String])]) -> Result<Vec<usize>> {
+    let mut dependencies = Vec::with_capacity(passes.len());
+    for (_, pass_dependencies) in passes {
+        let indices = pass_dependencies
+            .iter()
+            .map(|dependency| {
+                passes
+                    .iter()
+                    .position(|(other, _)| other == dependency)
+                    .ok_or_else(|| Error::MissingPass(dependency.to_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        dependencies.push(indices);
+    }
+
+    // Repeatedly pick the first pass whose dependencies have all been ordered
+    let mut order = Vec::with_capacity(passes.len());
+    let mut ordered = vec![false; passes.len()];
+    while order.len() < passes.len() {
+        let next = (0..passes.len()).find(|&i| {
+            !ordered[i]
+                && dependencies[i]
+                    .iter()
+                    .all(|&dependency| ordered[dependency])
+        });
+
+        match next {
+            Some(i) => {
+                ordered[i] = true;
+                order.push(i);
+            }
+            None => return Err(Error::CyclicPasses),
+        }
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, renderpass::RenderPassSpec};
+    use ash::vk;
+
+    #[test]
+    fn resolve_order() {
+        let shadow = vec![];
+        let main = vec!["shadow".to_owned()];
+        let ui = vec![];
+
+        let order = super::resolve_order(&[("main", &main), ("shadow", &shadow), ("ui", &ui)]);
+        assert_eq!(order.unwrap(), [1, 0, 2]);
+    }
+
+    #[test]
+    fn resolve_order_missing() {
+        let main = vec!["shadow".to_owned()];
+
+        let order = super::resolve_order(&[("main", &main)]);
+        assert!(matches!(order, Err(Error::MissingPass(name)) if name == "shadow"));
+    }
+
+    #[test]
+    fn resolve_order_cyclic() {
+        let a = vec!["b".to_owned()];
+        let b = vec!["a".to_owned()];
+
+        let order = super::resolve_order(&[("a", &a), ("b", &b)]);
+        assert!(matches!(order, Err(Error::CyclicPasses)));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn set_clear_color() {
+        let context = vulkan::test_context();
+
+        let contents =
+            std::fs::read_to_string("../sandbox/data/renderpasses/default.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        let renderpass = RenderPass::new(
+            &context.device,
+            spec,
+            vk::Format::B8G8R8A8_SRGB,
+            vk::Format::D32_SFLOAT,
+        )
+        .unwrap();
+
+        let mut graph: RenderGraph<()> = RenderGraph::new();
+        graph.add_pass(
+            "main",
+            Arc::new(renderpass),
+            PassTarget::PerImage(Vec::new()),
+            Color::black(),
+            &[],
+            |_, _| {},
+        );
+
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        graph.set_clear_color("main", red).unwrap();
+        assert_eq!(graph.clear_color("main"), Some(red));
+
+        assert!(matches!(
+            graph.set_clear_color("post", red),
+            Err(Error::MissingPass(_))
+        ));
+        assert_eq!(graph.clear_color("post"), None);
+    }
+}
@@ -4,6 +4,7 @@ use super::Result;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 
 #[derive(Serialize, Deserialize, Clone)]
 /// Specifies how to create a renderpass
@@ -15,10 +16,37 @@ pub struct RenderPassSpec {
     pub attachments: Vec<Attachment>,
 }
 
+impl RenderPassSpec {
+    /// Returns the references to the input attachments of each subpass
+    /// Input attachments are read by shaders instead of written to
+    fn input_attachment_refs(&self) -> Vec<Vec<vk::AttachmentReference>> {
+        self.subpasses
+            .iter()
+            .map(|subpass| {
+                subpass
+                    .input_attachments
+                    .iter()
+                    .map(|index| vk::AttachmentReference {
+                        attachment: *index as u32,
+                        layout: match self.attachments[*index].format {
+                            ImageFormat::Depth => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                            _ => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        },
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Subpass {
     pub color_attachments: Vec<usize>,
     pub depth_attachment: Option<usize>,
+    /// Attachments written by a previous subpass and read in the shaders of this one
+    /// Requires a dependency from the writing subpass
+    #[serde(default)]
+    pub input_attachments: Vec<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -98,11 +126,22 @@ pub struct RenderPass {
     device: ash::Device,
     renderpass: vk::RenderPass,
     spec: RenderPassSpec,
+    color_format: vk::Format,
+    depth_format: vk::Format,
 }
 
 impl Resource for RenderPass {
     fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
-        let spec: RenderPassSpec = serde_json::from_str(&ex::fs::read_to_string(path)?)?;
+        let contents = ex::fs::read_to_string(path)?;
+        Self::load_from_reader(resourcemanager, &mut contents.as_bytes(), path)
+    }
+
+    fn load_from_reader(
+        resourcemanager: &super::ResourceManager,
+        reader: &mut dyn Read,
+        _: &str,
+    ) -> Result<Self> {
+        let spec: RenderPassSpec = serde_json::from_reader(reader)?;
         let context = resourcemanager.context();
         let swapchain = match resourcemanager.get_swapchain() {
             Some(swapchain) => swapchain,
@@ -154,6 +193,8 @@ impl RenderPass {
                     .collect()
             })
             .collect();
+        let subpass_input_attachments = spec.input_attachment_refs();
+
         let subpass_depth_attachment: Vec<_> = spec
             .subpasses
             .iter()
@@ -167,8 +208,8 @@ impl RenderPass {
             .map(|i| vk::SubpassDescription {
                 flags: Default::default(),
                 pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
-                input_attachment_count: 0,
-                p_input_attachments: std::ptr::null(),
+                input_attachment_count: subpass_input_attachments[i].len() as u32,
+                p_input_attachments: subpass_input_attachments[i].as_ptr(),
                 color_attachment_count: subpass_color_attachments[i].len() as u32,
                 p_color_attachments: subpass_color_attachments[i].as_ptr(),
                 p_resolve_attachments: std::ptr::null(),
@@ -209,6 +250,8 @@ impl RenderPass {
             device: device.clone(),
             renderpass,
             spec,
+            color_format,
+            depth_format,
         })
     }
 
@@ -217,6 +260,20 @@ impl RenderPass {
         self.renderpass
     }
 
+    pub fn spec(&self) -> &RenderPassSpec {
+        &self.spec
+    }
+
+    /// Returns the format used for color attachments
+    pub fn color_format(&self) -> vk::Format {
+        self.color_format
+    }
+
+    /// Returns the format used for depth attachments
+    pub fn depth_format(&self) -> vk::Format {
+        self.depth_format
+    }
+
     /// Returns self created again from spec but with updated values
     /// Called when swapchain is recreated
     pub fn recreate(
@@ -233,3 +290,100 @@ impl Drop for RenderPass {
         unsafe { self.device.destroy_render_pass(self.renderpass, None) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, DescriptorType};
+
+    /// A G-buffer pass followed by a lighting pass reading its color attachment
+    const DEFERRED: &str = r#"{
+        "subpasses": [
+            { "color_attachments": [0], "depth_attachment": 2 },
+            { "color_attachments": [1], "depth_attachment": null, "input_attachments": [0, 2] }
+        ],
+        "dependencies": [
+            {
+                "src_subpass": { "Internal": 0 },
+                "dst_subpass": { "Internal": 1 },
+                "src_stage": "ColorAttachmentOutput",
+                "dst_stage": "FragmentShader",
+                "src_access": "ColorAttachmentWrite",
+                "dst_access": "InputAttachmentRead"
+            }
+        ],
+        "attachments": [
+            {
+                "store_op": "DontCare",
+                "load_op": "Clear",
+                "initial_layout": "Undefined",
+                "final_layout": "ShaderReadOnly",
+                "layout": "ColorAttachment",
+                "sample_count": 1,
+                "format": "Color"
+            },
+            {
+                "store_op": "Store",
+                "load_op": "Clear",
+                "initial_layout": "Undefined",
+                "final_layout": "ShaderReadOnly",
+                "layout": "ColorAttachment",
+                "sample_count": 1,
+                "format": "Color"
+            },
+            {
+                "store_op": "DontCare",
+                "load_op": "Clear",
+                "initial_layout": "Undefined",
+                "final_layout": "DepthStencilReadOnly",
+                "layout": "DepthStencilAttachment",
+                "sample_count": 1,
+                "format": "Depth"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn input_attachment_refs() {
+        let spec: RenderPassSpec = serde_json::from_str(DEFERRED).unwrap();
+
+        let refs = spec.input_attachment_refs();
+        assert!(refs[0].is_empty());
+        assert_eq!(refs[1].len(), 2);
+        assert_eq!(refs[1][0].attachment, 0);
+        assert_eq!(refs[1][0].layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(refs[1][1].attachment, 2);
+        assert_eq!(
+            refs[1][1].layout,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        );
+
+        // Existing single subpass specs have no input attachments
+        let contents =
+            std::fs::read_to_string("../sandbox/data/renderpasses/offscreen.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        assert!(spec.input_attachment_refs().iter().all(Vec::is_empty));
+
+        assert_eq!(
+            vk::DescriptorType::from(DescriptorType::InputAttachment),
+            vk::DescriptorType::INPUT_ATTACHMENT
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn two_subpasses() {
+        let context = vulkan::test_context();
+        let spec: RenderPassSpec = serde_json::from_str(DEFERRED).unwrap();
+
+        let renderpass = RenderPass::new(
+            &context.device,
+            spec,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::D32_SFLOAT,
+        )
+        .unwrap();
+        assert_ne!(renderpass.vk(), vk::RenderPass::null());
+        assert_eq!(renderpass.spec().subpasses[1].input_attachments, vec![0, 2]);
+    }
+}
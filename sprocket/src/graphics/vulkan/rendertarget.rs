@@ -0,0 +1,218 @@
+use super::renderpass::ImageFormat;
+use super::{CommandBuffer, Error, Framebuffer, RenderPass, Result, Texture, VulkanContext};
+use crate::graphics::{Color, Extent2D};
+use ash::vk;
+use std::sync::Arc;
+
+/// An offscreen image that can be rendered to and later sampled from
+/// Used for post processing, shadow maps and UI composition
+/// Owns an optional color texture, an optional depth texture and a framebuffer for the given
+/// renderpass
+pub struct RenderTarget {
+    color: Option<Texture>,
+    depth: Option<Texture>,
+    framebuffer: Framebuffer,
+    renderpass: Arc<RenderPass>,
+}
+
+impl RenderTarget {
+    /// Creates a render target of the given size compatible with renderpass
+    /// The renderpass may have at most one color attachment and one depth attachment, E.g; a
+    /// depth only renderpass for shadow maps
+    /// The textures will be in the final layouts of their attachments after rendering, which
+    /// should be ShaderReadOnly for sampling color and DepthStencilReadOnly for sampling depth
+    pub fn new(
+        context: &VulkanContext,
+        renderpass: Arc<RenderPass>,
+        extent: Extent2D,
+    ) -> Result<RenderTarget> {
+        let attachments = &renderpass.spec().attachments;
+
+        let color_attachments: Vec<_> = attachments
+            .iter()
+            .filter(|attachment| matches!(attachment.format, ImageFormat::Color))
+            .collect();
+
+        let depth_attachments: Vec<_> = attachments
+            .iter()
+            .filter(|attachment| matches!(attachment.format, ImageFormat::Depth))
+            .collect();
+
+        if color_attachments.len() > 1 || depth_attachments.len() > 1 {
+            return Err(Error::UnimplementedFeature(
+                "Render targets with more than one color or depth attachment",
+            ));
+        }
+
+        // The renderpass transitions the images when rendered to
+        let color = match color_attachments.first() {
+            Some(attachment) => {
+                let mut color = Texture::new(
+                    &context.allocator,
+                    &context.device,
+                    renderpass.color_format(),
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::ImageAspectFlags::COLOR,
+                    vk::ImageTiling::OPTIMAL,
+                    extent,
+                )?;
+                color.set_layout(attachment.final_layout.into());
+                Some(color)
+            }
+            None => None,
+        };
+
+        let depth = match depth_attachments.first() {
+            Some(attachment) => {
+                let mut depth = Texture::new(
+                    &context.allocator,
+                    &context.device,
+                    renderpass.depth_format(),
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::ImageAspectFlags::DEPTH,
+                    vk::ImageTiling::OPTIMAL,
+                    extent,
+                )?;
+                depth.set_layout(attachment.final_layout.into());
+                Some(depth)
+            }
+            None => None,
+        };
+
+        // Order the textures by the attachment indices of the renderpass
+        // Both exist if the renderpass has attachments of their format
+        let framebuffer_attachments: Vec<&Texture> = attachments
+            .iter()
+            .filter_map(|attachment| match attachment.format {
+                ImageFormat::Depth => depth.as_ref(),
+                _ => color.as_ref(),
+            })
+            .collect();
+
+        let framebuffer = Framebuffer::new(
+            &context.device,
+            &framebuffer_attachments,
+            &renderpass,
+            extent,
+        )?;
+
+        Ok(RenderTarget {
+            color,
+            depth,
+            framebuffer,
+            renderpass,
+        })
+    }
+
+    /// Returns the color texture which can be bound for sampling if the renderpass has a color
+    /// attachment
+    pub fn color(&self) -> Option<&Texture> {
+        self.color.as_ref()
+    }
+
+    /// Returns the depth texture which can be bound for sampling if the renderpass has a depth
+    /// attachment
+    pub fn depth(&self) -> Option<&Texture> {
+        self.depth.as_ref()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn renderpass(&self) -> &Arc<RenderPass> {
+        &self.renderpass
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        self.framebuffer.extent()
+    }
+
+    /// Records draw commands into the target using a one time command buffer
+    /// The renderpass is begun and the viewport and scissor set before calling record
+    /// Blocks until the commands have finished executing
+    /// Does not require a swapchain and can be used with a headless context
+    pub fn render<F>(&self, context: &VulkanContext, clear_color: Color, record: F) -> Result<()>
+    where
+        F: FnOnce(&mut CommandBuffer),
+    {
+        context.immediate_submit(|commandbuffer| {
+            commandbuffer.begin_renderpass(&self.renderpass, &self.framebuffer, clear_color);
+            commandbuffer.set_viewport(self.extent());
+            commandbuffer.set_scissor(self.extent());
+
+            record(commandbuffer);
+
+            commandbuffer.end_renderpass();
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+    use crate::graphics::vulkan::renderpass::RenderPassSpec;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn shadow_map() {
+        let context = vulkan::test_context();
+
+        let contents = std::fs::read_to_string("../sandbox/data/renderpasses/shadow.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        let renderpass = RenderPass::new(
+            &context.device,
+            spec,
+            vk::Format::B8G8R8A8_SRGB,
+            vk::Format::D32_SFLOAT,
+        )
+        .unwrap();
+
+        let extent = Extent2D::new(256, 256);
+        let shadow_map = RenderTarget::new(&context, Arc::new(renderpass), extent).unwrap();
+        assert!(shadow_map.color().is_none());
+
+        let depth = shadow_map.depth().unwrap();
+        assert_eq!(depth.format(), vk::Format::D32_SFLOAT);
+        assert_eq!(
+            depth.usage(),
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn offscreen_target() {
+        let context = vulkan::test_context();
+
+        let contents =
+            std::fs::read_to_string("../sandbox/data/renderpasses/offscreen.json").unwrap();
+        let spec: RenderPassSpec = serde_json::from_str(&contents).unwrap();
+        let renderpass = RenderPass::new(
+            &context.device,
+            spec,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::D32_SFLOAT,
+        )
+        .unwrap();
+
+        let target =
+            RenderTarget::new(&context, Arc::new(renderpass), Extent2D::new(64, 64)).unwrap();
+
+        let color = target.color().unwrap();
+        assert!(color
+            .usage()
+            .contains(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED));
+        assert_eq!(color.layout(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert!(target.depth().is_some());
+
+        let extent = vk::Extent2D::from(target.framebuffer().extent());
+        assert_eq!((extent.width, extent.height), (64, 64));
+
+        target
+            .render(&context, Color::new(0.0, 0.0, 0.0, 1.0), |_| {})
+            .unwrap();
+    }
+}
@@ -1,17 +1,45 @@
-use super::{Material, Model, Pipeline, RenderPass, Result, Swapchain, Texture, VulkanContext};
+use super::{
+    AsyncTransfer, ComputePipeline, DescriptorAllocation, DescriptorAllocator, DescriptorSetLayout,
+    Material, Model, Pipeline, RenderPass, Result, Swapchain, Texture, TextureUsageHint, Transfer,
+    VulkanContext,
+};
 use ash::version::DeviceV1_0;
 use log::*;
 use std::{
+    any::{Any, TypeId},
     collections::HashMap,
+    hash::{Hash, Hasher},
+    io::Read,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
     sync::{Arc, Mutex, RwLock},
+    thread::{self, JoinHandle},
+    time::SystemTime,
 };
 
+/// The maximum number of threads reading resources loaded with load_async
+const MAX_LOAD_WORKERS: usize = 4;
+
 /// Represents a resource soon to be deleted
 struct Garbage<T> {
     resource: Arc<T>,
     cycles_remaining: u32,
 }
 
+impl ResourceInfo {
+    /// Returns the path the resource is stored as
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type name of the resource without the module path, E.g; Texture
+    pub fn ty(&self) -> &'static str {
+        self.ty
+    }
+}
+
 impl<T> Garbage<T> {
     pub fn new(resource: Arc<T>, cycles_remaining: u32) -> Self {
         Garbage {
@@ -41,21 +69,263 @@ pub trait Resource {
     fn load(resourcemanager: &ResourceManager, path: &str) -> Result<Self>
     where
         Self: Sized;
+
+    /// Loads the resource from the contents of a file read from reader, E.g; an entry in an
+    /// archive or embedded bytes
+    /// name identifies the resource in errors
+    /// The default ignores reader and loads name from disk, for resources which can only be loaded
+    /// from files
+    fn load_from_reader(
+        resourcemanager: &ResourceManager,
+        _reader: &mut dyn Read,
+        name: &str,
+    ) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::load(resourcemanager, name)
+    }
+
+    /// Returns the files besides path that the resource was loaded from, E.g; shaders
+    /// Used to detect changes when hot reloading
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A resource whose loading can be split into reading from disk and creating it with the graphics
+/// context
+/// Used by load_async to do the reading on a worker thread
+pub trait AsyncResource: Resource + Sized {
+    /// The data read from disk, E.g; decoded pixels
+    type Data: Send + 'static;
+
+    /// Reads and decodes the resource at path without using the graphics context
+    fn read(path: &str) -> Result<Self::Data>;
+
+    /// Creates the resource from the data read from path
+    /// The data is uploaded with transfer, which may record the copies to submit them later
+    fn upload(
+        resourcemanager: &ResourceManager,
+        path: &str,
+        data: Self::Data,
+        transfer: &Transfer,
+    ) -> Result<Self>;
+}
+
+/// Creates a resource on the polling thread from data read on a worker thread
+type Upload<T> = Box<dyn FnOnce(&ResourceManager, &Transfer) -> Result<T> + Send>;
+
+/// A resource whose copies to the device have been submitted but not necessarily finished
+struct Uploading<T> {
+    /// Dropped first to wait for the copies before the resource is destroyed
+    transfer: AsyncTransfer,
+    path: String,
+    resource: T,
+}
+
+/// A job run on a load worker
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The sender queueing jobs for the worker threads and the threads
+type Workers = (Sender<Job>, Vec<JoinHandle<()>>);
+
+/// A bounded pool of threads reading resources loaded with load_async
+/// The threads are started by the first load, and jobs are queued while all threads are busy
+struct LoadWorkers {
+    state: Mutex<Option<Workers>>,
+}
+
+impl LoadWorkers {
+    fn new() -> Self {
+        LoadWorkers {
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Runs job on the first free worker
+    fn execute(&self, job: Job) {
+        let mut state = self.state.lock().unwrap();
+        let (sender, _) = state.get_or_insert_with(Self::spawn);
+
+        // Workers only stop once the sender is dropped
+        sender.send(job).expect("Load workers stopped");
+    }
+
+    fn spawn() -> Workers {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let count = thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1)
+            .min(MAX_LOAD_WORKERS);
+
+        let threads = (0..count)
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("resource loader {}", index))
+                    .spawn(move || loop {
+                        let job = match receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        // A panicking job disconnects its channel which is reported by poll
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    })
+                    .expect("Failed to spawn load worker")
+            })
+            .collect();
+
+        (sender, threads)
+    }
+}
+
+impl Drop for LoadWorkers {
+    fn drop(&mut self) {
+        if let Some((sender, threads)) = self.state.get_mut().unwrap().take() {
+            drop(sender);
+            for thread in threads {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// A lightweight reference to a resource loaded by a ResourceManager
+/// Resolving a handle indexes a list instead of hashing the path, and is meant for hot paths
+/// Handles to the same path are equal and keep referring to the resource when it is reloaded or
+/// recreated
+/// A handle does not keep the resource alive, it resolves to None once the resource has been
+/// collected as garbage
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: usize) -> Self {
+        Handle {
+            index,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state)
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// The resources of a ResourceSystem indexed by handle
+/// Paths keep their handle after the resource is collected so that handles stay stable
+struct Entries<T> {
+    /// The loaded resource of each handle, None if it has been collected
+    resources: Vec<Option<Arc<T>>>,
+    paths: Vec<String>,
+    handles: HashMap<String, usize>,
+}
+
+impl<T> Entries<T> {
+    /// Returns the handle of path, creating an empty slot if the path is new
+    fn reserve(&mut self, path: &str) -> Handle<T> {
+        if let Some(&index) = self.handles.get(path) {
+            return Handle::new(index);
+        }
+
+        self.resources.push(None);
+        self.paths.push(path.to_owned());
+        self.handles.insert(path.to_owned(), self.paths.len() - 1);
+        Handle::new(self.paths.len() - 1)
+    }
+
+    /// Stores resource as path and returns its handle
+    fn insert(&mut self, path: &str, resource: Arc<T>) -> Handle<T> {
+        let handle = self.reserve(path);
+        self.resources[handle.index] = Some(resource);
+        handle
+    }
+
+    /// Returns the handle and resource of path if loaded
+    fn get(&self, path: &str) -> Option<(Handle<T>, &Arc<T>)> {
+        let index = *self.handles.get(path)?;
+        self.resources[index]
+            .as_ref()
+            .map(|resource| (Handle::new(index), resource))
+    }
+
+    /// Iterates the paths and resources of all loaded resources
+    fn iter(&self) -> impl Iterator<Item = (&String, &Arc<T>)> {
+        self.paths
+            .iter()
+            .zip(self.resources.iter())
+            .filter_map(|(path, resource)| resource.as_ref().map(|resource| (path, resource)))
+    }
 }
 
 /// Manages a single type of resource
 /// Used internally in ResourceManager
 /// Should not be used standalone but can be used to assemble your own type of resource manager
 pub struct ResourceSystem<T: Resource> {
-    resources: RwLock<HashMap<String, Arc<T>>>,
+    entries: RwLock<Entries<T>>,
     garbage: Mutex<Vec<Garbage<T>>>,
+    /// The latest modification time of the files each resource was loaded from
+    modified: Mutex<HashMap<String, SystemTime>>,
+    /// Resources being read on worker threads by path
+    pending: Mutex<HashMap<String, Receiver<Result<Upload<T>>>>>,
+    /// Resources read on worker threads whose uploads have been submitted
+    uploading: Mutex<Vec<Uploading<T>>>,
 }
 
 impl<T: Resource> ResourceSystem<T> {
     pub fn new() -> Self {
         ResourceSystem {
-            resources: RwLock::new(HashMap::new()),
+            entries: RwLock::new(Entries {
+                resources: Vec::new(),
+                paths: Vec::new(),
+                handles: HashMap::new(),
+            }),
             garbage: Mutex::new(Vec::new()),
+            modified: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            uploading: Mutex::new(Vec::new()),
         }
     }
 
@@ -64,29 +334,257 @@ impl<T: Resource> ResourceSystem<T> {
     /// If a resource with the name already exists, the existing one will be returned
     /// Will wait for write lock of textures
     pub fn load(&self, resourcemanager: &ResourceManager, path: &str) -> Result<Arc<T>> {
-        match self.resources.read().unwrap().get(path) {
-            Some(resource) => return Ok(Arc::clone(resource)),
-            None => {}
+        self.load_with_handle(resourcemanager, path)
+            .map(|(_, resource)| resource)
+    }
+
+    /// Loads and stores a resource from the contents read from reader if it doesn't already exist
+    /// The resource will be stored as name
+    /// If a resource with the name already exists, the existing one will be returned without
+    /// reading
+    pub fn load_from_reader(
+        &self,
+        resourcemanager: &ResourceManager,
+        reader: &mut dyn Read,
+        name: &str,
+    ) -> Result<Arc<T>> {
+        if let Some(resource) = self.get(name) {
+            return Ok(resource);
         }
 
-        // Load outside match to drop RwLock read guard
+        let resource = Arc::new(T::load_from_reader(resourcemanager, reader, name)?);
+        self.store(name, Arc::clone(&resource));
+        Ok(resource)
+    }
+
+    /// Loads and stores a resource if it doesn't already exist and returns its handle
+    /// Loading the same path several times returns equal handles
+    pub fn load_handle(&self, resourcemanager: &ResourceManager, path: &str) -> Result<Handle<T>> {
+        self.load_with_handle(resourcemanager, path)
+            .map(|(handle, _)| handle)
+    }
+
+    fn load_with_handle(
+        &self,
+        resourcemanager: &ResourceManager,
+        path: &str,
+    ) -> Result<(Handle<T>, Arc<T>)> {
+        if let Some((handle, resource)) = self.entries.read().unwrap().get(path) {
+            return Ok((handle, Arc::clone(resource)));
+        }
+
+        // Load after dropping the RwLock read guard
         let resource = Arc::new(T::load(resourcemanager, path)?);
+        let handle = self.store(path, Arc::clone(&resource));
+        Ok((handle, resource))
+    }
+
+    /// Creates the resources that have finished reading on worker threads and submits their
+    /// uploads to the transfer queue without waiting for them
+    /// Resources are stored once their uploads have finished, which a later poll may notice
+    /// Loads that failed are logged and can be retried with load_async
+    /// Returns the number of resources stored
+    pub fn poll(&self, resourcemanager: &ResourceManager) -> usize {
+        let mut read = Vec::new();
+
+        self.pending.lock().unwrap().retain(|path, receiver| {
+            match receiver.try_recv() {
+                Ok(upload) => read.push((path.to_owned(), upload)),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => {
+                    error!("Worker thread loading '{}' panicked", path);
+                }
+            }
+            false
+        });
+
+        // Upload after releasing the pending lock since resources may load other resources
+        for (path, upload) in read {
+            // The resource may have been loaded synchronously in the meantime
+            if self.get(&path).is_some() {
+                continue;
+            }
+
+            match upload.and_then(|upload| Self::submit_upload(resourcemanager, upload)) {
+                Ok((resource, transfer)) => self.uploading.lock().unwrap().push(Uploading {
+                    transfer,
+                    path,
+                    resource,
+                }),
+                Err(e) => error!("Failed to load '{}': {}", path, e),
+            }
+        }
+
+        let mut finished = Vec::new();
+        {
+            let mut uploading = self.uploading.lock().unwrap();
+            let mut index = 0;
+            while index < uploading.len() {
+                match uploading[index].transfer.is_finished() {
+                    Ok(false) => index += 1,
+                    Ok(true) => finished.push(uploading.swap_remove(index)),
+                    Err(e) => {
+                        error!("Failed to upload '{}': {}", uploading[index].path, e);
+                        uploading.swap_remove(index);
+                    }
+                }
+            }
+        }
+
+        let count = finished.len();
+        for upload in finished {
+            if self.get(&upload.path).is_none() {
+                self.store(&upload.path, Arc::new(upload.resource));
+            }
+        }
 
-        self.resources
-            .write()
+        count
+    }
+
+    /// Creates a resource which records its copies into a new upload and submits it
+    fn submit_upload(
+        resourcemanager: &ResourceManager,
+        upload: Upload<T>,
+    ) -> Result<(T, AsyncTransfer)> {
+        let mut transfer = AsyncTransfer::new(resourcemanager.context())?;
+        let resource = upload(resourcemanager, &transfer.transfer())?;
+        transfer.submit()?;
+        Ok((resource, transfer))
+    }
+
+    /// Returns true if path is being read on a worker thread or uploaded
+    pub fn is_pending(&self, path: &str) -> bool {
+        self.pending.lock().unwrap().contains_key(path)
+            || self
+                .uploading
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|upload| upload.path == path)
+    }
+
+    /// Returns true if the resource a handle refers to has been loaded and not collected
+    pub fn is_ready(&self, handle: Handle<T>) -> bool {
+        self.resolve(handle).is_some()
+    }
+
+    /// Stores a loaded resource and records its modification time
+    fn store(&self, path: &str, resource: Arc<T>) -> Handle<T> {
+        if let Some(modified) = Self::modified_time(path, &resource) {
+            self.modified
+                .lock()
+                .unwrap()
+                .insert(path.to_owned(), modified);
+        }
+
+        self.entries.write().unwrap().insert(path, resource)
+    }
+
+    /// Returns the paths of all loaded resources whose files have changed since they were loaded
+    pub fn changed(&self) -> Vec<String> {
+        let entries = self.entries.read().unwrap();
+        let modified = self.modified.lock().unwrap();
+
+        entries
+            .iter()
+            .filter(|(path, resource)| {
+                match (Self::modified_time(path, resource), modified.get(*path)) {
+                    (Some(current), Some(loaded)) => current > *loaded,
+                    _ => false,
+                }
+            })
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+
+    /// Returns the paths of all loaded resources
+    pub fn paths(&self) -> Vec<String> {
+        self.entries
+            .read()
             .unwrap()
-            .insert(path.to_owned(), Arc::clone(&resource));
-        Ok(resource)
+            .iter()
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+
+    /// Loads the resources at paths again from disk and replaces the stored ones
+    /// Existing references will keep the old resource while handles resolve to the new one
+    /// A resource that fails to load keeps the old one and is retried on the next reload
+    /// Returns the number of reloaded resources
+    pub fn reload(&self, resourcemanager: &ResourceManager, paths: &[String]) -> usize {
+        let mut count = 0;
+
+        for path in paths {
+            info!("Reloading '{}'", path);
+            let resource = match T::load(resourcemanager, path) {
+                Ok(resource) => Arc::new(resource),
+                Err(e) => {
+                    error!("Failed to reload '{}': {}", path, e);
+                    continue;
+                }
+            };
+
+            self.store(path, resource);
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Returns true if f returns true for all loaded resources
+    pub fn all<F: Fn(&T) -> bool>(&self, f: F) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .all(|(_, resource)| f(resource))
+    }
+
+    /// Replaces every loaded resource with the result of f
+    /// Nothing is replaced if f fails for any resource
+    pub fn recreate<F: Fn(&T) -> Result<T>>(&self, f: F) -> Result<()> {
+        let mut entries = self.entries.write().unwrap();
+
+        let recreated = entries
+            .resources
+            .iter()
+            .map(|resource| match resource {
+                Some(resource) => f(resource).map(|resource| Some(Arc::new(resource))),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        entries.resources = recreated;
+        Ok(())
+    }
+
+    /// Returns the latest modification time of the files a resource depends on
+    fn modified_time(path: &str, resource: &T) -> Option<SystemTime> {
+        std::iter::once(path.to_owned())
+            .chain(resource.dependencies())
+            .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .max()
     }
 
     /// path to return a reference to an already loaded texture
     /// Returns None if the texture isn't loaded
     pub fn get(&self, path: &str) -> Option<Arc<T>> {
-        self.resources
+        self.entries
             .read()
             .unwrap()
             .get(path)
-            .map(|v| Arc::clone(v))
+            .map(|(_, resource)| Arc::clone(resource))
+    }
+
+    /// Returns the resource a handle refers to
+    /// Returns None if the resource has been collected
+    pub fn resolve(&self, handle: Handle<T>) -> Option<Arc<T>> {
+        self.entries
+            .read()
+            .unwrap()
+            .resources
+            .get(handle.index)
+            .and_then(|resource| resource.as_ref().map(Arc::clone))
     }
 
     /// Goes through the loaded resources and places all resources with no other references in a garbage
@@ -94,7 +592,7 @@ impl<T: Resource> ResourceSystem<T> {
     pub fn collect_garbage(&self, garbage_cycles: u32) {
         // Acquire a lock for the whole function to avoid having a resource getting a user midway through TOCTOU
         let mut garbage = self.garbage.lock().unwrap();
-        let mut resources = self.resources.write().unwrap();
+        let mut entries = self.entries.write().unwrap();
 
         // Remove garbage with 0 cycles remaining
         garbage.retain(|v| v.cycles_remaining > 0);
@@ -102,20 +600,23 @@ impl<T: Resource> ResourceSystem<T> {
         garbage.iter_mut().for_each(|v| v.cycles_remaining -= 1);
 
         // Remove all elements with one 1 (self) strong reference and place into garbage
-        resources.retain(|_, r| {
-            if Arc::strong_count(&r) == 1 {
-                garbage.push(Garbage::new(Arc::clone(&r), garbage_cycles));
-                false
-            } else {
-                true
+        // The slot is kept so that the path gets the same handle if loaded again
+        for slot in entries.resources.iter_mut() {
+            let unused = match slot {
+                Some(r) => Arc::strong_count(r) == 1,
+                None => false,
+            };
+
+            if unused {
+                garbage.push(Garbage::new(slot.take().unwrap(), garbage_cycles));
             }
-        });
+        }
     }
 
     pub fn info(&self) -> Vec<ResourceInfo> {
         let ty = std::any::type_name::<T>();
         let ty = &ty[ty.rfind("::").map(|v| v + 2).unwrap_or(0)..];
-        self.resources
+        self.entries
             .read()
             .unwrap()
             .iter()
@@ -129,8 +630,71 @@ impl<T: Resource> ResourceSystem<T> {
     }
 }
 
+impl<T: AsyncResource + 'static> ResourceSystem<T> {
+    /// Starts reading the resource at path on a load worker and returns its handle
+    /// The handle resolves to None until poll has uploaded the resource on the transfer queue and
+    /// the upload has finished, since the graphics context can only be used from one thread
+    /// Does not start another read if the resource is already loaded or being loaded
+    pub fn load_async(&self, resourcemanager: &ResourceManager, path: &str) -> Handle<T> {
+        let mut entries = self.entries.write().unwrap();
+        let mut pending = self.pending.lock().unwrap();
+
+        let handle = entries.reserve(path);
+        if entries.resources[handle.index].is_some()
+            || pending.contains_key(path)
+            || self
+                .uploading
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|upload| upload.path == path)
+        {
+            return handle;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let owned_path = path.to_owned();
+
+        resourcemanager.workers.execute(Box::new(move || {
+            let upload = T::read(&owned_path).map(|data| -> Upload<T> {
+                Box::new(move |resourcemanager, transfer| {
+                    T::upload(resourcemanager, &owned_path, data, transfer)
+                })
+            });
+
+            // The receiver is dropped if the resource manager is
+            let _ = sender.send(upload);
+        }));
+
+        pending.insert(path.to_owned(), receiver);
+        handle
+    }
+}
+
+/// Type erased access to the resource system of a user defined type
+trait AnyResourceSystem {
+    fn as_any(&self) -> &dyn Any;
+    fn collect_garbage(&self, garbage_cycles: u32);
+    fn info(&self) -> Vec<ResourceInfo>;
+}
+
+impl<T: Resource + 'static> AnyResourceSystem for ResourceSystem<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn collect_garbage(&self, garbage_cycles: u32) {
+        ResourceSystem::collect_garbage(self, garbage_cycles)
+    }
+
+    fn info(&self) -> Vec<ResourceInfo> {
+        ResourceSystem::info(self)
+    }
+}
+
 /// Keeps track of loaded resources across threads
 /// Automatically reference counts resources and removes no longer used ones with .cleanup()
+/// The device must be idle when the resource manager is dropped, E.g; by Application::shutdown
 pub struct ResourceManager {
     context: Arc<VulkanContext>,
     // The current swapchain
@@ -139,7 +703,16 @@ pub struct ResourceManager {
     models: ResourceSystem<Model>,
     renderpasses: ResourceSystem<RenderPass>,
     pipelines: ResourceSystem<Pipeline>,
+    compute_pipelines: ResourceSystem<ComputePipeline>,
     materials: ResourceSystem<Material>,
+    /// The resource systems of user defined types, created on first use
+    custom: RwLock<HashMap<TypeId, Arc<dyn AnyResourceSystem>>>,
+    /// Shared descriptor pools for the sets of materials
+    descriptor_allocator: Arc<Mutex<DescriptorAllocator>>,
+    /// Whether changed files are reloaded by reload_changed
+    watching: AtomicBool,
+    /// Read the resources loaded with load_async
+    workers: LoadWorkers,
 }
 
 impl ResourceManager {
@@ -147,13 +720,18 @@ impl ResourceManager {
     /// Should only exist one per application or graphics context
     pub fn new(context: Arc<VulkanContext>) -> Self {
         ResourceManager {
-            context,
             textures: ResourceSystem::new(),
             models: ResourceSystem::new(),
             swapchain: RwLock::new(None),
             renderpasses: ResourceSystem::new(),
             pipelines: ResourceSystem::new(),
+            compute_pipelines: ResourceSystem::new(),
             materials: ResourceSystem::new(),
+            custom: RwLock::new(HashMap::new()),
+            workers: LoadWorkers::new(),
+            descriptor_allocator: Arc::new(Mutex::new(DescriptorAllocator::new(&context.device))),
+            watching: AtomicBool::new(false),
+            context,
         }
     }
 
@@ -175,6 +753,16 @@ impl ResourceManager {
             .map(|val| Arc::clone(val))
     }
 
+    /// Allocates descriptor sets from the shared descriptor pools
+    /// The sets are returned to the pools when the allocation is dropped
+    pub fn allocate_descriptor_sets(
+        &self,
+        layout: &DescriptorSetLayout,
+        count: u32,
+    ) -> Result<DescriptorAllocation> {
+        DescriptorAllocation::new(&self.descriptor_allocator, layout, count)
+    }
+
     /// Loads and stores a texture if it doesn't already exist
     /// The texture will be stored as the path name
     /// If a texture with the name already exists, the existing one will be returned
@@ -182,12 +770,56 @@ impl ResourceManager {
         self.textures.load(&self, path)
     }
 
+    /// Loads a texture like load_texture with the format given by hint
+    /// The texture is stored as hint.resource_path(path)
+    pub fn load_texture_with_hint(
+        &self,
+        path: &str,
+        hint: TextureUsageHint,
+    ) -> Result<Arc<Texture>> {
+        self.textures.load(self, &hint.resource_path(path))
+    }
+
+    /// Loads and stores a texture from the contents of an image file read from reader, E.g; an
+    /// entry in an archive or embedded bytes
+    /// The texture will be stored as name
+    /// If a texture with the name already exists, the existing one will be returned
+    pub fn load_texture_from_reader(
+        &self,
+        reader: &mut dyn Read,
+        name: &str,
+    ) -> Result<Arc<Texture>> {
+        self.textures.load_from_reader(self, reader, name)
+    }
+
     /// path to return a reference to an already loaded texture
     /// Returns None if the texture isn't loaded
     pub fn get_texture(&self, path: &str) -> Option<Arc<Texture>> {
         self.textures.get(path)
     }
 
+    /// Loads a texture like load_texture and returns a handle to it
+    pub fn load_texture_handle(&self, path: &str) -> Result<Handle<Texture>> {
+        self.textures.load_handle(self, path)
+    }
+
+    /// Returns the texture a handle refers to
+    /// Returns None if the texture has been collected
+    pub fn resolve_texture(&self, handle: Handle<Texture>) -> Option<Arc<Texture>> {
+        self.textures.resolve(handle)
+    }
+
+    /// Starts loading a texture on a worker thread and returns its handle
+    /// The texture is available once a call to poll_loads has finished it
+    pub fn load_texture_async(&self, path: &str) -> Handle<Texture> {
+        self.textures.load_async(self, path)
+    }
+
+    /// Returns true if a texture has been loaded, synchronously or asynchronously
+    pub fn is_texture_ready(&self, handle: Handle<Texture>) -> bool {
+        self.textures.is_ready(handle)
+    }
+
     /// Loads and stores a model if it doesn't already exist
     /// The model will be stored as the path name
     /// If a model with the name already exists, the existing one will be returned
@@ -202,6 +834,28 @@ impl ResourceManager {
         self.models.get(path)
     }
 
+    /// Starts loading a model on a worker thread and returns its handle
+    /// The model is available once a call to poll_loads has finished it
+    pub fn load_model_async(&self, path: &str) -> Handle<Model> {
+        self.models.load_async(self, path)
+    }
+
+    /// Returns true if a model has been loaded, synchronously or asynchronously
+    pub fn is_model_ready(&self, handle: Handle<Model>) -> bool {
+        self.models.is_ready(handle)
+    }
+
+    /// Loads a model like load_model and returns a handle to it
+    pub fn load_model_handle(&self, path: &str) -> Result<Handle<Model>> {
+        self.models.load_handle(self, path)
+    }
+
+    /// Returns the model a handle refers to
+    /// Returns None if the model has been collected
+    pub fn resolve_model(&self, handle: Handle<Model>) -> Option<Arc<Model>> {
+        self.models.resolve(handle)
+    }
+
     /// path to return a reference to an already loaded model
     /// Returns None if the model isn't loaded
     pub fn get_pipeline(&self, path: &str) -> Option<Arc<Pipeline>> {
@@ -215,6 +869,44 @@ impl ResourceManager {
         self.pipelines.load(&self, path)
     }
 
+    /// Loads a pipeline like load_pipeline and returns a handle to it
+    pub fn load_pipeline_handle(&self, path: &str) -> Result<Handle<Pipeline>> {
+        self.pipelines.load_handle(self, path)
+    }
+
+    /// Returns the pipeline a handle refers to
+    /// Returns None if the pipeline has been collected
+    pub fn resolve_pipeline(&self, handle: Handle<Pipeline>) -> Option<Arc<Pipeline>> {
+        self.pipelines.resolve(handle)
+    }
+
+    /// Loads and stores a compute pipeline from json if it doesn't already exist
+    /// The compute pipeline will be stored as the path name
+    /// If a compute pipeline with the name already exists, the existing one will be returned
+    pub fn load_compute_pipeline(&self, path: &str) -> Result<Arc<ComputePipeline>> {
+        self.compute_pipelines.load(self, path)
+    }
+
+    /// path to return a reference to an already loaded compute pipeline
+    /// Returns None if the compute pipeline isn't loaded
+    pub fn get_compute_pipeline(&self, path: &str) -> Option<Arc<ComputePipeline>> {
+        self.compute_pipelines.get(path)
+    }
+
+    /// Loads a compute pipeline like load_compute_pipeline and returns a handle to it
+    pub fn load_compute_pipeline_handle(&self, path: &str) -> Result<Handle<ComputePipeline>> {
+        self.compute_pipelines.load_handle(self, path)
+    }
+
+    /// Returns the compute pipeline a handle refers to
+    /// Returns None if the compute pipeline has been collected
+    pub fn resolve_compute_pipeline(
+        &self,
+        handle: Handle<ComputePipeline>,
+    ) -> Option<Arc<ComputePipeline>> {
+        self.compute_pipelines.resolve(handle)
+    }
+
     /// Loads and stores a renderpass from json if it doesn't already exist
     /// The renderpass will be stored as the path name
     /// If a renderpass with the name already exists, the existing one will be returned
@@ -228,6 +920,17 @@ impl ResourceManager {
         self.renderpasses.get(path)
     }
 
+    /// Loads a renderpass like load_renderpass and returns a handle to it
+    pub fn load_renderpass_handle(&self, path: &str) -> Result<Handle<RenderPass>> {
+        self.renderpasses.load_handle(self, path)
+    }
+
+    /// Returns the renderpass a handle refers to
+    /// Returns None if the renderpass has been collected
+    pub fn resolve_renderpass(&self, handle: Handle<RenderPass>) -> Option<Arc<RenderPass>> {
+        self.renderpasses.resolve(handle)
+    }
+
     /// Loads and stores a material from json if it doesn't already exist
     /// The material will be stored as the path name
     /// If a material with the name already exists, the existing one will be returned
@@ -240,6 +943,67 @@ impl ResourceManager {
     pub fn get_material(&self, path: &str) -> Option<Arc<Material>> {
         self.materials.get(path)
     }
+
+    /// Loads a material like load_material and returns a handle to it
+    pub fn load_material_handle(&self, path: &str) -> Result<Handle<Material>> {
+        self.materials.load_handle(self, path)
+    }
+
+    /// Returns the material a handle refers to
+    /// Returns None if the material has been collected
+    pub fn resolve_material(&self, handle: Handle<Material>) -> Option<Arc<Material>> {
+        self.materials.resolve(handle)
+    }
+
+    /// Loads and stores a resource of a user defined type, E.g; audio clips or level data
+    /// Each type is cached separately and garbage collected like the built in resources
+    /// Prefer the typed methods for built in resources, which are cached separately from these
+    pub fn load<T: Resource + 'static>(&self, path: &str) -> Result<Arc<T>> {
+        self.with_custom(|system: &ResourceSystem<T>| system.load(self, path))
+    }
+
+    /// Returns a loaded resource of a user defined type
+    pub fn get<T: Resource + 'static>(&self, path: &str) -> Option<Arc<T>> {
+        self.with_custom(|system: &ResourceSystem<T>| system.get(path))
+    }
+
+    /// Calls f with the resource system of T, creating it if it does not exist
+    /// No lock is held while f runs so that resources can load other resources
+    fn with_custom<T, F, R>(&self, f: F) -> R
+    where
+        T: Resource + 'static,
+        F: FnOnce(&ResourceSystem<T>) -> R,
+    {
+        let id = TypeId::of::<T>();
+        let existing = self.custom.read().unwrap().get(&id).cloned();
+
+        let system = match existing {
+            Some(system) => system,
+            None => Arc::clone(
+                self.custom
+                    .write()
+                    .unwrap()
+                    .entry(id)
+                    .or_insert_with(|| Arc::new(ResourceSystem::<T>::new())),
+            ),
+        };
+
+        let system = system
+            .as_any()
+            .downcast_ref::<ResourceSystem<T>>()
+            .expect("Resource system stored under the wrong type");
+        f(system)
+    }
+
+    /// Submits the uploads of the asynchronous loads whose files have been read on worker threads,
+    /// and stores the resources whose uploads have finished
+    /// The resources are created on the calling thread and uploaded on the transfer queue
+    /// Should be called periodically from the thread owning the graphics context
+    /// Returns the number of resources stored
+    pub fn poll_loads(&self) -> usize {
+        self.textures.poll(self) + self.models.poll(self)
+    }
+
     /// Will place each resource no longer used in a garbage list
     /// The actual resource will get deleted after garbage_cycles cleanup cycles so that it is no longer in use by a pipeline
     /// Should only be called from one thread to avoid thread blocking
@@ -248,7 +1012,12 @@ impl ResourceManager {
         self.models.collect_garbage(garbage_cycles);
         self.renderpasses.collect_garbage(garbage_cycles);
         self.pipelines.collect_garbage(garbage_cycles);
+        self.compute_pipelines.collect_garbage(garbage_cycles);
         self.materials.collect_garbage(garbage_cycles);
+
+        for system in self.custom.read().unwrap().values() {
+            system.collect_garbage(garbage_cycles);
+        }
     }
 
     pub fn recreate(&self) -> Result<()> {
@@ -258,50 +1027,88 @@ impl ResourceManager {
         let color_format = swapchain.format();
         let depth_format = swapchain.depth_format();
 
-        // Separate into separate scopes to drop Write lock
-        {
-            let mut renderpasses = self.renderpasses.resources.write().unwrap();
+        // Pipelines use dynamic viewports and only depend on the swapchain through the renderpass
+        // formats, and materials through the image count
+        // Nothing needs to be recreated if those are unchanged, E.g; when resizing
+        let formats_unchanged = self.renderpasses.all(|renderpass| {
+            renderpass.color_format() == color_format && renderpass.depth_format() == depth_format
+        });
 
-            let new_renderpasses: HashMap<_, _> = renderpasses
-                .iter()
-                .map(|(k, v)| match v.recreate(color_format, depth_format) {
-                    Ok(v) => Ok((k.to_owned(), Arc::new(v))),
-                    Err(e) => Err(e),
-                })
-                .collect::<Result<HashMap<_, _>>>()?;
+        let image_count_unchanged = self
+            .materials
+            .all(|material| material.descriptor_sets().len() == swapchain.image_count());
 
-            // Replace the old with the new
-            let _ = std::mem::replace(&mut *renderpasses, new_renderpasses);
+        if formats_unchanged && image_count_unchanged {
+            return Ok(());
         }
-        {
-            let mut pipelines = self.pipelines.resources.write().unwrap();
-            // Now recreate the pipelines
-            // They will query self for the renderpasses which are now replaced
-            let new_pipelines: HashMap<_, _> = pipelines
-                .iter()
-                .map(|(k, v)| match v.recreate(&self) {
-                    Ok(v) => Ok((k.to_owned(), Arc::new(v))),
-                    Err(e) => Err(e),
-                })
-                .collect::<Result<HashMap<_, _>>>()?;
 
-            let _ = std::mem::replace(&mut *pipelines, new_pipelines);
+        self.renderpasses
+            .recreate(|renderpass| renderpass.recreate(color_format, depth_format))?;
+
+        // Now recreate the pipelines and materials
+        // They will query self for the renderpasses and pipelines which are now replaced
+        self.pipelines
+            .recreate(|pipeline| pipeline.recreate(&self))?;
+        self.materials
+            .recreate(|material| material.recreate(&self))?;
+        Ok(())
+    }
+
+    /// Enables hot reloading of resources in reload_changed
+    /// The modification times of the files of all resources are recorded when loaded
+    pub fn watch(&self) {
+        self.watching.store(true, Ordering::Relaxed);
+    }
+
+    /// Reloads all resources whose files have changed on disk since they were loaded
+    /// Resources referencing a reloaded resource, E.g; materials using a changed texture, are
+    /// reloaded as well
+    /// Does nothing unless watch has been called
+    /// Should be called periodically from the main loop, waits for the device to be idle if
+    /// anything changed
+    /// Returns the number of reloaded resources
+    pub fn reload_changed(&self) -> usize {
+        if !self.watching.load(Ordering::Relaxed) {
+            return 0;
         }
+
+        let textures = self.textures.changed();
+        let models = self.models.changed();
+        let renderpasses = self.renderpasses.changed();
+        let mut pipelines = self.pipelines.changed();
+        let compute_pipelines = self.compute_pipelines.changed();
+        let mut materials = self.materials.changed();
+
+        if textures.is_empty()
+            && models.is_empty()
+            && renderpasses.is_empty()
+            && pipelines.is_empty()
+            && compute_pipelines.is_empty()
+            && materials.is_empty()
         {
-            let mut materials = self.materials.resources.write().unwrap();
-            // Now recreate the pipelines
-            // They will query self for the renderpasses which are now replaced
-            let new_materials: HashMap<_, _> = materials
-                .iter()
-                .map(|(k, v)| match v.recreate(&self) {
-                    Ok(v) => Ok((k.to_owned(), Arc::new(v))),
-                    Err(e) => Err(e),
-                })
-                .collect::<Result<HashMap<_, _>>>()?;
+            return 0;
+        }
 
-            let _ = std::mem::replace(&mut *materials, new_materials);
+        if let Err(e) = unsafe { self.context.device.device_wait_idle() } {
+            error!("Failed to wait for device '{}'", e);
+            return 0;
         }
-        Ok(())
+
+        // Dependents hold references to the old resources and are reloaded as well
+        if !renderpasses.is_empty() {
+            pipelines = self.pipelines.paths();
+        }
+
+        if !textures.is_empty() || !pipelines.is_empty() {
+            materials = self.materials.paths();
+        }
+
+        self.textures.reload(self, &textures)
+            + self.models.reload(self, &models)
+            + self.renderpasses.reload(self, &renderpasses)
+            + self.pipelines.reload(self, &pipelines)
+            + self.compute_pipelines.reload(self, &compute_pipelines)
+            + self.materials.reload(self, &materials)
     }
 
     /// Returns a descripctive status about the resources currently managed
@@ -311,8 +1118,13 @@ impl ResourceManager {
         result.extend(self.models.info());
         result.extend(self.renderpasses.info());
         result.extend(self.pipelines.info());
+        result.extend(self.compute_pipelines.info());
         result.extend(self.materials.info());
 
+        for system in self.custom.read().unwrap().values() {
+            result.extend(system.info());
+        }
+
         result
     }
 }
@@ -320,7 +1132,254 @@ impl ResourceManager {
 impl Drop for ResourceManager {
     fn drop(&mut self) {
         info!("Dropping resource manager");
-        unsafe { self.context.device.device_wait_idle().unwrap() }
         // Drop all other values
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn load_workers_are_bounded() {
+        let workers = LoadWorkers::new();
+        let (sender, receiver) = mpsc::channel();
+
+        // A panicking job does not stop its worker
+        workers.execute(Box::new(|| panic!("Failed to read")));
+
+        for index in 0..32 {
+            let sender = sender.clone();
+            workers.execute(Box::new(move || {
+                let name = thread::current().name().map(str::to_owned);
+                sender.send((index, name)).unwrap();
+            }));
+        }
+        drop(sender);
+
+        let results: Vec<_> = receiver.iter().collect();
+        assert_eq!(results.len(), 32);
+
+        let threads: HashSet<_> = results.into_iter().map(|(_, name)| name).collect();
+        assert!(threads.len() <= MAX_LOAD_WORKERS);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn load_texture_from_reader() {
+        let context = vulkan::test_context();
+
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+        let bytes = std::fs::read("../sandbox/data/textures/grid.png").unwrap();
+
+        let texture = resourcemanager
+            .load_texture_from_reader(&mut std::io::Cursor::new(bytes), "embedded/grid.png")
+            .unwrap();
+        let stored = resourcemanager.get_texture("embedded/grid.png").unwrap();
+        assert!(Arc::ptr_eq(&texture, &stored));
+
+        assert!(resourcemanager
+            .load_texture_from_reader(&mut std::io::Cursor::new(Vec::new()), "empty.png")
+            .is_err());
+    }
+
+    /// A user defined resource
+    struct LevelData {
+        name: String,
+    }
+
+    impl Resource for LevelData {
+        fn load(_: &ResourceManager, path: &str) -> Result<Self> {
+            Ok(LevelData {
+                name: path.to_owned(),
+            })
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn load_custom_resource() {
+        let context = vulkan::test_context();
+
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+        assert!(resourcemanager.get::<LevelData>("level_1").is_none());
+
+        let first = resourcemanager.load::<LevelData>("level_1").unwrap();
+        let second = resourcemanager.load::<LevelData>("level_1").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.name, "level_1");
+
+        assert_eq!(resourcemanager.info().len(), 1);
+
+        // Collected once unused
+        drop((first, second));
+        resourcemanager.collect_garbage(1);
+        assert!(resourcemanager.get::<LevelData>("level_1").is_none());
+    }
+
+    #[test]
+    fn handles() {
+        let system = ResourceSystem::<LevelData>::new();
+        let first = Arc::new(LevelData {
+            name: "level_1".to_owned(),
+        });
+
+        let handle = system.store("level_1", Arc::clone(&first));
+        assert_eq!(system.store("level_1", Arc::clone(&first)), handle);
+        assert_ne!(
+            system.store(
+                "level_2",
+                Arc::new(LevelData {
+                    name: "level_2".to_owned()
+                })
+            ),
+            handle
+        );
+        assert!(Arc::ptr_eq(&system.resolve(handle).unwrap(), &first));
+
+        // A stale handle resolves to None once the resource is collected
+        drop(first);
+        system.collect_garbage(1);
+        assert!(system.resolve(handle).is_none());
+        assert!(!system.is_ready(handle));
+
+        // Loading the path again gives the same handle
+        let second = Arc::new(LevelData {
+            name: "level_1".to_owned(),
+        });
+        assert_eq!(system.store("level_1", Arc::clone(&second)), handle);
+        assert!(Arc::ptr_eq(&system.resolve(handle).unwrap(), &second));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn texture_handles() {
+        let context = vulkan::test_context();
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+
+        let path = "../sandbox/data/textures/grid.png";
+        let handle = resourcemanager.load_texture_handle(path).unwrap();
+        assert_eq!(resourcemanager.load_texture_handle(path).unwrap(), handle);
+
+        let texture = resourcemanager.load_texture(path).unwrap();
+        assert!(Arc::ptr_eq(
+            &resourcemanager.resolve_texture(handle).unwrap(),
+            &texture
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn load_textures_async() {
+        let context = vulkan::test_context();
+
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+        let paths = [
+            "../sandbox/data/textures/grid.png",
+            "../sandbox/data/textures/concrete.png",
+            "../sandbox/data/textures/color_grid.png",
+        ];
+
+        // Loading a pending path again returns the same handle without reading it again
+        let handles: Vec<_> = paths
+            .iter()
+            .chain(paths.iter())
+            .map(|path| resourcemanager.load_texture_async(path))
+            .collect();
+        assert!(handles[..3] == handles[3..]);
+
+        let start = Instant::now();
+        let mut stored = 0;
+        while handles
+            .iter()
+            .any(|handle| !resourcemanager.is_texture_ready(*handle))
+        {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            stored += resourcemanager.poll_loads();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(stored, 3);
+        for path in &paths {
+            assert!(!resourcemanager.textures.is_pending(path));
+            let count = resourcemanager
+                .info()
+                .iter()
+                .filter(|info| info.name() == *path)
+                .count();
+            assert_eq!(count, 1);
+        }
+    }
+
+    /// Writes contents to a file in the temp directory and returns its path
+    fn write_temp(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    /// Sets the modification time of a file to later than any previous write
+    fn touch(path: &str) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+    }
+
+    #[test]
+    fn changed_files() {
+        let system = ResourceSystem::<LevelData>::new();
+        let paths = [
+            write_temp("sprocket_changed_level_1.json", "{}"),
+            write_temp("sprocket_changed_level_2.json", "{}"),
+        ];
+
+        for path in &paths {
+            system.store(path, Arc::new(LevelData { name: path.clone() }));
+        }
+        assert!(system.changed().is_empty());
+
+        touch(&paths[1]);
+        assert_eq!(system.changed(), vec![paths[1].clone()]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn reload_changed() {
+        let context = vulkan::test_context();
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+
+        let shader = vulkan::tests::write_empty_compute_shader("sprocket_reload.comp.spv");
+        let spec = write_temp(
+            "sprocket_reload.json",
+            &format!(
+                r#"{{ "shader": "{}", "layouts": [], "push_constants": [] }}"#,
+                shader
+            ),
+        );
+
+        let pipeline = resourcemanager.load_compute_pipeline(&spec).unwrap();
+        let texture = resourcemanager
+            .load_texture("../sandbox/data/textures/grid.png")
+            .unwrap();
+
+        // Changes are ignored until watched
+        touch(&spec);
+        assert_eq!(resourcemanager.reload_changed(), 0);
+
+        resourcemanager.watch();
+        assert_eq!(resourcemanager.reload_changed(), 1);
+
+        let reloaded = resourcemanager.get_compute_pipeline(&spec).unwrap();
+        assert!(!Arc::ptr_eq(&pipeline, &reloaded));
+        let unchanged = resourcemanager
+            .get_texture("../sandbox/data/textures/grid.png")
+            .unwrap();
+        assert!(Arc::ptr_eq(&texture, &unchanged));
+
+        // The new modification time is recorded
+        assert_eq!(resourcemanager.reload_changed(), 0);
+    }
+}
@@ -1,29 +1,98 @@
-use ash::version::DeviceV1_0;
+use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk;
+use serde::{Deserialize, Serialize};
 
-use super::Result;
+use super::enums::{AddressMode, Filter, MipmapMode};
+use super::{Result, VulkanContext};
+
+/// Describes how a texture is sampled
+/// Fields left out when deserializing use the same values as the default sampler
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SamplerSpec {
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmap_mode: MipmapMode,
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub address_mode_w: AddressMode,
+    /// The maximum anisotropy level, None disables anisotropic filtering
+    /// Clamped to what the device supports
+    pub anisotropy: Option<f32>,
+}
+
+impl Default for SamplerSpec {
+    fn default() -> Self {
+        SamplerSpec {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmap_mode: MipmapMode::Linear,
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            anisotropy: Some(16.0),
+        }
+    }
+}
+
+impl SamplerSpec {
+    /// Returns the default sampler for cubemaps
+    /// Clamps to the edge to avoid seams between faces
+    pub fn cubemap() -> SamplerSpec {
+        SamplerSpec {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            ..Default::default()
+        }
+    }
+}
 
 pub struct Sampler {
     device: ash::Device,
     sampler: vk::Sampler,
+    spec: SamplerSpec,
 }
 
 impl Sampler {
+    /// Creates a sampler with the default spec
     pub fn new(device: &ash::Device) -> Result<Sampler> {
+        Self::create(device, SamplerSpec::default())
+    }
+
+    /// Creates a sampler from a spec
+    /// The anisotropy is clamped to the limit of the physical device
+    pub fn with_spec(context: &VulkanContext, spec: &SamplerSpec) -> Result<Sampler> {
+        let limits = unsafe {
+            context
+                .instance
+                .get_physical_device_properties(context.physical_device)
+                .limits
+        };
+
+        let mut spec = spec.clone();
+        spec.anisotropy = spec
+            .anisotropy
+            .map(|anisotropy| anisotropy.min(limits.max_sampler_anisotropy));
+
+        Self::create(&context.device, spec)
+    }
+
+    fn create(device: &ash::Device, spec: SamplerSpec) -> Result<Sampler> {
         let sampler_info = vk::SamplerCreateInfo {
             s_type: vk::StructureType::SAMPLER_CREATE_INFO,
-            mag_filter: vk::Filter::LINEAR,
-            min_filter: vk::Filter::LINEAR,
-            address_mode_u: vk::SamplerAddressMode::REPEAT,
-            address_mode_v: vk::SamplerAddressMode::REPEAT,
-            address_mode_w: vk::SamplerAddressMode::REPEAT,
-            anisotropy_enable: vk::TRUE,
-            max_anisotropy: 16.0,
+            mag_filter: spec.mag_filter.into(),
+            min_filter: spec.min_filter.into(),
+            address_mode_u: spec.address_mode_u.into(),
+            address_mode_v: spec.address_mode_v.into(),
+            address_mode_w: spec.address_mode_w.into(),
+            anisotropy_enable: spec.anisotropy.is_some() as vk::Bool32,
+            max_anisotropy: spec.anisotropy.unwrap_or(1.0),
             border_color: vk::BorderColor::INT_OPAQUE_BLACK,
             unnormalized_coordinates: vk::FALSE,
             compare_enable: vk::FALSE,
             compare_op: vk::CompareOp::ALWAYS,
-            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            mipmap_mode: spec.mipmap_mode.into(),
             mip_lod_bias: 0.0,
             min_lod: 0.0,
             max_lod: 0.0,
@@ -36,12 +105,19 @@ impl Sampler {
         Ok(Sampler {
             device: device.clone(),
             sampler,
+            spec,
         })
     }
 
     pub fn vk(&self) -> vk::Sampler {
         self.sampler
     }
+
+    /// Returns the spec the sampler was created with
+    /// The anisotropy is the clamped value actually used
+    pub fn spec(&self) -> &SamplerSpec {
+        &self.spec
+    }
 }
 
 impl Drop for Sampler {
@@ -49,3 +125,29 @@ impl Drop for Sampler {
         unsafe { self.device.destroy_sampler(self.sampler, None) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn with_spec() {
+        let context = vulkan::test_context();
+
+        let spec = SamplerSpec {
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            anisotropy: Some(f32::MAX),
+            ..SamplerSpec::cubemap()
+        };
+        let sampler = Sampler::with_spec(&context, &spec).unwrap();
+
+        // Created with the spec apart from the anisotropy clamped to the device limit
+        assert_eq!(sampler.spec().address_mode_u, AddressMode::ClampToEdge);
+        assert_eq!(sampler.spec().mag_filter, Filter::Nearest);
+        assert!(sampler.spec().anisotropy.unwrap() < f32::MAX);
+        assert_ne!(sampler.vk(), vk::Sampler::null());
+    }
+}
@@ -0,0 +1,145 @@
+use super::buffer;
+use super::Transfer;
+use super::VkAllocator;
+use super::{Error, Result};
+use ash::vk;
+use std::sync::Arc;
+
+/// A buffer of arbitrary data read and written by shaders
+/// Used for compute and per instance data too large for uniform buffers
+pub struct StorageBuffer {
+    allocator: VkAllocator,
+    buffer: vk::Buffer,
+    memory: vk_mem::Allocation,
+    size: vk::DeviceSize,
+    /// True if the buffer can be written to directly from the host
+    host_visible: bool,
+}
+
+impl StorageBuffer {
+    /// Creates a host visible storage buffer of size bytes
+    /// The contents are written directly with write
+    pub fn new(allocator: &VkAllocator, size: u64) -> Result<StorageBuffer> {
+        let (buffer, memory) = Self::allocate(allocator, size, vk_mem::MemoryUsage::CpuToGpu)?;
+
+        Ok(StorageBuffer {
+            allocator: Arc::clone(allocator),
+            buffer,
+            memory,
+            size,
+            host_visible: true,
+        })
+    }
+
+    /// Creates a device local storage buffer filled with data through a staging buffer
+    /// The contents can not be written from the host afterwards, only by shaders
+    /// The buffer is owned by the graphics queue family after the upload
+    pub fn new_gpu_only<T>(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        data: &[T],
+    ) -> Result<StorageBuffer> {
+        let size = std::mem::size_of_val(data).max(1) as u64;
+
+        let (staging_buffer, staging_memory, staging_info) =
+            buffer::create_staging(allocator, size)?;
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                staging_info.get_mapped_data(),
+                std::mem::size_of_val(data),
+            );
+        }
+
+        let (buffer, memory) = Self::allocate(allocator, size, vk_mem::MemoryUsage::GpuOnly)?;
+
+        buffer::copy(device, transfer, staging_buffer, buffer, size)?;
+
+        buffer::destroy_staging(allocator, transfer, staging_buffer, staging_memory)?;
+
+        Ok(StorageBuffer {
+            allocator: Arc::clone(allocator),
+            buffer,
+            memory,
+            size,
+            host_visible: false,
+        })
+    }
+
+    fn allocate(
+        allocator: &VkAllocator,
+        size: vk::DeviceSize,
+        usage: vk_mem::MemoryUsage,
+    ) -> Result<(vk::Buffer, vk_mem::Allocation)> {
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
+            &vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(
+                    vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::TRANSFER_DST
+                        | vk::BufferUsageFlags::TRANSFER_SRC,
+                )
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build(),
+            &vk_mem::AllocationCreateInfo {
+                usage,
+                ..Default::default()
+            },
+        )?;
+
+        Ok((buffer, memory))
+    }
+
+    /// Writes data offset bytes into the buffer
+    /// Returns an error if the buffer is not host visible or the data does not fit
+    pub fn write<T>(&self, data: &[T], offset: u64) -> Result<()> {
+        if !self.host_visible {
+            return Err(Error::NotHostVisible);
+        }
+
+        let size = std::mem::size_of_val(data);
+        if offset + size as u64 > self.size {
+            return Err(Error::BufferOverflow(
+                offset as usize + size,
+                self.size as usize,
+            ));
+        }
+
+        let mapped: *mut u8 = self.allocator.lock().unwrap().map_memory(&self.memory)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                mapped.offset(offset as isize),
+                size,
+            )
+        }
+        self.allocator.lock().unwrap().unmap_memory(&self.memory)?;
+
+        Ok(())
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Returns the size in bytes of the buffer
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn host_visible(&self) -> bool {
+        self.host_visible
+    }
+}
+
+impl Drop for StorageBuffer {
+    fn drop(&mut self) {
+        self.allocator
+            .lock()
+            .unwrap()
+            .destroy_buffer(self.buffer, &self.memory)
+            .expect("Failed to free vulkan memory");
+    }
+}
@@ -1,10 +1,63 @@
-use super::{Texture, VkAllocator};
+use super::{CommandBuffer, Texture, VkAllocator, VulkanContext};
 use crate::graphics::Extent2D;
 use crate::*;
 use ash::vk;
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
 
-use super::Result;
+use super::{Error, Result};
+
+/// The formats the depth image may have in order of preference
+const DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+/// Specifies how images are presented to the screen
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum PresentMode {
+    /// Waits for vertical blank, vsync on
+    /// Always supported
+    Fifo,
+    /// Replaces the queued image with newer ones, no tearing and low latency
+    Mailbox,
+    /// Presents immediately, may tear
+    Immediate,
+    /// Waits for vertical blank unless the frame is late, in which case it may tear
+    FifoRelaxed,
+}
+
+/// The present modes falling back to if the requested mode is not supported, in order of
+/// preference
+/// FIFO is always supported and immediate is only used if the surface somehow lacks it
+const FALLBACK_PRESENT_MODES: [vk::PresentModeKHR; 4] = [
+    vk::PresentModeKHR::MAILBOX,
+    vk::PresentModeKHR::FIFO_RELAXED,
+    vk::PresentModeKHR::FIFO,
+    vk::PresentModeKHR::IMMEDIATE,
+];
+
+impl From<PresentMode> for vk::PresentModeKHR {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => Self::FIFO,
+            PresentMode::Mailbox => Self::MAILBOX,
+            PresentMode::Immediate => Self::IMMEDIATE,
+            PresentMode::FifoRelaxed => Self::FIFO_RELAXED,
+        }
+    }
+}
+
+/// The settings a swapchain is created with
+#[derive(Clone, Copy)]
+pub struct SwapchainSpec {
+    /// The size of the images, used if the surface does not dictate one
+    pub extent: Extent2D,
+    pub present_mode: PresentMode,
+    /// The number of images requested, clamped to the limits of the surface
+    pub image_count: u32,
+}
 
 pub struct Swapchain {
     swapchain: vk::SwapchainKHR,
@@ -13,28 +66,39 @@ pub struct Swapchain {
     depth_image: Texture,
     format: vk::Format,
     extent: Extent2D,
+    /// True if the images can be copied from, required by record_capture
+    readable: bool,
 }
 
 impl Swapchain {
+    /// Creates a swapchain for surface with the image count clamped to the limits of the surface
     pub fn new(
-        instance: &ash::Instance,
-        physical_device: &vk::PhysicalDevice,
-        device: &ash::Device,
-        allocator: &VkAllocator,
-        surface_loader: &ash::extensions::khr::Surface,
+        context: &VulkanContext,
         surface: &vk::SurfaceKHR,
-        queue_families: &graphics::vulkan::QueueFamilies,
-        extent: Extent2D,
+        spec: SwapchainSpec,
     ) -> Result<Swapchain> {
+        let instance = &context.instance;
+        let device = &context.device;
+        let queue_families = &context.queue_families;
+
         unsafe {
             let (capabilities, formats, present_modes) =
-                Self::query_support(physical_device, surface_loader, surface)?;
+                Self::query_support(&context.physical_device, &context.surface_loader, surface)?;
 
             let format = Self::pick_format(formats);
-            let present_mode = Self::pick_present_mode(present_modes);
-            let extent = Self::pick_extent(&capabilities, extent);
-
-            let min_image_count = 3;
+            let present_mode = Self::pick_present_mode(&present_modes, spec.present_mode);
+            let extent = Self::pick_extent(&capabilities, spec.extent);
+            let min_image_count = Self::pick_image_count(&capabilities, spec.image_count);
+
+            // Allow copying from the presented images for screenshots where supported
+            let readable = capabilities
+                .supported_usage_flags
+                .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+            let image_usage = if readable {
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+            } else {
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+            };
 
             let swapchain_loader = ash::extensions::khr::Swapchain::new(instance, device);
 
@@ -44,7 +108,7 @@ impl Swapchain {
                 .image_color_space(format.color_space)
                 .image_format(format.format)
                 .image_extent(extent)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                .image_usage(image_usage)
                 .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
                 .pre_transform(capabilities.current_transform)
                 .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -81,11 +145,20 @@ impl Swapchain {
                     extent.into(),
                     image,
                     format.format,
+                    image_usage,
                     vk::ImageLayout::UNDEFINED,
                 )?)
             }
 
-            let depth_image = Texture::new_depth(allocator, device, extent.into())?;
+            let depth_format = super::find_supported_format(
+                instance,
+                context.physical_device,
+                &DEPTH_FORMATS,
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+            )?;
+            let depth_image =
+                Texture::new_depth(&context.allocator, device, depth_format, extent.into())?;
 
             Ok(Swapchain {
                 swapchain,
@@ -94,6 +167,7 @@ impl Swapchain {
                 depth_image,
                 format: format.format,
                 extent: extent.into(),
+                readable,
             })
         }
     }
@@ -117,15 +191,29 @@ impl Swapchain {
         formats[0]
     }
 
-    fn pick_present_mode(present_modes: Vec<vk::PresentModeKHR>) -> vk::PresentModeKHR {
-        for mode in &present_modes {
-            if *mode == vk::PresentModeKHR::MAILBOX {
-                info!("Choosing MAILBOX present mode");
-                return *mode;
-            }
+    /// Picks the requested present mode if supported, otherwise the first supported of
+    /// FALLBACK_PRESENT_MODES
+    fn pick_present_mode(
+        present_modes: &[vk::PresentModeKHR],
+        requested: PresentMode,
+    ) -> vk::PresentModeKHR {
+        let requested = requested.into();
+        if present_modes.contains(&requested) {
+            info!("Choosing {:?} present mode", requested);
+            return requested;
         }
-        info!("Choosing IMMEDIATE present mode");
-        vk::PresentModeKHR::IMMEDIATE
+
+        let fallback = FALLBACK_PRESENT_MODES
+            .iter()
+            .cloned()
+            .find(|mode| present_modes.contains(mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO);
+
+        warn!(
+            "Present mode {:?} is not supported, falling back to {:?}",
+            requested, fallback
+        );
+        fallback
     }
 
     fn pick_extent(capabilities: &vk::SurfaceCapabilitiesKHR, extent: Extent2D) -> vk::Extent2D {
@@ -145,6 +233,20 @@ impl Swapchain {
         }
     }
 
+    /// Clamps the requested image count to the limits of the surface
+    fn pick_image_count(capabilities: &vk::SurfaceCapabilitiesKHR, requested: u32) -> u32 {
+        // A max image count of 0 means the surface has no limit
+        let max_image_count = match capabilities.max_image_count {
+            0 => u32::MAX,
+            count => count,
+        };
+
+        min(
+            max(requested, capabilities.min_image_count),
+            max_image_count,
+        )
+    }
+
     pub fn image_count(&self) -> usize {
         self.images.len()
     }
@@ -161,6 +263,86 @@ impl Swapchain {
         &self.depth_image
     }
 
+    /// Records copying the image at index into a host visible buffer
+    /// Needs to be recorded after the image has been rendered to and is in PRESENT_SRC_KHR layout,
+    /// and submitted before the image is presented
+    /// The image is given back to presentation in the same layout
+    pub fn record_capture(
+        &self,
+        allocator: &VkAllocator,
+        commandbuffer: &CommandBuffer,
+        index: usize,
+    ) -> Result<FrameCapture> {
+        if !self.readable {
+            return Err(Error::MissingFeature("swapchain transfer source usage"));
+        }
+
+        let capture = FrameCapture::new(allocator, self.extent, self.format)?;
+
+        let image = self.images[index].image();
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+                .build()
+        };
+
+        // Wait for the renderpass to finish writing the image
+        commandbuffer.pipeline_barrier(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            &[],
+            &[],
+            &[barrier(
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                vk::AccessFlags::TRANSFER_READ,
+            )],
+        );
+
+        commandbuffer.copy_image_to_buffer(
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            capture.buffer,
+            self.extent,
+            vk::ImageAspectFlags::COLOR,
+        );
+
+        // Make the copy visible to the host and give the image back to presentation
+        commandbuffer.pipeline_barrier(
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            &[vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ)
+                .build()],
+            &[],
+            &[barrier(
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::AccessFlags::empty(),
+            )],
+        );
+
+        Ok(capture)
+    }
+
     /// Returns the index to the next available image in the swapchain
     pub fn acquire_next_image(&self, semaphore: &vk::Semaphore) -> Result<(u32, bool)> {
         unsafe {
@@ -229,3 +411,142 @@ impl Drop for Swapchain {
         };
     }
 }
+
+/// A swapchain image copied to host memory by the commands recorded with
+/// Swapchain::record_capture
+pub struct FrameCapture {
+    allocator: VkAllocator,
+    buffer: vk::Buffer,
+    memory: vk_mem::Allocation,
+    info: vk_mem::AllocationInfo,
+    extent: Extent2D,
+    format: vk::Format,
+}
+
+impl FrameCapture {
+    fn new(allocator: &VkAllocator, extent: Extent2D, format: vk::Format) -> Result<FrameCapture> {
+        match format {
+            vk::Format::B8G8R8A8_SRGB
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::R8G8B8A8_UNORM => {}
+            format => return Err(Error::UnsupportedFormat(format)),
+        };
+
+        let size = extent.width as u64 * extent.height as u64 * 4;
+        let (buffer, memory, info) = allocator.lock().unwrap().create_buffer(
+            &vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build(),
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::GpuToCpu,
+                flags: vk_mem::AllocationCreateFlags::MAPPED,
+                ..Default::default()
+            },
+        )?;
+
+        Ok(FrameCapture {
+            allocator: allocator.clone(),
+            buffer,
+            memory,
+            info,
+            extent,
+            format,
+        })
+    }
+
+    /// Returns the size of the captured image
+    pub fn extent(&self) -> Extent2D {
+        self.extent
+    }
+
+    /// Returns the captured image as tightly packed rgba8 pixels, row by row from the top
+    /// The commands the capture was recorded into need to have finished executing
+    /// The bytes are copied without conversion, so an sRGB swapchain gives sRGB encoded pixels as
+    /// expected by image files
+    pub fn pixels(&self) -> Result<Vec<u8>> {
+        let size = self.extent.width as usize * self.extent.height as usize * 4;
+        self.allocator
+            .lock()
+            .unwrap()
+            .invalidate_allocation(&self.memory, 0, size)?;
+
+        let mut pixels = vec![0; size];
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.info.get_mapped_data(), pixels.as_mut_ptr(), size);
+        }
+
+        match self.format {
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(pixels)
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        iferr!(
+            "Failed to destroy capture buffer",
+            self.allocator
+                .lock()
+                .unwrap()
+                .destroy_buffer(self.buffer, &self.memory)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_image_count() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 2,
+            ..Default::default()
+        };
+        assert_eq!(Swapchain::pick_image_count(&capabilities, 3), 2);
+        assert_eq!(Swapchain::pick_image_count(&capabilities, 1), 2);
+
+        // No upper limit
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(Swapchain::pick_image_count(&capabilities, 8), 8);
+    }
+
+    #[test]
+    fn pick_present_mode() {
+        use vk::PresentModeKHR as Mode;
+        let pick = Swapchain::pick_present_mode;
+
+        let all = [
+            Mode::IMMEDIATE,
+            Mode::FIFO,
+            Mode::FIFO_RELAXED,
+            Mode::MAILBOX,
+        ];
+        assert_eq!(pick(&all, PresentMode::Immediate), Mode::IMMEDIATE);
+        assert_eq!(pick(&all, PresentMode::Fifo), Mode::FIFO);
+
+        // Falls back to mailbox, then FIFO relaxed, then FIFO, then immediate
+        assert_eq!(pick(&all[..3], PresentMode::Mailbox), Mode::FIFO_RELAXED);
+        assert_eq!(pick(&all[..2], PresentMode::Mailbox), Mode::FIFO);
+        assert_eq!(pick(&all[..1], PresentMode::Mailbox), Mode::IMMEDIATE);
+        assert_eq!(pick(&all[1..], PresentMode::Immediate), Mode::MAILBOX);
+
+        // FIFO is assumed if nothing is reported
+        assert_eq!(pick(&[], PresentMode::Mailbox), Mode::FIFO);
+    }
+}
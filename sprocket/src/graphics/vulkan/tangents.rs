@@ -0,0 +1,172 @@
+use super::Vertex;
+use crate::math::{Vec3, Vec4};
+
+/// Texture coordinate determinants smaller than this are treated as degenerate
+const UV_EPSILON: f32 = 1e-8;
+
+/// Generates smooth normals for vertices without one
+/// Each triangle contributes its area weighted face normal to its corners
+/// Vertices which already have a normal are kept
+pub fn generate_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::zero(); vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        let p0 = vertices[i0].position;
+        let e1 = vertices[i1].position - p0;
+        let e2 = vertices[i2].position - p0;
+
+        let normal = Vec3::cross(&e1, &e2);
+        normals[i0] += normal;
+        normals[i1] += normal;
+        normals[i2] += normal;
+    }
+
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        if vertex.normal.try_norm().is_none() {
+            vertex.normal = normal.try_norm().unwrap_or_else(Vec3::up);
+        }
+    }
+}
+
+/// Generates tangents from the positions and texture coordinates using Lengyel's method
+/// The tangents are orthogonalized against the vertex normals and w stores the handedness of the
+/// bitangent, which is reconstructed as cross(normal, tangent) * w
+/// Triangles with degenerate texture coordinates are skipped, and vertices without any usable
+/// triangle get an arbitrary tangent perpendicular to the normal
+/// Should be called after the vertices have been deduplicated and given normals
+pub fn generate_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::zero(); vertices.len()];
+    let mut bitangents = vec![Vec3::zero(); vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        let (v0, v1, v2) = (&vertices[i0], &vertices[i1], &vertices[i2]);
+
+        let e1 = v1.position - v0.position;
+        let e2 = v2.position - v0.position;
+        let duv1 = v1.uv - v0.uv;
+        let duv2 = v2.uv - v0.uv;
+
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < UV_EPSILON {
+            continue;
+        }
+
+        let r = 1.0 / det;
+        let tangent = (e1 * duv2.y - e2 * duv1.y) * r;
+        let bitangent = (e2 * duv1.x - e1 * duv2.x) * r;
+
+        for &i in &[i0, i1, i2] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = vertex.normal;
+
+        // Gram-Schmidt orthogonalize
+        let tangent = tangents[i] - normal * Vec3::dot(&normal, &tangents[i]);
+        let tangent = match tangent.try_norm() {
+            Some(tangent) => tangent,
+            None => perpendicular(normal),
+        };
+
+        let handedness = if Vec3::dot(&Vec3::cross(&normal, &tangent), &bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        vertex.tangent = Vec4::from_vec3(tangent, handedness);
+    }
+}
+
+/// Returns a unit vector perpendicular to normal
+/// Falls back to the x axis if normal is zero
+fn perpendicular(normal: Vec3) -> Vec3 {
+    // Cross with the axis least aligned with the normal for stability
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::right()
+    } else {
+        Vec3::up()
+    };
+
+    Vec3::cross(&normal, &axis)
+        .try_norm()
+        .unwrap_or_else(Vec3::right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec2;
+
+    const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    /// A quad in the xy plane with the given texture coordinates for each corner
+    fn quad(uvs: [(f32, f32); 4]) -> Vec<Vertex> {
+        let positions = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        positions
+            .iter()
+            .zip(uvs.iter())
+            .map(|(&(x, y), &(u, v))| Vertex::new(Vec3::new(x, y, 0.0), Vec2::new(u, v)))
+            .collect()
+    }
+
+    #[test]
+    fn uv_mapped_quad() {
+        let mut vertices = quad([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        generate_normals(&mut vertices, &QUAD_INDICES);
+        generate_tangents(&mut vertices, &QUAD_INDICES);
+
+        for vertex in &vertices {
+            assert!(vertex.normal.approx_eq(&Vec3::forward(), 1e-6));
+
+            // The tangent points along +U and is orthogonal to the normal
+            let tangent = vertex.tangent.xyz();
+            assert!(tangent.approx_eq(&Vec3::right(), 1e-6));
+            assert!(Vec3::dot(&tangent, &vertex.normal).abs() < 1e-6);
+
+            // The reconstructed bitangent points along +V
+            let bitangent = Vec3::cross(&vertex.normal, &tangent) * vertex.tangent.w;
+            assert!(bitangent.approx_eq(&Vec3::up(), 1e-6));
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs() {
+        // V decreases along +y
+        let mut vertices = quad([(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)]);
+        generate_normals(&mut vertices, &QUAD_INDICES);
+        generate_tangents(&mut vertices, &QUAD_INDICES);
+
+        for vertex in &vertices {
+            assert!(vertex.tangent.xyz().approx_eq(&Vec3::right(), 1e-6));
+            assert_eq!(vertex.tangent.w, -1.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs() {
+        let mut vertices = quad([(0.5, 0.5); 4]);
+        generate_normals(&mut vertices, &QUAD_INDICES);
+        generate_tangents(&mut vertices, &QUAD_INDICES);
+
+        // Falls back to a unit tangent perpendicular to the normal
+        for vertex in &vertices {
+            let tangent = vertex.tangent.xyz();
+            assert!((tangent.mag() - 1.0).abs() < 1e-6);
+            assert!(Vec3::dot(&tangent, &vertex.normal).abs() < 1e-6);
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use super::{buffer, resources::Resource, CommandBuffer, CommandPool, Error, Result, VkAllocator};
+use super::{
+    buffer,
+    resources::{AsyncResource, Resource},
+    Error, Result, Transfer, VkAllocator, VulkanContext,
+};
 use crate::graphics::Extent2D;
 use ash::version::DeviceV1_0;
 use ash::vk;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::ffi::CString;
+use std::io::Read;
 use std::sync::Arc;
 
 pub struct Texture {
@@ -12,10 +19,56 @@ pub struct Texture {
     memory: Option<vk_mem::Allocation>,
     view: vk::ImageView,
     format: vk::Format,
+    usage: vk::ImageUsageFlags,
     layout: vk::ImageLayout,
     size: vk::DeviceSize,
     extent: Extent2D,
     owns_image: bool,
+    /// The number of array layers, 6 for cubemaps
+    layer_count: u32,
+    view_type: vk::ImageViewType,
+    /// The image file loaded if it differs from the resource path
+    source: Option<String>,
+}
+
+/// Appended to the resource path of textures loaded with the linear hint
+const LINEAR_SUFFIX: &str = "#linear";
+
+/// Describes how the pixels of a loaded texture are interpreted when sampled
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextureUsageHint {
+    /// sRGB encoded colors decoded to linear when sampled, E.g; albedo
+    #[default]
+    Color,
+    /// Data sampled as is, E.g; normal and roughness maps
+    Linear,
+}
+
+impl TextureUsageHint {
+    /// Returns the format of the loaded texture
+    pub fn format(self) -> vk::Format {
+        match self {
+            TextureUsageHint::Color => vk::Format::R8G8B8A8_SRGB,
+            TextureUsageHint::Linear => vk::Format::R8G8B8A8_UNORM,
+        }
+    }
+
+    /// Returns the path a texture of the image at path is stored as in the resource manager
+    /// The same image loaded with different hints are different textures
+    pub fn resource_path(self, path: &str) -> String {
+        match self {
+            TextureUsageHint::Color => path.to_owned(),
+            TextureUsageHint::Linear => format!("{}{}", path, LINEAR_SUFFIX),
+        }
+    }
+
+    /// Splits a resource path into the image path and the hint
+    fn from_resource_path(path: &str) -> (&str, Self) {
+        match path.strip_suffix(LINEAR_SUFFIX) {
+            Some(path) => (path, TextureUsageHint::Linear),
+            None => (path, TextureUsageHint::Color),
+        }
+    }
 }
 
 #[link(name = "stb_image", kind = "static")]
@@ -27,16 +80,62 @@ extern "C" {
         channels: *mut i32,
         desired_channels: i32,
     ) -> *mut u8;
+
+    pub fn stbi_load_from_memory(
+        buffer: *const u8,
+        len: i32,
+        x: *mut i32,
+        y: *mut i32,
+        channels: *mut i32,
+        desired_channels: i32,
+    ) -> *mut u8;
+
+    pub fn stbi_image_free(pixels: *mut u8);
 }
 
-impl Resource for Texture {
-    // Load a texture from an image file on disk
-    fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
-        let context = resourcemanager.context();
-        let allocator = &context.allocator;
-        let device = &context.device;
-        let queue = context.graphics_queue;
-        let commandpool = context.generic_pool();
+/// Describes the image and view created by Texture::create
+struct ImageSpec {
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    image_aspect: vk::ImageAspectFlags,
+    tiling: vk::ImageTiling,
+    extent: Extent2D,
+    view_type: vk::ImageViewType,
+}
+
+/// The decoded rgba pixels of an image file
+pub struct Pixels {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    /// Decodes the contents of an image file in any format supported by stb_image
+    /// name identifies the image in errors
+    pub fn decode(bytes: &[u8], name: &str) -> Result<Pixels> {
+        // stb_image takes the length as an int
+        let len = i32::try_from(bytes.len()).map_err(|_| Error::ImageReadError(name.to_owned()))?;
+
+        let mut width = 0;
+        let mut height = 0;
+        let mut channels = 0;
+        let pixels = unsafe {
+            stbi_load_from_memory(
+                bytes.as_ptr(),
+                len,
+                &mut width,
+                &mut height,
+                &mut channels,
+                4,
+            )
+        };
+
+        Self::from_stbi(pixels, width, height, name)
+    }
+
+    /// Reads and decodes an image file in any format supported by stb_image
+    pub fn open(path: &str) -> Result<Pixels> {
         let filename = CString::new(path).expect("Failed to convert path into CString");
         let mut width = 0;
         let mut height = 0;
@@ -44,77 +143,109 @@ impl Resource for Texture {
         let pixels =
             unsafe { stbi_load(filename.as_ptr(), &mut width, &mut height, &mut channels, 4) };
 
+        Self::from_stbi(pixels, width, height, path)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the tightly packed rgba8 pixels, row by row from the top
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Copies and frees pixels decoded by stb_image with 4 channels
+    fn from_stbi(pixels: *mut u8, width: i32, height: i32, name: &str) -> Result<Pixels> {
         if pixels.is_null() {
-            return Err(Error::ImageReadError(path.to_owned()));
+            return Err(Error::ImageReadError(name.to_owned()));
         }
 
         // The size of the loaded image with alpha channel
-        // May differ from the vkimage memrequirement size
-        let image_size = width * height * 4;
+        // Computed as usize since the product may not fit in an int
+        let image_size = width as usize * height as usize * 4;
+        let data = unsafe { std::slice::from_raw_parts(pixels, image_size) }.to_vec();
+        unsafe { stbi_image_free(pixels) };
+
+        Ok(Pixels {
+            width: width as u32,
+            height: height as u32,
+            data,
+        })
+    }
+}
 
-        let format = vk::Format::R8G8B8A8_SRGB;
-        let mut texture = Texture::new(
-            allocator,
-            device,
-            format,
-            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            vk::ImageAspectFlags::COLOR,
-            vk::ImageTiling::OPTIMAL,
-            (width, height).into(),
-        )?;
+impl Resource for Texture {
+    // Load a texture from an image file on disk
+    fn load(resourcemanager: &super::ResourceManager, path: &str) -> Result<Self> {
+        let pixels = Self::read(path)?;
+        Self::upload(
+            resourcemanager,
+            path,
+            pixels,
+            &resourcemanager.context().transfer(),
+        )
+    }
 
-        // Transition layout for transfer
-        transition_image_layout(
-            device,
-            commandpool,
-            queue,
-            texture.image,
-            vk::ImageAspectFlags::COLOR,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        )?;
+    /// Loads a texture from the contents of an image file
+    fn load_from_reader(
+        resourcemanager: &super::ResourceManager,
+        reader: &mut dyn Read,
+        name: &str,
+    ) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|_| Error::ImageReadError(name.to_owned()))?;
+
+        let pixels = Pixels::decode(&bytes, name)?;
+        Self::upload(
+            resourcemanager,
+            name,
+            pixels,
+            &resourcemanager.context().transfer(),
+        )
+    }
 
-        // Create and copy image pixel data to stagin buffer
-        let (staging_buffer, staging_memory, staging_info) =
-            buffer::create_staging(allocator, texture.size)?;
-        // Copy the data into the staging buffer
-        let data = staging_info.get_mapped_data();
+    fn dependencies(&self) -> Vec<String> {
+        self.source.iter().cloned().collect()
+    }
+}
 
-        unsafe {
-            std::ptr::copy_nonoverlapping(pixels as _, data, image_size as usize);
-        }
+impl AsyncResource for Texture {
+    type Data = Pixels;
 
-        // Transfer the staging buffer to the image
-        buffer::copy_to_image(
-            device,
-            queue,
-            commandpool,
-            staging_buffer,
-            texture.image,
-            texture.extent,
-            vk::ImageAspectFlags::COLOR,
-        )?;
+    fn read(path: &str) -> Result<Pixels> {
+        let (path, _) = TextureUsageHint::from_resource_path(path);
+        Pixels::open(path)
+    }
 
-        // Transition layout for shader read only optimal
-        transition_image_layout(
-            device,
-            commandpool,
-            queue,
-            texture.image,
-            vk::ImageAspectFlags::COLOR,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    fn upload(
+        resourcemanager: &super::ResourceManager,
+        path: &str,
+        pixels: Pixels,
+        transfer: &Transfer,
+    ) -> Result<Self> {
+        let context = resourcemanager.context();
+        let (source, hint) = TextureUsageHint::from_resource_path(path);
+
+        let mut texture = Texture::from_pixels(
+            &context.allocator,
+            &context.device,
+            transfer,
+            pixels.width,
+            pixels.height,
+            hint.format(),
+            &pixels.data,
         )?;
 
-        texture.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-
-        // Free staging buffer
-        allocator
-            .borrow()
-            .destroy_buffer(staging_buffer, &staging_memory)?;
-
-        // Free the pixels
-        unsafe { Box::from_raw(pixels) };
+        if source != path {
+            texture.source = Some(source.to_owned());
+        }
         Ok(texture)
     }
 }
@@ -130,7 +261,39 @@ impl Texture {
         tiling: vk::ImageTiling,
         extent: Extent2D,
     ) -> Result<Texture> {
+        Self::create(
+            allocator,
+            device,
+            ImageSpec {
+                format,
+                usage,
+                image_aspect,
+                tiling,
+                extent,
+                view_type: vk::ImageViewType::TYPE_2D,
+            },
+        )
+    }
+
+    /// Creates an image with one layer, or six cube compatible layers if view_type is TYPE_CUBE
+    fn create(allocator: &VkAllocator, device: &ash::Device, spec: ImageSpec) -> Result<Texture> {
+        let ImageSpec {
+            format,
+            usage,
+            image_aspect,
+            tiling,
+            extent,
+            view_type,
+        } = spec;
+
+        let layer_count = layer_count(view_type);
+        let flags = match view_type {
+            vk::ImageViewType::CUBE => vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            _ => vk::ImageCreateFlags::empty(),
+        };
+
         let image_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
             .image_type(vk::ImageType::TYPE_2D)
             .extent(vk::Extent3D {
                 width: extent.width,
@@ -138,7 +301,7 @@ impl Texture {
                 depth: 1,
             })
             .mip_levels(1)
-            .array_layers(1)
+            .array_layers(layer_count)
             .format(format)
             .tiling(tiling)
             .initial_layout(vk::ImageLayout::UNDEFINED)
@@ -152,12 +315,13 @@ impl Texture {
         };
 
         let (image, memory, _) = allocator
-            .borrow()
+            .lock()
+            .unwrap()
             .create_image(&image_info, image_allocation_info)?;
 
         // Create image view
         let view_info = vk::ImageViewCreateInfo::builder()
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(view_type)
             .format(format)
             .components(vk::ComponentMapping {
                 r: vk::ComponentSwizzle::IDENTITY,
@@ -170,7 +334,7 @@ impl Texture {
                 base_mip_level: 0,
                 level_count: 1,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count,
             })
             .image(image);
 
@@ -184,21 +348,151 @@ impl Texture {
             memory: Some(memory),
             view,
             format,
+            usage,
             extent,
             size,
             owns_image: true,
             layout: vk::ImageLayout::UNDEFINED,
+            layer_count,
+            view_type,
+            source: None,
         })
     }
 
+    /// Creates a sampleable texture from pixel data in memory
+    /// The pixels are tightly packed rows of the given format
+    /// The texture is left in SHADER_READ_ONLY_OPTIMAL layout
+    pub fn from_pixels(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        pixels: &[u8],
+    ) -> Result<Texture> {
+        Self::create_from_pixels(
+            allocator,
+            device,
+            transfer,
+            Extent2D { width, height },
+            format,
+            vk::ImageViewType::TYPE_2D,
+            pixels,
+        )
+    }
+
+    /// Creates a sampleable cubemap from the pixel data of six square faces of equal size
+    /// The faces are in the order +X, -X, +Y, -Y, +Z, -Z
+    /// The texture is left in SHADER_READ_ONLY_OPTIMAL layout
+    pub fn new_cubemap(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        size: u32,
+        format: vk::Format,
+        faces: [&[u8]; 6],
+    ) -> Result<Texture> {
+        let pixels = faces.concat();
+
+        Self::create_from_pixels(
+            allocator,
+            device,
+            transfer,
+            Extent2D {
+                width: size,
+                height: size,
+            },
+            format,
+            vk::ImageViewType::CUBE,
+            &pixels,
+        )
+    }
+
+    /// Loads a cubemap from six image files
+    /// The faces are in the order +X, -X, +Y, -Y, +Z, -Z and need to be square and of equal size
+    /// Should be sampled with SamplerSpec::cubemap
+    pub fn load_cubemap(context: &VulkanContext, paths: [&str; 6]) -> Result<Texture> {
+        let (size, faces) = read_cubemap_faces(paths)?;
+
+        Self::new_cubemap(
+            &context.allocator,
+            &context.device,
+            &context.transfer(),
+            size,
+            vk::Format::R8G8B8A8_SRGB,
+            [
+                &faces[0].data,
+                &faces[1].data,
+                &faces[2].data,
+                &faces[3].data,
+                &faces[4].data,
+                &faces[5].data,
+            ],
+        )
+    }
+
+    fn create_from_pixels(
+        allocator: &VkAllocator,
+        device: &ash::Device,
+        transfer: &Transfer,
+        extent: Extent2D,
+        format: vk::Format,
+        view_type: vk::ImageViewType,
+        pixels: &[u8],
+    ) -> Result<Texture> {
+        validate_pixels(extent, format, view_type, pixels.len())?;
+
+        let mut texture = Texture::create(
+            allocator,
+            device,
+            ImageSpec {
+                format,
+                usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+                image_aspect: vk::ImageAspectFlags::COLOR,
+                tiling: vk::ImageTiling::OPTIMAL,
+                extent,
+                view_type,
+            },
+        )?;
+
+        // Create and copy image pixel data to stagin buffer
+        // The image memory may be larger than the pixel data
+        let (staging_buffer, staging_memory, staging_info) =
+            buffer::create_staging(allocator, texture.size)?;
+        let data = staging_info.get_mapped_data();
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), data, pixels.len());
+        }
+
+        // Transfer the staging buffer to the image which leaves it ready for sampling
+        buffer::copy_to_image(
+            device,
+            transfer,
+            staging_buffer,
+            texture.image,
+            texture.extent,
+            vk::ImageAspectFlags::COLOR,
+            texture.layer_count,
+        )?;
+
+        texture.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+
+        // Free staging buffer
+        buffer::destroy_staging(allocator, transfer, staging_buffer, staging_memory)?;
+
+        Ok(texture)
+    }
+
     /// Creates a new texture that can be used as a depth attachment
     /// The contents and layout of the image is undefined
     pub fn new_depth(
         allocator: &VkAllocator,
         device: &ash::Device,
+        format: vk::Format,
         extent: Extent2D,
     ) -> Result<Texture> {
-        let format = vk::Format::D32_SFLOAT;
         let texture = Texture::new(
             allocator,
             device,
@@ -218,6 +512,7 @@ impl Texture {
         extent: Extent2D,
         image: vk::Image,
         format: vk::Format,
+        usage: vk::ImageUsageFlags,
         layout: vk::ImageLayout,
     ) -> Result<Texture> {
         let view_create_info = vk::ImageViewCreateInfo::builder()
@@ -252,10 +547,14 @@ impl Texture {
             memory: None,
             view,
             format,
+            usage,
             extent,
             size,
             owns_image: false,
             layout,
+            layer_count: 1,
+            view_type: vk::ImageViewType::TYPE_2D,
+            source: None,
         })
     }
 
@@ -271,9 +570,77 @@ impl Texture {
         self.layout
     }
 
+    /// Sets the layout the image is expected to be in when used
+    /// Does not transition the image, E.g; used when a renderpass transitions the image
+    pub fn set_layout(&mut self, layout: vk::ImageLayout) {
+        self.layout = layout;
+    }
+
     pub fn format(&self) -> vk::Format {
         self.format
     }
+
+    /// Returns the usage the image was created with
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    /// Returns the number of array layers of the image, 6 for cubemaps
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+
+    /// Returns the type of the image view, TYPE_CUBE for cubemaps
+    pub fn view_type(&self) -> vk::ImageViewType {
+        self.view_type
+    }
+}
+
+/// Returns the number of array layers of an image viewed as view_type
+fn layer_count(view_type: vk::ImageViewType) -> u32 {
+    match view_type {
+        vk::ImageViewType::CUBE => 6,
+        _ => 1,
+    }
+}
+
+/// Checks that len bytes are tightly packed pixels of format filling every layer of extent
+fn validate_pixels(
+    extent: Extent2D,
+    format: vk::Format,
+    view_type: vk::ImageViewType,
+    len: usize,
+) -> Result<()> {
+    let pixel_size = bytes_per_pixel(format).ok_or(Error::UnsupportedFormat(format))?;
+    let expected_size = extent.width as usize
+        * extent.height as usize
+        * pixel_size
+        * layer_count(view_type) as usize;
+
+    if len != expected_size {
+        return Err(Error::PixelDataMismatch(len, expected_size));
+    }
+
+    Ok(())
+}
+
+/// Reads the six faces of a cubemap
+/// Returns the size of the faces, which need to be square and of equal size
+fn read_cubemap_faces(paths: [&str; 6]) -> Result<(u32, Vec<Pixels>)> {
+    let mut size = None;
+    let mut faces = Vec::with_capacity(6);
+
+    for path in paths.iter() {
+        let pixels = Pixels::open(path)?;
+
+        if pixels.width != pixels.height || pixels.width != *size.get_or_insert(pixels.width) {
+            return Err(Error::MismatchedCubemapFace(path.to_string()));
+        }
+
+        faces.push(pixels);
+    }
+
+    Ok((size.unwrap(), faces))
 }
 
 impl Drop for Texture {
@@ -283,7 +650,8 @@ impl Drop for Texture {
                 self.allocator
                     .as_ref()
                     .expect("Missing allocator for owned image")
-                    .borrow()
+                    .lock()
+                    .unwrap()
                     .destroy_image(self.image, &self.memory.unwrap())
                     .expect("Failed to free image")
             }
@@ -292,90 +660,169 @@ impl Drop for Texture {
     }
 }
 
-fn transition_image_layout(
-    device: &ash::Device,
-    commandpool: &CommandPool,
-    queue: vk::Queue,
-    image: vk::Image,
-    image_aspect: vk::ImageAspectFlags,
-    old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-) -> Result<()> {
-    let commandbuffer = &mut CommandBuffer::new_primary(device, commandpool, 1)?[0];
-
-    commandbuffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
-
-    let (src_access_mask, dst_access_mask, src_stage_mask, dst_stage_mask) =
-        match (old_layout, new_layout) {
-            (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
-                vk::AccessFlags::default(),
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::TRANSFER,
-            ),
-            (vk::ImageLayout::UNDEFINED, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL) => (
-                vk::AccessFlags::default(),
-                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            ),
-            (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::AccessFlags::SHADER_READ,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-            ),
-
-            (src, dst) => return Err(Error::UnsupportedTransition(src, dst)),
-        };
+/// Returns the size in bytes of one pixel for uncompressed color formats
+fn bytes_per_pixel(format: vk::Format) -> Option<usize> {
+    match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => Some(1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB => Some(2),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::R32_SFLOAT => Some(4),
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_SFLOAT => Some(8),
+        vk::Format::R32G32B32A32_SFLOAT => Some(16),
+        _ => None,
+    }
+}
 
-    let barrier = vk::ImageMemoryBarrier {
-        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
-        new_layout,
-        old_layout,
-        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-        image,
-        subresource_range: vk::ImageSubresourceRange {
-            aspect_mask: image_aspect,
-            base_mip_level: 0,
-            level_count: 1,
-            base_array_layer: 0,
-            layer_count: 1,
-        },
-        src_access_mask,
-        dst_access_mask,
-        p_next: std::ptr::null(),
-    };
-
-    unsafe {
-        device.cmd_pipeline_barrier(
-            commandbuffer.vk(),
-            src_stage_mask,
-            dst_stage_mask,
-            vk::DependencyFlags::default(),
-            &[],
-            &[],
-            &[barrier],
-        )
+fn has_stencil_component(format: vk::Format) -> bool {
+    return format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::{self, ResourceManager};
+
+    #[test]
+    fn usage_hint() {
+        assert_eq!(TextureUsageHint::default(), TextureUsageHint::Color);
+        assert_eq!(TextureUsageHint::Color.format(), vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(
+            TextureUsageHint::Linear.format(),
+            vk::Format::R8G8B8A8_UNORM
+        );
+
+        for &hint in &[TextureUsageHint::Color, TextureUsageHint::Linear] {
+            let path = hint.resource_path("normal.png");
+            assert_eq!(
+                TextureUsageHint::from_resource_path(&path),
+                ("normal.png", hint)
+            );
+        }
     }
 
-    commandbuffer.end()?;
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn load_with_hint() {
+        let context = vulkan::test_context();
 
-    CommandBuffer::submit(
-        device,
-        &[&commandbuffer],
-        queue,
-        &[],
-        &[],
-        &[],
-        vk::Fence::null(),
-    )?;
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+        let path = "../sandbox/data/textures/grid.png";
 
-    Ok(())
-}
+        let color = resourcemanager.load_texture(path).unwrap();
+        let linear = resourcemanager
+            .load_texture_with_hint(path, TextureUsageHint::Linear)
+            .unwrap();
+        assert_eq!(color.format(), vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(linear.format(), vk::Format::R8G8B8A8_UNORM);
+    }
 
-fn has_stencil_component(format: vk::Format) -> bool {
-    return format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT;
+    const GRID: &str = "../sandbox/data/textures/grid.png";
+    const CONCRETE: &str = "../sandbox/data/textures/concrete.png";
+
+    #[test]
+    fn cubemap_faces() {
+        let (size, faces) = read_cubemap_faces([GRID; 6]).unwrap();
+        assert_eq!(size, 1024);
+        assert_eq!(faces.len(), 6);
+        assert!(faces.iter().all(|face| face.data.len() == 1024 * 1024 * 4));
+
+        // Faces need to be of equal size
+        let mismatched = [GRID, GRID, GRID, CONCRETE, GRID, GRID];
+        assert!(matches!(
+            read_cubemap_faces(mismatched),
+            Err(Error::MismatchedCubemapFace(path)) if path == CONCRETE
+        ));
+        assert!(matches!(
+            read_cubemap_faces(["missing.png"; 6]),
+            Err(Error::ImageReadError(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn load_cubemap() {
+        let context = vulkan::test_context();
+
+        let cubemap = Texture::load_cubemap(&context, [CONCRETE; 6]).unwrap();
+        assert_eq!(cubemap.view_type(), vk::ImageViewType::CUBE);
+        assert_eq!(cubemap.layer_count(), 6);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn uploads_free_command_buffers() {
+        let context = vulkan::test_context();
+        let resourcemanager = ResourceManager::new(Arc::new(context));
+        let context = resourcemanager.context();
+
+        // Loaded directly to not be cached by path
+        for _ in 0..32 {
+            let texture = <Texture as Resource>::load(&resourcemanager, CONCRETE).unwrap();
+            drop(texture);
+
+            let transfer = context.transfer();
+            assert_eq!(transfer.commandpool.allocated_count(), 0);
+            assert_eq!(transfer.graphics_pool.allocated_count(), 0);
+        }
+
+        Texture::load_cubemap(context, [CONCRETE; 6]).unwrap();
+        assert_eq!(context.generic_pool().allocated_count(), 0);
+    }
+
+    #[test]
+    fn validate_pixel_data() {
+        let extent = Extent2D {
+            width: 2,
+            height: 2,
+        };
+        let rgba = vk::Format::R8G8B8A8_UNORM;
+        let flat = vk::ImageViewType::TYPE_2D;
+
+        assert!(validate_pixels(extent, rgba, flat, 16).is_ok());
+        assert!(matches!(
+            validate_pixels(extent, rgba, flat, 15),
+            Err(Error::PixelDataMismatch(15, 16))
+        ));
+        assert!(validate_pixels(extent, rgba, vk::ImageViewType::CUBE, 16 * 6).is_ok());
+        assert!(matches!(
+            validate_pixels(extent, vk::Format::BC1_RGB_UNORM_BLOCK, flat, 16),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn from_pixels() {
+        let context = vulkan::test_context();
+        let upload = |pixels: &[u8]| {
+            Texture::from_pixels(
+                &context.allocator,
+                &context.device,
+                &context.transfer(),
+                2,
+                2,
+                vk::Format::R8G8B8A8_UNORM,
+                pixels,
+            )
+        };
+
+        // A 2x2 black and white checkerboard
+        let checkerboard = [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+        ]
+        .concat();
+
+        let texture = upload(&checkerboard).unwrap();
+        assert_eq!(texture.layout(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert!(matches!(
+            upload(&checkerboard[..12]),
+            Err(Error::PixelDataMismatch(12, 16))
+        ));
+    }
 }
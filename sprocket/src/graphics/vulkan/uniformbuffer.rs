@@ -1,5 +1,4 @@
-use super::Result;
-use super::VkAllocator;
+use super::{Error, Result, VkAllocator, VulkanContext};
 use crate::math::Mat4;
 use ash::vk;
 use std::sync::Arc;
@@ -18,7 +17,7 @@ pub struct UniformBuffer {
 }
 impl UniformBuffer {
     pub fn new(allocator: &VkAllocator, size: u64) -> Result<UniformBuffer> {
-        let (buffer, memory, _) = allocator.borrow().create_buffer(
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
             &vk::BufferCreateInfo::builder()
                 .size(size)
                 .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
@@ -45,9 +44,9 @@ impl UniformBuffer {
         let offset = offset.unwrap_or(0);
 
         // Copy the data into the buffer
-        let mapped: *mut u8 = self.allocator.borrow().map_memory(&self.memory)?;
+        let mapped: *mut u8 = self.allocator.lock().unwrap().map_memory(&self.memory)?;
         unsafe { std::ptr::copy_nonoverlapping(data as _, mapped.offset(offset as isize), size) }
-        self.allocator.borrow().unmap_memory(&self.memory)?;
+        self.allocator.lock().unwrap().unmap_memory(&self.memory)?;
 
         Ok(())
     }
@@ -65,8 +64,143 @@ impl UniformBuffer {
 impl Drop for UniformBuffer {
     fn drop(&mut self) {
         self.allocator
-            .borrow()
+            .lock()
+            .unwrap()
             .destroy_buffer(self.buffer, &self.memory)
             .expect("Failed to free vulkan memory");
     }
 }
+
+/// A ring of equally sized slices sub-allocated from one uniform buffer, E.g; for per object
+/// uniforms
+/// Slices are bound through a UniformBufferDynamic binding written with
+/// DescriptorSet::write_uniform_pool, with the offset of the slice as the dynamic offset, so many
+/// objects share one buffer and descriptor set
+/// Allocation wraps around when the ring is full, so a slice must no longer be in use by the
+/// device after capacity more allocations
+pub struct UniformBufferPool {
+    buffer: UniformBuffer,
+    /// The size in bytes of the data in each slice
+    slice_size: u64,
+    /// The distance between slices, the slice size rounded up to the offset alignment
+    stride: u64,
+    capacity: u64,
+    /// The index of the next slice to allocate
+    next: u64,
+}
+
+impl UniformBufferPool {
+    /// Creates a pool of capacity slices of slice_size bytes
+    /// Slices are aligned to the minimum uniform buffer offset alignment of the device
+    /// Returns an error if the pool would be empty or its size overflows
+    pub fn new(context: &VulkanContext, slice_size: u64, capacity: u64) -> Result<Self> {
+        let alignment = context.info().min_uniform_buffer_offset_alignment;
+        let stride = align_up(slice_size, alignment);
+        let size = stride
+            .checked_mul(capacity)
+            .filter(|&size| size > 0)
+            .ok_or(Error::InvalidPoolSize(slice_size, capacity))?;
+
+        let buffer = UniformBuffer::new(&context.allocator, size)?;
+
+        Ok(UniformBufferPool {
+            buffer,
+            slice_size,
+            stride,
+            capacity,
+            next: 0,
+        })
+    }
+
+    /// Returns the offset of the next slice in the ring
+    pub fn allocate(&mut self) -> u64 {
+        let offset = self.next * self.stride;
+        self.next = (self.next + 1) % self.capacity;
+        offset
+    }
+
+    /// Writes data into the next slice and returns its offset to bind the slice with
+    /// Returns an error if data is larger than a slice
+    pub fn write<T>(&mut self, data: &T) -> Result<u32> {
+        let size = std::mem::size_of::<T>();
+        if size as u64 > self.slice_size {
+            return Err(Error::BufferOverflow(size, self.slice_size as usize));
+        }
+
+        let offset = self.allocate();
+        self.buffer.write(data, Some(offset))?;
+        Ok(offset as u32)
+    }
+
+    /// Starts allocating from the first slice again
+    /// None of the slices may be in use by the device
+    pub fn reset(&mut self) {
+        self.next = 0;
+    }
+
+    pub fn buffer(&self) -> &UniformBuffer {
+        &self.buffer
+    }
+
+    /// Returns the size in bytes of the data in each slice
+    pub fn slice_size(&self) -> u64 {
+        self.slice_size
+    }
+
+    /// Returns the distance in bytes between the offsets of consecutive slices
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    /// Returns the number of slices in the ring
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+}
+
+/// Rounds size up to a multiple of alignment
+/// An alignment of 0 leaves size unchanged
+fn align_up(size: u64, alignment: u64) -> u64 {
+    match alignment {
+        0 => size,
+        _ => size.div_ceil(alignment) * alignment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+
+    #[test]
+    fn align() {
+        assert_eq!(align_up(0, 256), 0);
+        assert_eq!(align_up(1, 256), 256);
+        assert_eq!(align_up(256, 256), 256);
+        assert_eq!(align_up(257, 256), 512);
+        assert_eq!(align_up(100, 0), 100);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn pool_slices_are_aligned_and_disjoint() {
+        let context = vulkan::test_context();
+
+        let alignment = context.info().min_uniform_buffer_offset_alignment.max(1);
+        let slice_size = std::mem::size_of::<UniformBufferObject>() as u64;
+        let mut pool = UniformBufferPool::new(&context, slice_size, 4).unwrap();
+
+        let offsets: Vec<u64> = (0..5).map(|_| pool.allocate()).collect();
+        for pair in offsets[..4].windows(2) {
+            assert_eq!(pair[0] % alignment, 0);
+            assert!(pair[0] + slice_size <= pair[1]);
+        }
+        assert!(offsets[3] + slice_size <= pool.buffer().size());
+
+        // Wraps around
+        assert_eq!(offsets[4], 0);
+
+        assert!(UniformBufferPool::new(&context, slice_size, 0).is_err());
+        assert!(UniformBufferPool::new(&context, slice_size, u64::MAX).is_err());
+    }
+}
@@ -1,15 +1,22 @@
 use super::buffer;
-use super::CommandPool;
+use super::Transfer;
 use crate::math::*;
 use ash::vk;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use super::{Result, VkAllocator};
+use super::{Error, Result, VkAllocator};
 
 #[derive(Debug)]
 pub struct Vertex {
     pub position: Vec3,
     pub uv: Vec2,
+    /// Multiplied with the texture color, white by default
+    pub color: Vec4,
+    pub normal: Vec3,
+    /// The tangent for normal mapping, w is the handedness of the bitangent
+    /// Generated with tangents::generate_tangents
+    pub tangent: Vec4,
 }
 
 impl Vertex {
@@ -17,9 +24,24 @@ impl Vertex {
         Vertex {
             position,
             uv: texcoord,
+            color: Vec4::one(),
+            normal: Vec3::zero(),
+            tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
         }
     }
 
+    /// Sets the vertex color
+    pub fn with_color(mut self, color: Vec4) -> Vertex {
+        self.color = color;
+        self
+    }
+
+    /// Sets the vertex normal
+    pub fn with_normal(mut self, normal: Vec3) -> Vertex {
+        self.normal = normal;
+        self
+    }
+
     pub fn binding_description() -> vk::VertexInputBindingDescription {
         vk::VertexInputBindingDescription::builder()
             .binding(0)
@@ -28,6 +50,15 @@ impl Vertex {
             .build()
     }
 
+    /// Describes the per instance model matrices of an InstanceBuffer at binding 1
+    pub fn instance_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(1)
+            .stride(std::mem::size_of::<Mat4>() as u32)
+            .input_rate(vk::VertexInputRate::INSTANCE)
+            .build()
+    }
+
     pub fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
         vec![
             // Position
@@ -37,23 +68,119 @@ impl Vertex {
                 .format(vk::Format::R32G32B32_SFLOAT)
                 .offset(offsetof!(Vertex, position) as u32)
                 .build(),
-            // Color
+            // Texture coordinates
             vk::VertexInputAttributeDescription::builder()
                 .binding(0)
                 .location(1)
                 .format(vk::Format::R32G32_SFLOAT)
                 .offset(offsetof!(Vertex, uv) as u32)
                 .build(),
+            // Color after the instance model matrix to keep its locations
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(6)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offsetof!(Vertex, color) as u32)
+                .build(),
+            // Normal
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(7)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offsetof!(Vertex, normal) as u32)
+                .build(),
+            // Tangent and bitangent handedness
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(8)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offsetof!(Vertex, tangent) as u32)
+                .build(),
+            // Instance model matrix, one location per 4 floats
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(2)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(0)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(3)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(16)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(4)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(5)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(48)
+                .build(),
         ]
     }
 }
 
+/// Describes which vertex attributes a pipeline consumes
+/// All layouts read from the same Vertex buffers
+/// The vertex color is read at location 6, the normal at 7 and the tangent at 8
+/// Instanced layouts additionally read a model matrix at location 2 from an InstanceBuffer
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+pub enum VertexLayout {
+    /// All attributes of Vertex
+    #[default]
+    Standard,
+    /// Only the position at location 0, used for depth only passes
+    PositionOnly,
+    /// All attributes of Vertex and the instance model matrix
+    Instanced,
+    /// The position and the instance model matrix
+    InstancedPositionOnly,
+    /// No vertex input, vertices are generated from gl_VertexIndex
+    Empty,
+}
+
+impl VertexLayout {
+    pub fn binding_descriptions(&self) -> Vec<vk::VertexInputBindingDescription> {
+        match self {
+            VertexLayout::Standard | VertexLayout::PositionOnly => {
+                vec![Vertex::binding_description()]
+            }
+            VertexLayout::Instanced | VertexLayout::InstancedPositionOnly => vec![
+                Vertex::binding_description(),
+                Vertex::instance_binding_description(),
+            ],
+            VertexLayout::Empty => Vec::new(),
+        }
+    }
+
+    pub fn attribute_descriptions(&self) -> Vec<vk::VertexInputAttributeDescription> {
+        let mut attributes = Vertex::attribute_descriptions();
+        match self {
+            VertexLayout::Standard => attributes.retain(|attribute| attribute.binding == 0),
+            VertexLayout::PositionOnly => attributes.truncate(1),
+            VertexLayout::Instanced => {}
+            VertexLayout::InstancedPositionOnly => {
+                attributes.retain(|attribute| attribute.binding == 1 || attribute.location == 0)
+            }
+            VertexLayout::Empty => attributes.clear(),
+        }
+        attributes
+    }
+}
+
 pub struct VertexBuffer {
     allocator: VkAllocator,
     buffer: vk::Buffer,
     memory: vk_mem::Allocation,
     size: vk::DeviceSize,
     count: u32,
+    /// Whether the buffer is host visible and written with write
+    dynamic: bool,
 }
 
 const DEFAULT_SIZE: u64 = 1024;
@@ -65,8 +192,7 @@ impl VertexBuffer {
     pub fn new(
         allocator: &VkAllocator,
         device: &ash::Device,
-        queue: vk::Queue,
-        commandpool: &CommandPool,
+        transfer: &Transfer,
         vertices: &[Vertex],
     ) -> Result<VertexBuffer> {
         let buffer_size = match vertices.len() {
@@ -83,7 +209,7 @@ impl VertexBuffer {
             std::ptr::copy_nonoverlapping(vertices.as_ptr() as _, data, buffer_size as usize);
         }
 
-        let (buffer, memory, _) = allocator.borrow().create_buffer(
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
             &vk::BufferCreateInfo::builder()
                 .size(buffer_size)
                 .usage(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER)
@@ -95,18 +221,9 @@ impl VertexBuffer {
             },
         )?;
 
-        buffer::copy(
-            device,
-            queue,
-            commandpool,
-            staging_buffer,
-            buffer,
-            buffer_size,
-        )?;
+        buffer::copy(device, transfer, staging_buffer, buffer, buffer_size)?;
 
-        allocator
-            .borrow()
-            .destroy_buffer(staging_buffer, &staging_memory)?;
+        buffer::destroy_staging(allocator, transfer, staging_buffer, staging_memory)?;
 
         Ok(VertexBuffer {
             allocator: Arc::clone(allocator),
@@ -114,9 +231,115 @@ impl VertexBuffer {
             memory,
             size: buffer_size,
             count: vertices.len() as u32,
+            dynamic: false,
+        })
+    }
+
+    /// Creates a host visible vertex buffer with space for capacity vertices
+    /// The vertices are written directly with write instead of through a staging buffer
+    pub fn new_dynamic(allocator: &VkAllocator, capacity: usize) -> Result<VertexBuffer> {
+        let size = (capacity.max(1) * std::mem::size_of::<Vertex>()) as u64;
+        let (buffer, memory) = Self::allocate_dynamic(allocator, size)?;
+
+        Ok(VertexBuffer {
+            allocator: Arc::clone(allocator),
+            buffer,
+            memory,
+            size,
+            count: 0,
+            dynamic: true,
         })
     }
 
+    fn allocate_dynamic(
+        allocator: &VkAllocator,
+        size: vk::DeviceSize,
+    ) -> Result<(vk::Buffer, vk_mem::Allocation)> {
+        let (buffer, memory, _) = allocator.lock().unwrap().create_buffer(
+            &vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(vk::BufferUsageFlags::VERTEX_BUFFER)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build(),
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::CpuToGpu,
+                ..Default::default()
+            },
+        )?;
+
+        Ok((buffer, memory))
+    }
+
+    /// Writes the vertices to a dynamic buffer replacing the previous contents
+    /// Reallocates the buffer if the vertices do not fit
+    /// The buffer must not be in use by the device
+    pub fn write(&mut self, vertices: &[Vertex]) -> Result<()> {
+        debug_assert!(self.dynamic, "Attempt to write to a static vertex buffer");
+
+        let size = std::mem::size_of_val(vertices) as u64;
+
+        if size > self.size {
+            let new_size = size.next_power_of_two();
+            let (buffer, memory) = Self::allocate_dynamic(&self.allocator, new_size)?;
+
+            self.allocator
+                .lock()
+                .unwrap()
+                .destroy_buffer(self.buffer, &self.memory)?;
+
+            self.buffer = buffer;
+            self.memory = memory;
+            self.size = new_size;
+        }
+
+        self.update(vertices, 0)?;
+        self.count = vertices.len() as u32;
+        Ok(())
+    }
+
+    /// Copies vertices into a dynamic buffer starting at the vertex offset
+    /// Unlike write the buffer is never reallocated and the vertices need to fit
+    /// Does not change the vertex count, use set_count
+    /// The updated range must not be in use by the device
+    pub fn update(&self, vertices: &[Vertex], offset: usize) -> Result<()> {
+        debug_assert!(self.dynamic, "Attempt to update a static vertex buffer");
+
+        let required = offset + vertices.len();
+        if required > self.capacity() {
+            return Err(Error::BufferOverflow(required, self.capacity()));
+        }
+
+        let vertex_size = std::mem::size_of::<Vertex>();
+        let mapped: *mut u8 = self.allocator.lock().unwrap().map_memory(&self.memory)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                vertices.as_ptr() as *const u8,
+                mapped.add(offset * vertex_size),
+                std::mem::size_of_val(vertices),
+            )
+        }
+        self.allocator.lock().unwrap().unmap_memory(&self.memory)?;
+
+        Ok(())
+    }
+
+    /// Sets the number of vertices drawn from the buffer
+    /// Used after writing vertices with update
+    /// Returns an error if count exceeds the capacity of the buffer
+    pub fn set_count(&mut self, count: u32) -> Result<()> {
+        if count as usize > self.capacity() {
+            return Err(Error::BufferOverflow(count as usize, self.capacity()));
+        }
+
+        self.count = count;
+        Ok(())
+    }
+
+    /// Returns the number of vertices the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.size as usize / std::mem::size_of::<Vertex>()
+    }
+
     pub fn buffer(&self) -> vk::Buffer {
         self.buffer
     }
@@ -130,8 +353,30 @@ impl VertexBuffer {
 impl Drop for VertexBuffer {
     fn drop(&mut self) {
         self.allocator
-            .borrow()
+            .lock()
+            .unwrap()
             .destroy_buffer(self.buffer, &self.memory)
             .expect("Failed to free vulkan memory");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn set_count() {
+        let context = vulkan::test_context();
+
+        let mut vertexbuffer = VertexBuffer::new_dynamic(&context.allocator, 4).unwrap();
+        let capacity = vertexbuffer.capacity() as u32;
+
+        assert!(vertexbuffer.set_count(capacity).is_ok());
+        assert!(matches!(
+            vertexbuffer.set_count(capacity + 1),
+            Err(Error::BufferOverflow(_, _))
+        ));
+    }
+}
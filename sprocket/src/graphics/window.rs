@@ -1,28 +1,236 @@
 use super::glfw::*;
 use super::Extent2D;
 use crate::event::Event;
-use crate::event::KeyCode;
+use crate::event::{KeyCode, Modifiers};
 use log::{debug, error, info, warn};
+use std::cell::Cell;
 use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::mpsc;
 
 use num_traits::FromPrimitive;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WindowMode {
     Windowed,
     Borderless,
+    /// Fullscreen on the primary monitor
     Fullscreen,
+    /// Fullscreen on the given monitor, falls back to the primary monitor if it is disconnected
+    FullscreenOn(MonitorId),
+    /// A borderless window covering the primary monitor in its current video mode
+    /// Looks like fullscreen without changing the video mode or taking exclusive control of the
+    /// monitor, the requested size is ignored
+    BorderlessFullscreen,
 }
+
+impl WindowMode {
+    /// Returns the size of a window created with the requested size on a monitor in vidmode
+    /// A size of -1 uses the size of the monitor
+    fn size(self, width: i32, height: i32, vidmode: &GLFWvidmode) -> (i32, i32) {
+        match self {
+            WindowMode::BorderlessFullscreen => (vidmode.width, vidmode.height),
+            _ => (
+                if width == -1 { vidmode.width } else { width },
+                if height == -1 { vidmode.height } else { height },
+            ),
+        }
+    }
+}
+
+/// Identifies a connected monitor by its index in the list returned by Window::monitors
+/// The primary monitor is always 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorId(pub usize);
+
+/// A resolution and refresh rate supported by a monitor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoMode {
+    pub width: i32,
+    pub height: i32,
+    pub red_bits: i32,
+    pub green_bits: i32,
+    pub blue_bits: i32,
+    pub refresh_rate: i32,
+}
+
+impl From<&GLFWvidmode> for VideoMode {
+    fn from(mode: &GLFWvidmode) -> Self {
+        VideoMode {
+            width: mode.width,
+            height: mode.height,
+            red_bits: mode.redBits,
+            green_bits: mode.greenBits,
+            blue_bits: mode.blueBits,
+            refresh_rate: mode.refreshRate,
+        }
+    }
+}
+
+/// Describes a connected monitor
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub id: MonitorId,
+    pub name: String,
+    /// The mode the monitor is currently in
+    pub current_mode: VideoMode,
+    /// All modes supported by the monitor, sorted by ascending color depth, resolution and refresh
+    /// rate
+    pub modes: Vec<VideoMode>,
+}
+/// Describes how the cursor behaves when over the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorMode {
+    /// The cursor is visible and behaves normally
+    Normal,
+    /// The cursor is hidden when over the window but not locked
+    Hidden,
+    /// The cursor is hidden and locked to the window
+    /// Relative movement is sent as MouseMotion events, unaccelerated if supported
+    Disabled,
+}
+
 /// This is the userpointer given to the data
 /// Needs to be separate so that the address is known and not moved
 struct WindowData {
+    /// The id of the window
+    id: u32,
     sender: mpsc::Sender<Event>,
     in_focus: bool,
+    iconified: bool,
     width: i32,
     height: i32,
+    /// The size in pixels, larger than width and height on HiDPI displays
+    framebuffer_width: i32,
+    framebuffer_height: i32,
+    cursor_mode: CursorMode,
+    /// The previous cursor position used for calculating relative motion
+    last_cursor_position: Option<(f64, f64)>,
+}
+
+impl WindowData {
+    fn extent(&self) -> Extent2D {
+        Extent2D {
+            width: self.width as u32,
+            height: self.height as u32,
+        }
+    }
+
+    fn framebuffer_extent(&self) -> Extent2D {
+        Extent2D {
+            width: self.framebuffer_width as u32,
+            height: self.framebuffer_height as u32,
+        }
+    }
+
+    /// Stores the size of the window in screen coordinates and sends a resize event
+    fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.sender
+            .send(Event::WindowResize(width, height))
+            .expect("Failed to send window resize event");
+    }
+
+    /// Stores the size of the framebuffer in pixels and sends a framebuffer resize event
+    fn resize_framebuffer(&mut self, width: i32, height: i32) {
+        self.framebuffer_width = width;
+        self.framebuffer_height = height;
+        self.sender
+            .send(Event::FramebufferResize(width, height))
+            .expect("Failed to send framebuffer resize event");
+    }
+
+    /// Stores whether the window is minimized and sends an iconify event
+    fn iconify(&mut self, iconified: bool) {
+        self.iconified = iconified;
+        self.sender
+            .send(Event::WindowIconify(iconified))
+            .expect("Failed to send window iconify event");
+    }
+
+    /// Returns true if the window is not minimized and has a framebuffer with an area
+    fn drawable(&self) -> bool {
+        !self.iconified && self.framebuffer_width > 0 && self.framebuffer_height > 0
+    }
+
+    /// Sends a key event with the held modifiers decoded from the GLFW key, action and mods
+    fn key(&self, key: i32, action: i32, mods: i32) {
+        let key = KeyCode::from_i32(key).unwrap_or(KeyCode::Invalid);
+        let mods = Modifiers::from_glfw(mods);
+        let event = match action {
+            GLFW_PRESS => Event::KeyPress(key, mods),
+            GLFW_RELEASE => Event::KeyRelease(key, mods),
+            GLFW_REPEAT => Event::KeyRepeat(key, mods),
+            _ => {
+                warn!("Unknown key action {}", action);
+                return;
+            }
+        };
+        self.sender.send(event).expect("Failed to send key event");
+    }
+
+    /// Sends the cursor position, and the motion since the last position if the cursor is disabled
+    fn move_cursor(&mut self, x: f64, y: f64) {
+        self.sender
+            .send(Event::MousePosition(x as i32, y as i32))
+            .expect("Failed to send mouse position event");
+
+        if self.cursor_mode == CursorMode::Disabled {
+            if let Some((last_x, last_y)) = self.last_cursor_position {
+                self.sender
+                    .send(Event::MouseMotion((x - last_x) as i32, (y - last_y) as i32))
+                    .expect("Failed to send mouse motion event");
+            }
+            self.last_cursor_position = Some((x, y));
+        }
+    }
+
+    /// Sends a refresh event and redraws the window with the refresh handler of the current poll
+    fn refresh(&self) {
+        self.sender
+            .send(Event::WindowRefresh)
+            .expect("Failed to send window refresh event");
+
+        REFRESH_HANDLER.with(|handler| {
+            // Taken while called to not alias it if the handler polls again
+            if let Some(on_refresh) = handler.take() {
+                unsafe { (*on_refresh)(self.id) };
+                handler.set(Some(on_refresh));
+            }
+        });
+    }
+}
+
+/// The id given to the next created window
+static NEXT_WINDOW_ID: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    /// Called by the refresh callback during Window::poll_events_with
+    static REFRESH_HANDLER: Cell<Option<*mut (dyn FnMut(u32) + 'static)>> = Cell::new(None);
+}
+
+/// Calls f with on_refresh set as the handler called by window refresh callbacks
+fn with_refresh_handler<R>(on_refresh: &mut dyn FnMut(u32), f: impl FnOnce() -> R) -> R {
+    // The handler is removed before it goes out of scope
+    let on_refresh: *mut (dyn FnMut(u32) + 'static) =
+        unsafe { std::mem::transmute::<*mut (dyn FnMut(u32) + '_), _>(on_refresh) };
+
+    /// Restores the previous handler when dropped, even if f panics
+    struct Restore(Option<*mut (dyn FnMut(u32) + 'static)>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            REFRESH_HANDLER.with(|handler| handler.set(self.0));
+        }
+    }
+
+    let _restore = Restore(REFRESH_HANDLER.with(|handler| handler.replace(Some(on_refresh))));
+    f()
 }
 
 pub struct Window {
+    id: u32,
     title: String,
     raw_window: *mut GLFWwindow,
     data: *mut WindowData,
@@ -64,38 +272,77 @@ impl Window {
     ) -> Window {
         let mut monitor: *const GLFWmonitor = ptr::null();
         let raw_window = unsafe {
-            let primary = glfwGetPrimaryMonitor();
-            let vidmode = glfwGetVideoMode(primary);
-            if width == -1 {
-                width = (*vidmode).width;
-            }
-            if height == -1 {
-                height = (*vidmode).height;
-            }
+            // The monitor the native resolution is taken from
+            let target = match mode {
+                WindowMode::FullscreenOn(id) => Self::raw_monitor(id).unwrap_or_else(|| {
+                    warn!(
+                        "Monitor {:?} is not connected, using the primary monitor",
+                        id
+                    );
+                    glfwGetPrimaryMonitor()
+                }),
+                _ => glfwGetPrimaryMonitor(),
+            };
+            let vidmode = &*glfwGetVideoMode(target);
+            let size = mode.size(width, height, vidmode);
+            width = size.0;
+            height = size.1;
 
+            // Hints persist between windows
+            glfwDefaultWindowHints();
             glfwWindowHint(GLFW_CLIENT_API, GLFW_NO_API);
             match mode {
                 WindowMode::Borderless => glfwWindowHint(GLFW_DECORATED, 0),
                 WindowMode::Windowed => {}
-                WindowMode::Fullscreen => {
+                WindowMode::Fullscreen | WindowMode::FullscreenOn(_) => {
+                    glfwWindowHint(GLFW_DECORATED, 0);
+                    monitor = target;
+                }
+                WindowMode::BorderlessFullscreen => {
                     glfwWindowHint(GLFW_DECORATED, 0);
-                    monitor = primary;
+                    glfwWindowHint(GLFW_RED_BITS, vidmode.redBits);
+                    glfwWindowHint(GLFW_GREEN_BITS, vidmode.greenBits);
+                    glfwWindowHint(GLFW_BLUE_BITS, vidmode.blueBits);
+                    glfwWindowHint(GLFW_REFRESH_RATE, vidmode.refreshRate);
                 }
             }
             let c_title =
                 std::ffi::CString::new(title).expect("Failed to convert window title to c_str");
 
-            glfwCreateWindow(width, height, c_title.as_ptr(), monitor, ptr::null())
+            let raw_window =
+                glfwCreateWindow(width, height, c_title.as_ptr(), monitor, ptr::null());
+
+            // Cover the monitor from its top left corner
+            if mode == WindowMode::BorderlessFullscreen {
+                let (mut x, mut y) = (0, 0);
+                glfwGetMonitorPos(target, &mut x, &mut y);
+                glfwSetWindowPos(raw_window, x, y);
+            }
+
+            raw_window
+        };
+
+        let (mut framebuffer_width, mut framebuffer_height) = (0, 0);
+        unsafe {
+            glfwGetFramebufferSize(raw_window, &mut framebuffer_width, &mut framebuffer_height)
         };
 
+        let id = NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed);
         let window = Window {
+            id,
             title: String::from(title),
             raw_window,
             data: Box::into_raw(Box::new(WindowData {
+                id,
                 width,
                 height,
+                framebuffer_width,
+                framebuffer_height,
                 sender,
                 in_focus: false,
+                iconified: false,
+                cursor_mode: CursorMode::Normal,
+                last_cursor_position: None,
             })),
         };
 
@@ -108,29 +355,174 @@ impl Window {
             glfwSetScrollCallback(raw_window, scroll_callback);
             glfwSetCursorPosCallback(raw_window, mouse_position_callback);
             glfwSetWindowSizeCallback(raw_window, window_size_callback);
+            glfwSetFramebufferSizeCallback(raw_window, framebuffer_size_callback);
             glfwSetWindowFocusCallback(raw_window, window_focus_callback);
+            glfwSetWindowIconifyCallback(raw_window, window_iconify_callback);
+            glfwSetWindowRefreshCallback(raw_window, window_refresh_callback);
             glfwSetCharCallback(raw_window, char_callback);
         }
 
         window
     }
 
+    /// Returns all connected monitors with the primary monitor first
+    /// GLFW needs to be initialized
+    pub fn monitors() -> Vec<Monitor> {
+        unsafe {
+            let mut count = 0;
+            let monitors = glfwGetMonitors(&mut count);
+            if monitors.is_null() {
+                return Vec::new();
+            }
+
+            std::slice::from_raw_parts(monitors, count as usize)
+                .iter()
+                .enumerate()
+                .map(|(i, &monitor)| {
+                    let name = glfwGetMonitorName(monitor);
+                    let name = match name.is_null() {
+                        true => String::new(),
+                        false => std::ffi::CStr::from_ptr(name)
+                            .to_string_lossy()
+                            .into_owned(),
+                    };
+
+                    let mut mode_count = 0;
+                    let modes = glfwGetVideoModes(monitor, &mut mode_count);
+                    let modes = match modes.is_null() {
+                        true => Vec::new(),
+                        false => std::slice::from_raw_parts(modes, mode_count as usize)
+                            .iter()
+                            .map(VideoMode::from)
+                            .collect(),
+                    };
+
+                    Monitor {
+                        id: MonitorId(i),
+                        name,
+                        current_mode: VideoMode::from(&*glfwGetVideoMode(monitor)),
+                        modes,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    /// Returns the glfw monitor with id if it is connected
+    unsafe fn raw_monitor(id: MonitorId) -> Option<*const GLFWmonitor> {
+        let mut count = 0;
+        let monitors = glfwGetMonitors(&mut count);
+        if monitors.is_null() || id.0 >= count as usize {
+            return None;
+        }
+
+        Some(*monitors.add(id.0))
+    }
+
     pub fn process_events(&self) {
         unsafe { glfwPollEvents() };
     }
 
+    /// Polls the events of all windows
+    /// on_refresh is called with the id of each window whose contents need to be redrawn from
+    /// within the poll, E.g; while a window is resized on platforms where resizing blocks the poll
+    pub fn poll_events_with<F: FnMut(u32)>(mut on_refresh: F) {
+        with_refresh_handler(&mut on_refresh, || unsafe { glfwPollEvents() });
+    }
+
     pub fn in_focus(&self) -> bool {
         unsafe { (*self.data).in_focus }
     }
 
+    /// Returns true if the window is minimized
+    /// A minimized window has a zero sized framebuffer on some platforms and should not be drawn to
+    pub fn minimized(&self) -> bool {
+        unsafe { (*self.data).iconified }
+    }
+
+    /// Returns true if the window can be drawn to
+    /// Is false while minimized or when the framebuffer has no area
+    pub fn drawable(&self) -> bool {
+        unsafe { (*self.data).drawable() }
+    }
+
+    /// Minimizes the window
+    pub fn minimize(&mut self) {
+        unsafe { glfwIconifyWindow(self.raw_window) }
+    }
+
+    /// Maximizes the window
+    pub fn maximize(&mut self) {
+        unsafe { glfwMaximizeWindow(self.raw_window) }
+    }
+
+    /// Restores the window from being minimized or maximized
+    pub fn restore(&mut self) {
+        unsafe { glfwRestoreWindow(self.raw_window) }
+    }
+
     pub fn should_close(&self) -> bool {
         unsafe { glfwWindowShouldClose(self.raw_window) != 0 }
     }
 
+    /// Sets how the cursor behaves when over the window
+    /// Raw mouse motion is enabled when disabling the cursor if supported by the platform
+    pub fn set_cursor_mode(&mut self, mode: CursorMode) {
+        unsafe {
+            let value = match mode {
+                CursorMode::Normal => GLFW_CURSOR_NORMAL,
+                CursorMode::Hidden => GLFW_CURSOR_HIDDEN,
+                CursorMode::Disabled => GLFW_CURSOR_DISABLED,
+            };
+
+            glfwSetInputMode(self.raw_window, GLFW_CURSOR, value);
+
+            if glfwRawMouseMotionSupported() != 0 {
+                glfwSetInputMode(
+                    self.raw_window,
+                    GLFW_RAW_MOUSE_MOTION,
+                    (mode == CursorMode::Disabled) as i32,
+                );
+            }
+
+            (*self.data).cursor_mode = mode;
+            (*self.data).last_cursor_position = None;
+        }
+    }
+
+    pub fn cursor_mode(&self) -> CursorMode {
+        unsafe { (*self.data).cursor_mode }
+    }
+
+    /// Returns an id unique to this window for the lifetime of the program
+    /// Unlike the raw window pointer it is never reused after the window is destroyed
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
     pub fn title(&self) -> &str {
         &self.title
     }
 
+    /// Changes the title of the window
+    pub fn set_title(&mut self, title: &str) {
+        let c_title =
+            std::ffi::CString::new(title).expect("Failed to convert window title to c_str");
+        unsafe { glfwSetWindowTitle(self.raw_window, c_title.as_ptr()) };
+        self.title = String::from(title);
+    }
+
+    /// Resizes the window
+    /// The cached size is updated immediately and a resize event is sent when the window manager
+    /// has applied it
+    pub fn set_size(&mut self, width: i32, height: i32) {
+        unsafe {
+            glfwSetWindowSize(self.raw_window, width, height);
+            (*self.data).width = width;
+            (*self.data).height = height;
+        }
+    }
+
     pub fn width(&self) -> u32 {
         unsafe { (*self.data).width as u32 }
     }
@@ -140,12 +532,21 @@ impl Window {
     }
 
     pub fn extent(&self) -> Extent2D {
-        unsafe {
-            Extent2D {
-                width: (*self.data).width as u32,
-                height: (*self.data).height as u32,
-            }
-        }
+        unsafe { (*self.data).extent() }
+    }
+
+    /// Returns the size of the window framebuffer in pixels
+    /// Differs from extent on HiDPI displays and should be used for swapchains and viewports
+    pub fn framebuffer_extent(&self) -> Extent2D {
+        unsafe { (*self.data).framebuffer_extent() }
+    }
+
+    /// Returns the ratio between pixels and screen coordinates on each axis
+    /// Is (1, 1) unless the window is on a HiDPI display
+    pub fn content_scale(&self) -> (f32, f32) {
+        let (mut x, mut y) = (1.0, 1.0);
+        unsafe { glfwGetWindowContentScale(self.raw_window, &mut x, &mut y) };
+        (x, y)
     }
 
     pub fn aspect(&self) -> f32 {
@@ -189,38 +590,26 @@ extern "C" fn key_callback(
     key: i32,
     _scancode: i32,
     action: i32,
-    _mods: i32,
+    mods: i32,
 ) {
     unsafe {
         if let Some(data) = get_data(window) {
-            let key = KeyCode::from_i32(key).unwrap_or(KeyCode::Invalid);
-            let event = match action {
-                GLFW_PRESS => Event::KeyPress(key),
-                GLFW_RELEASE => Event::KeyRelease(key),
-                GLFW_REPEAT => Event::KeyRepeat(key),
-                _ => {
-                    warn!("Unknown key action {}", action);
-                    return;
-                }
-            };
-            (*data)
-                .sender
-                .send(event)
-                .expect("Failed to send window close event");
+            (*data).key(key, action, mods)
         };
     }
 }
 #[no_mangle]
-extern "C" fn mouse_button_callback(window: *mut GLFWwindow, button: i32, action: i32) {
+extern "C" fn mouse_button_callback(window: *mut GLFWwindow, button: i32, action: i32, mods: i32) {
     unsafe {
         if let Some(data) = get_data(window) {
             // Convert button 0-5 to keycode which starts with mouse buttons after keyboard keys
             let key =
                 KeyCode::from_i32(button + KeyCode::Mouse0 as i32).unwrap_or(KeyCode::Invalid);
+            let mods = Modifiers::from_glfw(mods);
             let event = match action {
-                GLFW_PRESS => Event::KeyPress(key),
-                GLFW_RELEASE => Event::KeyRelease(key),
-                GLFW_REPEAT => Event::KeyRepeat(key),
+                GLFW_PRESS => Event::KeyPress(key, mods),
+                GLFW_RELEASE => Event::KeyRelease(key, mods),
+                GLFW_REPEAT => Event::KeyRepeat(key, mods),
                 _ => {
                     warn!("Unknown key action {}", action);
                     return;
@@ -250,10 +639,7 @@ extern "C" fn scroll_callback(window: *mut GLFWwindow, xoffset: f64, yoffset: f6
 extern "C" fn mouse_position_callback(window: *mut GLFWwindow, xpos: f64, ypos: f64) {
     unsafe {
         if let Some(data) = get_data(window) {
-            (*data)
-                .sender
-                .send(Event::MousePosition(xpos as i32, ypos as i32))
-                .expect("Failed to send window close event");
+            (*data).move_cursor(xpos, ypos);
         };
     }
 }
@@ -261,12 +647,15 @@ extern "C" fn mouse_position_callback(window: *mut GLFWwindow, xpos: f64, ypos:
 extern "C" fn window_size_callback(window: *mut GLFWwindow, width: i32, height: i32) {
     unsafe {
         if let Some(data) = get_data(window) {
-            (*data).width = width;
-            (*data).height = height;
-            (*data)
-                .sender
-                .send(Event::WindowResize(width, height))
-                .expect("Failed to send window close event");
+            (*data).resize(width, height);
+        };
+    }
+}
+#[no_mangle]
+extern "C" fn framebuffer_size_callback(window: *mut GLFWwindow, width: i32, height: i32) {
+    unsafe {
+        if let Some(data) = get_data(window) {
+            (*data).resize_framebuffer(width, height);
         };
     }
 }
@@ -282,6 +671,22 @@ extern "C" fn window_focus_callback(window: *mut GLFWwindow, focus: i32) {
         };
     }
 }
+#[no_mangle]
+extern "C" fn window_iconify_callback(window: *mut GLFWwindow, iconified: i32) {
+    unsafe {
+        if let Some(data) = get_data(window) {
+            (*data).iconify(iconified != 0);
+        };
+    }
+}
+#[no_mangle]
+extern "C" fn window_refresh_callback(window: *mut GLFWwindow) {
+    unsafe {
+        if let Some(data) = get_data(window) {
+            (*data).refresh();
+        };
+    }
+}
 extern "C" fn char_callback(window: *mut GLFWwindow, codepoint: u32) {
     unsafe {
         if let Some(data) = get_data(window) {
@@ -308,3 +713,245 @@ impl Drop for Window {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window_data(sender: mpsc::Sender<Event>) -> WindowData {
+        WindowData {
+            id: 3,
+            sender,
+            in_focus: false,
+            iconified: false,
+            width: 800,
+            height: 600,
+            framebuffer_width: 800,
+            framebuffer_height: 600,
+            cursor_mode: CursorMode::Normal,
+            last_cursor_position: None,
+        }
+    }
+
+    #[test]
+    fn framebuffer_extent() {
+        let (sender, receiver) = mpsc::channel();
+        let mut data = window_data(sender);
+
+        // A content scale of 2 doubles the framebuffer
+        data.resize(640, 480);
+        data.resize_framebuffer(1280, 960);
+        assert_eq!(receiver.try_recv(), Ok(Event::WindowResize(640, 480)));
+        assert_eq!(receiver.try_recv(), Ok(Event::FramebufferResize(1280, 960)));
+
+        assert_eq!(data.extent().width, 640);
+        assert_eq!(data.extent().height, 480);
+        assert_eq!(data.framebuffer_extent().width, 1280);
+        assert_eq!(data.framebuffer_extent().height, 960);
+    }
+
+    #[test]
+    fn relative_mouse_motion() {
+        let (sender, receiver) = mpsc::channel();
+        let mut data = window_data(sender);
+
+        // Only the position is sent with a normal cursor
+        data.move_cursor(10.0, 20.0);
+        data.move_cursor(15.0, 30.0);
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![Event::MousePosition(10, 20), Event::MousePosition(15, 30)]
+        );
+
+        // The first position of a disabled cursor has no motion
+        data.cursor_mode = CursorMode::Disabled;
+        data.move_cursor(100.0, 100.0);
+        data.move_cursor(90.0, 125.0);
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::MousePosition(100, 100),
+                Event::MousePosition(90, 125),
+                Event::MouseMotion(-10, 25),
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn cursor_mode() {
+        Window::init_glfw();
+        let (sender, _receiver) = mpsc::channel();
+        let mut window = Window::new("cursor_mode", 64, 64, WindowMode::Windowed, sender);
+        assert_eq!(window.cursor_mode(), CursorMode::Normal);
+
+        for &mode in &[CursorMode::Hidden, CursorMode::Disabled, CursorMode::Normal] {
+            window.set_cursor_mode(mode);
+            assert_eq!(window.cursor_mode(), mode);
+        }
+    }
+
+    #[test]
+    fn video_mode() {
+        let mode = GLFWvidmode {
+            width: 1920,
+            height: 1080,
+            redBits: 8,
+            greenBits: 8,
+            blueBits: 8,
+            refreshRate: 144,
+        };
+
+        assert_eq!(
+            VideoMode::from(&mode),
+            VideoMode {
+                width: 1920,
+                height: 1080,
+                red_bits: 8,
+                green_bits: 8,
+                blue_bits: 8,
+                refresh_rate: 144,
+            }
+        );
+    }
+
+    #[test]
+    fn borderless_fullscreen_size() {
+        let vidmode = GLFWvidmode {
+            width: 2560,
+            height: 1440,
+            redBits: 8,
+            greenBits: 8,
+            blueBits: 8,
+            refreshRate: 60,
+        };
+
+        // The requested size is ignored
+        assert_eq!(
+            WindowMode::BorderlessFullscreen.size(800, 600, &vidmode),
+            (2560, 1440)
+        );
+        assert_eq!(WindowMode::Borderless.size(800, 600, &vidmode), (800, 600));
+        assert_eq!(WindowMode::Windowed.size(-1, 600, &vidmode), (2560, 600));
+        assert_eq!(WindowMode::Fullscreen.size(-1, -1, &vidmode), (2560, 1440));
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn monitors() {
+        Window::init_glfw();
+
+        // The primary monitor is always first
+        let monitors = Window::monitors();
+        assert!(!monitors.is_empty());
+        assert_eq!(monitors[0].id, MonitorId(0));
+        assert!(!monitors[0].modes.is_empty());
+        assert!(monitors[0].modes.contains(&monitors[0].current_mode));
+
+        for (i, monitor) in monitors.iter().enumerate() {
+            assert_eq!(monitor.id, MonitorId(i));
+        }
+
+        // Disconnected monitors are not resolved
+        assert!(unsafe { Window::raw_monitor(MonitorId(0)) }.is_some());
+        assert!(unsafe { Window::raw_monitor(MonitorId(monitors.len())) }.is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn set_title_and_size() {
+        Window::init_glfw();
+        let (sender, _receiver) = mpsc::channel();
+        let mut window = Window::new("before", 64, 64, WindowMode::Windowed, sender);
+        assert_eq!(window.title(), "before");
+
+        window.set_title("after");
+        assert_eq!(window.title(), "after");
+
+        // The cached size changes before the window manager applies it
+        window.set_size(128, 96);
+        assert_eq!(window.width(), 128);
+        assert_eq!(window.height(), 96);
+    }
+
+    #[test]
+    fn key_with_modifiers() {
+        let (sender, receiver) = mpsc::channel();
+        let data = window_data(sender);
+
+        // Ctrl+S
+        data.key(KeyCode::S as i32, GLFW_PRESS, 0x2);
+        data.key(KeyCode::S as i32, GLFW_RELEASE, 0);
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(Event::KeyPress(KeyCode::S, Modifiers::CONTROL))
+        );
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(Event::KeyRelease(KeyCode::S, Modifiers::NONE))
+        );
+
+        // Caps lock is ignored
+        data.key(KeyCode::S as i32, GLFW_REPEAT, 0x1 | 0x2 | 0x10);
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(Event::KeyRepeat(
+                KeyCode::S,
+                Modifiers::SHIFT | Modifiers::CONTROL
+            ))
+        );
+
+        data.key(KeyCode::S as i32, 7, 0);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn drawable() {
+        let (sender, receiver) = mpsc::channel();
+        let mut data = window_data(sender);
+        assert!(data.drawable());
+
+        data.iconify(true);
+        assert!(!data.drawable());
+        data.iconify(false);
+        assert!(data.drawable());
+        assert_eq!(receiver.try_recv(), Ok(Event::WindowIconify(true)));
+        assert_eq!(receiver.try_recv(), Ok(Event::WindowIconify(false)));
+
+        // Some platforms only shrink the framebuffer when minimized
+        data.resize_framebuffer(0, 0);
+        assert!(!data.drawable());
+        data.resize_framebuffer(800, 0);
+        assert!(!data.drawable());
+    }
+
+    #[test]
+    fn refresh_callback() {
+        let (sender, receiver) = mpsc::channel();
+        let data = window_data(sender);
+
+        // Outside of a poll only the event is sent
+        data.refresh();
+        assert_eq!(receiver.try_recv(), Ok(Event::WindowRefresh));
+
+        let mut refreshed = Vec::new();
+        with_refresh_handler(&mut |id| refreshed.push(id), || data.refresh());
+        assert_eq!(receiver.try_recv(), Ok(Event::WindowRefresh));
+        assert_eq!(refreshed, vec![3]);
+
+        data.refresh();
+        assert_eq!(refreshed.len(), 1);
+    }
+
+    #[test]
+    fn refresh_handler_restored_on_panic() {
+        let result = std::panic::catch_unwind(|| {
+            with_refresh_handler(&mut |_| {}, || panic!("poll panicked"));
+        });
+
+        assert!(result.is_err());
+        assert!(REFRESH_HANDLER.with(|handler| handler.get()).is_none());
+    }
+}
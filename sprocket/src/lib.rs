@@ -1,22 +1,26 @@
 #[macro_use]
 pub mod macros;
 pub mod application;
+pub mod diagnostics;
 pub mod event;
 pub mod graphics;
 pub mod logger;
 pub mod math;
 pub mod utils;
 pub use application::Application;
-pub use graphics::window::{Window, WindowMode};
+pub use graphics::window::{CursorMode, Window, WindowMode};
 /// Exports logging macros
 pub use log::{debug, error, info, trace, warn};
 pub use math::{Vec2, Vec3, Vec4};
 
 mod time;
-pub use time::Time;
+pub use time::{FrameLimiter, Time};
 
 mod timer;
-pub use timer::Timer;
+pub use timer::{Scheduler, Timer};
+
+pub mod scene;
+pub use scene::Scene;
 
 // Systems
 pub mod systems;
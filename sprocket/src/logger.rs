@@ -1,15 +1,88 @@
-use std::{thread, time::Duration};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use colorful::{Color, Colorful};
 use log::{Level, LevelFilter, Metadata, Record};
 
-struct Logger;
+struct Logger {
+    /// Records are additionally written to file if any
+    file: Option<Mutex<File>>,
+    /// Level of records from modules without an override
+    level: LevelFilter,
+    /// Level overrides by module path prefix, the longest matching prefix is used
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Logger {
+    fn new(
+        file: Option<Mutex<File>>,
+        level: LevelFilter,
+        modules: Vec<(&str, LevelFilter)>,
+    ) -> Self {
+        Logger {
+            file,
+            level,
+            modules: modules
+                .into_iter()
+                .map(|(module, level)| (module.to_owned(), level))
+                .collect(),
+        }
+    }
+
+    /// Returns the level filter for records from target
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| is_in_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    /// Returns the most verbose level of all modules
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max)
+    }
+}
+
+/// Returns true if target is module or one of its submodules
+/// The crate name can be left out of module, E.g; graphics matches sprocket::graphics::vulkan
+fn is_in_module(target: &str, module: &str) -> bool {
+    let starts_with = |path: &str| {
+        path.starts_with(module)
+            && (path.len() == module.len() || path[module.len()..].starts_with("::"))
+    };
+
+    starts_with(target)
+        || target
+            .find("::")
+            .map(|i| starts_with(&target[i + 2..]))
+            .unwrap_or(false)
+}
+
+/// Describes what happens to an existing log file when the logger is initialized
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFileMode {
+    /// Clears the existing file
+    Truncate,
+    /// Renames the existing file by appending .1 to the path, replacing any previous one
+    Rotate,
+}
 
 const ERR_SLEEP_DURATION: u64 = 400;
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= log::max_level()
+        metadata.level() <= self.level(metadata.target())
     }
 
     fn log(&self, record: &Record) {
@@ -36,17 +109,134 @@ impl log::Log for Logger {
             record.target().split("::").last().unwrap_or("unknown"),
             record.args()
         );
+
+        if let Some(file) = &self.file {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+            let mut file = file.lock().unwrap();
+            // Logging failures can't be logged
+            let _ = writeln!(
+                file,
+                "{}.{:03} [{}] {} - {}",
+                timestamp.as_secs(),
+                timestamp.subsec_millis(),
+                level,
+                record.module_path().unwrap_or_else(|| record.target()),
+                record.args()
+            );
+        }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
 }
 
-static LOGGER: Logger = Logger;
-
 pub type VerbosityLevel = log::LevelFilter;
 
-pub fn init(level: LevelFilter) {
-    log::set_logger(&LOGGER)
-        .map(|()| log::set_max_level(level))
+/// Initializes the logger to write to the terminal
+/// Records from modules in modules use the level of the longest matching module path, E.g;
+/// [("graphics::vulkan", LevelFilter::Trace)], and level otherwise
+pub fn init(level: LevelFilter, modules: Vec<(&str, LevelFilter)>) {
+    set_logger(Logger::new(None, level, modules))
+}
+
+/// Installs the logger for the rest of the program
+fn set_logger(logger: Logger) {
+    let max_level = logger.max_level();
+    log::set_logger(Box::leak(Box::new(logger)))
+        .map(|()| log::set_max_level(max_level))
         .unwrap_or_else(|e| eprintln!("Failed to initialize logger {}", e))
 }
+
+/// Initializes the logger to write to both the terminal and a file at path
+/// Each line in the file is prefixed with a unix timestamp and the module path
+/// Records are filtered by level and modules like init
+/// Returns an error if the log file could not be created
+pub fn init_with_file<P: AsRef<Path>>(
+    level: LevelFilter,
+    modules: Vec<(&str, LevelFilter)>,
+    path: P,
+    mode: LogFileMode,
+) -> io::Result<()> {
+    let path = path.as_ref();
+
+    if mode == LogFileMode::Rotate && path.exists() {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        fs::rename(path, rotated)?;
+    }
+
+    set_logger(Logger::new(
+        Some(Mutex::new(File::create(path)?)),
+        level,
+        modules,
+    ));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+
+    #[test]
+    fn module_levels() {
+        let logger = Logger::new(
+            None,
+            LevelFilter::Warn,
+            vec![("graphics", LevelFilter::Trace)],
+        );
+
+        let enabled = |level, target| {
+            logger.enabled(&Metadata::builder().level(level).target(target).build())
+        };
+
+        assert!(enabled(Level::Trace, "sprocket::graphics::vulkan"));
+        assert!(enabled(Level::Trace, "graphics"));
+        assert!(!enabled(Level::Trace, "sprocket::physics"));
+        assert!(!enabled(Level::Trace, "sprocket::graphicsfoo"));
+        assert!(enabled(Level::Warn, "sprocket::physics"));
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn file_output() {
+        let path = std::env::temp_dir().join("sprocket_file_output.log");
+        let logger = Logger::new(
+            Some(Mutex::new(File::create(&path).unwrap())),
+            LevelFilter::Info,
+            Vec::new(),
+        );
+
+        let log = |level, message| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("sprocket::graphics")
+                    .module_path(Some("sprocket::graphics"))
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+
+        log(Level::Info, "first");
+        log(Level::Debug, "filtered");
+        log(Level::Warn, "second");
+        logger.flush();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("[INFO] sprocket::graphics - first"));
+        assert!(lines[1].ends_with("[WARN] sprocket::graphics - second"));
+
+        // Prefixed with a timestamp
+        assert!(lines[0].split(' ').next().unwrap().parse::<f64>().is_ok());
+    }
+}
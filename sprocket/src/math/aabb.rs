@@ -0,0 +1,134 @@
+use super::vec3::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// An axis aligned bounding box
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Creates a bounding box from min and max corners
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Creates the smallest bounding box containing all points
+    /// Returns a zero sized box at the origin if there are no points
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        let mut points = points.into_iter();
+
+        let first = match points.next() {
+            Some(point) => point,
+            None => return Aabb::new(Vec3::zero(), Vec3::zero()),
+        };
+
+        points.fold(Aabb::new(first, first), |acc, point| {
+            acc.union(&Aabb::new(point, point))
+        })
+    }
+
+    /// Returns the center of the box
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns the half size of the box along each axis
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns true if the point is inside or on the edge of the box
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.min.x
+            && point.y >= self.min.y
+            && point.z >= self.min.z
+            && point.x <= self.max.x
+            && point.y <= self.max.y
+            && point.z <= self.max.z
+    }
+
+    /// Returns true if the boxes overlap or touch
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x
+            && self.min.y <= other.max.y
+            && self.min.z <= other.max.z
+            && self.max.x >= other.min.x
+            && self.max.y >= other.min.y
+            && self.max.z >= other.min.z
+    }
+
+    /// Returns the point in the box closest to point
+    /// Returns point itself if it is inside the box
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        Vec3::max(self.min, Vec3::min(self.max, point))
+    }
+
+    /// Returns the smallest box containing both a and b
+    pub fn union(&self, other: &Self) -> Self {
+        Aabb {
+            min: Vec3::min(self.min, other.min),
+            max: Vec3::max(self.max, other.max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit() -> Aabb {
+        Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::one())
+    }
+
+    #[test]
+    fn from_points() {
+        let points = vec![
+            Vec3::new(1.0, -2.0, 0.5),
+            Vec3::new(-3.0, 4.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+        ];
+        let aabb = Aabb::from_points(points);
+        assert_eq!(aabb.min, Vec3::new(-3.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 4.0, 2.0));
+        assert_eq!(aabb.center(), Vec3::new(-1.0, 1.0, 1.0));
+        assert_eq!(aabb.extents(), Vec3::new(2.0, 3.0, 1.0));
+
+        let empty = Aabb::from_points(Vec::new());
+        assert_eq!(empty, Aabb::new(Vec3::zero(), Vec3::zero()));
+    }
+
+    #[test]
+    fn union() {
+        let other = Aabb::new(Vec3::new(0.0, 2.0, -0.5), Vec3::new(3.0, 3.0, 0.5));
+        let union = unit().union(&other);
+        assert_eq!(union.min, Vec3::new(-1.0, -1.0, -1.0));
+        assert_eq!(union.max, Vec3::new(3.0, 3.0, 1.0));
+        assert_eq!(union, other.union(&unit()));
+    }
+
+    #[test]
+    fn intersects() {
+        let touching = Aabb::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+        let apart = Aabb::new(Vec3::new(1.5, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+        let inside = Aabb::new(Vec3::zero(), Vec3::new(0.5, 0.5, 0.5));
+
+        assert!(unit().intersects(&touching));
+        assert!(!unit().intersects(&apart));
+        assert!(!apart.intersects(&unit()));
+        assert!(unit().intersects(&inside));
+        assert!(inside.intersects(&unit()));
+    }
+
+    #[test]
+    fn closest_point() {
+        let inside = Vec3::new(0.5, -0.5, 0.0);
+        assert_eq!(unit().closest_point(inside), inside);
+        assert!(unit().contains(inside));
+
+        let outside = Vec3::new(3.0, -0.5, -5.0);
+        assert!(!unit().contains(outside));
+        assert_eq!(unit().closest_point(outside), Vec3::new(1.0, -0.5, -1.0));
+    }
+}
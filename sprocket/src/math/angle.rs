@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// An angle in degrees
+/// Converts into Rad for functions taking angles
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Deg(pub f32);
+
+/// An angle in radians
+/// Plain f32 values convert into Rad and are treated as radians
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Rad(pub f32);
+
+impl From<Deg> for Rad {
+    fn from(deg: Deg) -> Self {
+        Rad(deg.0.to_radians())
+    }
+}
+
+impl From<Rad> for Deg {
+    fn from(rad: Rad) -> Self {
+        Deg(rad.0.to_degrees())
+    }
+}
+
+impl From<f32> for Rad {
+    fn from(radians: f32) -> Self {
+        Rad(radians)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Mat4;
+
+    #[test]
+    fn deg_to_rad() {
+        let rad = Rad::from(Deg(180.0));
+        assert!((rad.0 - std::f32::consts::PI).abs() < f32::EPSILON);
+        assert!((Deg::from(rad).0 - 180.0).abs() < f32::EPSILON);
+
+        // Degrees produce the same rotations as the equivalent radians
+        assert!(Mat4::rotate_y(Deg(180.0)) == Mat4::rotate_y(std::f32::consts::PI));
+        assert!(
+            Mat4::perspective(1.5, Deg(90.0), 0.1, 100.0)
+                == Mat4::perspective(1.5, std::f32::consts::FRAC_PI_2, 0.1, 100.0)
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use super::Vec3;
+use super::{Quat, Rad, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
 use std::ops;
 
+#[derive(Serialize, Deserialize)]
 pub struct Mat4([f32; 16]);
 
 impl Mat4 {
@@ -26,8 +28,9 @@ impl Mat4 {
         ])
     }
 
-    pub fn perspective(aspect: f32, fov: f32, near: f32, far: f32) -> Self {
-        let s = 1.0 / (fov * 0.5).tan();
+    /// Creates a perspective projection with a vertical field of view of fov
+    pub fn perspective<A: Into<Rad>>(aspect: f32, fov: A, near: f32, far: f32) -> Self {
+        let s = 1.0 / (fov.into().0 * 0.5).tan();
         Mat4([
             s / aspect,
             0.0,
@@ -72,6 +75,36 @@ impl Mat4 {
         ])
     }
 
+    /// Creates a view matrix looking from eye towards target
+    /// The view looks down the negative z axis like perspective expects
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let f = (target - eye).norm();
+        let s = Vec3::cross(&f, &up).norm();
+        let u = Vec3::cross(&s, &f);
+
+        Mat4([
+            s.x,
+            u.x,
+            -f.x,
+            0.0,
+
+            s.y,
+            u.y,
+            -f.y,
+            0.0,
+
+            s.z,
+            u.z,
+            -f.z,
+            0.0,
+
+            -Vec3::dot(&s, &eye),
+            -Vec3::dot(&u, &eye),
+            Vec3::dot(&f, &eye),
+            1.0,
+        ])
+    }
+
     pub fn translate(v: Vec3) -> Self {
         Mat4([
             1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, v.x, v.y, v.z, 1.0,
@@ -85,7 +118,35 @@ impl Mat4 {
         ])
     }
 
-    pub fn rotate_x(angle: f32) -> Self {
+    /// Creates a rotation matrix from a normalized quaternion
+    pub fn rotate(rotation: Quat) -> Self {
+        let Quat { x, y, z, w } = rotation;
+
+        Mat4([
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y + w * z),
+            2.0 * (x * z - w * y),
+            0.0,
+
+            2.0 * (x * y - w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z + w * x),
+            0.0,
+
+            2.0 * (x * z + w * y),
+            2.0 * (y * z - w * x),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ])
+    }
+
+    pub fn rotate_x<A: Into<Rad>>(angle: A) -> Self {
+        let angle = angle.into().0;
         let cosa = angle.cos();
         let sina = angle.sin();
 
@@ -94,7 +155,8 @@ impl Mat4 {
         ])
     }
 
-    pub fn rotate_z(angle: f32) -> Self {
+    pub fn rotate_z<A: Into<Rad>>(angle: A) -> Self {
+        let angle = angle.into().0;
         let cosa = angle.cos();
         let sina = angle.sin();
 
@@ -103,7 +165,8 @@ impl Mat4 {
         ])
     }
 
-    pub fn rotate_y(angle: f32) -> Self {
+    pub fn rotate_y<A: Into<Rad>>(angle: A) -> Self {
+        let angle = angle.into().0;
         let cosa = angle.cos();
         let sina = angle.sin();
 
@@ -111,6 +174,38 @@ impl Mat4 {
             cosa, 0.0, sina, 0.0, 0.0, 1.0, 0.0, 0.0, -sina, 0.0, cosa, 0.0, 0.0, 0.0, 0.0, 1.0,
         ])
     }
+
+    /// Transforms a point by the matrix
+    /// The point is treated as a row vector with w = 1
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(
+            point.x * m[0] + point.y * m[4] + point.z * m[8] + m[12],
+            point.x * m[1] + point.y * m[5] + point.z * m[9] + m[13],
+            point.x * m[2] + point.y * m[6] + point.z * m[10] + m[14],
+        )
+    }
+
+    /// Returns row i
+    /// The translation is in row 3 as points are row vectors
+    /// Panics if i is out of bounds
+    pub fn row(&self, i: usize) -> Vec4 {
+        Vec4::new(self[(i, 0)], self[(i, 1)], self[(i, 2)], self[(i, 3)])
+    }
+
+    /// Returns column i
+    /// Panics if i is out of bounds
+    pub fn col(&self, i: usize) -> Vec4 {
+        Vec4::new(self[(0, i)], self[(1, i)], self[(2, i)], self[(3, i)])
+    }
+
+    /// Returns true if all elements differ by at most epsilon
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    }
 }
 
 impl ops::Mul for Mat4 {
@@ -128,6 +223,20 @@ impl ops::Mul for Mat4 {
     }
 }
 
+impl Clone for Mat4 {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for Mat4 {}
+
+impl PartialEq for Mat4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
 impl std::fmt::Display for Mat4 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -141,14 +250,94 @@ impl std::fmt::Display for Mat4 {
     }
 }
 
+/// Returns the index into the row major elements of (row, col)
+/// Panics if either is out of bounds, instead of wrapping into the next row
+fn element_index((row, col): (usize, usize)) -> usize {
+    assert!(
+        row < 4 && col < 4,
+        "Matrix index ({}, {}) out of bounds",
+        row,
+        col
+    );
+    row * 4 + col
+}
+
+/// Indexes the element at (row, col)
 impl std::ops::Index<(usize, usize)> for Mat4 {
     type Output = f32;
     fn index(&self, index: (usize, usize)) -> &Self::Output {
-        &self.0[index.0 * 4 + index.1]
+        &self.0[element_index(index)]
     }
 }
 impl std::ops::IndexMut<(usize, usize)> for Mat4 {
     fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
-        &mut self.0[index.0 * 4 + index.1]
+        &mut self.0[element_index(index)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index() {
+        let mut m = Mat4::translate(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(0, 3)], 0.0);
+        assert_eq!(m[(3, 1)], 2.0);
+
+        assert_eq!(m.row(3), Vec4::new(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(m.col(2), Vec4::new(0.0, 0.0, 1.0, 3.0));
+
+        m[(3, 2)] = 5.0;
+        assert_eq!(m[(3, 2)], 5.0);
+        assert!(m == Mat4::translate(Vec3::new(1.0, 2.0, 5.0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_bounds() {
+        // Would wrap into the first element of the next row without the bounds check
+        let _ = Mat4::identity()[(0, 4)];
+    }
+
+    #[test]
+    fn approx_eq() {
+        let m = Mat4::translate(Vec3::new(1.0, 2.0, 3.0));
+        assert!(m == Mat4::translate(Vec3::new(1.0, 2.0, 3.0)));
+
+        let mut near = m;
+        near[(3, 0)] += 1e-4;
+        assert!(m != near);
+        assert!(m.approx_eq(&near, 1e-3));
+        assert!(!m.approx_eq(&near, 1e-5));
+    }
+
+    #[test]
+    fn serde() {
+        let m = Mat4::translate(Vec3::new(1.0, 2.0, 3.0));
+
+        // Stored as a flat array in row order
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(
+            json,
+            "[1.0,0.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,0.0,1.0,0.0,1.0,2.0,3.0,1.0]"
+        );
+        assert!(serde_json::from_str::<Mat4>(&json).unwrap() == m);
+    }
+
+    #[test]
+    fn look_at() {
+        let eye = Vec3::new(0.0, 0.0, 5.0);
+        let view = Mat4::look_at(eye, Vec3::zero(), Vec3::up());
+
+        // The target is down the negative z axis and the eye is at the origin of view space
+        assert!(view
+            .transform_point(Vec3::zero())
+            .approx_eq(&Vec3::new(0.0, 0.0, -5.0), 1e-6));
+        assert!(view.transform_point(eye).approx_eq(&Vec3::zero(), 1e-6));
+        assert!(view
+            .transform_point(Vec3::new(1.0, 2.0, 0.0))
+            .approx_eq(&Vec3::new(1.0, 2.0, -5.0), 1e-6));
     }
 }
@@ -0,0 +1,170 @@
+use super::angle::Rad;
+use super::vec3::Vec3;
+use serde::{Deserialize, Serialize};
+use std::ops;
+
+/// A quaternion representing a rotation in 3D space
+/// Should be kept normalized to represent a valid rotation
+#[derive(Serialize, Deserialize)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    /// Creates a quaternion representing no rotation
+    pub fn identity() -> Self {
+        Quat {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        }
+    }
+
+    /// Creates a rotation of angle around axis
+    /// The axis does not need to be normalized
+    pub fn axis_angle<A: Into<Rad>>(axis: Vec3, angle: A) -> Self {
+        let angle = angle.into().0;
+        let axis = axis.norm();
+        let s = (angle * 0.5).sin();
+
+        Quat {
+            x: axis.x * s,
+            y: axis.y * s,
+            z: axis.z * s,
+            w: (angle * 0.5).cos(),
+        }
+    }
+
+    /// Returns the length of the quaternion
+    pub fn mag(&self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
+    }
+
+    /// Returns the quaternion scaled to unit length
+    pub fn norm(&self) -> Self {
+        let mag = self.mag();
+        Quat {
+            x: self.x / mag,
+            y: self.y / mag,
+            z: self.z / mag,
+            w: self.w / mag,
+        }
+    }
+
+    /// Returns the inverse rotation of a normalized quaternion
+    pub fn conjugate(&self) -> Self {
+        Quat {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: self.w,
+        }
+    }
+}
+
+// Traits
+impl Clone for Quat {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for Quat {}
+
+impl std::fmt::Display for Quat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}, {}, {})", self.x, self.y, self.z, self.w)
+    }
+}
+
+impl std::fmt::Debug for Quat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}, {}, {})", self.x, self.y, self.z, self.w)
+    }
+}
+
+/// Combines two rotations
+/// The resulting rotation applies rhs first and then self
+impl ops::Mul for Quat {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Quat {
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        }
+    }
+}
+
+/// Rotates a vector
+impl ops::Mul<Vec3> for Quat {
+    type Output = Vec3;
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = Vec3::cross(&q, &rhs) * 2.0;
+        rhs + t * self.w + Vec3::cross(&q, &t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Deg;
+
+    fn approx_eq(a: Quat, b: Quat) -> bool {
+        (a.x - b.x).abs() < 1e-6
+            && (a.y - b.y).abs() < 1e-6
+            && (a.z - b.z).abs() < 1e-6
+            && (a.w - b.w).abs() < 1e-6
+    }
+
+    #[test]
+    fn axis_angle() {
+        // The axis is normalized
+        let q = Quat::axis_angle(Vec3::new(0.0, 2.0, 0.0), Deg(90.0));
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(approx_eq(q, Quat::new(0.0, half, 0.0, half)));
+        assert!((q.mag() - 1.0).abs() < 1e-6);
+
+        assert!(approx_eq(
+            Quat::axis_angle(Vec3::right(), 0.0),
+            Quat::identity()
+        ));
+    }
+
+    #[test]
+    fn rotate_vector() {
+        let q = Quat::axis_angle(Vec3::up(), Deg(90.0));
+        assert!((q * Vec3::forward()).approx_eq(&Vec3::right(), 1e-6));
+        assert!((q * Vec3::up()).approx_eq(&Vec3::up(), 1e-6));
+        assert!((Quat::identity() * Vec3::new(1.0, 2.0, 3.0)) == Vec3::new(1.0, 2.0, 3.0));
+
+        // The conjugate undoes the rotation
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        assert!((q.conjugate() * (q * v)).approx_eq(&v, 1e-6));
+    }
+
+    #[test]
+    fn combine() {
+        let a = Quat::axis_angle(Vec3::up(), Deg(30.0));
+        let b = Quat::axis_angle(Vec3::up(), Deg(60.0));
+        assert!(approx_eq(a * b, Quat::axis_angle(Vec3::up(), Deg(90.0))));
+        assert!(approx_eq(a * Quat::identity(), a));
+
+        // rhs is applied first
+        let yaw = Quat::axis_angle(Vec3::up(), Deg(90.0));
+        let pitch = Quat::axis_angle(Vec3::right(), Deg(90.0));
+        let v = Vec3::forward();
+        assert!(((yaw * pitch) * v).approx_eq(&(yaw * (pitch * v)), 1e-6));
+        assert!(!((yaw * pitch) * v).approx_eq(&(pitch * (yaw * v)), 1e-3));
+    }
+}
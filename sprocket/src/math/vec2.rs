@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::ops;
 /// Representation of 3D vectors and points
+#[repr(C)]
+#[derive(Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -11,6 +14,12 @@ impl Vec2 {
         Vec2 { x, y }
     }
 
+    /// Returns the components as a slice, E.g; for writing into uniform or vertex buffers
+    pub fn as_slice(&self) -> &[f32] {
+        // The struct is repr(C) with only f32 fields
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const f32, 2) }
+    }
+
     /// Creates a vector with all components being zero
     pub fn zero() -> Self {
         Self { x: 0.0, y: 0.0 }
@@ -53,6 +62,17 @@ impl Vec2 {
         a - normal * Self::dot(&a, &normal)
     }
 
+    /// Returns the distance between two points
+    pub fn distance(a: Self, b: Self) -> f32 {
+        (b - a).mag()
+    }
+
+    /// Returns the squared distance between two points
+    /// Is faster than distance due to not using sqrt
+    pub fn distance_sqr(a: Self, b: Self) -> f32 {
+        (b - a).sqrmag()
+    }
+
     /// Linearly interpolates between two vectors with t
     /// When t > 1, lerp(a, b) = b
     /// When t = 0, lerp(a, b) = t
@@ -91,8 +111,28 @@ impl Vec2 {
     }
 
     /// Returns the normal version the vector
+    /// A zero length vector gives non finite components, use try_norm if the vector can be zero
     pub fn norm(&self) -> Vec2 {
-        *self / self.mag()
+        let mag = self.mag();
+        debug_assert!(mag > 0.0, "Normalizing a zero length vector");
+        *self / mag
+    }
+
+    /// Returns the normal version the vector
+    /// Returns None if the vector is zero length
+    pub fn try_norm(&self) -> Option<Vec2> {
+        let mag = self.mag();
+        if mag > f32::EPSILON {
+            Some(*self / mag)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if all components differ by at most epsilon
+    /// Prefer over == when comparing results of float arithmetic
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon && (self.y - other.y).abs() <= epsilon
     }
 }
 
@@ -105,6 +145,12 @@ impl Clone for Vec2 {
 
 impl Copy for Vec2 {}
 
+impl PartialEq for Vec2 {
+    fn eq(&self, other: &Self) -> bool {
+        self.x == other.x && self.y == other.y
+    }
+}
+
 impl std::fmt::Display for Vec2 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {})", self.x, self.y)
@@ -239,3 +285,63 @@ impl From<(i32, i32)> for Vec2 {
         }
     }
 }
+
+impl From<[f32; 2]> for Vec2 {
+    fn from(a: [f32; 2]) -> Self {
+        Vec2 { x: a[0], y: a[1] }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let a = Vec2::new(1.0, 1.0);
+        let b = Vec2::new(4.0, 5.0);
+        assert_eq!(Vec2::distance(a, b), 5.0);
+        assert_eq!(Vec2::distance_sqr(b, a), 25.0);
+    }
+
+    #[test]
+    fn approx_eq() {
+        let v = Vec2::new(1.0, 2.0);
+        assert_eq!(v, Vec2::new(1.0, 2.0));
+        assert!(v != Vec2::new(1.0, 2.0 + 1e-4));
+
+        // Within epsilon but not exactly equal
+        let near = Vec2::new(1.0, 2.0 + 1e-4);
+        assert!(v.approx_eq(&near, 1e-3));
+        assert!(!v.approx_eq(&near, 1e-5));
+
+        // Normalizing rounds, so a renormalized vector is only approximately equal
+        let norm = (v * 3.0).norm();
+        assert!(norm.approx_eq(&v.norm().norm(), 1e-6));
+        assert!(!norm.approx_eq(&-norm, 1e-6));
+    }
+
+    #[test]
+    fn try_norm() {
+        assert_eq!(Vec2::zero().try_norm(), None);
+
+        let norm = Vec2::new(3.0, -4.0).try_norm().unwrap();
+        assert!((norm.mag() - 1.0).abs() < 1e-6);
+        assert!(norm.approx_eq(&Vec2::new(3.0, -4.0).norm(), 1e-6));
+    }
+
+    #[test]
+    fn array_conversions() {
+        let v = Vec2::new(1.0, 2.0);
+        let a: [f32; 2] = v.into();
+        assert_eq!(a, [1.0, 2.0]);
+        assert_eq!(Vec2::from(a), v);
+        assert_eq!(v.as_slice(), &a);
+    }
+}
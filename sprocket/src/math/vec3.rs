@@ -1,6 +1,10 @@
+use super::quat::Quat;
 use super::vec2::Vec2;
+use serde::{Deserialize, Serialize};
 use std::ops;
 /// Representation of 3D vectors and points
+#[repr(C)]
+#[derive(Serialize, Deserialize)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -13,6 +17,12 @@ impl Vec3 {
         Vec3 { x, y, z }
     }
 
+    /// Returns the components as a slice, E.g; for writing into uniform or vertex buffers
+    pub fn as_slice(&self) -> &[f32] {
+        // The struct is repr(C) with only f32 fields
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const f32, 3) }
+    }
+
     pub fn from_vec3(xy: Vec2, z: f32) -> Self {
         Vec3 {
             x: xy.x,
@@ -66,6 +76,11 @@ impl Vec3 {
         }
     }
 
+    /// Returns the vector rotated by a normalized quaternion
+    pub fn rotate(&self, rotation: Quat) -> Self {
+        rotation * *self
+    }
+
     /// Returns the dot product of two vectors
     /// The dot product is the cosine of the angle between a, b
     /// Example outputs assuming a, b are normalized
@@ -103,6 +118,29 @@ impl Vec3 {
         a - normal * Self::dot(&a, &normal)
     }
 
+    /// Returns the distance between two points
+    pub fn distance(a: Self, b: Self) -> f32 {
+        (b - a).mag()
+    }
+
+    /// Returns the squared distance between two points
+    /// Is faster than distance due to not using sqrt
+    pub fn distance_sqr(a: Self, b: Self) -> f32 {
+        (b - a).sqrmag()
+    }
+
+    /// Returns the angle between two vectors in radians
+    /// Returns 0 if either vector is zero
+    pub fn angle(a: Self, b: Self) -> f32 {
+        let mag = a.mag() * b.mag();
+        if mag == 0.0 {
+            return 0.0;
+        }
+
+        // Clamp to avoid NaN from rounding errors on parallel vectors
+        (Self::dot(&a, &b) / mag).clamp(-1.0, 1.0).acos()
+    }
+
     /// Linearly interpolates between two vectors with t
     /// When t > 1, lerp(a, b) = b
     /// When t = 0, lerp(a, b) = t
@@ -126,6 +164,16 @@ impl Vec3 {
         a * (1.0 - t) + b * t
     }
 
+    /// Returns a vector of the smallest components of a and b
+    pub fn min(a: Self, b: Self) -> Self {
+        Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+    }
+
+    /// Returns a vector of the largest components of a and b
+    pub fn max(a: Self, b: Self) -> Self {
+        Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+    }
+
     // Instance method
 
     /// Returns the magnitude/length of the vector
@@ -141,52 +189,44 @@ impl Vec3 {
     }
 
     /// Returns the normal version the vector
+    /// A zero length vector gives non finite components, use try_norm if the vector can be zero
     pub fn norm(&self) -> Vec3 {
-        *self / self.mag()
+        let mag = self.mag();
+        debug_assert!(mag > 0.0, "Normalizing a zero length vector");
+        *self / mag
+    }
+
+    /// Returns the normal version the vector
+    /// Returns None if the vector is zero length
+    pub fn try_norm(&self) -> Option<Vec3> {
+        let mag = self.mag();
+        if mag > f32::EPSILON {
+            Some(*self / mag)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the vector with its magnitude limited to max
+    /// The direction is preserved, a negative max is treated as 0
+    pub fn clamp_magnitude(self, max: f32) -> Vec3 {
+        let max = max.max(0.0);
+        let sqrmag = self.sqrmag();
+        if sqrmag > max * max {
+            self * (max / sqrmag.sqrt())
+        } else {
+            self
+        }
     }
 
     /// Returns the smallest component
     pub fn smallest(&self) -> f32 {
-        if self.x < self.y {
-            if self.x < self.z {
-                return self.x;
-            }
-            return self.z;
-        } else if self.y < self.x {
-            if self.y < self.z {
-                return self.y;
-            }
-            return self.z;
-        } else if self.z < self.x {
-            if self.z < self.y {
-                return self.z;
-            }
-            return self.y;
-        }
-        // All are equal
-        self.x
+        self.x.min(self.y).min(self.z)
     }
 
     /// Returns the largest component
     pub fn largest(&self) -> f32 {
-        if self.x > self.y {
-            if self.x > self.z {
-                return self.x;
-            }
-            return self.z;
-        } else if self.y > self.x {
-            if self.y > self.z {
-                return self.y;
-            }
-            return self.z;
-        } else if self.z > self.x {
-            if self.z > self.y {
-                return self.z;
-            }
-            return self.y;
-        }
-        // All are equal
-        self.x
+        self.x.max(self.y).max(self.z)
     }
 
     pub fn xy(&self) -> Vec2 {
@@ -195,6 +235,14 @@ impl Vec3 {
             y: self.y,
         }
     }
+
+    /// Returns true if all components differ by at most epsilon
+    /// Prefer over == when comparing results of float arithmetic
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+    }
 }
 
 // Traits
@@ -206,6 +254,12 @@ impl Clone for Vec3 {
 
 impl Copy for Vec3 {}
 
+impl PartialEq for Vec3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.x == other.x && self.y == other.y && self.z == other.z
+    }
+}
+
 impl std::fmt::Display for Vec3 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {}, {})", self.x, self.y, self.z)
@@ -337,6 +391,12 @@ impl ops::DivAssign<f32> for Vec3 {
     }
 }
 
+impl From<Vec2> for Vec3 {
+    fn from(xy: Vec2) -> Self {
+        Vec3::from_vec3(xy, 0.0)
+    }
+}
+
 impl From<(f32, f32, f32)> for Vec3 {
     fn from(t: (f32, f32, f32)) -> Self {
         Vec3 {
@@ -356,3 +416,144 @@ impl From<(i32, i32, i32)> for Vec3 {
         }
     }
 }
+
+impl From<[f32; 3]> for Vec3 {
+    fn from(a: [f32; 3]) -> Self {
+        Vec3 {
+            x: a[0],
+            y: a[1],
+            z: a[2],
+        }
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let a = Vec3::zero();
+        let b = Vec3::new(3.0, 4.0, 0.0);
+        assert_eq!(Vec3::distance(a, b), 5.0);
+        assert_eq!(Vec3::distance(b, a), 5.0);
+        assert_eq!(Vec3::distance_sqr(a, b), 25.0);
+    }
+
+    #[test]
+    fn angle() {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+        assert!((Vec3::angle(Vec3::right(), Vec3::up()) - FRAC_PI_2).abs() < 1e-6);
+        assert!((Vec3::angle(Vec3::right(), Vec3::new(2.0, 2.0, 0.0)) - FRAC_PI_4).abs() < 1e-6);
+        assert!((Vec3::angle(Vec3::up(), -Vec3::up()) - PI).abs() < 1e-6);
+
+        // Parallel vectors do not give NaN from rounding
+        let v = Vec3::new(0.1, 0.2, 0.3);
+        let parallel = Vec3::angle(v, v * 3.0);
+        assert!(!parallel.is_nan() && parallel < 1e-3);
+        assert_eq!(Vec3::angle(v, Vec3::zero()), 0.0);
+    }
+
+    #[test]
+    fn smallest_and_largest() {
+        // All orders of distinct components and of ties, with the smallest and largest
+        let cases = [
+            ([1.0, 2.0, 3.0], 1.0, 3.0),
+            ([2.0, 2.0, 1.0], 1.0, 2.0),
+            ([2.0, 2.0, 3.0], 2.0, 3.0),
+            ([-1.0, -3.0, -3.0], -3.0, -1.0),
+            ([1.0, 1.0, 1.0], 1.0, 1.0),
+        ];
+
+        for &([a, b, c], smallest, largest) in &cases {
+            let permutations = [
+                [a, b, c],
+                [a, c, b],
+                [b, a, c],
+                [b, c, a],
+                [c, a, b],
+                [c, b, a],
+            ];
+
+            for &[x, y, z] in &permutations {
+                let v = Vec3::new(x, y, z);
+                assert_eq!(v.smallest(), smallest, "{:?}", v);
+                assert_eq!(v.largest(), largest, "{:?}", v);
+            }
+        }
+    }
+
+    #[test]
+    fn component_min_max() {
+        let a = Vec3::new(-1.0, 2.0, -3.0);
+        let b = Vec3::new(1.0, -2.0, -4.0);
+
+        assert_eq!(Vec3::min(a, b), Vec3::new(-1.0, -2.0, -4.0));
+        assert_eq!(Vec3::max(a, b), Vec3::new(1.0, 2.0, -3.0));
+        assert_eq!(Vec3::min(b, a), Vec3::min(a, b));
+        assert_eq!(Vec3::max(a, a), a);
+    }
+
+    #[test]
+    fn from_vec2() {
+        assert_eq!(Vec3::from(Vec2::new(1.0, -2.0)), Vec3::new(1.0, -2.0, 0.0));
+    }
+
+    #[test]
+    fn serde() {
+        let v = Vec3::new(1.0, -2.5, 3.0);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, r#"{"x":1.0,"y":-2.5,"z":3.0}"#);
+        assert_eq!(serde_json::from_str::<Vec3>(&json).unwrap(), v);
+    }
+
+    #[test]
+    fn clamp_magnitude() {
+        let v = Vec3::new(3.0, 4.0, 0.0);
+        assert_eq!(v.clamp_magnitude(10.0), v);
+        assert_eq!(v.clamp_magnitude(2.5), Vec3::new(1.5, 2.0, 0.0));
+        assert_eq!(v.clamp_magnitude(-1.0), Vec3::zero());
+    }
+
+    #[test]
+    fn approx_eq() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(v, Vec3::new(1.0, 2.0, 3.0));
+        assert!(v != Vec3::new(1.0, 2.0 + 1e-4, 3.0));
+
+        // Within epsilon but not exactly equal
+        let near = Vec3::new(1.0, 2.0 + 1e-4, 3.0);
+        assert!(v.approx_eq(&near, 1e-3));
+        assert!(!v.approx_eq(&near, 1e-5));
+
+        // Normalizing rounds, so a renormalized vector is only approximately equal
+        let norm = (v * 3.0).norm();
+        assert!(norm.approx_eq(&v.norm().norm(), 1e-6));
+        assert!(!norm.approx_eq(&-norm, 1e-6));
+    }
+
+    #[test]
+    fn try_norm() {
+        assert_eq!(Vec3::zero().try_norm(), None);
+
+        let norm = Vec3::new(3.0, -4.0, 12.0).try_norm().unwrap();
+        assert!((norm.mag() - 1.0).abs() < 1e-6);
+        assert!(norm.approx_eq(&Vec3::new(3.0, -4.0, 12.0).norm(), 1e-6));
+    }
+
+    #[test]
+    fn array_conversions() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let a: [f32; 3] = v.into();
+        assert_eq!(a, [1.0, 2.0, 3.0]);
+        assert_eq!(Vec3::from(a), v);
+        assert_eq!(v.as_slice(), &a);
+    }
+}
@@ -1,6 +1,9 @@
 use super::vec3::Vec3;
+use serde::{Deserialize, Serialize};
 use std::ops;
 /// Representation of 3D vectors and points
+#[repr(C)]
+#[derive(Serialize, Deserialize)]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
@@ -14,6 +17,12 @@ impl Vec4 {
         Vec4 { x, y, z, w }
     }
 
+    /// Returns the components as a slice, E.g; for writing into uniform or vertex buffers
+    pub fn as_slice(&self) -> &[f32] {
+        // The struct is repr(C) with only f32 fields
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const f32, 4) }
+    }
+
     pub fn from_vec3(xyz: Vec3, w: f32) -> Self {
         Vec4 {
             x: xyz.x,
@@ -88,6 +97,16 @@ impl Vec4 {
         a * (1.0 - t) + b * t
     }
 
+    /// Returns a vector of the smallest components of a and b
+    pub fn min(a: Self, b: Self) -> Self {
+        Vec4::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z), a.w.min(b.w))
+    }
+
+    /// Returns a vector of the largest components of a and b
+    pub fn max(a: Self, b: Self) -> Self {
+        Vec4::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z), a.w.max(b.w))
+    }
+
     // Instance method
 
     /// Returns the magnitude/length of the vector
@@ -103,8 +122,22 @@ impl Vec4 {
     }
 
     /// Returns the normal version the vector
+    /// A zero length vector gives non finite components, use try_norm if the vector can be zero
     pub fn norm(&self) -> Vec4 {
-        *self / self.mag()
+        let mag = self.mag();
+        debug_assert!(mag > 0.0, "Normalizing a zero length vector");
+        *self / mag
+    }
+
+    /// Returns the normal version the vector
+    /// Returns None if the vector is zero length
+    pub fn try_norm(&self) -> Option<Vec4> {
+        let mag = self.mag();
+        if mag > f32::EPSILON {
+            Some(*self / mag)
+        } else {
+            None
+        }
     }
 
     pub fn xyz(&self) -> Vec3 {
@@ -114,6 +147,15 @@ impl Vec4 {
             z: self.z,
         }
     }
+
+    /// Returns true if all components differ by at most epsilon
+    /// Prefer over == when comparing results of float arithmetic
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+            && (self.w - other.w).abs() <= epsilon
+    }
 }
 
 // Traits
@@ -125,6 +167,12 @@ impl Clone for Vec4 {
 
 impl Copy for Vec4 {}
 
+impl PartialEq for Vec4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.x == other.x && self.y == other.y && self.z == other.z && self.w == other.w
+    }
+}
+
 impl std::fmt::Display for Vec4 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {}, {}, {})", self.x, self.y, self.z, self.w)
@@ -267,6 +315,12 @@ impl ops::DivAssign<f32> for Vec4 {
     }
 }
 
+impl From<Vec3> for Vec4 {
+    fn from(xyz: Vec3) -> Self {
+        Vec4::from_vec3(xyz, 0.0)
+    }
+}
+
 impl From<(f32, f32, f32, f32)> for Vec4 {
     fn from(t: (f32, f32, f32, f32)) -> Self {
         Vec4 {
@@ -288,3 +342,76 @@ impl From<(i32, i32, i32, i32)> for Vec4 {
         }
     }
 }
+
+impl From<[f32; 4]> for Vec4 {
+    fn from(a: [f32; 4]) -> Self {
+        Vec4 {
+            x: a[0],
+            y: a[1],
+            z: a[2],
+            w: a[3],
+        }
+    }
+}
+
+impl From<Vec4> for [f32; 4] {
+    fn from(v: Vec4) -> Self {
+        [v.x, v.y, v.z, v.w]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn component_min_max() {
+        let a = Vec4::new(-1.0, 2.0, -3.0, 0.0);
+        let b = Vec4::new(1.0, -2.0, -4.0, -0.5);
+
+        assert_eq!(Vec4::min(a, b), Vec4::new(-1.0, -2.0, -4.0, -0.5));
+        assert_eq!(Vec4::max(a, b), Vec4::new(1.0, 2.0, -3.0, 0.0));
+        assert_eq!(Vec4::min(b, a), Vec4::min(a, b));
+    }
+
+    #[test]
+    fn from_vec3() {
+        let v = Vec4::from(Vec3::new(1.0, -2.0, 3.0));
+        assert_eq!(v, Vec4::new(1.0, -2.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn approx_eq() {
+        let v = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(v, Vec4::new(1.0, 2.0, 3.0, 4.0));
+        assert!(v != Vec4::new(1.0, 2.0, 3.0 + 1e-4, 4.0));
+
+        // Within epsilon but not exactly equal
+        let near = Vec4::new(1.0, 2.0, 3.0 + 1e-4, 4.0);
+        assert!(v.approx_eq(&near, 1e-3));
+        assert!(!v.approx_eq(&near, 1e-5));
+
+        // Normalizing rounds, so a renormalized vector is only approximately equal
+        let norm = (v * 3.0).norm();
+        assert!(norm.approx_eq(&v.norm().norm(), 1e-6));
+        assert!(!norm.approx_eq(&-norm, 1e-6));
+    }
+
+    #[test]
+    fn try_norm() {
+        assert_eq!(Vec4::zero().try_norm(), None);
+
+        let norm = Vec4::new(3.0, -4.0, 12.0, 1.0).try_norm().unwrap();
+        assert!((norm.mag() - 1.0).abs() < 1e-6);
+        assert!(norm.approx_eq(&Vec4::new(3.0, -4.0, 12.0, 1.0).norm(), 1e-6));
+    }
+
+    #[test]
+    fn array_conversions() {
+        let v = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        let a: [f32; 4] = v.into();
+        assert_eq!(a, [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(Vec4::from(a), v);
+        assert_eq!(v.as_slice(), &a);
+    }
+}
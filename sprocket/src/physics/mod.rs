@@ -1,2 +1,4 @@
+pub mod spatial_grid;
 pub mod transform;
+pub use spatial_grid::SpatialGrid;
 pub use transform::Transform;
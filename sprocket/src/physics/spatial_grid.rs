@@ -0,0 +1,276 @@
+use crate::ecs::Entity;
+use crate::math::*;
+use std::collections::{HashMap, HashSet};
+
+/// The integer coordinates of a cell
+type Cell = (i32, i32, i32);
+
+/// Entities overlapping more cells than this are not stored in cells but checked by every query
+const MAX_ENTITY_CELLS: u64 = 512;
+
+/// Buckets the bounding boxes of entities into uniform cells for broad phase queries, E.g; finding
+/// the entities near a point for collision or interaction
+/// Each entity is stored in every cell its box overlaps
+/// Moving entities are updated by inserting them again, or by rebuilding the grid each frame
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    /// The box each entity was inserted with
+    /// Used for exact checks of the entities in the queried cells and for removal
+    bounds: HashMap<Entity, Aabb>,
+    /// Entities too large to store in cells, E.g; with infinite bounds
+    oversized: HashSet<Entity>,
+}
+
+impl SpatialGrid {
+    /// Creates an empty grid with cubic cells of cell_size
+    /// The cell size should be about the size of the entities and the query radius, too small
+    /// cells store large entities in many cells, and too large cells need more exact checks
+    /// Panics if cell_size is not positive and finite
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "Cell size must be positive and finite, got {}",
+            cell_size
+        );
+
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            bounds: HashMap::new(),
+            oversized: HashSet::new(),
+        }
+    }
+
+    /// Clears the grid and inserts all entities
+    pub fn rebuild<I: IntoIterator<Item = (Entity, Aabb)>>(&mut self, entities: I) {
+        self.clear();
+        for (entity, bounds) in entities {
+            self.insert(entity, bounds);
+        }
+    }
+
+    /// Inserts an entity with its bounding box
+    /// An entity already in the grid is moved to the new bounds
+    /// A point sized box can be used for entities without a size, E.g;
+    /// Aabb::new(transform.position, transform.position)
+    pub fn insert(&mut self, entity: Entity, bounds: Aabb) {
+        self.remove(entity);
+
+        let range = self.cell_range(&bounds);
+        if cell_count(range) > MAX_ENTITY_CELLS {
+            self.oversized.insert(entity);
+        } else {
+            for cell in cells_in(range) {
+                self.cells.entry(cell).or_default().push(entity);
+            }
+        }
+        self.bounds.insert(entity, bounds);
+    }
+
+    /// Removes an entity from the grid
+    /// Returns false if the entity was not in the grid
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let bounds = match self.bounds.remove(&entity) {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+
+        if self.oversized.remove(&entity) {
+            return true;
+        }
+
+        for cell in cells_in(self.cell_range(&bounds)) {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|&other| other != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        true
+    }
+
+    /// Removes all entities
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.bounds.clear();
+        self.oversized.clear();
+    }
+
+    /// Returns the number of entities in the grid
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    /// Returns the box an entity was inserted with
+    pub fn bounds(&self, entity: Entity) -> Option<&Aabb> {
+        self.bounds.get(&entity)
+    }
+
+    /// Returns the entities whose boxes overlap or touch area
+    pub fn query_aabb(&self, area: Aabb) -> Vec<Entity> {
+        self.query(&area, |bounds| bounds.intersects(&area))
+    }
+
+    /// Returns the entities whose boxes are within radius of point
+    pub fn nearby(&self, point: Vec3, radius: f32) -> Vec<Entity> {
+        let extents = Vec3::new(radius, radius, radius);
+        let area = Aabb::new(point - extents, point + extents);
+
+        self.query(&area, |bounds| {
+            Vec3::distance_sqr(bounds.closest_point(point), point) <= radius * radius
+        })
+    }
+
+    /// Returns the entities in the cells overlapping area that match filter
+    /// Each entity is returned once even if it is stored in several of the cells
+    /// If area covers more cells than there are entities all entities are checked instead
+    fn query<F: Fn(&Aabb) -> bool>(&self, area: &Aabb, filter: F) -> Vec<Entity> {
+        let range = self.cell_range(area);
+        if cell_count(range) > self.bounds.len() as u64 {
+            return self
+                .bounds
+                .iter()
+                .filter(|(_, bounds)| filter(bounds))
+                .map(|(&entity, _)| entity)
+                .collect();
+        }
+
+        let mut visited = HashSet::new();
+        let mut result: Vec<Entity> = self
+            .oversized
+            .iter()
+            .copied()
+            .filter(|entity| filter(&self.bounds[entity]))
+            .collect();
+
+        for cell in cells_in(range) {
+            let entities = match self.cells.get(&cell) {
+                Some(entities) => entities,
+                None => continue,
+            };
+
+            for &entity in entities {
+                if visited.insert(entity) && filter(&self.bounds[&entity]) {
+                    result.push(entity);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns the cell containing point
+    fn cell(&self, point: Vec3) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Returns the first and last cell overlapped by bounds
+    fn cell_range(&self, bounds: &Aabb) -> (Cell, Cell) {
+        (self.cell(bounds.min), self.cell(bounds.max))
+    }
+}
+
+/// Returns the number of cells in range without overflowing
+fn cell_count((min, max): (Cell, Cell)) -> u64 {
+    let len = |min: i32, max: i32| (max as i64 - min as i64 + 1).max(0) as u64;
+
+    len(min.0, max.0)
+        .saturating_mul(len(min.1, max.1))
+        .saturating_mul(len(min.2, max.2))
+}
+
+/// Returns the cells in range
+fn cells_in((min, max): (Cell, Cell)) -> impl Iterator<Item = Cell> {
+    (min.0..=max.0).flat_map(move |x| {
+        (min.1..=max.1).flat_map(move |y| (min.2..=max.2).map(move |z| (x, y, z)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::EntityManager;
+
+    /// Returns a deterministic point in a 100 unit cube
+    fn point(i: usize) -> Vec3 {
+        let hash = |seed: usize| ((i * 7919 + seed) % 1000) as f32 / 10.0;
+        Vec3::new(hash(13), hash(5003), hash(9241))
+    }
+
+    fn sorted(entities: Vec<Entity>) -> Vec<usize> {
+        let mut indices: Vec<usize> = entities.into_iter().map(|entity| entity.index()).collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    #[test]
+    fn nearby_matches_brute_force() {
+        let mut entity_manager = EntityManager::new();
+        let mut grid = SpatialGrid::new(4.0);
+        let mut points = Vec::new();
+
+        for i in 0..1000 {
+            let entity = entity_manager.create_entity();
+            let position = point(i);
+            grid.insert(entity, Aabb::new(position, position));
+            points.push((entity, position));
+        }
+
+        for (i, &radius) in [0.5, 5.0, 20.0].iter().enumerate() {
+            let center = point(i * 31 + 7);
+            let expected = points
+                .iter()
+                .filter(|(_, position)| Vec3::distance_sqr(*position, center) <= radius * radius)
+                .map(|(entity, _)| *entity)
+                .collect();
+
+            assert_eq!(sorted(grid.nearby(center, radius)), sorted(expected));
+        }
+
+        // Covers far more cells than entities
+        assert_eq!(grid.nearby(Vec3::new(50.0, 50.0, 50.0), 1e4).len(), 1000);
+    }
+
+    #[test]
+    fn unbounded_queries() {
+        let mut entity_manager = EntityManager::new();
+        let mut grid = SpatialGrid::new(1.0);
+
+        let small = entity_manager.create_entity();
+        grid.insert(
+            small,
+            Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.5, 0.5, 0.5)),
+        );
+
+        let infinite = entity_manager.create_entity();
+        let max = Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        grid.insert(infinite, Aabb::new(-max, max));
+
+        let center = Vec3::new(0.0, 0.0, 0.0);
+        assert_eq!(
+            sorted(grid.nearby(center, 1.0)),
+            sorted(vec![small, infinite])
+        );
+        assert_eq!(grid.nearby(center, f32::INFINITY).len(), 2);
+        assert!(grid.nearby(Vec3::new(f32::NAN, 0.0, 0.0), 1.0).len() <= 2);
+
+        assert!(grid.remove(infinite));
+        assert_eq!(grid.nearby(Vec3::new(100.0, 0.0, 0.0), 1.0), Vec::new());
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_cell_size() {
+        SpatialGrid::new(0.0);
+    }
+}
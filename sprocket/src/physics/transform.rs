@@ -1,19 +1,122 @@
 use crate::math::*;
+use serde::{Deserialize, Serialize};
 
 /// A component representing a the position, rotation, and scale of an entity
+/// Missing fields are deserialized as the default transform
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
 pub struct Transform {
     pub position: Vec3,
-    // rotation: Quaternion,
-    // scale: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
 }
 
 impl Transform {
+    /// Creates a transform at position with no rotation and a scale of one
     pub fn new(position: Vec3) -> Self {
-        Transform { position }
+        Transform {
+            position,
+            rotation: Quat::identity(),
+            scale: Vec3::one(),
+        }
+    }
+
+    /// Returns the direction the transform is facing, Vec3::forward rotated by the rotation
+    pub fn forward(&self) -> Vec3 {
+        Vec3::forward().rotate(self.rotation)
+    }
+
+    /// Returns Vec3::right rotated by the rotation
+    pub fn right(&self) -> Vec3 {
+        Vec3::right().rotate(self.rotation)
+    }
+
+    /// Returns Vec3::up rotated by the rotation
+    pub fn up(&self) -> Vec3 {
+        Vec3::up().rotate(self.rotation)
+    }
+
+    /// Creates a new worldmatrix from the contained position, rotation, and scale
+    /// Scales first, then rotates, then translates
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::scale(self.scale) * Mat4::rotate(self.rotation) * Mat4::translate(self.position)
     }
 
     /// Creates a new worldmatrix from the contained position, rotation, and scale
+    #[deprecated(note = "use Transform::matrix instead")]
     pub fn create_worldmatrix(&self) -> Mat4 {
-        Mat4::translate(self.position)
+        self.matrix()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::new(Vec3::zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns true if a and b are equal within epsilon
+    fn approx_eq(a: Vec3, b: Vec3) -> bool {
+        (a - b).mag() < 1e-6
+    }
+
+    #[test]
+    fn basis_vectors() {
+        let mut transform = Transform::default();
+        assert!(approx_eq(transform.forward(), Vec3::forward()));
+
+        // Turning right around up faces along +X
+        transform.rotation = Quat::axis_angle(Vec3::up(), Deg(90.0));
+        assert!(approx_eq(transform.forward(), Vec3::right()));
+        assert!(approx_eq(transform.right(), -Vec3::forward()));
+        assert!(approx_eq(transform.up(), Vec3::up()));
+    }
+
+    #[test]
+    fn serde() {
+        let mut transform = Transform::new(Vec3::new(1.0, 2.0, 3.0));
+        transform.rotation = Quat::axis_angle(Vec3::up(), Deg(90.0));
+
+        let json = serde_json::to_string(&transform).unwrap();
+        let parsed: Transform = serde_json::from_str(&json).unwrap();
+        assert!(parsed.matrix() == transform.matrix());
+
+        // Missing fields are the default
+        let parsed: Transform =
+            serde_json::from_str(r#"{ "position": { "x": 1.0, "y": 2.0, "z": 3.0 } }"#).unwrap();
+        assert_eq!(parsed.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(parsed.scale, Vec3::one());
+        assert!(parsed.matrix() == Mat4::translate(parsed.position));
+    }
+
+    #[test]
+    fn matrix() {
+        let identity = Transform::default().matrix();
+        assert!(identity.approx_eq(&Mat4::identity(), 1e-6));
+
+        let mut transform = Transform::new(Vec3::new(1.0, 2.0, 3.0));
+        transform.scale = Vec3::new(2.0, 3.0, 4.0);
+        let matrix = transform.matrix();
+
+        // Scaled before translated
+        assert!(approx_eq(
+            matrix.transform_point(Vec3::one()),
+            Vec3::new(3.0, 5.0, 7.0)
+        ));
+        assert!(approx_eq(
+            matrix.transform_point(Vec3::zero()),
+            transform.position
+        ));
+
+        // Rotated after scaling and before translating
+        transform.rotation = Quat::axis_angle(Vec3::up(), Deg(90.0));
+        assert!(approx_eq(
+            transform.matrix().transform_point(Vec3::forward()),
+            transform.position + Vec3::right() * 4.0
+        ));
     }
 }
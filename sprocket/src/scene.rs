@@ -0,0 +1,30 @@
+use crate::graphics::Result;
+use crate::physics::Transform;
+use serde::{Deserialize, Serialize};
+
+/// Describes a single entity in a scene
+#[derive(Serialize, Deserialize)]
+pub struct EntitySpec {
+    #[serde(default)]
+    pub transform: Transform,
+    /// Path to a model loaded through the resource manager
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Path to a material loaded through the resource manager
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+/// A declarative description of the entities in a scene
+/// Loaded from json and instantiated with Application::load_scene
+#[derive(Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<EntitySpec>,
+}
+
+impl Scene {
+    /// Reads a scene from a json file
+    pub fn load(path: &str) -> Result<Scene> {
+        Ok(serde_json::from_str(&ex::fs::read_to_string(path)?)?)
+    }
+}
@@ -1,7 +1,24 @@
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+/// The default number of frames averaged by average_delta and average_framerate
+const DEFAULT_AVERAGE_WINDOW: usize = 60;
+
+/// The maximum number of fixed steps returned by fixed_step for a single frame
+/// Time exceeding this after a stall is discarded so that the simulation can catch up
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// The remaining frame time FrameLimiter spins instead of sleeping for
+/// Sleeping can overshoot by about a scheduler tick
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// The largest time scale accepted by set_scale
+/// Larger scales are clamped since scaled deltas would overflow
+const MAX_SCALE: f32 = 1000.0;
+
 /// Contains time information of a certain part of the program
 /// Tracks elapsed time, delta time, frame count
+/// The game clock can be scaled and paused independently of wall clock time
 pub struct Time {
     /// The time on the last update
     cur: Instant,
@@ -11,6 +28,19 @@ pub struct Time {
     framecount: usize,
     delta: Duration,
     elapsed: Duration,
+    /// The delta time of the game clock, affected by scale and pause
+    scaled_delta: Duration,
+    /// The elapsed time of the game clock, affected by scale and pause
+    scaled_elapsed: Duration,
+    scale: f32,
+    paused: bool,
+    /// The raw deltas of the last average_window frames
+    deltas: VecDeque<Duration>,
+    average_window: usize,
+    /// Game time not yet consumed by fixed steps
+    accumulator: Duration,
+    /// The step size of the last call to fixed_step
+    fixed_delta: Duration,
 }
 
 impl Time {
@@ -24,6 +54,14 @@ impl Time {
             framecount: 0,
             delta: Duration::from_secs(0),
             elapsed: Duration::from_secs(0),
+            scaled_delta: Duration::from_secs(0),
+            scaled_elapsed: Duration::from_secs(0),
+            scale: 1.0,
+            paused: false,
+            deltas: VecDeque::with_capacity(DEFAULT_AVERAGE_WINDOW),
+            average_window: DEFAULT_AVERAGE_WINDOW,
+            accumulator: Duration::from_secs(0),
+            fixed_delta: Duration::from_secs(0),
         }
     }
 
@@ -31,25 +69,81 @@ impl Time {
     /// This function will not panic even if current time is less than previous time
     /// If current time is less than previous time, deltatime will be 0 for that frame
     pub fn update(&mut self) {
+        self.update_at(Instant::now())
+    }
+
+    /// Advances the time to a frame starting at now
+    fn update_at(&mut self, now: Instant) {
         self.prev = self.cur;
-        self.cur = Instant::now();
+        self.cur = now;
 
         self.delta = self.cur.saturating_duration_since(self.prev);
 
         self.elapsed = self.cur.saturating_duration_since(self.init);
+
+        self.scaled_delta = match self.paused {
+            true => Duration::from_secs(0),
+            false => self.delta.mul_f32(self.scale),
+        };
+        self.scaled_elapsed += self.scaled_delta;
+
+        self.deltas.push_back(self.delta);
+        while self.deltas.len() > self.average_window {
+            self.deltas.pop_front();
+        }
+
         self.framecount += 1;
     }
 
-    /// Returns the duration between the last frame and start of current frame in seconds
+    /// Sets the speed of the game clock relative to wall clock time
+    /// A scale of 0.5 runs the game at half speed
+    /// Scales are clamped between 0 and MAX_SCALE, and infinite or NaN scales are ignored
+    pub fn set_scale(&mut self, scale: f32) {
+        if !scale.is_finite() {
+            log::warn!("Ignoring non finite time scale {}", scale);
+            return;
+        }
+
+        self.scale = scale.clamp(0.0, MAX_SCALE);
+    }
+
+    /// Returns the speed of the game clock relative to wall clock time
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Pauses the game clock
+    /// Scaled delta time will be 0 until resumed while the framecount keeps advancing
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes a paused game clock
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the scaled duration between the last frame and start of current frame in seconds
+    /// Is 0 while paused
     pub fn delta_f32(&self) -> f32 {
-        self.delta.as_secs_f32()
+        self.scaled_delta.as_secs_f32()
     }
 
-    /// Returns the raw duration between the last frame and start of current frame
+    /// Returns the raw unscaled duration between the last frame and start of current frame
+    /// Not affected by scale or pause
     pub fn delta_raw(&self) -> Duration {
         self.delta
     }
 
+    /// Returns the instant the current frame started at, E.g; the last call to update
+    pub fn frame_start(&self) -> Instant {
+        self.cur
+    }
+
     /// Returns the duration between the last frame and start of current frame in whole milliseconds
     /// Can be used for precise timing and benchmarking
     /// A whole smaller time unit does not lose precision to rounding errors like floats
@@ -64,12 +158,14 @@ impl Time {
         self.delta.as_micros() as usize
     }
 
-    /// Returns the elapsed time since creation of self and the start of the current frame in seconds
+    /// Returns the scaled elapsed time of the game clock at the start of the current frame in
+    /// seconds
     pub fn elapsed_f32(&self) -> f32 {
-        self.elapsed.as_secs_f32()
+        self.scaled_elapsed.as_secs_f32()
     }
 
     /// Returns the elapsed time since creation of self and the start of the current frame
+    /// Not affected by scale or pause
     pub fn elapsed_raw(&self) -> Duration {
         self.elapsed
     }
@@ -81,8 +177,278 @@ impl Time {
         self.framecount
     }
 
+    /// Accumulates the scaled delta time of the current frame and returns how many steps of dt should be
+    /// run this frame
+    /// Should be called once per frame after update
+    /// The time not consumed by the steps carries over to the next frame
+    /// At most MAX_FIXED_STEPS are returned, the remaining time is dropped to avoid falling
+    /// further behind after a stall
+    pub fn fixed_step(&mut self, dt: Duration) -> u32 {
+        self.fixed_delta = dt;
+        if dt.as_nanos() == 0 {
+            return 0;
+        }
+
+        self.accumulator += self.scaled_delta;
+
+        // Compared before narrowing since a long stall with a tiny step can exceed u32
+        let steps = self.accumulator.as_nanos() / dt.as_nanos();
+        if steps > MAX_FIXED_STEPS as u128 {
+            self.accumulator = Duration::from_secs(0);
+            return MAX_FIXED_STEPS;
+        }
+
+        let steps = steps as u32;
+        self.accumulator -= dt * steps;
+        steps
+    }
+
+    /// Returns how far between the previous and next fixed step the current frame is, from 0 to 1
+    /// Used to interpolate rendered state between fixed steps
+    pub fn alpha(&self) -> f32 {
+        if self.fixed_delta.as_nanos() == 0 {
+            return 0.0;
+        }
+
+        self.accumulator.as_secs_f32() / self.fixed_delta.as_secs_f32()
+    }
+
+    /// Returns the step size of the last call to fixed_step in seconds
+    pub fn fixed_delta_f32(&self) -> f32 {
+        self.fixed_delta.as_secs_f32()
+    }
+
     /// Returns the framerate between this and the previous frame
+    /// Not affected by scale or pause
     pub fn framerate(&self) -> f32 {
-        1.0 / self.delta_f32()
+        1.0 / self.delta.as_secs_f32()
+    }
+
+    /// Sets the number of frames averaged by average_delta and average_framerate
+    /// A window of 0 is treated as 1
+    pub fn set_average_window(&mut self, frames: usize) {
+        self.average_window = frames.max(1);
+        while self.deltas.len() > self.average_window {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Returns the average raw delta time in seconds of the last frames
+    /// Returns 0 if no frames have been updated
+    pub fn average_delta(&self) -> f32 {
+        if self.deltas.is_empty() {
+            return 0.0;
+        }
+
+        let total: Duration = self.deltas.iter().sum();
+        total.as_secs_f32() / self.deltas.len() as f32
+    }
+
+    /// Returns the shortest raw delta time in seconds of the last frames
+    /// Returns 0 if no frames have been updated
+    pub fn min_delta(&self) -> f32 {
+        self.deltas
+            .iter()
+            .min()
+            .map(|delta| delta.as_secs_f32())
+            .unwrap_or(0.0)
+    }
+
+    /// Returns the longest raw delta time in seconds of the last frames, E.g; to find stutters
+    /// Returns 0 if no frames have been updated
+    pub fn max_delta(&self) -> f32 {
+        self.deltas
+            .iter()
+            .max()
+            .map(|delta| delta.as_secs_f32())
+            .unwrap_or(0.0)
+    }
+
+    /// Returns the framerate averaged over the last frames
+    /// Less noisy than framerate and suitable for display
+    /// Returns 0 if no frames have been updated
+    pub fn average_framerate(&self) -> f32 {
+        match self.average_delta() {
+            delta if delta > 0.0 => 1.0 / delta,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Caps the framerate by waiting out the remainder of each frame
+/// Sleeps for most of the remainder and spins for the last SPIN_THRESHOLD since sleeping is
+/// imprecise
+pub struct FrameLimiter {
+    min_frame_duration: Duration,
+}
+
+impl FrameLimiter {
+    /// Creates a limiter for at most max_fps frames per second
+    /// A max_fps of 0 is treated as 1
+    pub fn new(max_fps: u32) -> Self {
+        FrameLimiter {
+            min_frame_duration: Duration::from_secs(1) / max_fps.max(1),
+        }
+    }
+
+    /// Returns the shortest duration a frame is allowed to take
+    pub fn min_frame_duration(&self) -> Duration {
+        self.min_frame_duration
+    }
+
+    /// Blocks until the minimum frame duration has passed since frame_start
+    /// Returns the duration of the frame including the wait
+    pub fn wait(&self, frame_start: Instant) -> Duration {
+        self.wait_with(frame_start, Instant::now, std::thread::sleep)
+    }
+
+    /// Blocks until the minimum frame duration has passed since frame_start using the supplied
+    /// clock and sleep function
+    /// Returns the duration of the frame including the wait
+    pub fn wait_with<N, S>(&self, frame_start: Instant, mut now: N, mut sleep: S) -> Duration
+    where
+        N: FnMut() -> Instant,
+        S: FnMut(Duration),
+    {
+        loop {
+            let elapsed = now().saturating_duration_since(frame_start);
+            if elapsed >= self.min_frame_duration {
+                return elapsed;
+            }
+
+            let remaining = self.min_frame_duration - elapsed;
+            if remaining > SPIN_THRESHOLD {
+                sleep(remaining - SPIN_THRESHOLD);
+            } else {
+                std::thread::yield_now();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale() {
+        let mut time = Time::new();
+
+        time.set_scale(-1.0);
+        assert_eq!(time.scale(), 0.0);
+
+        time.set_scale(0.5);
+        time.set_scale(f32::INFINITY);
+        time.set_scale(f32::NAN);
+        assert_eq!(time.scale(), 0.5);
+
+        time.set_scale(1e30);
+        assert_eq!(time.scale(), MAX_SCALE);
+
+        // Feeds a frame of the given length
+        let step = |time: &mut Time, ms| time.update_at(time.cur + Duration::from_millis(ms));
+
+        time.set_scale(0.5);
+        step(&mut time, 20);
+        assert_eq!(time.delta_raw(), Duration::from_millis(20));
+        assert!((time.delta_f32() - 0.01).abs() < 1e-6);
+
+        // The raw clock keeps advancing while paused
+        time.pause();
+        step(&mut time, 30);
+        assert_eq!(time.delta_f32(), 0.0);
+        assert_eq!(time.delta_raw(), Duration::from_millis(30));
+        assert!((time.elapsed_f32() - 0.01).abs() < 1e-6);
+
+        time.resume();
+        step(&mut time, 10);
+        assert!((time.delta_f32() - 0.005).abs() < 1e-6);
+        assert_eq!(time.framecount(), 3);
+    }
+
+    #[test]
+    fn average_framerate() {
+        let mut time = Time::new();
+        assert_eq!(time.average_framerate(), 0.0);
+
+        for &ms in &[10, 20, 30] {
+            time.update_at(time.cur + Duration::from_millis(ms));
+        }
+        assert!((time.average_delta() - 0.02).abs() < 1e-6);
+        assert!((time.average_framerate() - 50.0).abs() < 1e-3);
+        assert!((time.min_delta() - 0.01).abs() < 1e-6);
+
+        // Averages are not affected by scale or pause
+        time.set_scale(2.0);
+        time.pause();
+        time.update_at(time.cur + Duration::from_millis(20));
+        assert!((time.average_delta() - 0.02).abs() < 1e-6);
+
+        // Shrinking the window drops the oldest frames
+        time.set_average_window(2);
+        assert!((time.average_delta() - 0.025).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fixed_step() {
+        let mut time = Time::new();
+        let dt = Duration::from_millis(10);
+
+        // Feeds a frame of the given length
+        let mut step = |ms| {
+            time.scaled_delta = Duration::from_millis(ms);
+            time.fixed_step(dt)
+        };
+
+        assert_eq!(step(4), 0);
+        assert_eq!(step(4), 0);
+        // The remainder carries over
+        assert_eq!(step(4), 1);
+        assert_eq!(step(25), 2);
+        assert_eq!(time.accumulator, Duration::from_millis(7));
+        assert!((time.alpha() - 0.7).abs() < 1e-5);
+
+        // Stalls are capped
+        time.scaled_delta = Duration::from_secs(1);
+        assert_eq!(time.fixed_step(dt), MAX_FIXED_STEPS);
+        assert_eq!(time.accumulator, Duration::from_secs(0));
+
+        // More steps than fit in a u32 are capped too
+        time.scaled_delta = Duration::from_secs(10);
+        assert_eq!(time.fixed_step(Duration::from_nanos(1)), MAX_FIXED_STEPS);
+        assert_eq!(time.accumulator, Duration::from_secs(0));
+
+        assert_eq!(time.fixed_step(Duration::from_secs(0)), 0);
+    }
+
+    #[test]
+    fn frame_limiter() {
+        let limiter = FrameLimiter::new(60);
+        assert_eq!(limiter.min_frame_duration(), Duration::from_secs(1) / 60);
+
+        // A simulated clock where the frame took 1ms and each spin takes 100us
+        let start = Instant::now();
+        let elapsed = std::cell::Cell::new(Duration::from_millis(1));
+        let slept = std::cell::Cell::new(Duration::from_secs(0));
+
+        let frame = limiter.wait_with(
+            start,
+            || {
+                let now = start + elapsed.get();
+                elapsed.set(elapsed.get() + Duration::from_micros(100));
+                now
+            },
+            |duration| {
+                slept.set(slept.get() + duration);
+                elapsed.set(elapsed.get() + duration);
+            },
+        );
+
+        assert!(frame >= limiter.min_frame_duration());
+        assert!(frame < limiter.min_frame_duration() + Duration::from_micros(200));
+        // Most of the wait is slept rather than spun
+        assert!(
+            slept.get() >= limiter.min_frame_duration() - Duration::from_millis(1) - SPIN_THRESHOLD
+        );
     }
 }
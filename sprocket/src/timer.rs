@@ -99,3 +99,135 @@ impl Timer {
         self.remaining().map(|d| d.as_secs_f32())
     }
 }
+
+/// A callback scheduled to run when its timer is signaled
+struct Scheduled {
+    timer: Timer,
+    callback: Box<dyn FnMut()>,
+    repeat: bool,
+}
+
+/// Runs closures after a duration or repeatedly at an interval
+/// Due callbacks are run when calling tick, usually once per frame
+#[derive(Default)]
+pub struct Scheduler {
+    entries: Vec<Scheduled>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Schedules callback to run once after duration has passed
+    pub fn after<F: FnMut() + 'static>(&mut self, duration: Duration, callback: F) {
+        self.schedule(duration, callback, false)
+    }
+
+    /// Schedules callback to run every time duration has passed
+    /// The interval restarts when the callback is run
+    pub fn every<F: FnMut() + 'static>(&mut self, duration: Duration, callback: F) {
+        self.schedule(duration, callback, true)
+    }
+
+    fn schedule<F: FnMut() + 'static>(&mut self, duration: Duration, callback: F, repeat: bool) {
+        self.entries.push(Scheduled {
+            timer: Timer::with_target(duration),
+            callback: Box::new(callback),
+            repeat,
+        })
+    }
+
+    /// Runs the callbacks of all signaled timers and restarts the repeating ones
+    /// One shot callbacks are removed after running
+    /// Returns the number of callbacks that were run
+    pub fn tick(&mut self) -> usize {
+        let mut fired = 0;
+
+        for entry in &mut self.entries {
+            if !entry.timer.signaled() {
+                continue;
+            }
+
+            (entry.callback)();
+            fired += 1;
+
+            if entry.repeat {
+                entry.timer.restart();
+            } else {
+                entry.timer.stop();
+            }
+        }
+
+        self.entries.retain(|entry| entry.timer.running());
+        fired
+    }
+
+    /// Returns the number of scheduled callbacks
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all scheduled callbacks without running them
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Returns a counter and a callback incrementing it
+    fn counter() -> (Rc<Cell<u32>>, impl FnMut() + 'static) {
+        let count = Rc::new(Cell::new(0));
+        let callback_count = Rc::clone(&count);
+        (count, move || callback_count.set(callback_count.get() + 1))
+    }
+
+    #[test]
+    fn one_shot() {
+        let mut scheduler = Scheduler::new();
+        let (count, callback) = counter();
+        scheduler.after(Duration::from_millis(5), callback);
+
+        assert_eq!(scheduler.tick(), 0);
+        std::thread::sleep(Duration::from_millis(10));
+
+        // Fires exactly once and is removed
+        assert_eq!(scheduler.tick(), 1);
+        assert!(scheduler.is_empty());
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(scheduler.tick(), 0);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn repeating() {
+        let mut scheduler = Scheduler::new();
+        let (count, callback) = counter();
+        let (late_count, late_callback) = counter();
+        scheduler.every(Duration::from_secs(0), callback);
+        scheduler.every(Duration::from_secs(3600), late_callback);
+
+        for _ in 0..5 {
+            assert_eq!(scheduler.tick(), 1);
+        }
+
+        assert_eq!(count.get(), 5);
+        assert_eq!(late_count.get(), 0);
+        assert_eq!(scheduler.len(), 2);
+
+        scheduler.clear();
+        assert_eq!(scheduler.tick(), 0);
+        assert_eq!(count.get(), 5);
+    }
+}
@@ -1,5 +1,7 @@
 use std::env;
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
 /// Null terminates all str:s in collection
 pub fn vec_to_null_terminated(collection: &[&str]) -> Vec<CString> {
@@ -33,3 +35,136 @@ pub fn normalize_working_dir() {
     let path = &path[0..last_delimiter];
     env::set_current_dir(&path).expect("Failed to set working directory");
 }
+
+/// Writes tightly packed rgba8 pixels, row by row from the top, to a png file at path
+/// The image data is stored uncompressed
+pub fn save_png(path: &str, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let row_size = width as usize * 4;
+    if pixels.len() != row_size * height as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Pixel data of {} bytes does not match a {}x{} image",
+                pixels.len(),
+                width,
+                height
+            ),
+        ));
+    }
+
+    // Each scanline is prefixed by its filter type, 0 for none
+    let mut scanlines = Vec::with_capacity((row_size + 1) * height as usize);
+    if row_size > 0 {
+        for row in pixels.chunks_exact(row_size) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+    }
+
+    // Zlib stream of stored deflate blocks
+    let mut image_data = vec![0x78, 0x01];
+    let mut blocks = scanlines.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        image_data.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        image_data.push(last as u8);
+        image_data.extend_from_slice(&len.to_le_bytes());
+        image_data.extend_from_slice(&(!len).to_le_bytes());
+        image_data.extend_from_slice(block);
+    }
+    image_data.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type rgba, default compression and filtering, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+    write_png_chunk(&mut file, b"IHDR", &header)?;
+    write_png_chunk(&mut file, b"IDAT", &image_data)?;
+    write_png_chunk(&mut file, b"IEND", &[])?;
+    file.flush()
+}
+
+fn write_png_chunk<W: Write>(writer: &mut W, ty: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(ty)?;
+    writer.write_all(data)?;
+
+    let crc = !crc32(crc32(0xffff_ffff, ty), data);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// Feeds data into a running crc32 as used by png chunks
+/// The register starts as 0xffffffff and the result is inverted
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Checksum of the uncompressed data in a zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::vulkan::Pixels;
+
+    #[test]
+    fn save_png() {
+        let (width, height) = (3, 2);
+        let pixels: Vec<u8> = (0..width * height * 4).map(|i| (i * 11) as u8).collect();
+
+        let path = std::env::temp_dir().join("sprocket_save_png.png");
+        let path = path.to_str().unwrap();
+        super::save_png(path, width, height, &pixels).unwrap();
+
+        // Decoded with stb_image like textures
+        let decoded = Pixels::open(path).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (width, height));
+        assert_eq!(decoded.data(), &pixels[..]);
+
+        let result = super::save_png(path, width, height, &pixels[1..]);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn save_png_multiple_blocks() {
+        // Larger than a single stored deflate block of 65535 bytes
+        let (width, height) = (200, 100);
+        let pixels: Vec<u8> = (0..width * height * 4).map(|i| (i % 251) as u8).collect();
+
+        let path = std::env::temp_dir().join("sprocket_save_png_blocks.png");
+        let path = path.to_str().unwrap();
+        super::save_png(path, width, height, &pixels).unwrap();
+
+        assert_eq!(Pixels::open(path).unwrap().data(), &pixels[..]);
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(!crc32(0xffff_ffff, b"123456789"), 0xcbf4_3926);
+    }
+}